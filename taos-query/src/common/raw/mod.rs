@@ -11,7 +11,7 @@ use std::{
     ptr::NonNull,
     sync::Arc,
 };
-use std::{fmt::Debug, mem::transmute};
+use std::fmt::Debug;
 
 pub mod layout;
 pub mod meta;
@@ -181,38 +181,39 @@ impl RawBlock {
         use bytes::BufMut;
         debug_assert_eq!(fields.len(), lengths.len());
 
-        const fn bool_is_null(v: *const bool) -> bool {
+        // Values are read unaligned, the block may start at any offset of the message.
+        fn bool_is_null(v: *const bool) -> bool {
             unsafe { *(v as *const u8) == 0x02 }
         }
-        const fn tiny_int_is_null(v: *const i8) -> bool {
+        fn tiny_int_is_null(v: *const i8) -> bool {
             unsafe { *(v as *const u8) == 0x80 }
         }
-        const fn small_int_is_null(v: *const i16) -> bool {
-            unsafe { *(v as *const u16) == 0x8000 }
+        fn small_int_is_null(v: *const i16) -> bool {
+            unsafe { (v as *const u16).read_unaligned() == 0x8000 }
         }
-        const fn int_is_null(v: *const i32) -> bool {
-            unsafe { *(v as *const u32) == 0x80000000 }
+        fn int_is_null(v: *const i32) -> bool {
+            unsafe { (v as *const u32).read_unaligned() == 0x80000000 }
         }
-        const fn big_int_is_null(v: *const i64) -> bool {
-            unsafe { *(v as *const u64) == 0x8000000000000000 }
+        fn big_int_is_null(v: *const i64) -> bool {
+            unsafe { (v as *const u64).read_unaligned() == 0x8000000000000000 }
         }
-        const fn u_tiny_int_is_null(v: *const u8) -> bool {
-            unsafe { *(v as *const u8) == 0xFF }
+        fn u_tiny_int_is_null(v: *const u8) -> bool {
+            unsafe { *v == 0xFF }
         }
-        const fn u_small_int_is_null(v: *const u16) -> bool {
-            unsafe { *(v as *const u16) == 0xFFFF }
+        fn u_small_int_is_null(v: *const u16) -> bool {
+            unsafe { v.read_unaligned() == 0xFFFF }
         }
-        const fn u_int_is_null(v: *const u32) -> bool {
-            unsafe { *(v as *const u32) == 0xFFFFFFFF }
+        fn u_int_is_null(v: *const u32) -> bool {
+            unsafe { v.read_unaligned() == 0xFFFFFFFF }
         }
-        const fn u_big_int_is_null(v: *const u64) -> bool {
-            unsafe { *(v as *const u64) == 0xFFFFFFFFFFFFFFFF }
+        fn u_big_int_is_null(v: *const u64) -> bool {
+            unsafe { v.read_unaligned() == 0xFFFFFFFFFFFFFFFF }
         }
-        const fn float_is_null(v: *const f32) -> bool {
-            unsafe { *(v as *const u32) == 0x7FF00000 }
+        fn float_is_null(v: *const f32) -> bool {
+            unsafe { (v as *const u32).read_unaligned() == 0x7FF00000 }
         }
-        const fn double_is_null(v: *const f64) -> bool {
-            unsafe { *(v as *const u64) == 0x7FFFFF0000000000 }
+        fn double_is_null(v: *const f64) -> bool {
+            unsafe { (v as *const u64).read_unaligned() == 0x7FFFFF0000000000 }
         }
 
         // const BOOL_NULL: u8 = 0x2;
@@ -257,21 +258,13 @@ impl RawBlock {
                     offset += rows * std::mem::size_of::<$prim>() as usize;
                     // byte slice from start to end: `[start, end)`.
                     let data = bytes.slice(start..offset);
-                    // value as target type
-                    let value_slice = unsafe {
-                        std::slice::from_raw_parts(
-                            transmute::<*const u8, *const $prim>(data.as_ptr()),
-                            rows,
-                        )
-                    };
                     // Set data lengths for v3-compatible block.
                     data_lengths[i] = data.len() as u32;
 
                     // generate nulls bitmap.
                     let nulls = NullsMut::from_bools(
-                        value_slice
-                            .iter()
-                            .map(|v| paste::paste!{ [<$ty:snake _is_null>](v as _) })
+                        data.chunks_exact(std::mem::size_of::<$prim>())
+                            .map(|v| paste::paste!{ [<$ty:snake _is_null>](v.as_ptr() as _) })
                             // .map(|b| *b as u64 == paste::paste! { [<$ty:snake:upper _NULL>] }),
                     )
                     .into_nulls();
@@ -307,7 +300,7 @@ impl RawBlock {
                     let offsets = Offsets::from_offsets((0..rows).into_iter().map(|row| unsafe {
                         let offset = row as i32 * *length as i32;
                         let ptr = data_ptr.offset(offset as isize);
-                        let len = *transmute::<*const u8, *const u16>(ptr);
+                        let len = (ptr as *const u16).read_unaligned();
                         if len == 1 && *ptr.offset(2) == 0xFF {
                             -1
                         } else {
@@ -326,20 +319,15 @@ impl RawBlock {
                     offset += rows * std::mem::size_of::<i64>() as usize;
                     // byte slice from start to end: `[start, end)`.
                     let data = bytes.slice(start..offset);
-                    // value as target type
-                    let value_slice = unsafe {
-                        std::slice::from_raw_parts(
-                            transmute::<*const u8, *const i64>(data.as_ptr()),
-                            rows,
-                        )
-                    };
                     // Set data lengths for v3-compatible block.
                     data_lengths[i] = data.len() as u32;
 
                     // generate nulls bitmap.
-                    let nulls =
-                        NullsMut::from_bools(value_slice.iter().map(|b| big_int_is_null(b as _)))
-                            .into_nulls();
+                    let nulls = NullsMut::from_bools(
+                        data.chunks_exact(8)
+                            .map(|b| big_int_is_null(b.as_ptr() as _)),
+                    )
+                    .into_nulls();
                     // build column view
                     let column = ColumnView::Timestamp(TimestampView {
                         nulls,
@@ -357,8 +345,9 @@ impl RawBlock {
                     let offsets = Offsets::from_offsets((0..rows).into_iter().map(|row| unsafe {
                         let offset = row as i32 * *length as i32;
                         let ptr = data_ptr.offset(offset as isize);
-                        let len = *transmute::<*const u8, *const u16>(ptr);
-                        if len == 4 && *(ptr.offset(2) as *const u32) == 0xFFFFFFFF {
+                        let len = (ptr as *const u16).read_unaligned();
+                        if len == 4 && (ptr.offset(2) as *const u32).read_unaligned() == 0xFFFFFFFF
+                        {
                             -1
                         } else {
                             offset
@@ -384,8 +373,9 @@ impl RawBlock {
                     let offsets = Offsets::from_offsets((0..rows).into_iter().map(|row| unsafe {
                         let offset = row as i32 * *length as i32;
                         let ptr = data_ptr.offset(offset as isize);
-                        let len = *transmute::<*const u8, *const u16>(ptr);
-                        if len == 4 && *(ptr.offset(2) as *const u32) == 0xFFFFFFFF {
+                        let len = (ptr as *const u16).read_unaligned();
+                        if len == 4 && (ptr.offset(2) as *const u32).read_unaligned() == 0xFFFFFFFF
+                        {
                             -1
                         } else {
                             offset
@@ -421,6 +411,69 @@ impl RawBlock {
         }
     }
 
+    /// Check that `bytes` is a well-formed v3 raw block of `rows` rows, so it could be parsed by
+    /// [RawBlock::parse_from_raw_block] without reading out of bounds.
+    ///
+    /// The length in header should equal to the buffer length, which should be exactly filled by
+    /// the column data computed from the row count and column lengths.
+    pub fn check_raw_block(bytes: &[u8], rows: usize) -> Result<(), String> {
+        let header_len = std::mem::size_of::<Header>();
+        if bytes.len() < header_len {
+            return Err(format!("raw block of {} bytes is too short", bytes.len()));
+        }
+        let header = unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const Header) };
+        if header.len() != bytes.len() {
+            return Err(format!(
+                "raw block length {} in header mismatch with buffer length {}",
+                header.len(),
+                bytes.len()
+            ));
+        }
+        if header.nrows() != rows {
+            return Err(format!(
+                "raw block has {} rows but {rows} rows declared",
+                header.nrows()
+            ));
+        }
+        let cols = header.ncols();
+        let schema_len = std::mem::size_of::<ColSchema>();
+        let lengths_start = header_len + cols * schema_len;
+        let mut offset = lengths_start + cols * std::mem::size_of::<u32>();
+        if offset > bytes.len() {
+            return Err(format!(
+                "raw block of {} bytes is too short for {cols} columns",
+                bytes.len()
+            ));
+        }
+        let read_u32 = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        for col in 0..cols {
+            let ty = bytes[header_len + col * schema_len];
+            let length = read_u32(lengths_start + col * 4) as usize;
+            offset += match Ty::try_from_u8(ty) {
+                Some(Ty::VarChar | Ty::NChar | Ty::Json) => {
+                    std::mem::size_of::<i32>() * rows + length
+                }
+                Some(ty) if ty.fixed_length() > 0 && ty != Ty::Decimal => {
+                    ((rows + 7) >> 3) + rows * ty.fixed_length()
+                }
+                _ => return Err(format!("unsupported data type {ty} of column {col}")),
+            };
+            if offset > bytes.len() {
+                return Err(format!(
+                    "data of column {col} exceeds raw block length {}",
+                    bytes.len()
+                ));
+            }
+        }
+        if offset != bytes.len() {
+            return Err(format!(
+                "raw block length {} mismatch with column data length {offset}",
+                bytes.len()
+            ));
+        }
+        Ok(())
+    }
+
     pub fn parse_from_raw_block(bytes: impl Into<Bytes>, precision: Precision) -> Self {
         let schema_start: usize = std::mem::size_of::<Header>();
 
@@ -987,8 +1040,8 @@ impl crate::prelude::AsyncInlinable for RawBlock {
 
 #[tokio::test(flavor = "multi_thread")]
 async fn test_raw_from_v2() {
-    use std::ops::Deref;
     use crate::prelude::AsyncInlinable;
+    use std::ops::Deref;
     // pretty_env_logger::formatted_builder()
     //     .filter_level(log::LevelFilter::Trace)
     //     .init();
@@ -1217,3 +1270,91 @@ fn test_from_v2() {
     );
     dbg!(&raw);
 }
+
+#[test]
+fn test_check_raw_block() {
+    // 1 row of (tinyint 1, varchar "ab").
+    let mut bytes = Vec::new();
+    for v in [1u32, 0, 1, 2, 0] {
+        bytes.extend(v.to_le_bytes());
+    }
+    bytes.extend(0u64.to_le_bytes());
+    for (ty, len) in [(Ty::TinyInt, 1u32), (Ty::VarChar, 4)] {
+        bytes.push(ty as u8);
+        bytes.extend(len.to_le_bytes());
+    }
+    for len in [1u32, 4] {
+        bytes.extend(len.to_le_bytes());
+    }
+    bytes.extend([0, 1]);
+    bytes.extend(0i32.to_le_bytes());
+    bytes.extend([2, 0, b'a', b'b']);
+    let len = bytes.len() as u32;
+    bytes[4..8].copy_from_slice(&len.to_le_bytes());
+
+    RawBlock::check_raw_block(&bytes, 1).unwrap();
+    let raw = RawBlock::parse_from_raw_block(bytes.clone(), Precision::Millisecond);
+    assert_eq!(raw.nrows(), 1);
+    assert_eq!(raw.ncols(), 2);
+
+    assert!(RawBlock::check_raw_block(&bytes, 2).is_err());
+    assert!(RawBlock::check_raw_block(&bytes[..bytes.len() - 1], 1).is_err());
+    assert!(RawBlock::check_raw_block(&bytes[..10], 1).is_err());
+
+    // length in header larger than the columns need.
+    let mut longer = bytes.clone();
+    longer.push(0);
+    let len = longer.len() as u32;
+    longer[4..8].copy_from_slice(&len.to_le_bytes());
+    assert!(RawBlock::check_raw_block(&longer, 1).is_err());
+
+    // unknown data type.
+    let mut unknown = bytes;
+    unknown[28] = 200;
+    assert!(RawBlock::check_raw_block(&unknown, 1).is_err());
+}
+
+#[test]
+fn test_v2_unaligned() {
+    let mut bytes = vec![0u8];
+    // bigint column: 5, NULL
+    bytes.extend_from_slice(&5i64.to_le_bytes());
+    bytes.extend_from_slice(&i64::MIN.to_le_bytes());
+    // int column: NULL, 7
+    bytes.extend_from_slice(&i32::MIN.to_le_bytes());
+    bytes.extend_from_slice(&7i32.to_le_bytes());
+    // double column: 1.5, NULL
+    bytes.extend_from_slice(&1.5f64.to_le_bytes());
+    bytes.extend_from_slice(&0x7FFFFF0000000000u64.to_le_bytes());
+    // varchar column: "ab", NULL
+    bytes.extend_from_slice(&[2, 0, b'a', b'b', 1, 0, 0xFF, 0]);
+    // the block starts at an odd address, like one in the middle of a message.
+    let block = Bytes::from(bytes).slice(1..);
+    assert_eq!(block.as_ptr() as usize % 2, 1);
+
+    let raw = RawBlock::parse_from_raw_block_v2(
+        block,
+        &[
+            Field::new("b", Ty::BigInt, 8),
+            Field::new("i", Ty::Int, 4),
+            Field::new("d", Ty::Double, 8),
+            Field::new("v", Ty::VarChar, 2),
+        ],
+        &[8, 4, 8, 4],
+        2,
+        Precision::Millisecond,
+    );
+    // nulls are found by reading values at odd addresses, the views still read them in place.
+    for (row, col, null) in [
+        (0, 0, false),
+        (1, 0, true),
+        (0, 1, true),
+        (1, 1, false),
+        (0, 2, false),
+        (1, 2, true),
+        (0, 3, false),
+        (1, 3, true),
+    ] {
+        assert_eq!(raw.is_null(row, col), null, "row {row} col {col}");
+    }
+}
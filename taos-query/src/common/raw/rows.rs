@@ -2,7 +2,7 @@ use std::{marker::PhantomData, ptr::NonNull};
 
 use serde::{
     de::{DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor},
-    Deserializer,
+    Deserialize, Deserializer,
 };
 
 use crate::{
@@ -177,6 +177,39 @@ impl<'a> RowView<'a> {
     pub fn into_values(self) -> Vec<Value> {
         self.map(|(_, b)| b.to_value()).collect()
     }
+
+    /// Value of column `index` as `T`, `None` for NULL.
+    ///
+    /// `T` is any type deserializable from the value, like `i64` for integers or `&str` for
+    /// `VARCHAR`. Type mismatches are errors naming the column.
+    pub fn get<T: Deserialize<'a>>(&self, index: usize) -> Result<Option<T>, DeError> {
+        let name = self.raw.fields.get(index).ok_or_else(|| {
+            <DeError as serde::de::Error>::custom(format!(
+                "column index {index} out of range of {} columns",
+                self.raw.fields.len()
+            ))
+        })?;
+        let value = unsafe { self.raw.get_ref_unchecked(self.row, index) };
+        if value.is_null() {
+            return Ok(None);
+        }
+        T::deserialize(value).map(Some).map_err(|err| {
+            <DeError as serde::de::Error>::custom(format!("column `{name}` (index {index}): {err}"))
+        })
+    }
+
+    /// Value of the first column named `name` as `T`, see [RowView::get].
+    pub fn get_by_name<T: Deserialize<'a>>(&self, name: &str) -> Result<Option<T>, DeError> {
+        let index = self
+            .raw
+            .fields
+            .iter()
+            .position(|field| field == name)
+            .ok_or_else(|| {
+                <DeError as serde::de::Error>::custom(format!("no column named `{name}`"))
+            })?;
+        self.get(index)
+    }
 }
 
 pub(super) type DeError = taos_error::Error;
@@ -311,6 +311,45 @@ impl ColumnView {
         }
     }
 
+    /// Pointer to the contiguous payload of the column.
+    ///
+    /// Values of var-types (VarChar, NChar, Json) are located by [raw_offsets](#method.raw_offsets),
+    /// each prefixed with an u16 length. NChar values of v3 raw block are decoded to UTF-8 in place.
+    pub fn as_raw_ptr(&self) -> *const c_void {
+        match self {
+            ColumnView::Bool(view) => view.data.as_ptr() as _,
+            ColumnView::TinyInt(view) => view.data.as_ptr() as _,
+            ColumnView::SmallInt(view) => view.data.as_ptr() as _,
+            ColumnView::Int(view) => view.data.as_ptr() as _,
+            ColumnView::BigInt(view) => view.data.as_ptr() as _,
+            ColumnView::Float(view) => view.data.as_ptr() as _,
+            ColumnView::Double(view) => view.data.as_ptr() as _,
+            ColumnView::VarChar(view) => view.data.as_ptr() as _,
+            ColumnView::Timestamp(view) => view.data.as_ptr() as _,
+            ColumnView::NChar(view) => {
+                unsafe { view.nchar_to_utf8() };
+                view.data.as_ptr() as _
+            }
+            ColumnView::UTinyInt(view) => view.data.as_ptr() as _,
+            ColumnView::USmallInt(view) => view.data.as_ptr() as _,
+            ColumnView::UInt(view) => view.data.as_ptr() as _,
+            ColumnView::UBigInt(view) => view.data.as_ptr() as _,
+            ColumnView::Json(view) => view.data.as_ptr() as _,
+        }
+    }
+
+    /// Offsets of each row in the payload for var-types, negative for NULL values.
+    ///
+    /// Returns `None` for fixed-length types.
+    pub fn raw_offsets(&self) -> Option<&[i32]> {
+        match self {
+            ColumnView::VarChar(view) => Some(view.offsets.as_slice()),
+            ColumnView::NChar(view) => Some(view.offsets.as_slice()),
+            ColumnView::Json(view) => Some(view.offsets.as_slice()),
+            _ => None,
+        }
+    }
+
     pub fn iter(&self) -> ColumnViewIter {
         ColumnViewIter { view: self, row: 0 }
     }
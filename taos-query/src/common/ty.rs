@@ -321,8 +321,16 @@ impl Ty {
 
     #[inline]
     const fn from_u8(v: u8) -> Self {
+        match Self::try_from_u8(v) {
+            Some(ty) => ty,
+            None => panic!("unknown data type"),
+        }
+    }
+
+    /// Get type from its code, returns `None` for unknown codes.
+    pub const fn try_from_u8(v: u8) -> Option<Self> {
         use Ty::*;
-        match v {
+        Some(match v {
             0 => Null,
             1 => Bool,
             2 => TinyInt,
@@ -343,8 +351,8 @@ impl Ty {
             17 => Decimal,
             18 => Blob,
             19 => MediumBlob,
-            _ => panic!("unknown data type"),
-        }
+            _ => return None,
+        })
     }
 }
 impl From<u8> for Ty {
@@ -127,6 +127,11 @@ pub mod sync {
 
         fn affected_rows(&self) -> i32;
 
+        /// Affected rows of 64 bits, for connectors whose `affected_rows` truncates.
+        fn affected_rows64(&self) -> i64 {
+            self.affected_rows() as _
+        }
+
         fn precision(&self) -> Precision;
 
         fn fields(&self) -> &[Field];
@@ -187,7 +192,7 @@ pub mod sync {
         fn query<T: AsRef<str>>(&self, sql: T) -> Result<Self::ResultSet, Self::Error>;
 
         fn exec<T: AsRef<str>>(&self, sql: T) -> Result<usize, Self::Error> {
-            self.query(sql).map(|res| res.affected_rows() as _)
+            self.query(sql).map(|res| res.affected_rows64() as _)
         }
 
         fn write_raw_meta(&self, _: RawMeta) -> Result<(), Self::Error>;
@@ -421,6 +426,11 @@ mod r#async {
 
         fn affected_rows(&self) -> i32;
 
+        /// Affected rows of 64 bits, for connectors whose `affected_rows` truncates.
+        fn affected_rows64(&self) -> i64 {
+            self.affected_rows() as _
+        }
+
         fn precision(&self) -> Precision;
 
         fn fields(&self) -> &[Field];
@@ -492,7 +502,7 @@ mod r#async {
         async fn exec<T: AsRef<str> + Send + Sync>(&self, sql: T) -> Result<usize, Self::Error> {
             let sql = sql.as_ref();
             // log::debug!("exec sql: {sql}");
-            self.query(sql).await.map(|res| res.affected_rows() as _)
+            self.query(sql).await.map(|res| res.affected_rows64() as _)
         }
 
         async fn write_raw_meta(&self, _: RawMeta) -> Result<(), Self::Error>;
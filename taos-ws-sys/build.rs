@@ -2,6 +2,7 @@ extern crate cbindgen;
 
 use std::env;
 use std::path::Path;
+use std::process::Command;
 
 fn main() {
     let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
@@ -14,6 +15,29 @@ fn main() {
 
     let bindings = target_dir.join("taosws.h");
 
+    let version = env::var("CARGO_PKG_VERSION").unwrap();
+    let git_hash = git(&["rev-parse", "--short", "HEAD"]);
+    let client_info = match git_hash {
+        Some(hash) => format!("{version}-{hash}"),
+        None => version,
+    };
+    println!("cargo:rustc-env=TAOSWS_CLIENT_INFO={client_info}");
+    // watching git disables the default of rerunning on any change, so the header generated
+    // from sources is watched too.
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    // HEAD names the branch, whose commit is in a loose ref or in packed-refs after `git gc`.
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        let git_dir = Path::new(&crate_dir).join(git_dir);
+        for path in ["HEAD", "refs/heads", "packed-refs"] {
+            let path = git_dir.join(path);
+            // a missing path is always treated as changed by cargo.
+            if path.exists() {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+    }
+
     let config = cbindgen::Config::from_root_or_default(&crate_dir);
 
     cbindgen::Builder::new()
@@ -23,3 +47,15 @@ fn main() {
         .expect("Unable to generate bindings")
         .write_to_file(bindings);
 }
+
+/// Trimmed output of a successful git command, `None` if git is missing or fails.
+fn git(args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|out| out.trim().to_string())
+        .filter(|out| !out.is_empty())
+}
@@ -116,7 +116,7 @@ int main(int argc, char *argv[]) {
           break;
         case TSDB_DATA_TYPE_TIMESTAMP:
           memset(tmp, 0, 4096);
-          ws_timestamp_to_rfc3339(tmp, *(int64_t *)value, precision, true);
+          ws_timestamp_to_rfc3339_with_len(tmp, sizeof(tmp), *(int64_t *)value, precision, true);
           printf("\"%s\"", (char *)tmp);
           break;
         case TSDB_DATA_TYPE_VARCHAR:
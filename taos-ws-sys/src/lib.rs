@@ -1,17 +1,24 @@
 use std::{
+    borrow::Cow,
+    cell::RefCell,
     ffi::{c_void, CStr, CString},
     fmt::{Debug, Display},
     ops::{Deref, DerefMut},
     os::raw::c_char,
     str::Utf8Error,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
     time::Duration,
 };
 
+use once_cell::sync::OnceCell;
 use taos_error::Code;
 
 use taos_query::{
     block_in_place_or_global,
-    common::{Field, RawBlock as Block, Timestamp},
+    common::{BorrowedValue, Field, RawBlock as Block, Timestamp},
     common::{Precision, Ty},
     DsnError, Fetchable, Queryable, TBuilder,
 };
@@ -22,11 +29,122 @@ use taos_ws::{
 
 pub use taos_ws::query::asyn::WS_ERROR_NO;
 
+mod logger;
+mod options;
+mod status;
 pub mod stmt;
+pub mod tmq;
+mod tz;
 
 const EMPTY: &'static CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"\0") };
-static mut C_ERROR_CONTAINER: [u8; 4096] = [0; 4096];
-static mut C_ERRNO: Code = Code::Success;
+thread_local! {
+    /// Last error of the current thread, the string is owned here so it is valid until the next error.
+    static LAST_ERROR: RefCell<(Code, CString)> = RefCell::new((Code::Success, CString::default()));
+}
+
+/// Record an error in the thread-local last-error slot, it could be retrieved by
+/// `ws_last_errno()`/`ws_last_errstr()` or `ws_errno(NULL)`/`ws_errstr(NULL)`.
+fn set_c_error(err: &WsError) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = (err.code, err.message.clone()));
+}
+
+/// Reset the thread-local last-error slot.
+fn clear_c_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = (Code::Success, CString::default()));
+}
+
+fn last_errno() -> i32 {
+    LAST_ERROR.with(|last| last.borrow().0.into())
+}
+
+fn last_errstr() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        let last = last.borrow();
+        if last.0.success() {
+            EMPTY.as_ptr()
+        } else {
+            last.1.as_ptr()
+        }
+    })
+}
+
+/// Value returned by an `extern "C"` function when it panics.
+trait PanicDefault {
+    fn panic_default() -> Self;
+}
+
+impl PanicDefault for () {
+    fn panic_default() -> Self {}
+}
+
+impl PanicDefault for bool {
+    fn panic_default() -> Self {
+        false
+    }
+}
+
+impl PanicDefault for i32 {
+    fn panic_default() -> Self {
+        Code::Failed.into()
+    }
+}
+
+macro_rules! impl_panic_default_zero {
+    ($($ty:ty),*) => {
+        $(impl PanicDefault for $ty {
+            fn panic_default() -> Self {
+                0
+            }
+        })*
+    };
+}
+impl_panic_default_zero!(u32, i64, u64);
+
+impl<T> PanicDefault for *const T {
+    fn panic_default() -> Self {
+        std::ptr::null()
+    }
+}
+
+impl<T> PanicDefault for *mut T {
+    fn panic_default() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+/// Run the body of an `extern "C"` function, converting a panic into an error instead of
+/// unwinding into C.
+fn ffi_guard<R: PanicDefault>(f: impl FnOnce() -> R) -> R {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(r) => r,
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            log::error!("panic in ffi call: {message}");
+            set_c_error(&WsError::new(Code::Failed, &format!("panic: {message}")));
+            R::panic_default()
+        }
+    }
+}
+
+/// Build a C string from error message, interior NUL bytes are escaped as `\0`.
+fn error_message(message: impl Into<Vec<u8>>) -> CString {
+    CString::new(message).unwrap_or_else(|err| {
+        let bytes = err.into_vec();
+        let mut escaped = Vec::with_capacity(bytes.len() + 1);
+        for b in bytes {
+            if b == 0 {
+                escaped.extend_from_slice(b"\\0");
+            } else {
+                escaped.push(b);
+            }
+        }
+        CString::new(escaped).expect("NUL bytes have been escaped")
+    })
+}
 
 /// Opaque type definition for websocket connection.
 #[allow(non_camel_case_types)]
@@ -36,6 +154,16 @@ pub type WS_TAOS = c_void;
 #[allow(non_camel_case_types)]
 pub type WS_RES = c_void;
 
+/// Opaque type definition for a block detached from result set by `ws_take_block`.
+#[allow(non_camel_case_types)]
+pub type WS_BLOCK = c_void;
+
+/// Row type returned by `ws_fetch_row`, an array of pointers to each column value in the row.
+///
+/// A pointer is NULL when the value is NULL.
+#[allow(non_camel_case_types)]
+pub type WS_ROW = *const *const c_void;
+
 #[derive(Debug)]
 pub struct WsError {
     code: Code,
@@ -47,13 +175,35 @@ impl WsError {
     pub fn from_err(err: Box<dyn std::error::Error + 'static>) -> Self {
         Self {
             code: Code::Failed,
-            message: CString::new(err.to_string()).unwrap(),
+            message: error_message(err.to_string()),
             source: Some(err),
         }
     }
 }
+/// Tag of a boxed result handle (`WS_RES`, `WS_STMT`), see [`WsMaybeError`].
+const HANDLE_MAYBE_ERROR: u32 = 0x5753_5253;
+/// Tag of a boxed connection handle (`WS_TAOS`), see [`WsClient`].
+const HANDLE_CLIENT: u32 = 0x5753_434c;
+
+/// Read the tag which every boxed handle starts with.
+///
+/// The handle must be NULL or live, the tag of a freed handle is not readable.
+unsafe fn handle_tag(handle: *const c_void) -> Option<u32> {
+    (handle as *const u32).as_ref().copied()
+}
+
+/// Tag of a handle cleared when it's freed.
+const HANDLE_FREED: u32 = 0;
+
+/// Clear the tag of a handle to be freed, so a second free won't pass the tag check.
+unsafe fn clear_handle_tag(handle: *mut c_void) {
+    *(handle as *mut u32) = HANDLE_FREED;
+}
+
 #[derive(Debug)]
+#[repr(C)]
 pub struct WsMaybeError<T> {
+    tag: u32,
     error: Option<WsError>,
     data: *mut T,
     type_id: &'static str,
@@ -83,6 +233,28 @@ impl<T> WsMaybeError<T> {
     pub fn errstr(&self) -> Option<*const c_char> {
         self.error.as_ref().map(|s| s.message.as_ptr())
     }
+
+    /// Store the error in the handle and record it as the thread-local last error.
+    fn set_error(&mut self, err: impl Into<WsError>) {
+        let err = err.into();
+        set_c_error(&err);
+        self.error = Some(err);
+    }
+
+    /// Clear the error of a previous failed operation.
+    fn clear_error(&mut self) {
+        self.error = None;
+    }
+
+    /// Get the inner object, returns `None` when it's in error state.
+    pub fn safe_deref(&self) -> Option<&T> {
+        unsafe { self.data.as_ref() }
+    }
+
+    /// Get the mutable inner object, returns `None` when it's in error state.
+    pub fn safe_deref_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.data.as_mut() }
+    }
 }
 
 impl<T> Deref for WsMaybeError<T> {
@@ -102,6 +274,7 @@ impl<T> DerefMut for WsMaybeError<T> {
 impl<T: 'static> From<T> for WsMaybeError<T> {
     fn from(value: T) -> Self {
         Self {
+            tag: HANDLE_MAYBE_ERROR,
             error: None,
             data: Box::into_raw(Box::new(value)),
             type_id: std::any::type_name::<T>(),
@@ -112,6 +285,7 @@ impl<T: 'static> From<T> for WsMaybeError<T> {
 impl<T: 'static> From<Box<T>> for WsMaybeError<T> {
     fn from(value: Box<T>) -> Self {
         Self {
+            tag: HANDLE_MAYBE_ERROR,
             error: None,
             data: Box::into_raw(value),
             type_id: std::any::type_name::<T>(),
@@ -126,15 +300,21 @@ where
     fn from(value: Result<T, E>) -> Self {
         match value {
             Ok(value) => Self {
+                tag: HANDLE_MAYBE_ERROR,
                 error: None,
                 data: Box::into_raw(Box::new(value)),
                 type_id: std::any::type_name::<T>(),
             },
-            Err(err) => Self {
-                error: Some(err.into()),
-                data: std::ptr::null_mut(),
-                type_id: std::any::type_name::<T>(),
-            },
+            Err(err) => {
+                let err = err.into();
+                set_c_error(&err);
+                Self {
+                    tag: HANDLE_MAYBE_ERROR,
+                    error: Some(err),
+                    data: std::ptr::null_mut(),
+                    type_id: std::any::type_name::<T>(),
+                }
+            }
         }
     }
 }
@@ -146,15 +326,21 @@ where
     fn from(value: Result<Box<T>, E>) -> Self {
         match value {
             Ok(value) => Self {
+                tag: HANDLE_MAYBE_ERROR,
                 error: None,
                 data: Box::into_raw(value),
                 type_id: std::any::type_name::<T>(),
             },
-            Err(err) => Self {
-                error: Some(err.into()),
-                data: std::ptr::null_mut(),
-                type_id: std::any::type_name::<T>(),
-            },
+            Err(err) => {
+                let err = err.into();
+                set_c_error(&err);
+                Self {
+                    tag: HANDLE_MAYBE_ERROR,
+                    error: Some(err),
+                    data: std::ptr::null_mut(),
+                    type_id: std::any::type_name::<T>(),
+                }
+            }
         }
     }
 }
@@ -165,7 +351,7 @@ impl WsError {
     fn new(code: Code, message: &str) -> Self {
         Self {
             code,
-            message: CString::new(message).unwrap(),
+            message: error_message(message),
             source: None,
         }
     }
@@ -186,7 +372,7 @@ impl From<Utf8Error> for WsError {
     fn from(e: Utf8Error) -> Self {
         Self {
             code: Code::Failed,
-            message: CString::new(format!("{}", e)).unwrap(),
+            message: error_message(format!("{}", e)),
             source: Some(Box::new(e)),
         }
     }
@@ -196,7 +382,7 @@ impl From<Error> for WsError {
     fn from(e: Error) -> Self {
         Self {
             code: e.errno(),
-            message: CString::new(e.errstr()).unwrap(),
+            message: error_message(e.errstr()),
             source: None,
         }
     }
@@ -215,7 +401,17 @@ impl From<taos_ws::Error> for WsError {
     fn from(e: taos_ws::Error) -> Self {
         Self {
             code: e.errno(),
-            message: CString::new(e.errstr()).unwrap(),
+            message: error_message(e.errstr()),
+            source: None,
+        }
+    }
+}
+
+impl From<taos_ws::consumer::Error> for WsError {
+    fn from(e: taos_ws::consumer::Error) -> Self {
+        Self {
+            code: e.errno(),
+            message: error_message(e.errstr()),
             source: None,
         }
     }
@@ -225,13 +421,69 @@ impl From<DsnError> for WsError {
     fn from(e: DsnError) -> Self {
         Self {
             code: WS_ERROR_NO::DSN_ERROR.as_code(),
-            message: CString::new(e.to_string()).unwrap(),
+            message: error_message(e.to_string()),
             source: None,
         }
     }
 }
 
-type WsTaos = Result<Taos, WsError>;
+/// Connection object behind the `WS_TAOS` pointer.
+///
+/// It's shared by threads like `TAOS*`, so it's only accessed by `&WsClient`. Queries of threads
+/// go over one websocket and their responses are routed by request ids.
+#[derive(Debug)]
+#[repr(C)]
+struct WsClient {
+    tag: u32,
+    /// Shared with statements of this connection, which run query statements on it.
+    taos: Arc<Taos>,
+    server_info: OnceCell<CString>,
+    /// Shared with result sets of this connection, set when the connection is closed.
+    closed: Arc<AtomicBool>,
+    /// Time zone to format timestamps of result sets, set by `ws_set_timezone`.
+    tz: RwLock<tz::WsTimeZone>,
+    /// Default timeout of `ws_query` from `ws_options` when connected.
+    default_query_timeout: Option<Duration>,
+}
+
+impl WsClient {
+    fn new(taos: Taos, options: &options::WsOptions) -> Self {
+        Self {
+            tag: HANDLE_CLIENT,
+            taos: Arc::new(taos),
+            server_info: OnceCell::new(),
+            closed: Arc::new(AtomicBool::new(false)),
+            tz: RwLock::new(tz::WsTimeZone::Local),
+            default_query_timeout: options.query_timeout,
+        }
+    }
+
+    /// Result set of this connection, formats timestamps in the current time zone.
+    fn result_set(&self, rs: ResultSet) -> WsResultSet {
+        WsResultSet::new(rs, self.closed.clone()).with_tz(self.tz())
+    }
+
+    fn tz(&self) -> tz::WsTimeZone {
+        self.tz.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn server_info(&self) -> &CStr {
+        self.server_info.get_or_init(|| {
+            let version = self.taos.version();
+            CString::new(version.replace('\0', "")).unwrap_or_default()
+        })
+    }
+}
+
+impl Deref for WsClient {
+    type Target = Taos;
+
+    fn deref(&self) -> &Self::Target {
+        &self.taos
+    }
+}
+
+type WsTaos = Result<WsClient, WsError>;
 
 /// Only useful for developers who use along with TDengine 2.x `TAOS_FIELD` struct.
 /// It means that the struct has the same memory layout with the `TAOS_FIELD` struct
@@ -257,15 +509,21 @@ impl WS_FIELD_V2 {
     }
 }
 
+/// Copy field name into a NUL-terminated C array, truncated at UTF-8 boundary to at most 64 bytes.
+fn field_name(f_name: &str) -> [c_char; 65usize] {
+    let mut name = [0 as c_char; 65usize];
+    let mut len = f_name.len().min(64);
+    while !f_name.is_char_boundary(len) {
+        len -= 1;
+    }
+    unsafe { std::ptr::copy_nonoverlapping(f_name.as_ptr(), name.as_mut_ptr() as _, len) };
+    name
+}
+
 impl From<&Field> for WS_FIELD_V2 {
     fn from(field: &Field) -> Self {
-        let f_name = field.name();
-        let mut name = [0 as c_char; 65usize];
-        unsafe {
-            std::ptr::copy_nonoverlapping(f_name.as_ptr(), name.as_mut_ptr() as _, f_name.len())
-        };
         Self {
-            name,
+            name: field_name(field.name()),
             r#type: field.ty() as u8,
             bytes: field.bytes() as _,
         }
@@ -306,25 +564,239 @@ impl Debug for WS_FIELD {
 
 impl From<&Field> for WS_FIELD {
     fn from(field: &Field) -> Self {
-        let f_name = field.name();
-        let mut name = [0 as c_char; 65usize];
-        unsafe {
-            std::ptr::copy_nonoverlapping(f_name.as_ptr(), name.as_mut_ptr() as _, f_name.len())
-        };
         Self {
-            name,
+            name: field_name(field.name()),
             r#type: field.ty() as u8,
             bytes: field.bytes(),
         }
     }
 }
 
+/// Field struct with precision and scale, the same memory layout with `TAOS_FIELD_E` in taos.h.
+///
+/// `bytes` is the same to `WS_FIELD`, in bytes rather than characters for nchar.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct WS_FIELD_E {
+    pub name: [c_char; 65usize],
+    pub r#type: u8,
+    pub precision: u8,
+    pub scale: u8,
+    pub bytes: u32,
+}
+
+impl WS_FIELD_E {
+    pub fn name(&self) -> &CStr {
+        unsafe { CStr::from_ptr(self.name.as_ptr() as _) }
+    }
+    pub fn r#type(&self) -> Ty {
+        self.r#type.into()
+    }
+}
+
+impl Debug for WS_FIELD_E {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WS_FIELD_E")
+            .field("name", &self.name())
+            .field("type", &self.r#type)
+            .field("precision", &self.precision)
+            .field("scale", &self.scale)
+            .field("bytes", &self.bytes)
+            .finish()
+    }
+}
+
+/// Slot size of each value of the field in 2.x block layout.
+fn field_v2_slot(field: &Field) -> usize {
+    match field.ty() {
+        Ty::VarChar | Ty::NChar | Ty::Json => field.bytes() as usize + 2,
+        ty => ty.fixed_length(),
+    }
+}
+
+/// Convert block to TDengine 2.x layout: columns one by one, each one is `rows` fixed-size slots.
+///
+/// Fixed-width values are written as is, or the 2.x NULL sentinel of that type. Varchar, nchar
+/// and json values are prefixed by 2-byte length in a slot of `bytes + 2`, nchar is encoded in UCS-4.
+fn block_to_v2(block: &Block, fields: &[Field], buf: &mut Vec<u8>) {
+    fn put_var(buf: &mut Vec<u8>, slot: usize, data: &[u8]) {
+        let len = data.len().min(slot - 2);
+        buf.extend_from_slice(&(len as u16).to_le_bytes());
+        buf.extend_from_slice(&data[..len]);
+        buf.resize(buf.len() + slot - 2 - len, 0);
+    }
+    let rows = block.nrows();
+    for (col, field) in fields.iter().enumerate().take(block.ncols()) {
+        let slot = field_v2_slot(field);
+        buf.reserve(slot * rows);
+        for row in 0..rows {
+            let value = unsafe { block.get_ref_unchecked(row, col) };
+            match value {
+                BorrowedValue::Null(_) => match field.ty() {
+                    Ty::Bool => buf.push(0x02),
+                    Ty::TinyInt => buf.push(0x80),
+                    Ty::SmallInt => buf.extend_from_slice(&0x8000u16.to_le_bytes()),
+                    Ty::Int => buf.extend_from_slice(&0x80000000u32.to_le_bytes()),
+                    Ty::BigInt | Ty::Timestamp => {
+                        buf.extend_from_slice(&0x8000000000000000u64.to_le_bytes())
+                    }
+                    Ty::Float => buf.extend_from_slice(&0x7FF00000u32.to_le_bytes()),
+                    Ty::Double => buf.extend_from_slice(&0x7FFFFF0000000000u64.to_le_bytes()),
+                    Ty::VarChar => put_var(buf, slot, &[0xFF]),
+                    Ty::NChar | Ty::Json => put_var(buf, slot, &[0xFF; 4]),
+                    _ => buf.resize(buf.len() + slot, 0xFF),
+                },
+                BorrowedValue::VarChar(v) => put_var(buf, slot, v.as_bytes()),
+                BorrowedValue::NChar(v) => {
+                    let ucs4: Vec<u8> = v.chars().flat_map(|c| (c as u32).to_le_bytes()).collect();
+                    put_var(buf, slot, &ucs4)
+                }
+                BorrowedValue::Json(v) => put_var(buf, slot, &v),
+                _ => {
+                    let (_, _, ptr) = unsafe { block.get_raw_value_unchecked(row, col) };
+                    let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, slot) };
+                    buf.extend_from_slice(bytes);
+                }
+            }
+        }
+    }
+}
+
+/// Where the blocks of a `WS_RES` come from.
+#[derive(Debug)]
+enum ResultSource {
+    Query(Box<ResultSet>),
+    Tmq(Box<tmq::WsTmqMessage>),
+    /// Result of a schemaless insert, which has no blocks.
+    Schemaless {
+        req_id: u64,
+        timing: Duration,
+    },
+}
+
+impl ResultSource {
+    fn precision(&self) -> Precision {
+        match self {
+            ResultSource::Query(rs) => rs.precision(),
+            ResultSource::Tmq(msg) => msg.precision(),
+            ResultSource::Schemaless { .. } => Precision::Millisecond,
+        }
+    }
+
+    fn fields(&self) -> &[Field] {
+        match self {
+            ResultSource::Query(rs) => rs.fields(),
+            ResultSource::Tmq(msg) => msg.fields(),
+            ResultSource::Schemaless { .. } => &[],
+        }
+    }
+
+    /// Fields with precision and scale sent by server, timestamp fields default to the precision
+    /// of the result.
+    fn fields_e(&self) -> Box<[WS_FIELD_E]> {
+        let (precisions, scales) = match self {
+            ResultSource::Query(rs) => (rs.fields_precisions(), rs.fields_scales()),
+            _ => (&[][..], &[][..]),
+        };
+        let precision = self.precision();
+        self.fields()
+            .iter()
+            .enumerate()
+            .map(|(i, field)| WS_FIELD_E {
+                name: field_name(field.name()),
+                r#type: field.ty() as u8,
+                precision: precisions.get(i).copied().unwrap_or(match field.ty() {
+                    Ty::Timestamp => precision as u8,
+                    _ => 0,
+                }),
+                scale: scales.get(i).copied().unwrap_or(0),
+                bytes: field.bytes(),
+            })
+            .collect()
+    }
+
+    fn num_of_fields(&self) -> usize {
+        self.fields().len()
+    }
+
+    /// Results of update queries have no rows, fetching them should not request the server.
+    fn is_update(&self) -> bool {
+        match self {
+            ResultSource::Query(rs) => rs.is_update(),
+            ResultSource::Tmq(_) => false,
+            ResultSource::Schemaless { .. } => true,
+        }
+    }
+
+    fn fetch_raw_block(&mut self) -> Result<Option<Block>, Error> {
+        match self {
+            ResultSource::Query(rs) if rs.is_update() => Ok(None),
+            ResultSource::Query(rs) => rs.fetch_raw_block(),
+            ResultSource::Tmq(msg) => msg.fetch_raw_block(),
+            ResultSource::Schemaless { .. } => Ok(None),
+        }
+    }
+
+    async fn fetch_raw_block_async(&mut self) -> Result<Option<Block>, Error> {
+        match self {
+            ResultSource::Query(rs) if rs.is_update() => Ok(None),
+            ResultSource::Query(rs) => {
+                std::future::poll_fn(|cx| {
+                    taos_query::AsyncFetchable::fetch_raw_block(&mut **rs, cx)
+                })
+                .await
+            }
+            ResultSource::Tmq(msg) => msg.fetch_raw_block_async().await,
+            ResultSource::Schemaless { .. } => Ok(None),
+        }
+    }
+
+    fn req_id(&self) -> u64 {
+        match self {
+            ResultSource::Query(rs) => rs.req_id(),
+            ResultSource::Tmq(msg) => msg.req_id(),
+            ResultSource::Schemaless { req_id, .. } => *req_id,
+        }
+    }
+
+    fn take_timing(&self) -> Duration {
+        match self {
+            ResultSource::Query(rs) => rs.take_timing(),
+            ResultSource::Tmq(_) => Duration::ZERO,
+            ResultSource::Schemaless { timing, .. } => *timing,
+        }
+    }
+
+    fn stop(&self) {
+        if let ResultSource::Query(rs) = self {
+            block_in_place_or_global(rs.stop());
+        }
+    }
+}
+
 #[derive(Debug)]
 struct WsResultSet {
-    rs: ResultSet,
+    rs: ResultSource,
     block: Option<Block>,
-    fields: Vec<WS_FIELD>,
-    fields_v2: Vec<WS_FIELD_V2>,
+    /// Fields built once on the first `ws_fetch_fields`, so the pointers are stable.
+    fields: OnceCell<Box<[WS_FIELD]>>,
+    fields_v2: OnceCell<Box<[WS_FIELD_V2]>>,
+    fields_e: OnceCell<Box<[WS_FIELD_E]>>,
+    block_v2: Vec<u8>,
+    row: usize,
+    row_data: Vec<*const c_void>,
+    lengths: Vec<i32>,
+    column_nulls: Vec<Vec<c_char>>,
+    total_rows: u64,
+    affected_rows: i64,
+    stopped: AtomicBool,
+    closed: Arc<AtomicBool>,
+    fetch_lock: Arc<tokio::sync::Mutex<()>>,
+    /// Connection owned by the result set, e.g. the one of a query statement.
+    conn: Option<Arc<Taos>>,
+    tz: tz::WsTimeZone,
+    /// Current block is not copied out by `ws_fetch_block_into` for a too small buffer.
+    pending_block: bool,
 }
 
 // impl Deref for WsResultSet {
@@ -333,61 +805,203 @@ struct WsResultSet {
 // }
 
 impl WsResultSet {
-    fn new(rs: ResultSet) -> Self {
+    fn new(rs: ResultSet, closed: Arc<AtomicBool>) -> Self {
+        let affected_rows = rs.affected_rows64();
+        Self::with_source(ResultSource::Query(Box::new(rs)), affected_rows, closed)
+    }
+
+    fn with_source(rs: ResultSource, affected_rows: i64, closed: Arc<AtomicBool>) -> Self {
         Self {
             rs,
             block: None,
-            fields: Vec::new(),
-            fields_v2: Vec::new(),
+            fields: OnceCell::new(),
+            fields_v2: OnceCell::new(),
+            fields_e: OnceCell::new(),
+            block_v2: Vec::new(),
+            row: 0,
+            row_data: Vec::new(),
+            lengths: Vec::new(),
+            column_nulls: Vec::new(),
+            total_rows: 0,
+            affected_rows,
+            stopped: AtomicBool::new(false),
+            closed,
+            fetch_lock: Arc::new(tokio::sync::Mutex::new(())),
+            conn: None,
+            tz: tz::WsTimeZone::Local,
+            pending_block: false,
         }
     }
 
+    fn with_tz(mut self, tz: tz::WsTimeZone) -> Self {
+        self.tz = tz;
+        self
+    }
+
     fn precision(&self) -> Precision {
         self.rs.precision()
     }
 
     fn affected_rows(&self) -> i32 {
-        self.rs.affected_rows() as _
+        self.affected_rows.min(i32::MAX as i64) as _
+    }
+
+    fn affected_rows64(&self) -> i64 {
+        self.affected_rows
     }
 
     fn num_of_fields(&self) -> i32 {
         self.rs.num_of_fields() as _
     }
 
-    fn get_fields(&mut self) -> *const WS_FIELD {
-        if self.fields.len() == self.rs.num_of_fields() {
-            self.fields.as_ptr()
-        } else {
-            self.fields.clear();
-            self.fields
-                .extend(self.rs.fields().iter().map(WS_FIELD::from));
-            self.fields.as_ptr()
-        }
+    fn get_fields(&self) -> *const WS_FIELD {
+        self.fields
+            .get_or_init(|| self.rs.fields().iter().map(WS_FIELD::from).collect())
+            .as_ptr()
     }
-    fn get_fields_v2(&mut self) -> *const WS_FIELD_V2 {
-        if self.fields_v2.len() == self.rs.num_of_fields() {
-            self.fields_v2.as_ptr()
-        } else {
-            self.fields_v2.clear();
-            self.fields_v2
-                .extend(self.rs.fields().iter().map(WS_FIELD_V2::from));
-            self.fields_v2.as_ptr()
+    fn get_fields_v2(&self) -> *const WS_FIELD_V2 {
+        self.fields_v2
+            .get_or_init(|| self.rs.fields().iter().map(WS_FIELD_V2::from).collect())
+            .as_ptr()
+    }
+    fn get_fields_e(&self) -> *const WS_FIELD_E {
+        self.fields_e.get_or_init(|| self.rs.fields_e()).as_ptr()
+    }
+
+    unsafe fn fetch_block_v2(
+        &mut self,
+        ptr: *mut *const c_void,
+        rows: *mut i32,
+    ) -> Result<(), Error> {
+        let mut v3 = std::ptr::null();
+        self.fetch_block(&mut v3, rows)?;
+        self.block_v2.clear();
+        if let Some(block) = self.block.as_ref() {
+            block_to_v2(block, self.rs.fields(), &mut self.block_v2);
+            *ptr = self.block_v2.as_ptr() as _;
         }
+        Ok(())
     }
 
     unsafe fn fetch_block(&mut self, ptr: *mut *const c_void, rows: *mut i32) -> Result<(), Error> {
         log::debug!("fetch block with ptr {ptr:p}");
-        self.block = self.rs.fetch_raw_block()?;
-        if let Some(block) = self.block.as_ref() {
-            *ptr = block.as_raw_bytes().as_ptr() as _;
-            *rows = block.nrows() as _;
-        } else {
+        self.check_closed()?;
+        if self.is_stopped() {
+            log::debug!("query has been stopped, no more blocks");
+            self.block = None;
+            self.lengths.clear();
             *rows = 0;
+            return Ok(());
+        }
+        let block = self.rs.fetch_raw_block()?;
+        *rows = self.set_block(block);
+        if self.block.is_some() {
+            *ptr = self.raw_block();
         }
         log::debug!("fetch block with ptr {ptr:p} with rows {}", *rows);
         Ok(())
     }
 
+    /// Copy next block into `buf`, or keep it for the next call if `cap` is less than its size.
+    ///
+    /// Returns false if the buffer is too small, `written` is the required size then.
+    unsafe fn fetch_block_into(
+        &mut self,
+        buf: *mut c_void,
+        cap: usize,
+        written: *mut usize,
+        rows: *mut i32,
+    ) -> Result<bool, Error> {
+        if !self.pending_block {
+            let mut ptr = std::ptr::null();
+            self.fetch_block(&mut ptr, rows)?;
+        }
+        let bytes = self.block.as_ref().map(|b| b.as_raw_bytes()).unwrap_or(&[]);
+        *written = bytes.len();
+        *rows = self.block.as_ref().map(|b| b.nrows() as i32).unwrap_or(0);
+        if bytes.len() > cap {
+            self.pending_block = true;
+            return Ok(false);
+        }
+        if !bytes.is_empty() {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf as *mut u8, bytes.len());
+        }
+        self.pending_block = false;
+        Ok(true)
+    }
+
+    fn set_block(&mut self, block: Option<Block>) -> i32 {
+        if let (ResultSource::Tmq(_), Some(prev), Some(next)) = (&self.rs, &self.block, &block) {
+            // Blocks of a message may come from tables of different schemas.
+            if prev.fields() != next.fields() {
+                self.fields.take();
+                self.fields_v2.take();
+                self.fields_e.take();
+            }
+        }
+        self.block = block;
+        self.pending_block = false;
+        self.row = 0;
+        self.lengths.clear();
+        self.column_nulls.clear();
+        let rows = self.block.as_ref().map(|b| b.nrows()).unwrap_or(0);
+        self.total_rows += rows as u64;
+        rows as i32
+    }
+
+    fn take_block(&mut self) -> Option<Block> {
+        self.pending_block = false;
+        self.row = 0;
+        self.lengths.clear();
+        self.column_nulls.clear();
+        self.block_v2.clear();
+        self.block.take()
+    }
+
+    fn raw_block(&self) -> *const c_void {
+        self.block
+            .as_ref()
+            .map(|b| b.as_raw_bytes().as_ptr() as *const c_void)
+            .unwrap_or(std::ptr::null())
+    }
+
+    unsafe fn fetch_row(&mut self) -> Result<WS_ROW, Error> {
+        self.check_closed()?;
+        if self.is_stopped() {
+            return Ok(std::ptr::null());
+        }
+        loop {
+            if let Some(block) = self.block.as_ref() {
+                if self.row < block.nrows() {
+                    let row = self.row;
+                    self.row_data.clear();
+                    self.lengths.clear();
+                    for col in 0..block.ncols() {
+                        let (_, len, ptr) = block.get_raw_value_unchecked(row, col);
+                        self.row_data.push(ptr);
+                        self.lengths.push(if ptr.is_null() { 0 } else { len as _ });
+                    }
+                    self.row += 1;
+                    return Ok(self.row_data.as_ptr());
+                }
+            }
+            log::debug!("current block exhausted, fetch next block");
+            let block = self.rs.fetch_raw_block()?;
+            self.set_block(block);
+            if self.block.is_none() {
+                return Ok(std::ptr::null());
+            }
+        }
+    }
+
+    fn fetch_lengths(&self) -> *const i32 {
+        if self.lengths.is_empty() {
+            std::ptr::null()
+        } else {
+            self.lengths.as_ptr()
+        }
+    }
+
     unsafe fn get_raw_value(&mut self, row: usize, col: usize) -> (Ty, u32, *const c_void) {
         log::debug!("try to get raw value at ({row}, {col})");
         match self.block.as_ref() {
@@ -405,27 +1019,100 @@ impl WsResultSet {
         }
     }
 
+    /// Null indicators of column `col` in current block, one byte per row.
+    fn column_nulls(&mut self, col: usize) -> Option<&[c_char]> {
+        let block = self.block.as_ref()?;
+        if col >= block.ncols() {
+            return None;
+        }
+        if self.column_nulls.len() != block.ncols() {
+            self.column_nulls = vec![Vec::new(); block.ncols()];
+        }
+        let nulls = &mut self.column_nulls[col];
+        if nulls.len() != block.nrows() {
+            nulls.extend((0..block.nrows()).map(|row| block.is_null(row, col) as c_char));
+        }
+        Some(nulls)
+    }
+
     fn take_timing(&mut self) -> Duration {
         self.rs.take_timing()
     }
 
-    fn stop_query(&mut self) {
-        block_in_place_or_global(self.rs.stop());
+    fn check_closed(&self) -> Result<(), Error> {
+        if self.closed.load(Ordering::SeqCst) {
+            Err(Error::WsClosed(
+                "connection of the result set is closed".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    fn stop_query(&self) {
+        if !self.stopped.swap(true, Ordering::SeqCst) {
+            self.rs.stop();
+        }
     }
 }
 
+/// Environment variables of DSN for NULL or empty DSN, in order.
+const DSN_ENV_VARS: [&str; 2] = ["TDENGINE_CLOUD_DSN", "TAOS_DSN"];
+
+/// DSN from the first non-empty one of `DSN_ENV_VARS`, or the local server with default user.
+fn default_dsn(env: impl Fn(&str) -> Option<String>) -> String {
+    DSN_ENV_VARS
+        .iter()
+        .find_map(|key| env(key).filter(|dsn| !dsn.trim().is_empty()))
+        .unwrap_or_else(|| "ws://localhost:6041".to_string())
+}
+
 unsafe fn connect_with_dsn(dsn: *const c_char) -> WsTaos {
-    let dsn = if dsn.is_null() {
-        CStr::from_bytes_with_nul(b"taos://localhost:6041\0").unwrap()
-    } else {
-        CStr::from_ptr(dsn)
+    let dsn = match opt_c_str(dsn)?.filter(|dsn| !dsn.trim().is_empty()) {
+        Some(dsn) => Cow::Borrowed(dsn),
+        None => Cow::Owned(default_dsn(|key| std::env::var(key).ok())),
     };
-    let dsn = dsn.to_str()?;
-    let builder = TaosBuilder::from_dsn(dsn)?;
+    let options = options::WsOptions::current();
+    let mut builder = TaosBuilder::from_dsn(dsn.as_ref())?;
+    options.apply(&mut builder);
+    let mut taos = builder.build()?;
+
+    builder.ping(&mut taos)?;
+    Ok(WsClient::new(taos, &options))
+}
+
+unsafe fn opt_c_str<'a>(ptr: *const c_char) -> Result<Option<&'a str>, WsError> {
+    if ptr.is_null() {
+        Ok(None)
+    } else {
+        Ok(Some(CStr::from_ptr(ptr).to_str()?))
+    }
+}
+
+unsafe fn connect(
+    host: *const c_char,
+    user: *const c_char,
+    pass: *const c_char,
+    db: *const c_char,
+    port: u16,
+) -> WsTaos {
+    let options = options::WsOptions::current();
+    let mut builder = TaosBuilder::from_args(
+        opt_c_str(host)?,
+        port,
+        opt_c_str(user)?,
+        opt_c_str(pass)?,
+        opt_c_str(db)?,
+    );
+    options.apply(&mut builder);
     let mut taos = builder.build()?;
 
     builder.ping(&mut taos)?;
-    Ok(taos)
+    Ok(WsClient::new(taos, &options))
 }
 
 /// Enable inner log to stdout with environment RUST_LOG.
@@ -443,369 +1130,2922 @@ unsafe fn connect_with_dsn(dsn: *const c_char) -> WsTaos {
 /// ```
 #[no_mangle]
 pub unsafe extern "C" fn ws_enable_log() {
-    static ONCE_INIT: std::sync::Once = std::sync::Once::new();
-    ONCE_INIT.call_once(|| {
-        let mut builder = pretty_env_logger::formatted_timed_builder();
-        builder.format_timestamp_nanos();
-        if let Ok(s) = ::std::env::var("RUST_LOG") {
-            builder.parse_filters(&s);
-        }
-        builder.init();
-    });
-    log::debug!("enable logger to stdout");
+    ffi_guard(|| {
+        logger::enable();
+        log::debug!("enable logger");
+    })
 }
 
-/// Connect via dsn string, returns NULL if failed.
+/// Enable inner log to rotating files `taosws.log*` in `log_dir`, or back to stderr if NULL.
 ///
-/// Remember to check the return pointer is null and get error details.
+/// Could be called before or after connecting, and called again to change the directory.
+/// Returns 0 on success, non-zero if the directory or log file could not be created.
 ///
 /// # Example
 ///
 /// ```c
-/// char* dsn = "taos://localhost:6041";
-/// WS_TAOS* taos = ws_connect_with_dsn(dsn);
-/// if (taos == NULL) {
-///   int errno = ws_errno(NULL);
-///   char* errstr = ws_errstr(NULL);
-///   printf("Connection failed[%d]: %s", errno, errstr);
+/// ws_enable_log_in_dir("/var/log/taos");
+/// ws_set_log_level("debug");
+/// ```
+///
+/// # Safety
+///
+/// `log_dir` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ws_enable_log_in_dir(log_dir: *const c_char) -> i32 {
+    ffi_guard(|| {
+        let res = opt_c_str(log_dir).and_then(|dir| {
+            logger::enable_in_dir(dir)
+                .map_err(|err| WsError::new(Code::Failed, &format!("enable log failed: {err}")))
+        });
+        match res {
+            Ok(()) => {
+                clear_c_error();
+                log::debug!(
+                    "enable logger in dir {:?}",
+                    opt_c_str(log_dir).ok().flatten()
+                );
+                0
+            }
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
+}
+
+/// Set max log level, one of `off`, `error`, `warn`, `info`, `debug` and `trace`.
+///
+/// The logger is enabled if not yet. When RUST_LOG is set, its filters still apply.
+/// Returns 0 on success, non-zero for unknown level.
+///
+/// # Safety
+///
+/// `level` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ws_set_log_level(level: *const c_char) -> i32 {
+    ffi_guard(|| {
+        let res = opt_c_str(level).and_then(|level| {
+            let level = level.unwrap_or_default();
+            logger::set_level(level)
+                .map_err(|_| WsError::new(Code::Failed, &format!("invalid log level: {level:?}")))
+        });
+        match res {
+            Ok(()) => {
+                clear_c_error();
+                0
+            }
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
+}
+
+/// Connect via dsn string, returns NULL if failed.
+///
+/// A NULL or empty dsn is read from environment variable `TDENGINE_CLOUD_DSN` or `TAOS_DSN`,
+/// or `ws://localhost:6041` with user `root` and password `taosdata` if neither is set.
+///
+/// Remember to check the return pointer is null and get error details.
+///
+/// # Example
+///
+/// ```c
+/// char* dsn = "taos://localhost:6041";
+/// WS_TAOS* taos = ws_connect_with_dsn(dsn);
+/// if (taos == NULL) {
+///   int errno = ws_errno(NULL);
+///   char* errstr = ws_errstr(NULL);
+///   printf("Connection failed[%d]: %s", errno, errstr);
 ///   exit(-1);
 /// }
 /// ```
 #[no_mangle]
 pub unsafe extern "C" fn ws_connect_with_dsn(dsn: *const c_char) -> *mut WS_TAOS {
-    C_ERRNO = Code::Success;
-    match connect_with_dsn(dsn) {
-        Ok(client) => Box::into_raw(Box::new(client)) as _,
-        Err(err) => {
-            C_ERRNO = err.code;
-            let dst = C_ERROR_CONTAINER.as_mut_ptr();
-            let errstr = err.message.as_bytes_with_nul();
-            std::ptr::copy_nonoverlapping(errstr.as_ptr(), dst, errstr.len());
-            std::ptr::null_mut()
+    ffi_guard(|| {
+        clear_c_error();
+        match connect_with_dsn(dsn) {
+            Ok(client) => Box::into_raw(Box::new(client)) as _,
+            Err(err) => {
+                set_c_error(&err);
+                std::ptr::null_mut()
+            }
         }
-    }
+    })
+}
+
+/// Same to taos_connect, connect with discrete arguments, returns NULL if failed.
+///
+/// NULL or empty host defaults to `localhost`, port `0` defaults to `6041`,
+/// NULL user/pass fall back to `root`/`taosdata`, db is optional.
+///
+/// # Example
+///
+/// ```c
+/// WS_TAOS* taos = ws_connect("localhost", "root", "taosdata", NULL, 6041);
+/// if (taos == NULL) {
+///   int errno = ws_errno(NULL);
+///   char* errstr = ws_errstr(NULL);
+///   printf("Connection failed[%d]: %s", errno, errstr);
+///   exit(-1);
+/// }
+/// ```
+///
+/// # Safety
+///
+/// Each of the strings must be NULL or NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn ws_connect(
+    host: *const c_char,
+    user: *const c_char,
+    pass: *const c_char,
+    db: *const c_char,
+    port: u16,
+) -> *mut WS_TAOS {
+    ffi_guard(|| {
+        clear_c_error();
+        match connect(host, user, pass, db, port) {
+            Ok(client) => Box::into_raw(Box::new(client)) as _,
+            Err(err) => {
+                set_c_error(&err);
+                std::ptr::null_mut()
+            }
+        }
+    })
 }
 
 #[no_mangle]
-/// Same to taos_close. This should always be called after everything done with the connection.
+/// Same to taos_get_server_info, returns the server version string.
+///
+/// The version is cached in the connection, so the pointer is valid until `ws_close`.
+/// A NULL connection returns an empty string.
 pub unsafe extern "C" fn ws_get_server_info(taos: *mut WS_TAOS) -> *const c_char {
-    static mut VERSION_INFO: [u8; 128] = [0; 128];
-    if taos.is_null() {
-        return VERSION_INFO.as_ptr() as *const c_char;
-    }
-    if VERSION_INFO[0] == 0 {
-        if let Some(taos) = (taos as *mut Taos).as_mut() {
-            let v = taos.version();
-            std::ptr::copy_nonoverlapping(v.as_ptr(), VERSION_INFO.as_mut_ptr(), v.len());
+    ffi_guard(|| match (taos as *const WsClient).as_ref() {
+        Some(client) => client.server_info().as_ptr(),
+        None => EMPTY.as_ptr(),
+    })
+}
+
+#[no_mangle]
+/// Set time zone of the connection, like the `timezone` option of native connections.
+///
+/// `tz` is an IANA name like `Asia/Shanghai`, or a fixed offset like `+08:00` or `UTC-5`. Timestamps
+/// of later result sets of this connection are formatted in it, e.g. by `ws_get_value_as_string`.
+/// A NULL `tz` resets to the process time zone. Returns 0 on success, or an error code and keeps
+/// the current time zone if `tz` is invalid.
+///
+/// # Safety
+///
+/// `taos` must be NULL or a live connection, `tz` must be NULL or a NUL-terminated string.
+pub unsafe extern "C" fn ws_set_timezone(taos: *mut WS_TAOS, tz: *const c_char) -> i32 {
+    ffi_guard(|| {
+        let Some(client) = (taos as *const WsClient).as_ref() else {
+            return null_pointer_error("WS_TAOS is null");
+        };
+        let tz = opt_c_str(tz).and_then(|tz| match tz {
+            Some(tz) => tz::WsTimeZone::parse(tz).map_err(|err| WsError::new(Code::Failed, &err)),
+            None => Ok(tz::WsTimeZone::Local),
+        });
+        match tz {
+            Ok(tz) => {
+                log::debug!("set time zone of connection to {tz}");
+                *client.tz.write().unwrap_or_else(|e| e.into_inner()) = tz;
+                0
+            }
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
         }
-        VERSION_INFO.as_ptr() as *const c_char
-    } else {
-        VERSION_INFO.as_ptr() as *const c_char
+    })
+}
+
+const CLIENT_INFO: &str = concat!(env!("TAOSWS_CLIENT_INFO"), "\0");
+
+const fn parse_version_part(s: &str) -> u32 {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    let mut v = 0;
+    while i < bytes.len() {
+        v = v * 10 + (bytes[i] - b'0') as u32;
+        i += 1;
     }
+    v
+}
+
+const CLIENT_VERSION_NUMBER: u32 = parse_version_part(env!("CARGO_PKG_VERSION_MAJOR")) * 1_000_000
+    + parse_version_part(env!("CARGO_PKG_VERSION_MINOR")) * 1_000
+    + parse_version_part(env!("CARGO_PKG_VERSION_PATCH"));
+
+#[no_mangle]
+/// Same to taos_get_client_info, returns the version of this library, with git hash if available.
+///
+/// The returned string is static and identical on every call.
+pub extern "C" fn ws_get_client_info() -> *const c_char {
+    ffi_guard(|| CLIENT_INFO.as_ptr() as *const c_char)
+}
+
+#[no_mangle]
+/// Numeric library version as `major * 1000000 + minor * 1000 + patch`, e.g. `2000` for 0.2.0.
+pub extern "C" fn ws_client_version_number() -> u32 {
+    ffi_guard(|| CLIENT_VERSION_NUMBER)
 }
 
 #[no_mangle]
 /// Same to taos_close. This should always be called after everything done with the connection.
 pub unsafe extern "C" fn ws_close(taos: *mut WS_TAOS) {
-    if !taos.is_null() {
+    ffi_guard(|| {
+        if taos.is_null() {
+            return;
+        }
+        if handle_tag(taos) != Some(HANDLE_CLIENT) {
+            log::warn!("close an invalid or already closed connection {taos:p}, ignored");
+            return;
+        }
         log::debug!("close connection {taos:p}");
-        let client = Box::from_raw(taos as *mut Taos);
+        clear_handle_tag(taos);
+        let client = Box::from_raw(taos as *mut WsClient);
+        client.closed.store(true, Ordering::SeqCst);
         // client.close();
         drop(client);
+    })
+}
+
+unsafe fn select_db(taos: *mut WS_TAOS, db: *const c_char) -> WsResult<()> {
+    let client = (taos as *const WsClient)
+        .as_ref()
+        .ok_or(WsError::new(Code::Failed, "client pointer it null"))?;
+    if db.is_null() {
+        return Err(WsError::new(Code::Failed, "database name is null"));
+    }
+    let db = database_name(CStr::from_ptr(db).to_str()?)?;
+    client.select_db(db)?;
+    Ok(())
+}
+
+/// Database name of `db`, which may be quoted in backticks, like `` `db` ``.
+fn database_name(db: &str) -> WsResult<&str> {
+    let name = db
+        .strip_prefix('`')
+        .and_then(|db| db.strip_suffix('`'))
+        .unwrap_or(db);
+    if name.is_empty()
+        || name.len() > 64
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(WsError::new(
+            Code::Failed,
+            &format!("invalid database name: {db}"),
+        ));
     }
+    Ok(name)
+}
+
+/// Same to taos_select_db, switch to database `db` for current connection, which may be quoted
+/// in backticks like `` `db` ``.
+///
+/// Returns 0 on success, or the error code, the error message could be got by `ws_errstr(NULL)`.
+///
+/// # Safety
+///
+/// `taos` must be NULL or a live connection, `db` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ws_select_db(taos: *mut WS_TAOS, db: *const c_char) -> i32 {
+    ffi_guard(|| match select_db(taos, db) {
+        Ok(()) => {
+            clear_c_error();
+            0
+        }
+        Err(err) => {
+            set_c_error(&err);
+            err.code.into()
+        }
+    })
+}
+
+unsafe fn get_current_db(taos: *mut WS_TAOS) -> WsResult<String> {
+    let client = (taos as *const WsClient)
+        .as_ref()
+        .ok_or(WsError::new(Code::Failed, "client pointer it null"))?;
+    Ok(client.current_database()?.unwrap_or_default())
+}
+
+/// Same to taos_get_current_db, copy current database name into `database` with buffer length `len`.
+///
+/// `*required` is always set if not NULL, to the required length (including the trailing NUL), or
+/// 0 if the name could not be got. When the buffer is NULL or too small, the name is truncated
+/// and an error code is returned, so a NULL buffer asks for the length. Returns 0 on success.
+///
+/// # Safety
+///
+/// `taos` must be NULL or a live connection, `database` must be NULL or writable for `len`
+/// bytes and `required` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn ws_get_current_db(
+    taos: *mut WS_TAOS,
+    database: *mut c_char,
+    len: i32,
+    required: *mut i32,
+) -> i32 {
+    ffi_guard(|| {
+        let db = get_current_db(taos);
+        if !required.is_null() {
+            *required = db.as_ref().map_or(0, |db| db.len() as i32 + 1);
+        }
+        match db {
+            Ok(_) if database.is_null() || len <= 0 => {
+                let err = WsError::new(Code::Failed, "invalid database buffer");
+                set_c_error(&err);
+                err.code.into()
+            }
+            Ok(db) => {
+                let bytes = db.as_bytes();
+                let len = len as usize;
+                let n = bytes.len().min(len - 1);
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), database as *mut u8, n);
+                *database.add(n) = 0;
+                if bytes.len() < len {
+                    0
+                } else {
+                    let err = WsError::new(
+                        Code::Failed,
+                        &format!("buffer length {len} is too small for database name {db}"),
+                    );
+                    set_c_error(&err);
+                    err.code.into()
+                }
+            }
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
 }
 
 unsafe fn query_with_sql(taos: *mut WS_TAOS, sql: *const c_char) -> WsResult<WsResultSet> {
-    let client = (taos as *mut Taos)
-        .as_mut()
+    let client = (taos as *const WsClient)
+        .as_ref()
         .ok_or(WsError::new(Code::Failed, "client pointer it null"))?;
 
     let sql = CStr::from_ptr(sql as _).to_str()?;
-    let rs = client.query(sql)?;
-    Ok(WsResultSet::new(rs))
+    let rs = match client.default_query_timeout {
+        Some(timeout) => client.query_timeout(sql, timeout)?,
+        None => client.query(sql)?,
+    };
+    Ok(client.result_set(rs))
 }
 
-unsafe fn query_with_sql_timeout(
+/// Split sql into top-level statements by `;`, which are not in quotes or comments.
+///
+/// Statements of only whitespaces and comments are skipped.
+fn split_statements(sql: &str) -> Vec<&str> {
+    let bytes = sql.as_bytes();
+    let mut statements = Vec::new();
+    let (mut start, mut i, mut has_token) = (0, 0, false);
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`') => {
+                has_token = true;
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !bytes[i..].starts_with(b"*/") {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b';' => {
+                if has_token {
+                    statements.push(sql[start..i].trim());
+                }
+                start = i + 1;
+                has_token = false;
+            }
+            b if !b.is_ascii_whitespace() => has_token = true,
+            _ => (),
+        }
+        i += 1;
+    }
+    if has_token {
+        statements.push(sql[start..].trim());
+    }
+    statements
+}
+
+/// Execute statements separated by `;` one by one, returns result set of the last statement with
+/// affected rows of all statements.
+unsafe fn query_multi(taos: *mut WS_TAOS, sql: *const c_char) -> WsResult<WsResultSet> {
+    if sql.is_null() {
+        return Err(WsError::new(
+            WS_ERROR_NO::NULL_POINTER.as_code(),
+            "sql is null",
+        ));
+    }
+    log::debug!("query multi {:?}", CStr::from_ptr(sql));
+    let statements = split_statements(CStr::from_ptr(sql as _).to_str()?);
+    if statements.len() <= 1 {
+        return query_with_sql(taos, sql);
+    }
+    let client = (taos as *const WsClient)
+        .as_ref()
+        .ok_or(WsError::new(Code::Failed, "client pointer it null"))?;
+
+    let mut affected_rows = 0;
+    let mut last = None;
+    for (i, statement) in statements.iter().enumerate() {
+        log::trace!(
+            "query statement {} of {}: {statement}",
+            i + 1,
+            statements.len()
+        );
+        let rs = match client.default_query_timeout {
+            Some(timeout) => client.query_timeout(statement, timeout),
+            None => client.query(statement),
+        }
+        .map_err(|err| {
+            let err = WsError::from(err);
+            WsError::new(
+                err.code,
+                &format!(
+                    "statement {} failed: {}",
+                    i + 1,
+                    err.message.to_string_lossy()
+                ),
+            )
+        })?;
+        affected_rows += rs.affected_rows64();
+        last = Some(rs);
+    }
+    let mut rs = client.result_set(last.expect("at least two statements"));
+    rs.affected_rows = affected_rows;
+    Ok(rs)
+}
+
+unsafe fn query_with_req_id(
     taos: *mut WS_TAOS,
     sql: *const c_char,
-    timeout: Duration,
+    req_id: u64,
 ) -> WsResult<WsResultSet> {
-    let _ = timeout;
-    let client = (taos as *mut Taos)
-        .as_mut()
+    let client = (taos as *const WsClient)
+        .as_ref()
         .ok_or(WsError::new(Code::Failed, "client pointer it null"))?;
+    if sql.is_null() {
+        return Err(WsError::new(
+            WS_ERROR_NO::NULL_POINTER.as_code(),
+            "sql is null",
+        ));
+    }
 
     let sql = CStr::from_ptr(sql as _).to_str()?;
-    let rs = client.query(sql)?;
-    Ok(WsResultSet::new(rs))
+    log::debug!("query {sql:?} with req id {req_id}");
+    let rs = client.query_with_req_id(sql, req_id)?;
+    Ok(client.result_set(rs))
 }
 
-#[no_mangle]
-/// Query with a sql command, returns pointer to result set.
-///
-/// Please always use `ws_errno` to check it work and `ws_free_result` to free memory.
-pub unsafe extern "C" fn ws_query(taos: *mut WS_TAOS, sql: *const c_char) -> *mut WS_RES {
-    log::debug!("query {:?}", CStr::from_ptr(sql));
-    let res: WsMaybeError<WsResultSet> = query_with_sql(taos, sql).into();
-    log::debug!("query done: {:?}", res);
-    Box::into_raw(Box::new(res)) as _
+unsafe fn query_with_sql_timeout(
+    taos: *mut WS_TAOS,
+    sql: *const c_char,
+    timeout: Duration,
+) -> WsResult<WsResultSet> {
+    let client = (taos as *const WsClient)
+        .as_ref()
+        .ok_or(WsError::new(Code::Failed, "client pointer it null"))?;
+
+    let sql = CStr::from_ptr(sql as _).to_str()?;
+    let rs = if timeout.is_zero() {
+        client.query(sql)?
+    } else {
+        client.query_timeout(sql, timeout)?
+    };
+    Ok(client.result_set(rs))
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn ws_stop_query(rs: *mut WS_RES) {
-    match (rs as *mut WsMaybeError<WsResultSet>).as_mut() {
-        Some(rs) => {
-            rs.stop_query();
+#[allow(clippy::too_many_arguments)]
+unsafe fn schemaless_insert_raw(
+    taos: *mut WS_TAOS,
+    data: *const c_char,
+    len: i32,
+    total_rows: *mut i32,
+    protocol: i32,
+    precision: i32,
+    ttl: i32,
+    req_id: u64,
+) -> WsResult<WsResultSet> {
+    let client = (taos as *const WsClient)
+        .as_ref()
+        .ok_or(WsError::new(Code::Failed, "client pointer it null"))?;
+    if data.is_null() || len < 0 {
+        return Err(WsError::new(
+            WS_ERROR_NO::NULL_POINTER.as_code(),
+            "schemaless data is null or of negative length",
+        ));
+    }
+    let protocol = match protocol {
+        1..=3 => protocol as u8,
+        _ => {
+            return Err(WsError::new(
+                Code::Failed,
+                &format!("invalid schemaless protocol {protocol}"),
+            ))
+        }
+    };
+    let precision = match precision {
+        0 => "",
+        1 => "h",
+        2 => "m",
+        3 => "s",
+        4 => "ms",
+        5 => "u",
+        6 => "ns",
+        _ => {
+            return Err(WsError::new(
+                Code::Failed,
+                &format!("invalid schemaless precision {precision}"),
+            ))
         }
-        _ => {}
+    };
+    let data = std::slice::from_raw_parts(data as *const u8, len as usize);
+    let data = std::str::from_utf8(data)?;
+    let resp = client.schemaless_insert(data, protocol, precision, ttl, req_id)?;
+    if !total_rows.is_null() {
+        *total_rows = resp.total_rows.min(i32::MAX as usize) as i32;
     }
+    let rs = ResultSource::Schemaless {
+        req_id,
+        timing: resp.timing,
+    };
+    Ok(WsResultSet::with_source(
+        rs,
+        resp.affected_rows as i64,
+        client.closed.clone(),
+    ))
 }
 
 #[no_mangle]
-/// Query a sql with timeout.
+/// Same to taos_schemaless_insert_raw_ttl_with_reqid, write `len` bytes of schemaless `data`.
 ///
-/// Please always use `ws_errno` to check it work and `ws_free_result` to free memory.
-pub unsafe extern "C" fn ws_query_timeout(
+/// `protocol` is 1 for line protocol, 2 for OpenTSDB telnet and 3 for OpenTSDB json. `precision`
+/// is 0 for not configured, or 1 to 6 for hours, minutes, seconds, milliseconds, microseconds and
+/// nanoseconds, same to `TSDB_SML_TIMESTAMP_TYPE`. `ttl` and non-zero `req_id` are forwarded to
+/// taosAdapter. The rows accepted are written to `*total_rows` if it's not NULL, rows written are
+/// got by `ws_affected_rows`. Check the result by `ws_errno` and free it by `ws_free_result`.
+///
+/// # Safety
+///
+/// `taos` must be NULL or a live connection, `data` must be readable for `len` bytes and
+/// `total_rows` must be NULL or writable.
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn ws_schemaless_insert_raw(
     taos: *mut WS_TAOS,
-    sql: *const c_char,
-    seconds: u32,
+    data: *const c_char,
+    len: i32,
+    total_rows: *mut i32,
+    protocol: i32,
+    precision: i32,
+    ttl: i32,
+    req_id: u64,
 ) -> *mut WS_RES {
-    let res: WsMaybeError<WsResultSet> =
-        query_with_sql_timeout(taos, sql, Duration::from_secs(seconds as _)).into();
-    Box::into_raw(Box::new(res)) as _
+    ffi_guard(|| {
+        let res: WsMaybeError<WsResultSet> = schemaless_insert_raw(
+            taos, data, len, total_rows, protocol, precision, ttl, req_id,
+        )
+        .into();
+        log::debug!("schemaless insert done: {:?}", res);
+        Box::into_raw(Box::new(res)) as _
+    })
 }
 
-/// Get taosc execution timing duration as nanoseconds.
-#[no_mangle]
-pub unsafe extern "C" fn ws_take_timing(rs: *mut WS_RES) -> i64 {
-    match (rs as *mut WsMaybeError<WsResultSet>).as_mut() {
-        Some(rs) => rs.take_timing().as_nanos() as _,
+/// Copy the raw block of `len` bytes at `block` of table `table`, after checking the length in
+/// its header is `len` and its columns fill it with `rows` rows.
+unsafe fn raw_block_from_ptr(
+    rows: i32,
+    block: *const c_void,
+    len: i32,
+    table: *const c_char,
+) -> WsResult<Block> {
+    if block.is_null() {
+        return Err(WsError::new(
+            WS_ERROR_NO::NULL_POINTER.as_code(),
+            "raw block is null",
+        ));
+    }
+    let table = match opt_c_str(table)? {
+        Some(table) if !table.is_empty() => table,
+        _ => {
+            return Err(WsError::new(
+                WS_ERROR_NO::NULL_POINTER.as_code(),
+                "table name is null or empty",
+            ))
+        }
+    };
+    let rows = usize::try_from(rows)
+        .map_err(|_| WsError::new(Code::Failed, &format!("invalid raw block rows {rows}")))?;
+    // The second u32 of header is the block length.
+    let len = match usize::try_from(len) {
+        Ok(len) if len >= 8 => len,
         _ => {
-            C_ERRNO = Code::Failed;
-            let dst = C_ERROR_CONTAINER.as_mut_ptr();
-            const NULL_PTR_RES: &'static str = "WS_RES is null";
-            std::ptr::copy_nonoverlapping(NULL_PTR_RES.as_ptr(), dst, NULL_PTR_RES.len());
-            Code::Failed.into()
+            return Err(WsError::new(
+                Code::Failed,
+                &format!("raw block of {len} bytes is shorter than its header"),
+            ))
         }
+    };
+    let header_len = std::ptr::read_unaligned((block as *const u8).add(4) as *const u32) as usize;
+    if header_len != len {
+        return Err(WsError::new(
+            Code::Failed,
+            &format!(
+                "raw block length {header_len} in header mismatches the buffer of {len} bytes"
+            ),
+        ));
     }
+    let bytes = std::slice::from_raw_parts(block as *const u8, len);
+    Block::check_raw_block(bytes, rows).map_err(|err| WsError::new(Code::Failed, &err))?;
+    let mut raw = Block::parse_from_raw_block(bytes.to_vec(), Precision::Millisecond);
+    raw.with_table_name(table);
+    Ok(raw)
 }
 
-#[no_mangle]
-/// Always use this to ensure that the query is executed correctly.
-pub unsafe extern "C" fn ws_errno(rs: *mut WS_RES) -> i32 {
-    match (rs as *mut WsMaybeError<()>)
+unsafe fn write_raw_block(
+    taos: *mut WS_TAOS,
+    rows: i32,
+    block: *const c_void,
+    len: i32,
+    table: *const c_char,
+    fields: Option<(*const WS_FIELD, i32)>,
+) -> WsResult<()> {
+    let mut raw = raw_block_from_ptr(rows, block, len, table)?;
+    let client = (taos as *const WsClient)
         .as_ref()
-        .and_then(|s| s.errno())
-    {
-        Some(c) => c,
-        _ => C_ERRNO.into(),
+        .ok_or(WsError::new(Code::Failed, "client pointer it null"))?;
+    let Some((fields, num_fields)) = fields else {
+        client.write_raw_block_without_fields(&raw)?;
+        return Ok(());
+    };
+    if fields.is_null() || num_fields as usize != raw.ncols() {
+        return Err(WsError::new(
+            Code::Failed,
+            &format!(
+                "{num_fields} fields mismatch with {} columns of the raw block",
+                raw.ncols()
+            ),
+        ));
     }
-}
-
-#[no_mangle]
-/// Use this method to get a formatted error string when query errno is not 0.
-pub unsafe extern "C" fn ws_errstr(rs: *mut WS_RES) -> *const c_char {
-    match (rs as *mut WsMaybeError<()>)
-        .as_ref()
-        .and_then(|s| s.errstr())
-    {
-        Some(e) => e,
-        _ => {
-            if C_ERRNO.success() {
-                EMPTY.as_ptr()
-            } else {
-                C_ERROR_CONTAINER.as_ptr() as _
-            }
+    let fields = std::slice::from_raw_parts(fields, num_fields as usize);
+    for (col, (field, schema)) in fields.iter().zip(raw.fields()).enumerate() {
+        if field.r#type != schema.ty() as u8 {
+            return Err(WsError::new(
+                Code::Failed,
+                &format!(
+                    "field type {} of column {col} mismatch with {} in the raw block",
+                    field.r#type,
+                    schema.ty()
+                ),
+            ));
         }
     }
+    let names = fields
+        .iter()
+        .map(|field| field.name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    raw.with_field_names(names);
+    client.write_raw_block(&raw)?;
+    Ok(())
 }
 
 #[no_mangle]
-/// Works exactly the same to taos_affected_rows.
-pub unsafe extern "C" fn ws_affected_rows(rs: *const WS_RES) -> i32 {
-    match (rs as *mut WsMaybeError<WsResultSet>).as_ref() {
-        Some(rs) => rs.affected_rows(),
-        _ => 0,
-    }
+/// Same to taos_write_raw_block, write a v3 raw block of `rows` rows and `len` bytes to `table`.
+///
+/// The length in the block header should be `len`, and the row count and column lengths are
+/// checked to fill it before sending.
+/// Returns 0 on success, or the error code, the error message could be got by `ws_errstr(NULL)`.
+///
+/// # Safety
+///
+/// `taos` must be NULL or a live connection, `block` must be NULL or readable for `len` bytes
+/// and `table` must be NULL or a NUL-terminated string.
+pub unsafe extern "C" fn ws_write_raw_block(
+    taos: *mut WS_TAOS,
+    rows: i32,
+    block: *const c_void,
+    len: i32,
+    table: *const c_char,
+) -> i32 {
+    ffi_guard(
+        || match write_raw_block(taos, rows, block, len, table, None) {
+            Ok(()) => 0,
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        },
+    )
 }
 
 #[no_mangle]
-/// Returns number of fields in current result set.
-pub unsafe extern "C" fn ws_field_count(rs: *const WS_RES) -> i32 {
-    match (rs as *mut WsMaybeError<WsResultSet>).as_ref() {
-        Some(rs) => rs.num_of_fields(),
-        _ => 0,
-    }
+/// Same to taos_write_raw_block_with_fields, write a raw block with `num_fields` fields.
+///
+/// The field types should match the block columns, and the names are sent to taosAdapter so
+/// the columns are written to the table columns of the same names.
+///
+/// # Safety
+///
+/// The same to `ws_write_raw_block`, and `fields` must be NULL or an array of `num_fields`
+/// fields.
+pub unsafe extern "C" fn ws_write_raw_block_with_fields(
+    taos: *mut WS_TAOS,
+    rows: i32,
+    block: *const c_void,
+    len: i32,
+    table: *const c_char,
+    fields: *const WS_FIELD,
+    num_fields: i32,
+) -> i32 {
+    ffi_guard(
+        || match write_raw_block(taos, rows, block, len, table, Some((fields, num_fields))) {
+            Ok(()) => 0,
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        },
+    )
 }
 
 #[no_mangle]
-/// If the query is update query or not
-pub unsafe extern "C" fn ws_is_update_query(rs: *const WS_RES) -> bool {
-    match (rs as *mut WsMaybeError<WsResultSet>).as_ref() {
-        Some(rs) => rs.num_of_fields() == 0,
-        _ => true,
-    }
+/// Query with a sql command, returns pointer to result set.
+///
+/// Please always use `ws_errno` to check it work and `ws_free_result` to free memory.
+pub unsafe extern "C" fn ws_query(taos: *mut WS_TAOS, sql: *const c_char) -> *mut WS_RES {
+    ffi_guard(|| {
+        log::debug!("query {:?}", CStr::from_ptr(sql));
+        let res: WsMaybeError<WsResultSet> = query_with_sql(taos, sql).into();
+        log::debug!("query done: {:?}", res);
+        Box::into_raw(Box::new(res)) as _
+    })
 }
 
 #[no_mangle]
-/// Works like taos_fetch_fields, users should use it along with a `num_of_fields`.
-pub unsafe extern "C" fn ws_fetch_fields(rs: *mut WS_RES) -> *const WS_FIELD {
-    match (rs as *mut WsMaybeError<WsResultSet>).as_mut() {
-        Some(rs) => rs.get_fields(),
-        _ => std::ptr::null(),
-    }
+/// Works like `ws_query`, but executes multiple statements separated by `;`, like the taos CLI.
+///
+/// Statements are split at `;` out of quotes and comments, and executed one by one. The result set
+/// is of the last statement, but `ws_affected_rows` is the total of all statements. It stops at
+/// the first failed statement, with the error message prefixed by its 1-based index, e.g.
+/// `statement 2 failed: ...`, and statements before it are not rolled back.
+///
+/// # Safety
+///
+/// `taos` must be NULL or a live connection, `sql` must be NULL or a NUL-terminated string.
+pub unsafe extern "C" fn ws_query_multi(taos: *mut WS_TAOS, sql: *const c_char) -> *mut WS_RES {
+    ffi_guard(|| {
+        let res: WsMaybeError<WsResultSet> = query_multi(taos, sql).into();
+        log::debug!("query multi done: {:?}", res);
+        Box::into_raw(Box::new(res)) as _
+    })
 }
 
 #[no_mangle]
-/// To fetch v2-compatible fields structs.
-pub unsafe extern "C" fn ws_fetch_fields_v2(rs: *mut WS_RES) -> *const WS_FIELD_V2 {
-    match (rs as *mut WsMaybeError<WsResultSet>).as_mut() {
-        Some(rs) => rs.get_fields_v2(),
-        _ => std::ptr::null(),
-    }
-}
+/// Same to taos_query_with_reqid, query with a caller-supplied request id for tracing in taosAdapter.
+///
+/// The id should be unique among in-flight requests of the connection.
+///
+/// # Safety
+///
+/// `taos` must be NULL or a live connection, `sql` must be NULL or a NUL-terminated string.
+pub unsafe extern "C" fn ws_query_with_reqid(
+    taos: *mut WS_TAOS,
+    sql: *const c_char,
+    req_id: u64,
+) -> *mut WS_RES {
+    ffi_guard(|| {
+        let res: WsMaybeError<WsResultSet> = query_with_req_id(taos, sql, req_id).into();
+        log::debug!("query done: {:?}", res);
+        Box::into_raw(Box::new(res)) as _
+    })
+}
+
+#[no_mangle]
+/// Get the request id of the query or tmq poll which produced the result set, 0 if the query failed.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set.
+pub unsafe extern "C" fn ws_get_req_id(rs: *const WS_RES) -> u64 {
+    ffi_guard(|| {
+        match (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.rs.req_id(),
+            None => 0,
+        }
+    })
+}
+
+/// Callback of `ws_query_a`, `code` is the same as `ws_errno(res)`.
+#[allow(non_camel_case_types)]
+pub type __ws_async_fn_t = unsafe extern "C" fn(param: *mut c_void, res: *mut WS_RES, code: i32);
+
+unsafe fn query_a_callback(fp: __ws_async_fn_t, param: *mut c_void, res: WsResult<WsResultSet>) {
+    let res: WsMaybeError<WsResultSet> = res.into();
+    let code = res.errno().unwrap_or(0);
+    fp(param, Box::into_raw(Box::new(res)) as _, code)
+}
+
+#[no_mangle]
+/// Same to taos_query_a, query without blocking and deliver the result set through callback.
+///
+/// The callback is invoked exactly once from a runtime thread, with the result set that should
+/// be checked with `ws_errno` and freed with `ws_free_result` as the one from `ws_query`.
+/// The connection could be closed before the callback is called, the query still completes.
+///
+/// # Safety
+///
+/// `taos` must be NULL or a live connection, `sql` must be NULL or a NUL-terminated string.
+/// `param` is passed to the callback as is.
+pub unsafe extern "C" fn ws_query_a(
+    taos: *mut WS_TAOS,
+    sql: *const c_char,
+    fp: Option<__ws_async_fn_t>,
+    param: *mut c_void,
+) {
+    ffi_guard(|| {
+        let Some(fp) = fp else {
+            return;
+        };
+        let Some(client) = (taos as *const WsClient).as_ref() else {
+            return query_a_callback(
+                fp,
+                param,
+                Err(WsError::new(Code::Failed, "client pointer it null")),
+            );
+        };
+        let sql = match opt_c_str(sql) {
+            Ok(Some(sql)) => sql.to_string(),
+            Ok(None) => {
+                return query_a_callback(fp, param, Err(WsError::new(Code::Failed, "sql is null")))
+            }
+            Err(err) => return query_a_callback(fp, param, Err(err)),
+        };
+        log::debug!("query async {:?}", sql);
+        let param = param as usize;
+        // the task owns what it uses, the client may be closed before the query is done.
+        let (taos, closed, tz) = (client.taos.clone(), client.closed.clone(), client.tz());
+        taos_query::global_tokio_runtime().spawn(async move {
+            let res = taos_query::AsyncQueryable::query(&*taos, sql)
+                .await
+                .map(|rs| WsResultSet::new(rs, closed).with_tz(tz))
+                .map_err(WsError::from);
+            query_a_callback(fp, param as *mut c_void, res);
+        });
+    })
+}
+
+/// Same to taos_stop_query, stop the query and free the result on server side.
+///
+/// After stopped, `ws_fetch_block` and `ws_fetch_row` will always return 0 rows. It's safe to
+/// call it from a different thread than the one fetching, and calling it more than once is a no-op.
+/// The result set should still be freed with `ws_free_result`.
+#[no_mangle]
+pub unsafe extern "C" fn ws_stop_query(rs: *mut WS_RES) {
+    ffi_guard(|| {
+        if let Some(rs) = (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            rs.stop_query();
+        }
+    })
+}
+
+#[no_mangle]
+/// Query a sql with timeout in seconds, `0` means no timeout.
+///
+/// On expiry the result set carries the `0xE007` error code, the connection is still usable.
+/// Please always use `ws_errno` to check it work and `ws_free_result` to free memory.
+pub unsafe extern "C" fn ws_query_timeout(
+    taos: *mut WS_TAOS,
+    sql: *const c_char,
+    seconds: u32,
+) -> *mut WS_RES {
+    ffi_guard(|| {
+        let res: WsMaybeError<WsResultSet> =
+            query_with_sql_timeout(taos, sql, Duration::from_secs(seconds as _)).into();
+        Box::into_raw(Box::new(res)) as _
+    })
+}
+
+/// Get server side execution timing duration of the query as nanoseconds.
+///
+/// Returns -1 when `rs` is NULL or in error state.
+#[no_mangle]
+pub unsafe extern "C" fn ws_take_timing(rs: *mut WS_RES) -> i64 {
+    ffi_guard(|| {
+        let Some(maybe) = (rs as *mut WsMaybeError<WsResultSet>).as_mut() else {
+            set_c_error(&WsError::new(Code::Failed, "WS_RES is null"));
+            return -1;
+        };
+        match maybe.safe_deref_mut() {
+            Some(rs) => rs.take_timing().as_nanos().min(i64::MAX as u128) as _,
+            None => -1,
+        }
+    })
+}
+
+#[no_mangle]
+/// Same to taos_errno, always use this to ensure that the query is executed correctly.
+///
+/// It accepts either a `WS_RES`/`WS_STMT` or a `WS_TAOS` handle. For a connection handle or NULL,
+/// it returns the last handle-less error, e.g. the reason of a failed `ws_connect_with_dsn`.
+pub unsafe extern "C" fn ws_errno(rs: *mut WS_RES) -> i32 {
+    ffi_guard(|| {
+        let errno = match handle_tag(rs) {
+            Some(HANDLE_MAYBE_ERROR) => (rs as *const WsMaybeError<()>)
+                .as_ref()
+                .and_then(|s| s.errno()),
+            _ => None,
+        };
+        match errno {
+            Some(c) => c,
+            _ => last_errno(),
+        }
+    })
+}
+
+#[no_mangle]
+/// Same to taos_errstr, use this method to get a formatted error string when errno is not 0.
+///
+/// Accepts the same handles as `ws_errno`. The string is owned by the handle, use `ws_errstr_dup`
+/// to keep it after the handle is freed.
+///
+/// # Safety
+///
+/// The handle must not be freed, reading the error of a freed handle is undefined behavior.
+/// Copy the error with `ws_errstr_dup` before freeing the handle, or use `ws_last_errstr` after
+/// a call without a live handle.
+pub unsafe extern "C" fn ws_errstr(rs: *mut WS_RES) -> *const c_char {
+    ffi_guard(|| {
+        let errstr = match handle_tag(rs) {
+            Some(HANDLE_MAYBE_ERROR) => (rs as *const WsMaybeError<()>)
+                .as_ref()
+                .and_then(|s| s.errstr()),
+            _ => None,
+        };
+        match errstr {
+            Some(e) => e,
+            _ => last_errstr(),
+        }
+    })
+}
+
+#[no_mangle]
+/// Copy of the error string of `ws_errstr(handle)`, owned by the caller.
+///
+/// The copy is valid after the handle is freed, free it with `ws_free_str`.
+///
+/// # Safety
+///
+/// The handle must not be freed yet, as of `ws_errstr`.
+pub unsafe extern "C" fn ws_errstr_dup(handle: *mut c_void) -> *mut c_char {
+    ffi_guard(|| CStr::from_ptr(ws_errstr(handle as _)).to_owned().into_raw())
+}
+
+#[no_mangle]
+/// Free a string returned by `ws_errstr_dup`, NULL is ignored.
+///
+/// # Safety
+///
+/// `s` must be NULL or a string from `ws_errstr_dup`, and must not be used or freed again.
+pub unsafe extern "C" fn ws_free_str(s: *mut c_char) {
+    ffi_guard(|| {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+    })
+}
+
+#[no_mangle]
+/// Error code of the last failed call on the current thread, 0 if none.
+///
+/// It's updated by every function that fails, with or without a handle.
+pub extern "C" fn ws_last_errno() -> i32 {
+    ffi_guard(last_errno)
+}
+
+#[no_mangle]
+/// Error string of the last failed call on the current thread, empty if none.
+///
+/// The string is valid until the next failed call on the same thread.
+pub extern "C" fn ws_last_errstr() -> *const c_char {
+    ffi_guard(last_errstr)
+}
+
+#[no_mangle]
+/// Works exactly the same to taos_affected_rows, saturates at INT32_MAX.
+pub unsafe extern "C" fn ws_affected_rows(rs: *const WS_RES) -> i32 {
+    ffi_guard(|| {
+        match (rs as *mut WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.affected_rows(),
+            _ => 0,
+        }
+    })
+}
+
+#[no_mangle]
+/// Works exactly the same to taos_affected_rows64.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set.
+pub unsafe extern "C" fn ws_affected_rows64(rs: *const WS_RES) -> i64 {
+    ffi_guard(|| {
+        match (rs as *mut WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.affected_rows64(),
+            _ => 0,
+        }
+    })
+}
+
+#[no_mangle]
+/// Returns number of fields in current result set.
+pub unsafe extern "C" fn ws_field_count(rs: *const WS_RES) -> i32 {
+    ffi_guard(|| match (rs as *mut WsMaybeError<WsResultSet>).as_ref() {
+        Some(rs) => rs.num_of_fields(),
+        _ => 0,
+    })
+}
+
+#[no_mangle]
+/// If the query is update query or not, update queries like `insert` or `create table` only have
+/// affected rows, and `ws_fetch_block` on them returns 0 rows at once. NULL or failed results are
+/// treated as update queries.
+pub unsafe extern "C" fn ws_is_update_query(rs: *const WS_RES) -> bool {
+    ffi_guard(|| {
+        match (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.rs.is_update(),
+            None => true,
+        }
+    })
+}
+
+#[no_mangle]
+/// Works like taos_fetch_fields, users should use it along with a `num_of_fields`.
+///
+/// The fields are built once, so the pointer is the same in every call and valid until
+/// `ws_free_result`. For TMQ messages, it's rebuilt when a block of a different schema is fetched.
+pub unsafe extern "C" fn ws_fetch_fields(rs: *mut WS_RES) -> *const WS_FIELD {
+    ffi_guard(|| {
+        match (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.get_fields(),
+            _ => std::ptr::null(),
+        }
+    })
+}
+
+#[no_mangle]
+/// To fetch v2-compatible fields structs, the pointer is stable as `ws_fetch_fields`.
+pub unsafe extern "C" fn ws_fetch_fields_v2(rs: *mut WS_RES) -> *const WS_FIELD_V2 {
+    ffi_guard(|| {
+        match (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.get_fields_v2(),
+            _ => std::ptr::null(),
+        }
+    })
+}
+
+#[no_mangle]
+/// To fetch fields with precision and scale, like of decimal columns, the pointer is stable as
+/// `ws_fetch_fields`. Precision and scale are 0 if not sent by server, except timestamp fields
+/// which have the precision of the result set.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, the fields are owned by it.
+pub unsafe extern "C" fn ws_fetch_fields_e(rs: *mut WS_RES) -> *const WS_FIELD_E {
+    ffi_guard(|| {
+        match (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.get_fields_e(),
+            _ => std::ptr::null(),
+        }
+    })
+}
+
+/// Record a NULL pointer argument error and return its code.
+fn null_pointer_error(message: &str) -> i32 {
+    let err = WsError::new(WS_ERROR_NO::NULL_POINTER.as_code(), message);
+    set_c_error(&err);
+    err.code.into()
+}
+
 #[no_mangle]
 /// Works like taos_fetch_raw_block, it will always return block with format v3.
+///
+/// Returns `0xE008` and writes nothing if any of `rs`, `ptr` or `rows` is NULL.
 pub unsafe extern "C" fn ws_fetch_block(
     rs: *mut WS_RES,
     ptr: *mut *const c_void,
     rows: *mut i32,
 ) -> i32 {
-    match (rs as *mut WsMaybeError<WsResultSet>).as_mut() {
-        Some(rs) => match rs.fetch_block(ptr, rows) {
+    ffi_guard(|| {
+        if ptr.is_null() || rows.is_null() {
+            return null_pointer_error("ptr or rows is null");
+        }
+        let Some(maybe) = (rs as *mut WsMaybeError<WsResultSet>).as_mut() else {
+            return null_pointer_error("WS_RES is null");
+        };
+        let res = match maybe.safe_deref_mut() {
+            Some(rs) => rs.fetch_block(ptr, rows),
+            None => {
+                *rows = 0;
+                return maybe.errno().unwrap_or_else(|| Code::Failed.into());
+            }
+        };
+        match res {
             Ok(()) => 0,
             Err(err) => {
                 let code = err.errno();
-                rs.error = Some(err.into());
+                maybe.set_error(err);
                 code.into()
             }
-        },
-        _ => {
-            *rows = 0;
+        }
+    })
+}
 
-            C_ERRNO = Code::Failed;
-            let dst = C_ERROR_CONTAINER.as_mut_ptr();
-            const NULL_PTR_RES: &'static str = "WS_RES is null";
-            std::ptr::copy_nonoverlapping(NULL_PTR_RES.as_ptr(), dst, NULL_PTR_RES.len());
-            Code::Failed.into()
+#[no_mangle]
+/// Works like `ws_fetch_block`, but copies the raw block into the buffer owned by caller, so the
+/// data is still valid after next fetch or `ws_free_result`.
+///
+/// `written` is set to the size of the block, 0 with `rows` 0 when there's no more blocks. If `cap`
+/// is less than the block size, nothing is copied and `0xE00D` returned with the required size in
+/// `written`, the next call returns the same block, so it could be retried with a larger buffer.
+///
+/// ## Example
+///
+/// ```c
+/// size_t cap = 4096, written = 0;
+/// int32_t rows = 0;
+/// void *buf = malloc(cap);
+/// int32_t code = ws_fetch_block_into(rs, buf, cap, &written, &rows);
+/// if (code == 0xE00D) {
+///   buf = realloc(buf, written);
+///   cap = written;
+///   code = ws_fetch_block_into(rs, buf, cap, &written, &rows);
+/// }
+/// ```
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, `buf` must be NULL or writable for `cap` bytes,
+/// `written` and `rows` must be NULL or writable.
+pub unsafe extern "C" fn ws_fetch_block_into(
+    rs: *mut WS_RES,
+    buf: *mut c_void,
+    cap: usize,
+    written: *mut usize,
+    rows: *mut i32,
+) -> i32 {
+    ffi_guard(|| {
+        if written.is_null() || rows.is_null() || (buf.is_null() && cap > 0) {
+            return null_pointer_error("buf, written or rows is null");
         }
-    }
+        let Some(maybe) = (rs as *mut WsMaybeError<WsResultSet>).as_mut() else {
+            return null_pointer_error("WS_RES is null");
+        };
+        let res = match maybe.safe_deref_mut() {
+            Some(rs) => rs.fetch_block_into(buf, cap, written, rows),
+            None => {
+                *written = 0;
+                *rows = 0;
+                return maybe.errno().unwrap_or_else(|| Code::Failed.into());
+            }
+        };
+        match res {
+            Ok(true) => 0,
+            Ok(false) => {
+                let err = WsError::new(
+                    WS_ERROR_NO::BUFFER_TOO_SMALL.as_code(),
+                    &format!("buffer too small, {} bytes required", *written),
+                );
+                set_c_error(&err);
+                err.code.into()
+            }
+            Err(err) => {
+                let code = err.errno();
+                maybe.set_error(err);
+                code.into()
+            }
+        }
+    })
 }
 
 #[no_mangle]
-/// Same to taos_free_result. Every websocket result-set object should be freed with this method.
-pub unsafe extern "C" fn ws_free_result(rs: *mut WS_RES) {
-    if !rs.is_null() {
-        let _ = Box::from_raw(rs as *mut WsMaybeError<WsResultSet>);
-    }
+/// Works like `ws_fetch_block`, but returns the block in TDengine 2.x layout for legacy applications.
+///
+/// The layout is column-major without header: each column is `rows` slots of fixed size. For
+/// fixed-width types the slot is a plain value, NULL is the 2.x sentinel of that type, e.g.
+/// `INT_MIN` for int. For varchar/nchar/json the slot is `bytes + 2` (`bytes` from
+/// `ws_fetch_fields_v2`), with 2-byte length prefix, nchar is encoded in UCS-4. The buffer is owned
+/// by the result set and valid until the next fetch.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, `ptr` and `rows` must be NULL or writable. The
+/// block is invalidated by the next fetch.
+pub unsafe extern "C" fn ws_fetch_block_v2(
+    rs: *mut WS_RES,
+    ptr: *mut *const c_void,
+    rows: *mut i32,
+) -> i32 {
+    ffi_guard(|| {
+        if ptr.is_null() || rows.is_null() {
+            return null_pointer_error("ptr or rows is null");
+        }
+        let Some(maybe) = (rs as *mut WsMaybeError<WsResultSet>).as_mut() else {
+            return null_pointer_error("WS_RES is null");
+        };
+        let res = match maybe.safe_deref_mut() {
+            Some(rs) => rs.fetch_block_v2(ptr, rows),
+            None => {
+                *rows = 0;
+                return maybe.errno().unwrap_or_else(|| Code::Failed.into());
+            }
+        };
+        match res {
+            Ok(()) => 0,
+            Err(err) => {
+                let code = err.errno();
+                maybe.set_error(err);
+                code.into()
+            }
+        }
+    })
 }
 
 #[no_mangle]
-/// Same to taos_result_precision.
-pub unsafe extern "C" fn ws_result_precision(rs: *const WS_RES) -> i32 {
-    match (rs as *mut WsMaybeError<WsResultSet>).as_mut() {
-        Some(rs) => rs.precision() as i32,
-        _ => 0,
-    }
+/// Same to taos_fetch_raw_block_a, fetch next block without blocking.
+///
+/// The callback receives the number of rows fetched as `code`, 0 means end of data and
+/// negative value means an error occurred, check `ws_errno(res)` for details. After that,
+/// `ws_get_raw_block` and `ws_get_value_in_block` work on the fetched block until the next fetch.
+///
+/// Overlapping fetches on the same result set are serialized, each callback is invoked
+/// exactly once. Don't mix with `ws_fetch_block`/`ws_fetch_row` or free the result set while
+/// a fetch is in progress.
+///
+/// # Safety
+///
+/// `rs` must be a live result set until the callback is called. `param` is passed to the
+/// callback as is.
+pub unsafe extern "C" fn ws_fetch_raw_block_a(
+    rs: *mut WS_RES,
+    fp: Option<__ws_async_fn_t>,
+    param: *mut c_void,
+) {
+    ffi_guard(|| {
+        let Some(fp) = fp else {
+            return;
+        };
+        let Some(maybe) = (rs as *mut WsMaybeError<WsResultSet>).as_mut() else {
+            set_c_error(&WsError::new(Code::Failed, "WS_RES is null"));
+            return fp(param, rs, -i32::from(Code::Failed));
+        };
+        let Some(ws_rs) = maybe.safe_deref_mut() else {
+            let code = maybe.errno().unwrap_or(Code::Failed.into());
+            return fp(param, rs, -code.abs());
+        };
+        let lock = ws_rs.fetch_lock.clone();
+        let ptr = ws_rs as *mut WsResultSet as usize;
+        let (rs, param) = (rs as usize, param as usize);
+        taos_query::global_tokio_runtime().spawn(async move {
+            let _guard = lock.lock_owned().await;
+            let stopped = (*(ptr as *const WsResultSet)).is_stopped();
+            let res = if let Err(err) = (*(ptr as *const WsResultSet)).check_closed() {
+                Err(err)
+            } else if stopped {
+                Ok(None)
+            } else {
+                (*(ptr as *mut WsResultSet))
+                    .rs
+                    .fetch_raw_block_async()
+                    .await
+            };
+            let ws_rs = &mut *(ptr as *mut WsResultSet);
+            let code = match res {
+                Ok(block) => ws_rs.set_block(block),
+                Err(err) => {
+                    let code: i32 = err.errno().into();
+                    ws_rs.set_block(None);
+                    (*(rs as *mut WsMaybeError<WsResultSet>)).set_error(err);
+                    -code.abs()
+                }
+            };
+            fp(param as *mut c_void, rs as *mut WS_RES, code);
+        });
+    })
+}
+
+#[no_mangle]
+/// Move the current block out of the result set, so it's not invalidated by subsequent fetches.
+///
+/// Writes NULL to `*out` when there's no current block. The result set has no current block
+/// afterwards until the next fetch. The block should be freed with `ws_free_block`.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, `out` must be NULL or writable.
+pub unsafe extern "C" fn ws_take_block(rs: *mut WS_RES, out: *mut *mut WS_BLOCK) -> i32 {
+    ffi_guard(|| {
+        if out.is_null() {
+            return null_pointer_error("out is null");
+        }
+        *out = std::ptr::null_mut();
+        let Some(maybe) = (rs as *mut WsMaybeError<WsResultSet>).as_mut() else {
+            return null_pointer_error("WS_RES is null");
+        };
+        let Some(rs) = maybe.safe_deref_mut() else {
+            return maybe.errno().unwrap_or_else(|| Code::Failed.into());
+        };
+        if let Some(block) = rs.take_block() {
+            *out = Box::into_raw(Box::new(block)) as _;
+        }
+        0
+    })
+}
+
+#[no_mangle]
+/// Raw data of a block from `ws_take_block`, in the same format as from `ws_fetch_block`.
+///
+/// # Safety
+///
+/// `block` must be NULL or a block from `ws_take_block` not freed yet, the data is owned by
+/// the block.
+pub unsafe extern "C" fn ws_block_data(block: *const WS_BLOCK) -> *const c_void {
+    ffi_guard(|| match (block as *const Block).as_ref() {
+        Some(block) => block.as_raw_bytes().as_ptr() as _,
+        None => std::ptr::null(),
+    })
+}
+
+#[no_mangle]
+/// Number of rows of a block from `ws_take_block`, 0 if NULL.
+///
+/// # Safety
+///
+/// `block` must be NULL or a block from `ws_take_block` not freed yet.
+pub unsafe extern "C" fn ws_block_rows(block: *const WS_BLOCK) -> i32 {
+    ffi_guard(|| match (block as *const Block).as_ref() {
+        Some(block) => block.nrows() as _,
+        None => 0,
+    })
+}
+
+#[no_mangle]
+/// Free a block from `ws_take_block`.
+///
+/// # Safety
+///
+/// `block` must be NULL or a block from `ws_take_block`, and must not be used or freed again.
+pub unsafe extern "C" fn ws_free_block(block: *mut WS_BLOCK) {
+    ffi_guard(|| {
+        if !block.is_null() {
+            let _ = Box::from_raw(block as *mut Block);
+        }
+    })
+}
+
+#[no_mangle]
+/// Number of rows in the current block, 0 when no block fetched or on error.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set.
+pub unsafe extern "C" fn ws_block_num_rows(rs: *const WS_RES) -> i32 {
+    ffi_guard(|| {
+        match (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+            .and_then(|rs| rs.block.as_ref())
+        {
+            Some(block) => block.nrows() as _,
+            None => 0,
+        }
+    })
+}
+
+#[no_mangle]
+/// Number of columns in the current block, 0 when no block fetched or on error.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set.
+pub unsafe extern "C" fn ws_block_num_cols(rs: *const WS_RES) -> i32 {
+    ffi_guard(|| {
+        match (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+            .and_then(|rs| rs.block.as_ref())
+        {
+            Some(block) => block.ncols() as _,
+            None => 0,
+        }
+    })
 }
 
-/// To get value at (row, col) in a block (as a 2-dimension matrix), input row/col index,
-/// it will write the value type in *ty, and data length in *len, return a pointer to the real data.
-///
-/// For type which is var-data (varchar/nchar/json), the `*len` is the bytes length, others is fixed size of that type.
-///
-/// ## Example
-///
-/// ```c
-/// u8 ty = 0;
-/// int len = 0;
-/// void* v = ws_get_value_in_block(rs, 0, 0, &ty, &len);
-/// if (ty == TSDB_DATA_TYPE_TIMESTAMP) {
-///   int64_t* timestamp = (int64_t*)v;
-///   printf("ts: %d\n", *timestamp);
-/// }
-/// ```
-#[no_mangle]
-pub unsafe extern "C" fn ws_get_value_in_block(
-    rs: *mut WS_RES,
-    row: i32,
-    col: i32,
-    ty: *mut u8,
-    len: *mut u32,
-) -> *const c_void {
-    match (rs as *mut WsMaybeError<WsResultSet>).as_mut() {
-        Some(rs) => {
-            let value = rs.get_raw_value(row as _, col as _);
-            *ty = value.0 as u8;
-            *len = value.1 as _;
-            value.2
+#[no_mangle]
+/// Total rows fetched from the result set so far, across all blocks.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set.
+pub unsafe extern "C" fn ws_total_rows_fetched(rs: *const WS_RES) -> u64 {
+    ffi_guard(|| {
+        match (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.total_rows,
+            None => 0,
+        }
+    })
+}
+
+#[no_mangle]
+/// Same to taos_get_raw_block, returns the raw data of the block fetched by `ws_fetch_raw_block_a`
+/// or `ws_fetch_block`, NULL if no block is available.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, the block is invalidated by the next fetch.
+pub unsafe extern "C" fn ws_get_raw_block(rs: *const WS_RES) -> *const c_void {
+    ffi_guard(|| {
+        match (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.raw_block(),
+            None => std::ptr::null(),
+        }
+    })
+}
+
+/// Works like taos_fetch_row, returns NULL when all rows are fetched or an error occurred.
+///
+/// It fetches blocks internally, the row pointers are valid until the next `ws_fetch_row` or
+/// `ws_free_result` call. Use `ws_errno` to distinguish the end of the result set from errors.
+///
+/// ## Example
+///
+/// ```c
+/// WS_ROW row = NULL;
+/// while ((row = ws_fetch_row(rs)) != NULL) {
+///   if (row[0] != NULL) {
+///     printf("ts: %ld\n", *(int64_t *)row[0]);
+///   }
+/// }
+/// ```
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, the row is invalidated by the next fetch.
+#[no_mangle]
+pub unsafe extern "C" fn ws_fetch_row(rs: *mut WS_RES) -> WS_ROW {
+    ffi_guard(|| match (rs as *mut WsMaybeError<WsResultSet>).as_mut() {
+        Some(rs) => {
+            let res = match rs.safe_deref_mut() {
+                Some(inner) => inner.fetch_row(),
+                None => return std::ptr::null(),
+            };
+            match res {
+                Ok(row) => row,
+                Err(err) => {
+                    rs.set_error(err);
+                    std::ptr::null()
+                }
+            }
+        }
+        _ => std::ptr::null(),
+    })
+}
+
+/// Works like taos_fetch_lengths, returns byte lengths of each column in the row most recently
+/// returned by `ws_fetch_row`.
+///
+/// The array has `ws_field_count` elements, fixed-size types report their type size and
+/// NULL values report 0. It returns NULL when no row has been fetched.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, the array is invalidated by the next fetch.
+#[no_mangle]
+pub unsafe extern "C" fn ws_fetch_lengths(rs: *mut WS_RES) -> *const i32 {
+    ffi_guard(|| {
+        match (rs as *mut WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.fetch_lengths(),
+            _ => std::ptr::null(),
+        }
+    })
+}
+
+#[no_mangle]
+/// Same to taos_free_result. Every websocket result-set object should be freed with this method.
+pub unsafe extern "C" fn ws_free_result(rs: *mut WS_RES) {
+    ffi_guard(|| {
+        if rs.is_null() {
+            return;
+        }
+        if handle_tag(rs) != Some(HANDLE_MAYBE_ERROR) {
+            log::warn!("free an invalid or already freed result {rs:p}, ignored");
+            return;
+        }
+        clear_handle_tag(rs);
+        let _ = Box::from_raw(rs as *mut WsMaybeError<WsResultSet>);
+    })
+}
+
+#[no_mangle]
+/// Same to taos_result_precision, 0 for millisecond, 1 for microsecond and 2 for nanosecond.
+///
+/// Returns -1 if `rs` is NULL or failed, use `ws_result_precision_checked` to get the error code.
+pub unsafe extern "C" fn ws_result_precision(rs: *const WS_RES) -> i32 {
+    ffi_guard(|| {
+        match (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        {
+            Some(rs) => rs.precision() as i32,
+            None => -1,
+        }
+    })
+}
+
+#[no_mangle]
+/// Write precision of the result set to `precision`, returns 0 on success or the error code.
+///
+/// Returns `0xE008` if `rs` or `precision` is NULL, or the error code of a failed result set.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, `precision` must be NULL or writable.
+pub unsafe extern "C" fn ws_result_precision_checked(
+    rs: *const WS_RES,
+    precision: *mut i32,
+) -> i32 {
+    ffi_guard(|| {
+        if precision.is_null() {
+            return null_pointer_error("precision is null");
+        }
+        let Some(maybe) = (rs as *const WsMaybeError<WsResultSet>).as_ref() else {
+            return null_pointer_error("WS_RES is null");
+        };
+        match maybe.safe_deref() {
+            Some(rs) => {
+                *precision = rs.precision() as i32;
+                0
+            }
+            None => maybe.errno().unwrap_or_else(|| Code::Failed.into()),
+        }
+    })
+}
+
+/// To get value at (row, col) in a block (as a 2-dimension matrix), input row/col index,
+/// it will write the value type in *ty, and data length in *len, return a pointer to the real data.
+///
+/// For type which is var-data (varchar/nchar/json), the `*len` is the bytes length, others is fixed size of that type.
+/// NULL `ty` or `len` is skipped, and NULL is returned for a NULL or failed `rs`.
+///
+/// ## Example
+///
+/// ```c
+/// u8 ty = 0;
+/// int len = 0;
+/// void* v = ws_get_value_in_block(rs, 0, 0, &ty, &len);
+/// if (ty == TSDB_DATA_TYPE_TIMESTAMP) {
+///   int64_t* timestamp = (int64_t*)v;
+///   printf("ts: %d\n", *timestamp);
+/// }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn ws_get_value_in_block(
+    rs: *mut WS_RES,
+    row: i32,
+    col: i32,
+    ty: *mut u8,
+    len: *mut u32,
+) -> *const c_void {
+    ffi_guard(|| {
+        let value = match (rs as *mut WsMaybeError<WsResultSet>)
+            .as_mut()
+            .and_then(|rs| rs.safe_deref_mut())
+        {
+            Some(rs) => rs.get_raw_value(row as _, col as _),
+            None => (Ty::Null, 0, std::ptr::null()),
+        };
+        if let Some(ty) = ty.as_mut() {
+            *ty = value.0 as u8;
+        }
+        if let Some(len) = len.as_mut() {
+            *len = value.1 as _;
+        }
+        value.2
+    })
+}
+
+/// Write `bytes` with NUL terminator into `dest` of `len` bytes, truncated if not large enough.
+///
+/// Returns the bytes written (excluding NUL), or the required size (including NUL) if truncated,
+/// -1 if `dest` is NULL with non-zero `len`.
+unsafe fn write_c_str(dest: *mut u8, len: usize, bytes: &[u8]) -> i32 {
+    if len == 0 {
+        return bytes.len() as i32 + 1;
+    }
+    if dest.is_null() {
+        null_pointer_error("dest is null");
+        return -1;
+    }
+    let n = bytes.len().min(len - 1);
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), dest, n);
+    *dest.add(n) = 0;
+    if n < bytes.len() {
+        bytes.len() as i32 + 1
+    } else {
+        n as i32
+    }
+}
+
+fn timestamp_to_rfc3339(
+    raw: i64,
+    precision: i32,
+    use_z: bool,
+    tz: &tz::WsTimeZone,
+) -> Option<String> {
+    let precision = match precision {
+        0..=2 => Precision::from_u8(precision as u8),
+        _ => return None,
+    };
+    Some(tz.to_rfc3339(Timestamp::new(raw, precision), use_z))
+}
+
+/// Write timestamp in RFC3339 to `dest`, returns the same as `ws_timestamp_to_rfc3339_with_len`.
+unsafe fn write_timestamp(
+    dest: *mut u8,
+    len: usize,
+    raw: i64,
+    precision: i32,
+    use_z: bool,
+    tz: &tz::WsTimeZone,
+) -> i32 {
+    let Some(s) = timestamp_to_rfc3339(raw, precision, use_z, tz) else {
+        set_c_error(&WsError::new(
+            Code::Failed,
+            "precision integer only allow 0/1/2",
+        ));
+        return -1;
+    };
+    write_c_str(dest, len, s.as_bytes())
+}
+
+/// Convert timestamp to a NUL-terminated RFC3339 string in `dest` of `len` bytes.
+///
+/// Returns the string length written (excluding NUL) when `dest` is large enough, otherwise
+/// the required buffer size (including NUL) and writes a truncated string if `len > 0`.
+/// So the result is valid only if it's less than `len`. Returns -1 for invalid precision or
+/// NULL `dest` with non-zero `len`.
+///
+/// ## Example
+///
+/// ```c
+/// char ts[64] = {0};
+/// int32_t n = ws_timestamp_to_rfc3339_with_len(ts, sizeof(ts), 0, 0, true);
+/// if (n >= 0 && n < sizeof(ts)) {
+///   printf("%s\n", ts);
+/// }
+/// ```
+///
+/// # Safety
+///
+/// `dest` must be NULL or writable for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ws_timestamp_to_rfc3339_with_len(
+    dest: *mut u8,
+    len: usize,
+    raw: i64,
+    precision: i32,
+    use_z: bool,
+) -> i32 {
+    ffi_guard(|| write_timestamp(dest, len, raw, precision, use_z, &tz::WsTimeZone::Local))
+}
+
+/// Same to `ws_timestamp_to_rfc3339_with_len`, but in time zone `tz` rather than the process one.
+///
+/// `tz` is an IANA name like `Asia/Shanghai` or a fixed offset like `+08:00`, NULL for the process
+/// time zone, parsed on each call. It's reentrant, returns -1 for invalid precision and time
+/// zone.
+///
+/// ## Example
+///
+/// ```c
+/// char ts[64] = {0};
+/// int32_t n = ws_timestamp_to_rfc3339_tz(ts, sizeof(ts), 0, 0, "Asia/Shanghai");
+/// ```
+///
+/// # Safety
+///
+/// `dest` must be NULL or writable for `len` bytes, `tz` must be NULL or a NUL-terminated
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn ws_timestamp_to_rfc3339_tz(
+    dest: *mut u8,
+    len: usize,
+    raw: i64,
+    precision: i32,
+    tz: *const c_char,
+) -> i32 {
+    ffi_guard(|| {
+        let tz = opt_c_str(tz).and_then(|tz| match tz {
+            Some(tz) => tz::WsTimeZone::parse(tz).map_err(|err| WsError::new(Code::Failed, &err)),
+            None => Ok(tz::WsTimeZone::Local),
+        });
+        match tz {
+            Ok(tz) => write_timestamp(dest, len, raw, precision, false, &tz),
+            Err(err) => {
+                set_c_error(&err);
+                -1
+            }
+        }
+    })
+}
+
+/// Convert timestamp to C string.
+///
+/// Deprecated: `dest` has no capacity check and the string is not NUL-terminated,
+/// use `ws_timestamp_to_rfc3339_with_len` instead.
+#[no_mangle]
+#[deprecated(note = "use ws_timestamp_to_rfc3339_with_len instead")]
+pub unsafe extern "C" fn ws_timestamp_to_rfc3339(
+    dest: *mut u8,
+    raw: i64,
+    precision: i32,
+    use_z: bool,
+) {
+    ffi_guard(|| {
+        if let Some(s) = timestamp_to_rfc3339(raw, precision, use_z, &tz::WsTimeZone::Local) {
+            std::ptr::copy_nonoverlapping(s.as_ptr(), dest, s.len());
+        }
+    })
+}
+
+/// Printable representation of a value, timestamps are formatted in RFC3339 with `precision`
+/// in time zone `tz`.
+fn value_to_string<'a>(
+    value: &'a BorrowedValue,
+    precision: Precision,
+    tz: &tz::WsTimeZone,
+) -> Cow<'a, [u8]> {
+    use BorrowedValue::*;
+    match value {
+        Null(_) => Cow::Borrowed(b"NULL"),
+        Bool(v) => Cow::Borrowed(if *v { b"true" } else { b"false" }),
+        VarChar(v) => Cow::Borrowed(v.as_bytes()),
+        NChar(v) => Cow::Borrowed(v.as_bytes()),
+        Json(v) => Cow::Borrowed(v.as_ref()),
+        VarBinary(v) | Blob(v) | MediumBlob(v) => Cow::Borrowed(v),
+        Timestamp(v) => {
+            let ts = taos_query::common::Timestamp::new(v.as_raw_i64(), precision);
+            Cow::Owned(tz.to_rfc3339(ts, false).into_bytes())
+        }
+        TinyInt(v) => Cow::Owned(v.to_string().into_bytes()),
+        SmallInt(v) => Cow::Owned(v.to_string().into_bytes()),
+        Int(v) => Cow::Owned(v.to_string().into_bytes()),
+        BigInt(v) => Cow::Owned(v.to_string().into_bytes()),
+        UTinyInt(v) => Cow::Owned(v.to_string().into_bytes()),
+        USmallInt(v) => Cow::Owned(v.to_string().into_bytes()),
+        UInt(v) => Cow::Owned(v.to_string().into_bytes()),
+        UBigInt(v) => Cow::Owned(v.to_string().into_bytes()),
+        Float(v) => Cow::Owned(v.to_string().into_bytes()),
+        Double(v) => Cow::Owned(v.to_string().into_bytes()),
+        Decimal(v) => Cow::Owned(v.to_string().into_bytes()),
+    }
+}
+
+/// Format value at (row, col) of current block into `dest` of `len` bytes as a NUL-terminated string.
+///
+/// NULL values are written as `NULL`, bools as `true`/`false`, timestamps in RFC3339 with the
+/// precision of the result set, and varchar/nchar/json as is. Returns the same as
+/// `ws_timestamp_to_rfc3339_with_len`: the bytes written, or the required size if `len` is not
+/// large enough. Returns -1 if `rs` is NULL or failed, or row/col is out of range.
+///
+/// ## Example
+///
+/// ```c
+/// char buf[256];
+/// int n = ws_get_value_as_string(rs, 0, 0, buf, sizeof(buf));
+/// if (n >= 0 && n < sizeof(buf)) {
+///   printf("%s\n", buf);
+/// }
+/// ```
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, `dest` must be NULL or writable for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ws_get_value_as_string(
+    rs: *mut WS_RES,
+    row: i32,
+    col: i32,
+    dest: *mut c_char,
+    len: i32,
+) -> i32 {
+    ffi_guard(|| {
+        let Some(rs) = (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        else {
+            null_pointer_error("WS_RES is null");
+            return -1;
+        };
+        if row < 0 || col < 0 || len < 0 {
+            set_c_error(&WsError::new(
+                Code::Failed,
+                "row, col and len must not be negative",
+            ));
+            return -1;
+        }
+        let Some(value) = rs
+            .block
+            .as_ref()
+            .and_then(|block| block.get_ref(row as _, col as _))
+        else {
+            set_c_error(&WsError::new(Code::Failed, "row or col is out of range"));
+            return -1;
+        };
+        write_c_str(
+            dest as _,
+            len as _,
+            &value_to_string(&value, rs.precision(), &rs.tz),
+        )
+    })
+}
+
+/// Canonical type name of the type code in `WS_FIELD.type` or from `ws_get_value_in_block`,
+/// e.g. `TIMESTAMP`, `BIGINT UNSIGNED`. Returns `UNKNOWN` for unknown codes.
+///
+/// The returned string is static.
+#[no_mangle]
+pub extern "C" fn ws_type_name(r#type: u8) -> *const c_char {
+    ffi_guard(|| type_name(r#type).as_ptr())
+}
+
+fn type_name(ty: u8) -> &'static CStr {
+    static NAMES: OnceCell<Vec<Option<CString>>> = OnceCell::new();
+    static UNKNOWN: &CStr = c"UNKNOWN";
+    let names = NAMES.get_or_init(|| {
+        (0..=u8::MAX)
+            .map(|v| Ty::try_from_u8(v).map(|ty| CString::new(ty.name()).unwrap()))
+            .collect()
+    });
+    names[ty as usize].as_deref().unwrap_or(UNKNOWN)
+}
+
+/// Widen a numeric or bool value to double.
+fn value_to_double(value: &BorrowedValue) -> Result<f64, WsError> {
+    use BorrowedValue::*;
+    match value {
+        Null(_) => Err(WsError::new(
+            WS_ERROR_NO::NULL_VALUE.as_code(),
+            "value is null",
+        )),
+        Bool(v) => Ok(*v as u8 as f64),
+        TinyInt(v) => Ok(*v as f64),
+        SmallInt(v) => Ok(*v as f64),
+        Int(v) => Ok(*v as f64),
+        BigInt(v) => Ok(*v as f64),
+        UTinyInt(v) => Ok(*v as f64),
+        USmallInt(v) => Ok(*v as f64),
+        UInt(v) => Ok(*v as f64),
+        UBigInt(v) => Ok(*v as f64),
+        Float(v) => Ok(*v as f64),
+        Double(v) => Ok(*v),
+        v => Err(WsError::new(
+            WS_ERROR_NO::TYPE_MISMATCH.as_code(),
+            &format!("value of type {} is not numeric", v.ty().name()),
+        )),
+    }
+}
+
+/// Get value at (row, col) of current block as double, numeric and bool types are widened.
+///
+/// Returns 0 on success, `0xE009` for NULL value, `0xE00A` for non-numeric types like
+/// varchar or json, other non-zero codes for invalid arguments. `*out` is written only on success.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, `out` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn ws_get_double(rs: *mut WS_RES, row: i32, col: i32, out: *mut f64) -> i32 {
+    ffi_guard(|| {
+        if out.is_null() {
+            return null_pointer_error("out is null");
+        }
+        let Some(rs) = (rs as *const WsMaybeError<WsResultSet>)
+            .as_ref()
+            .and_then(|rs| rs.safe_deref())
+        else {
+            return null_pointer_error("WS_RES is null");
+        };
+        let value = if row < 0 || col < 0 {
+            None
+        } else {
+            rs.block
+                .as_ref()
+                .and_then(|block| block.get_ref(row as _, col as _))
+        };
+        let Some(value) = value else {
+            let err = WsError::new(Code::Failed, "row or col is out of range");
+            set_c_error(&err);
+            return err.code.into();
+        };
+        match value_to_double(&value) {
+            Ok(v) => {
+                *out = v;
+                0
+            }
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
+}
+
+/// Get raw data of column `col` in current block for bulk copies.
+///
+/// `*data` points to the contiguous column payload. For var-types (varchar, nchar, json),
+/// `*offsets` is the offset of each row in the payload (negative for NULL), and each value is
+/// prefixed with an u16 length, nchar values are in UTF-8. `*offsets` is NULL for fixed-length
+/// types, `offsets` itself could be NULL if not required. `*is_null` is a byte per row indicator.
+///
+/// Buffers are valid until next fetch. Returns 0 on success, non-zero on invalid arguments.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, each of the out pointers must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn ws_get_column(
+    rs: *mut WS_RES,
+    col: i32,
+    data: *mut *const c_void,
+    offsets: *mut *const i32,
+    is_null: *mut *const c_char,
+    rows: *mut i32,
+) -> i32 {
+    ffi_guard(|| {
+        if data.is_null() || is_null.is_null() || rows.is_null() {
+            return null_pointer_error("data, is_null or rows is null");
+        }
+        let Some(rs) = (rs as *mut WsMaybeError<WsResultSet>)
+            .as_mut()
+            .and_then(|rs| rs.safe_deref_mut())
+        else {
+            return null_pointer_error("WS_RES is null");
+        };
+        let nulls = if col < 0 {
+            None
+        } else {
+            rs.column_nulls(col as _).map(|nulls| nulls.as_ptr())
+        };
+        let (Some(nulls), Some(block)) = (nulls, rs.block.as_ref()) else {
+            let err = WsError::new(Code::Failed, "no current block or col is out of range");
+            set_c_error(&err);
+            return err.code.into();
+        };
+        let view = &block.column_views()[col as usize];
+        *data = view.as_raw_ptr();
+        if !offsets.is_null() {
+            *offsets = view
+                .raw_offsets()
+                .map(|offsets| offsets.as_ptr())
+                .unwrap_or(std::ptr::null());
+        }
+        *is_null = nulls;
+        *rows = block.nrows() as _;
+        0
+    })
+}
+
+#[no_mangle]
+/// Unimplemented currently.
+pub unsafe fn ws_print_row(rs: *mut WS_RES, row: i32) {
+    let (_, _) = (rs, row);
+    todo!()
+    // match (rs as *mut WsResultSet).as_mut() {
+    //     Some(rs) => rs.fetch_block(ptr, rows),
+    //     None => {
+    //         *rows = 0;
+    //         0
+    //     },
+    // }
+}
+
+#[cfg(test)]
+pub fn init_env() {
+    std::env::set_var("RUST_LOG", "debug");
+    unsafe { ws_enable_log() };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the nul-terminated `sql` on `taos` and free its result, panics on errors.
+    pub(crate) unsafe fn exec_sql(taos: *mut WS_TAOS, sql: &[u8]) {
+        let rs = ws_query(taos, sql.as_ptr() as _);
+        let code = ws_errno(rs);
+        assert!(code == 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+        ws_free_result(rs);
+    }
+
+    #[test]
+    fn dsn_error() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://unknown-host:15237\0" as *const u8 as _);
+            assert!(taos.is_null(), "connection return NULL when failed");
+            // check taos.is_null() when in production use case.
+            if taos.is_null() {
+                let code = ws_errno(taos);
+                assert!(code != 0);
+                let str = ws_errstr(taos);
+                dbg!(CStr::from_ptr(str));
+            }
+        }
+    }
+
+    #[test]
+    fn last_error() {
+        unsafe {
+            clear_c_error();
+            assert_eq!(ws_last_errno(), 0);
+            assert!(CStr::from_ptr(ws_last_errstr()).to_bytes().is_empty());
+
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\xff\0" as *const u8 as _);
+            assert!(taos.is_null());
+            let code = ws_last_errno();
+            assert!(code != 0);
+            let errstr = CStr::from_ptr(ws_last_errstr())
+                .to_str()
+                .unwrap()
+                .to_string();
+            assert!(!errstr.is_empty());
+            assert_eq!(ws_errno(std::ptr::null_mut()), code);
+
+            // error slot is thread-local.
+            std::thread::spawn(|| assert_eq!(ws_last_errno(), 0))
+                .join()
+                .unwrap();
+            assert_eq!(ws_last_errno(), code);
+        }
+    }
+
+    #[test]
+    fn null_out_pointers() {
+        unsafe {
+            let null_rs = std::ptr::null_mut();
+            let failed: WsMaybeError<WsResultSet> =
+                WsResult::<WsResultSet>::Err(WsError::new(Code::Failed, "failed")).into();
+            let failed_rs = Box::into_raw(Box::new(failed)) as *mut WS_RES;
+            let null_pointer: i32 = WS_ERROR_NO::NULL_POINTER.as_code().into();
+
+            for rs in [null_rs, failed_rs] {
+                for with_ptr in [false, true] {
+                    for with_rows in [false, true] {
+                        let mut ptr: *const c_void = std::ptr::null();
+                        let mut rows = -1;
+                        let ptr_arg = if with_ptr {
+                            &mut ptr as *mut _
+                        } else {
+                            std::ptr::null_mut()
+                        };
+                        let rows_arg = if with_rows {
+                            &mut rows as *mut _
+                        } else {
+                            std::ptr::null_mut()
+                        };
+                        let code = ws_fetch_block(rs, ptr_arg, rows_arg);
+                        assert!(code != 0);
+                        if rs.is_null() || !with_ptr || !with_rows {
+                            assert_eq!(code, null_pointer);
+                            assert_eq!(rows, -1);
+                        } else {
+                            assert_eq!(code, ws_errno(rs));
+                            assert_eq!(rows, 0);
+                        }
+                        assert!(ptr.is_null());
+                    }
+                }
+
+                for with_ty in [false, true] {
+                    for with_len in [false, true] {
+                        let mut ty: u8 = 0xff;
+                        let mut len: u32 = 0xff;
+                        let ty_arg = if with_ty {
+                            &mut ty as *mut _
+                        } else {
+                            std::ptr::null_mut()
+                        };
+                        let len_arg = if with_len {
+                            &mut len as *mut _
+                        } else {
+                            std::ptr::null_mut()
+                        };
+                        let v = ws_get_value_in_block(rs, 0, 0, ty_arg, len_arg);
+                        assert!(v.is_null());
+                        assert_eq!(ty, if with_ty { Ty::Null as u8 } else { 0xff });
+                        assert_eq!(len, if with_len { 0 } else { 0xff });
+                    }
+                }
+            }
+            ws_free_result(failed_rs);
+        }
+    }
+
+    #[test]
+    fn long_field_name() {
+        let name = field_name(&"a".repeat(65));
+        let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+        assert_eq!(name.to_bytes(), "a".repeat(64).as_bytes());
+
+        // do not split a 3-byte char at the 64 bytes limit.
+        let long = format!("{}中", "a".repeat(62));
+        let name = field_name(&long);
+        let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+        assert_eq!(name.to_str().unwrap(), "a".repeat(62));
+
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            // an expression without alias is named by its text, longer than 64 bytes here.
+            let sql = format!("select 1{}\0", "+1".repeat(40));
+            let rs = ws_query(taos, sql.as_ptr() as _);
+            assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+            let fields = ws_fetch_fields(rs);
+            let field = &*fields;
+            assert!(field.name().to_bytes().len() <= 64);
+            assert!(field.name().to_str().is_ok());
+            ws_free_result(rs);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn error_with_nul() {
+        let err = WsError::new(Code::Failed, "bad\0message");
+        assert_eq!(err.message.to_str().unwrap(), "bad\\0message");
+
+        let rs: WsMaybeError<()> = WsResult::<()>::Err(err).into();
+        let rs = Box::into_raw(Box::new(rs)) as *mut WS_RES;
+        unsafe {
+            let errstr = CStr::from_ptr(ws_errstr(rs)).to_str().unwrap();
+            assert_eq!(errstr, "bad\\0message");
+            ws_free_result(rs);
+        }
+    }
+
+    #[test]
+    fn panic_guard() {
+        let code = ffi_guard(|| -> i32 { panic!("boom") });
+        assert_eq!(code, i32::from(Code::Failed));
+        assert!(ffi_guard(|| -> *const c_char { panic!("boom") }).is_null());
+        let errstr = unsafe { CStr::from_ptr(ws_last_errstr()) };
+        assert!(errstr.to_str().unwrap().contains("boom"));
+    }
+
+    #[test]
+    fn value_as_string() {
+        let p = Precision::Millisecond;
+        let tz = tz::WsTimeZone::Local;
+        assert_eq!(
+            &*value_to_string(&BorrowedValue::Null(Ty::Int), p, &tz),
+            b"NULL"
+        );
+        assert_eq!(
+            &*value_to_string(&BorrowedValue::Bool(true), p, &tz),
+            b"true"
+        );
+        assert_eq!(&*value_to_string(&BorrowedValue::Int(-1), p, &tz), b"-1");
+        assert_eq!(
+            &*value_to_string(&BorrowedValue::VarChar("abc"), p, &tz),
+            b"abc"
+        );
+        let ts = BorrowedValue::Timestamp(Timestamp::new(0, p));
+        assert_eq!(
+            value_to_string(&ts, p, &tz).as_ref(),
+            timestamp_to_rfc3339(0, 0, false, &tz).unwrap().as_bytes()
+        );
+        let tz = tz::WsTimeZone::parse("-02:00").unwrap();
+        assert_eq!(
+            value_to_string(&ts, p, &tz).as_ref(),
+            b"1969-12-31T22:00:00.000-02:00"
+        );
+
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"select 1, true, 'abc', null\0" as *const u8 as _;
+            let rs = ws_query(taos, sql);
+            assert_eq!(ws_errno(rs), 0);
+            let mut ptr = std::ptr::null();
+            let mut rows = 0;
+            assert_eq!(ws_fetch_block(rs, &mut ptr, &mut rows), 0);
+            assert_eq!(rows, 1);
+
+            let mut buf = [0 as c_char; 32];
+            for (col, expected) in ["1", "true", "abc", "NULL"].into_iter().enumerate() {
+                let n = ws_get_value_as_string(rs, 0, col as _, buf.as_mut_ptr(), 32);
+                assert_eq!(n as usize, expected.len());
+                assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap(), expected);
+            }
+            let n = ws_get_value_as_string(rs, 0, 2, buf.as_mut_ptr(), 2);
+            assert_eq!(n, 4);
+            assert_eq!(ws_get_value_as_string(rs, 1, 0, buf.as_mut_ptr(), 32), -1);
+
+            ws_free_result(rs);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn get_double() {
+        assert_eq!(value_to_double(&BorrowedValue::Bool(true)).unwrap(), 1.0);
+        assert_eq!(value_to_double(&BorrowedValue::UBigInt(3)).unwrap(), 3.0);
+        assert_eq!(value_to_double(&BorrowedValue::Float(0.5)).unwrap(), 0.5);
+        let null_value: i32 = WS_ERROR_NO::NULL_VALUE.as_code().into();
+        let mismatch: i32 = WS_ERROR_NO::TYPE_MISMATCH.as_code().into();
+        let code: i32 = value_to_double(&BorrowedValue::Null(Ty::Int))
+            .unwrap_err()
+            .code
+            .into();
+        assert_eq!(code, null_value);
+        let code: i32 = value_to_double(&BorrowedValue::VarChar("1"))
+            .unwrap_err()
+            .code
+            .into();
+        assert_eq!(code, mismatch);
+
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"select 1, 2.5, true, 'abc', null\0" as *const u8 as _;
+            let rs = ws_query(taos, sql);
+            assert_eq!(ws_errno(rs), 0);
+            let mut ptr = std::ptr::null();
+            let mut rows = 0;
+            assert_eq!(ws_fetch_block(rs, &mut ptr, &mut rows), 0);
+
+            let mut v = 0.0;
+            assert_eq!(ws_get_double(rs, 0, 0, &mut v), 0);
+            assert_eq!(v, 1.0);
+            assert_eq!(ws_get_double(rs, 0, 1, &mut v), 0);
+            assert_eq!(v, 2.5);
+            assert_eq!(ws_get_double(rs, 0, 2, &mut v), 0);
+            assert_eq!(v, 1.0);
+            assert_eq!(ws_get_double(rs, 0, 3, &mut v), mismatch);
+            assert_eq!(ws_get_double(rs, 0, 4, &mut v), null_value);
+
+            ws_free_result(rs);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn type_names() {
+        let name = |ty: u8| {
+            unsafe { CStr::from_ptr(ws_type_name(ty)) }
+                .to_str()
+                .unwrap()
+        };
+        assert_eq!(name(Ty::Timestamp as u8), "TIMESTAMP");
+        assert_eq!(name(Ty::NChar as u8), "NCHAR");
+        assert_eq!(name(Ty::UBigInt as u8), "BIGINT UNSIGNED");
+        assert_eq!(name(200), "UNKNOWN");
+        assert_eq!(ws_type_name(Ty::Int as u8), ws_type_name(Ty::Int as u8));
+        for v in 0..=u8::MAX {
+            if let Some(ty) = Ty::try_from_u8(v) {
+                assert_eq!(ty as u8, v);
+                assert_eq!(name(v), ty.name());
+            }
+        }
+    }
+
+    #[test]
+    fn block_v2_layout() {
+        let fields = [
+            Field::new("i", Ty::Int, 4),
+            Field::new("b", Ty::Bool, 1),
+            Field::new("v", Ty::VarChar, 4),
+        ];
+        let mut raw = Vec::new();
+        // int column: 1, NULL
+        raw.extend_from_slice(&1i32.to_le_bytes());
+        raw.extend_from_slice(&i32::MIN.to_le_bytes());
+        // bool column: NULL, true
+        raw.extend_from_slice(&[0x02, 0x01]);
+        // varchar column: "ab", NULL
+        raw.extend_from_slice(&[2, 0, b'a', b'b', 0, 0]);
+        raw.extend_from_slice(&[1, 0, 0xFF, 0, 0, 0]);
+
+        let block = Block::parse_from_raw_block_v2(
+            raw.clone(),
+            &fields,
+            &[4, 1, 6],
+            2,
+            Precision::Millisecond,
+        );
+        assert!(block.is_null(1, 0));
+        assert!(block.is_null(0, 1));
+        assert!(block.is_null(1, 2));
+
+        let mut v2 = Vec::new();
+        block_to_v2(&block, &fields, &mut v2);
+        assert_eq!(v2, raw);
+    }
+
+    #[test]
+    fn block_dimensions() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"show databases\0" as *const u8 as _;
+            let rs = ws_query(taos, sql);
+            assert_eq!(ws_errno(rs), 0);
+            assert_eq!(ws_block_num_rows(rs), 0);
+            assert_eq!(ws_total_rows_fetched(rs), 0);
+
+            let cols = ws_field_count(rs);
+            let mut ptr = std::ptr::null();
+            let mut rows = 0;
+            let mut total = 0;
+            loop {
+                assert_eq!(ws_fetch_block(rs, &mut ptr, &mut rows), 0);
+                if rows == 0 {
+                    break;
+                }
+                total += rows as u64;
+                assert_eq!(ws_block_num_rows(rs), rows);
+                assert_eq!(ws_block_num_cols(rs), cols);
+                assert_eq!(ws_total_rows_fetched(rs), total);
+            }
+            assert!(total > 0);
+            assert_eq!(ws_total_rows_fetched(rs), total);
+            ws_free_result(rs);
+
+            assert_eq!(ws_block_num_rows(std::ptr::null()), 0);
+            assert_eq!(ws_block_num_cols(std::ptr::null()), 0);
+            assert_eq!(ws_total_rows_fetched(std::ptr::null()), 0);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn affected_rows64() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let rs = ws_query(taos, b"show databases\0" as *const u8 as _);
+            assert_eq!(ws_errno(rs), 0);
+            assert_eq!(ws_affected_rows64(rs), ws_affected_rows(rs) as i64);
+
+            // fake a large ingest
+            let ws_rs = (rs as *mut WsMaybeError<WsResultSet>)
+                .as_mut()
+                .and_then(|rs| rs.safe_deref_mut())
+                .unwrap();
+            ws_rs.affected_rows = 5_000_000_000;
+            assert_eq!(ws_affected_rows64(rs), 5_000_000_000);
+            assert_eq!(ws_affected_rows(rs), i32::MAX);
+
+            ws_rs.affected_rows = 42;
+            assert_eq!(ws_affected_rows64(rs), 42);
+            assert_eq!(ws_affected_rows(rs), 42);
+            ws_free_result(rs);
+
+            assert_eq!(ws_affected_rows64(std::ptr::null()), 0);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn take_timing() {
+        init_env();
+        unsafe {
+            assert_eq!(ws_take_timing(std::ptr::null_mut()), -1);
+
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let rs = ws_query(taos, b"show databases\0" as *const u8 as _);
+            assert_eq!(ws_errno(rs), 0);
+            assert!(ws_take_timing(rs) >= 0);
+            ws_free_result(rs);
+
+            let rs = ws_query(taos, b"select * from ws_no_such_db.t\0" as *const u8 as _);
+            assert_ne!(ws_errno(rs), 0);
+            assert_eq!(ws_take_timing(rs), -1);
+            ws_free_result(rs);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn log_in_dir() {
+        init_env();
+        unsafe {
+            let dir = std::env::temp_dir().join(format!("taosws-log-{}", std::process::id()));
+            let c_dir = CString::new(dir.to_str().unwrap()).unwrap();
+            assert_eq!(ws_enable_log_in_dir(c_dir.as_ptr()), 0);
+            log::error!("log to file in dir");
+            assert_eq!(ws_enable_log_in_dir(std::ptr::null()), 0);
+
+            let content = std::fs::read_to_string(dir.join("taosws.log")).unwrap();
+            assert!(content.contains("log to file in dir"));
+            std::fs::remove_dir_all(dir).unwrap();
+
+            assert_ne!(ws_set_log_level(b"verbose\0" as *const u8 as _), 0);
+            assert_ne!(ws_last_errno(), 0);
+            assert_eq!(ws_set_log_level(b"debug\0" as *const u8 as _), 0);
+        }
+    }
+
+    #[test]
+    fn free_after_close() {
+        init_env();
+        unsafe {
+            ws_free_result(std::ptr::null_mut());
+            ws_close(std::ptr::null_mut());
+
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"show databases\0" as *const u8 as _;
+            let rs = ws_query(taos, sql);
+            assert_eq!(ws_errno(rs), 0);
+            ws_close(taos);
+
+            // result set outlives the connection, fetch reports connection closed.
+            let mut ptr = std::ptr::null();
+            let mut rows = 0;
+            let code = ws_fetch_block(rs, &mut ptr, &mut rows);
+            assert_eq!(code, WS_ERROR_NO::CONN_CLOSED as i32);
+            assert!(ws_fetch_row(rs).is_null());
+            ws_free_result(rs);
+        }
+    }
+
+    #[test]
+    fn get_column() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            exec_sql(taos, b"drop database if exists ws_get_column\0");
+            exec_sql(taos, b"create database ws_get_column keep 36500\0");
+            exec_sql(
+                taos,
+                b"create table ws_get_column.t1 (ts timestamp, v int, s varchar(10))\0",
+            );
+            exec_sql(taos, b"insert into ws_get_column.t1 values(1655793421375, 1, 'abc') (1655793421376, null, null)\0");
+
+            let rs = ws_query(taos, b"select * from ws_get_column.t1\0" as *const u8 as _);
+            assert_eq!(ws_errno(rs), 0);
+            let mut ptr = std::ptr::null();
+            let mut rows = 0;
+            assert_eq!(ws_fetch_block(rs, &mut ptr, &mut rows), 0);
+            assert_eq!(rows, 2);
+
+            let mut data = std::ptr::null();
+            let mut offsets = std::ptr::null();
+            let mut is_null = std::ptr::null();
+            let mut n = 0;
+            let code = ws_get_column(rs, 1, &mut data, &mut offsets, &mut is_null, &mut n);
+            assert_eq!(code, 0);
+            assert_eq!(n, 2);
+            assert!(offsets.is_null());
+            let nulls = std::slice::from_raw_parts(is_null, 2);
+            assert_eq!(nulls, &[0, 1]);
+            assert_eq!(*(data as *const i32), 1);
+
+            let code = ws_get_column(rs, 2, &mut data, &mut offsets, &mut is_null, &mut n);
+            assert_eq!(code, 0);
+            let offs = std::slice::from_raw_parts(offsets, 2);
+            assert!(offs[0] >= 0);
+            assert!(offs[1] < 0);
+            let value = (data as *const u8).offset(offs[0] as isize);
+            let len = u16::from_le_bytes([*value, *value.add(1)]) as usize;
+            assert_eq!(std::slice::from_raw_parts(value.add(2), len), b"abc");
+            assert_eq!(std::slice::from_raw_parts(is_null, 2), &[0, 1]);
+
+            let code = ws_get_column(rs, 3, &mut data, &mut offsets, &mut is_null, &mut n);
+            assert_ne!(code, 0);
+
+            ws_free_result(rs);
+            exec_sql(taos, b"drop database if exists ws_get_column\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn take_block() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"show databases\0" as *const u8 as _;
+            let rs = ws_query(taos, sql);
+            assert_eq!(ws_errno(rs), 0);
+
+            let mut ptr = std::ptr::null();
+            let mut rows = 0;
+            assert_eq!(ws_fetch_block(rs, &mut ptr, &mut rows), 0);
+            assert!(rows > 0);
+
+            let mut block = std::ptr::null_mut();
+            assert_eq!(ws_take_block(rs, &mut block), 0);
+            assert!(!block.is_null());
+            assert_eq!(ws_block_rows(block), rows);
+            assert_eq!(ws_block_data(block), ptr);
+            assert!(ws_get_raw_block(rs).is_null());
+            assert_eq!(ws_block_num_rows(rs), 0);
+            assert_eq!(ws_block_num_cols(rs), 0);
+
+            // the block is still valid after more fetches and freeing the result set.
+            while rows > 0 {
+                assert_eq!(ws_fetch_block(rs, &mut ptr, &mut rows), 0);
+            }
+            ws_free_result(rs);
+            assert!(ws_block_rows(block) > 0);
+            ws_free_block(block);
+
+            assert_eq!(
+                ws_take_block(std::ptr::null_mut(), &mut block),
+                WS_ERROR_NO::NULL_POINTER as i32
+            );
+            assert!(block.is_null());
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn fetch_block_into() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"show databases\0" as *const u8 as _;
+            let rs = ws_query(taos, sql);
+            assert_eq!(ws_errno(rs), 0);
+
+            let mut written = 0;
+            let mut rows = 0;
+            let mut buf = vec![0u8; 8];
+            let code = ws_fetch_block_into(rs, buf.as_mut_ptr() as _, 8, &mut written, &mut rows);
+            assert_eq!(code, WS_ERROR_NO::BUFFER_TOO_SMALL as i32);
+            assert!(written > 8);
+            assert!(rows > 0);
+            assert_eq!(ws_errno(rs), 0);
+
+            // retry with the required size gets the same block.
+            let first = rows;
+            buf.resize(written, 0);
+            let code = ws_fetch_block_into(
+                rs,
+                buf.as_mut_ptr() as _,
+                buf.len(),
+                &mut written,
+                &mut rows,
+            );
+            assert_eq!(code, 0);
+            assert_eq!(written, buf.len());
+            assert_eq!(rows, first);
+            assert_eq!(
+                u32::from_le_bytes(buf[4..8].try_into().unwrap()) as usize,
+                written,
+                "block length in header"
+            );
+
+            while rows > 0 {
+                buf.resize(written.max(4096), 0);
+                let code = ws_fetch_block_into(
+                    rs,
+                    buf.as_mut_ptr() as _,
+                    buf.len(),
+                    &mut written,
+                    &mut rows,
+                );
+                assert_eq!(code, 0);
+            }
+            assert_eq!(written, 0);
+            ws_free_result(rs);
+
+            assert_eq!(
+                ws_fetch_block_into(
+                    std::ptr::null_mut(),
+                    buf.as_mut_ptr() as _,
+                    0,
+                    &mut written,
+                    &mut rows
+                ),
+                WS_ERROR_NO::NULL_POINTER as i32
+            );
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn connect_args() {
+        init_env();
+        unsafe {
+            let taos = ws_connect(
+                b"unknown-host\0" as *const u8 as _,
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                15237,
+            );
+            assert!(taos.is_null(), "connection return NULL when failed");
+            assert!(ws_errno(taos) != 0);
+            dbg!(CStr::from_ptr(ws_errstr(taos)));
+
+            let taos = ws_connect(
+                std::ptr::null(),
+                b"root\0" as *const u8 as _,
+                b"taosdata\0" as *const u8 as _,
+                std::ptr::null(),
+                0,
+            );
+            assert!(!taos.is_null(), "client pointer is not null when success");
+            assert_eq!(ws_errno(std::ptr::null_mut()), 0);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn query_a() {
+        init_env();
+        unsafe extern "C" fn callback(param: *mut c_void, res: *mut WS_RES, code: i32) {
+            let tx = &*(param as *const std::sync::mpsc::Sender<(usize, i32)>);
+            tx.send((res as usize, code)).unwrap();
+        }
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let (tx, rx) = std::sync::mpsc::channel::<(usize, i32)>();
+            let param = &tx as *const _ as *mut c_void;
+
+            let sql = b"show databases\0" as *const u8 as _;
+            ws_query_a(taos, sql, Some(callback), param);
+            let (rs, code) = rx.recv().unwrap();
+            let rs = rs as *mut WS_RES;
+            assert_eq!(code, 0);
+            assert_eq!(ws_errno(rs), 0);
+            assert!(ws_field_count(rs) > 0);
+            ws_free_result(rs);
+
+            let sql = b"show x\0" as *const u8 as _;
+            ws_query_a(taos, sql, Some(callback), param);
+            let (rs, code) = rx.recv().unwrap();
+            let rs = rs as *mut WS_RES;
+            assert_ne!(code, 0);
+            assert_eq!(code, ws_errno(rs));
+            ws_free_result(rs);
+
+            ws_query_a(std::ptr::null_mut(), sql, Some(callback), param);
+            let (rs, code) = rx.recv().unwrap();
+            assert_ne!(code, 0);
+            ws_free_result(rs as *mut WS_RES);
+
+            // closing with a query in flight, the callback is still called.
+            let sql = b"show databases\0" as *const u8 as _;
+            ws_query_a(taos, sql, Some(callback), param);
+            ws_close(taos);
+            let (rs, _) = rx.recv().unwrap();
+            ws_free_result(rs as *mut WS_RES);
+        }
+    }
+
+    #[test]
+    fn fetch_raw_block_a() {
+        init_env();
+        unsafe extern "C" fn callback(param: *mut c_void, res: *mut WS_RES, code: i32) {
+            let tx = &*(param as *const std::sync::mpsc::Sender<(usize, i32)>);
+            tx.send((res as usize, code)).unwrap();
+        }
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"show databases\0" as *const u8 as _;
+            let rs = ws_query(taos, sql);
+            assert_eq!(ws_errno(rs), 0);
+
+            let (tx, rx) = std::sync::mpsc::channel::<(usize, i32)>();
+            let param = &tx as *const _ as *mut c_void;
+
+            let mut total = 0;
+            loop {
+                ws_fetch_raw_block_a(rs, Some(callback), param);
+                let (res, rows) = rx.recv().unwrap();
+                assert_eq!(res, rs as usize);
+                assert!(rows >= 0);
+                if rows == 0 {
+                    assert!(ws_get_raw_block(rs).is_null());
+                    break;
+                }
+                assert!(!ws_get_raw_block(rs).is_null());
+                total += rows;
+            }
+            assert!(total > 0);
+
+            ws_free_result(rs);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn query_with_reqid() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"show databases\0" as *const u8 as _;
+            let rs = ws_query_with_reqid(taos, sql, 0x1234_5678);
+            assert_eq!(ws_errno(rs), 0);
+            assert_eq!(ws_get_req_id(rs), 0x1234_5678);
+            ws_free_result(rs);
+
+            assert_eq!(ws_get_req_id(std::ptr::null()), 0);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn split_statements() {
+        assert_eq!(
+            super::split_statements("show databases"),
+            ["show databases"]
+        );
+        assert_eq!(
+            super::split_statements("create database a; use a;\n insert into t values(now, 'x;y')"),
+            [
+                "create database a",
+                "use a",
+                "insert into t values(now, 'x;y')"
+            ]
+        );
+        assert_eq!(
+            super::split_statements(r#"select "a\";b", `c;d` from t; ;"#),
+            [r#"select "a\";b", `c;d` from t"#]
+        );
+        assert_eq!(
+            super::split_statements("-- drop; it\nselect 1 /* ; */; /* only comment; */ -- end;"),
+            ["-- drop; it\nselect 1 /* ; */"]
+        );
+        assert!(super::split_statements(" ; -- nothing").is_empty());
+    }
+
+    #[test]
+    fn current_db_required() {
+        unsafe {
+            let mut db = [0 as c_char; 8];
+            let mut required = -1;
+            let code = ws_get_current_db(std::ptr::null_mut(), db.as_mut_ptr(), 8, &mut required);
+            assert!(code != 0);
+            assert_eq!(required, 0);
+
+            required = -1;
+            let code = ws_get_current_db(std::ptr::null_mut(), db.as_mut_ptr(), 0, &mut required);
+            assert!(code != 0);
+            assert_eq!(required, 0);
         }
-        _ => {
-            *ty = Ty::Null as u8;
-            *len = 0;
-            std::ptr::null()
+    }
+
+    #[test]
+    fn query_multi_null_sql() {
+        unsafe {
+            let rs = ws_query_multi(std::ptr::null_mut(), std::ptr::null());
+            assert_eq!(ws_errno(rs), WS_ERROR_NO::NULL_POINTER as i32);
+            ws_free_result(rs);
         }
     }
-}
 
-/// Convert timestamp to C string.
-///
-/// This function use a thread-local variable to print, it may works in most cases but not always be thread-safe,
-///  use it only if it work as you expected.
-#[no_mangle]
-pub unsafe extern "C" fn ws_timestamp_to_rfc3339(
-    dest: *mut u8,
-    raw: i64,
-    precision: i32,
-    use_z: bool,
-) {
-    let precision = Precision::from_u8(precision as u8);
-    let s = format!(
-        "{}",
-        Timestamp::new(raw, precision)
-            .to_datetime_with_tz()
-            .to_rfc3339_opts(precision.to_seconds_format(), use_z)
-    );
+    #[test]
+    fn query_multi() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
 
-    std::ptr::copy_nonoverlapping(s.as_ptr(), dest, s.len());
-}
+            let sql = b"drop database if exists ws_query_multi;\
+                create database ws_query_multi;\
+                create table ws_query_multi.t1 (ts timestamp, v varchar(10));\
+                -- comments with ; are skipped\n\
+                insert into ws_query_multi.t1 values(now, 'a;b');\
+                insert into ws_query_multi.t1 values(now + 1s, 'c') (now + 2s, 'd');\
+                select * from ws_query_multi.t1;\0";
+            let rs = ws_query_multi(taos, sql.as_ptr() as _);
+            assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+            assert_eq!(ws_affected_rows64(rs), 3);
+            assert_eq!(ws_field_count(rs), 2);
+            let mut ptr = std::ptr::null();
+            let mut rows = 0;
+            assert_eq!(ws_fetch_block(rs, &mut ptr, &mut rows), 0);
+            assert_eq!(rows, 3);
+            ws_free_result(rs);
 
-#[no_mangle]
-/// Unimplemented currently.
-pub unsafe fn ws_print_row(rs: *mut WS_RES, row: i32) {
-    let (_, _) = (rs, row);
-    todo!()
-    // match (rs as *mut WsResultSet).as_mut() {
-    //     Some(rs) => rs.fetch_block(ptr, rows),
-    //     None => {
-    //         *rows = 0;
-    //         0
-    //     },
-    // }
-}
+            let sql = b"select 1; select * from ws_query_multi.not_exists; select 2\0";
+            let rs = ws_query_multi(taos, sql.as_ptr() as _);
+            assert_ne!(ws_errno(rs), 0);
+            let err = CStr::from_ptr(ws_errstr(rs)).to_str().unwrap();
+            assert!(err.starts_with("statement 2 failed"), "{err}");
+            ws_free_result(rs);
 
-#[cfg(test)]
-pub fn init_env() {
-    std::env::set_var("RUST_LOG", "debug");
-    unsafe { ws_enable_log() };
-}
+            let rs = ws_query(taos, b"drop database ws_query_multi\0" as *const u8 as _);
+            ws_free_result(rs);
+            ws_close(taos);
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
     #[test]
-    fn dsn_error() {
+    fn query_timeout() {
         init_env();
         unsafe {
-            let taos = ws_connect_with_dsn(b"ws://unknown-host:15237\0" as *const u8 as _);
-            assert!(taos.is_null(), "connection return NULL when failed");
-            // check taos.is_null() when in production use case.
-            if taos.is_null() {
-                let code = ws_errno(taos);
-                assert!(code != 0);
-                let str = ws_errstr(taos);
-                dbg!(CStr::from_ptr(str));
-            }
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"show databases\0" as *const u8 as _;
+            let rs = ws_query_timeout(taos, sql, 0);
+            assert_eq!(ws_errno(rs), 0);
+            ws_free_result(rs);
+
+            let rs = ws_query_timeout(taos, sql, 10);
+            assert_eq!(ws_errno(rs), 0);
+            ws_free_result(rs);
+
+            let rs = ws_query(taos, sql);
+            assert_eq!(ws_errno(rs), 0);
+            ws_free_result(rs);
+
+            ws_close(taos);
         }
     }
 
@@ -851,16 +4091,623 @@ mod tests {
             let code = ws_errno(rs);
             assert!(code == 0);
             assert!(ws_is_update_query(rs));
+            ws_free_result(rs);
+
+            let mut ptr = std::ptr::null();
+            let mut rows = -1;
+            for (sql, update) in [
+                (
+                    &b"create table if not exists ws_is_update.t1 (ts timestamp, v int)\0"[..],
+                    true,
+                ),
+                (&b"insert into ws_is_update.t1 values(now, 1)\0"[..], true),
+                (&b"select * from ws_is_update.t1\0"[..], false),
+            ] {
+                let rs = ws_query(taos, sql.as_ptr() as _);
+                assert_eq!(ws_errno(rs), 0);
+                assert_eq!(ws_is_update_query(rs), update);
+                assert_eq!(ws_fetch_block(rs, &mut ptr, &mut rows), 0);
+                assert_eq!(rows > 0, !update);
+                ws_free_result(rs);
+            }
+
+            assert!(ws_is_update_query(std::ptr::null()));
+            let rs = ws_query(taos, b"drop database ws_is_update\0" as *const u8 as _);
+            ws_free_result(rs);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn fetch_row() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"show databases\0" as *const u8 as _;
+            let rs = ws_query(taos, sql);
+            assert_eq!(ws_errno(rs), 0);
+
+            let cols = ws_field_count(rs);
+            assert!(cols > 0);
+
+            let mut rows = 0;
+            loop {
+                let row = ws_fetch_row(rs);
+                if row.is_null() {
+                    break;
+                }
+                let values = std::slice::from_raw_parts(row, cols as usize);
+                assert!(!values[0].is_null(), "database name should not be null");
+                let lengths = std::slice::from_raw_parts(ws_fetch_lengths(rs), cols as usize);
+                assert!(lengths[0] > 0);
+                rows += 1;
+            }
+            assert_eq!(ws_errno(rs), 0);
+            assert!(rows > 0);
+
+            ws_free_result(rs);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn concurrent_queries() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            // shared by threads as the integer address like a `TAOS*` in C.
+            let handle = taos as usize;
+            let deadline = std::time::Instant::now() + Duration::from_secs(3);
+            let threads: Vec<_> = (0..8i64)
+                .map(|t| {
+                    std::thread::spawn(move || {
+                        let taos = handle as *mut WS_TAOS;
+                        let mut queries = 0;
+                        while std::time::Instant::now() < deadline {
+                            let sql =
+                                CString::new(format!("select {t} as t, {queries} as i")).unwrap();
+                            let rs = ws_query(taos, sql.as_ptr());
+                            assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+                            let row = ws_fetch_row(rs);
+                            assert!(!row.is_null());
+                            let values = std::slice::from_raw_parts(row, 2);
+                            assert_eq!(*(values[0] as *const i64), t);
+                            assert_eq!(*(values[1] as *const i64), queries);
+                            assert!(ws_fetch_row(rs).is_null());
+                            ws_free_result(rs);
+                            queries += 1;
+                        }
+                        queries
+                    })
+                })
+                .collect();
+            for thread in threads {
+                assert!(thread.join().unwrap() > 0);
+            }
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stop_query() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            let sql = b"show databases\0" as *const u8 as _;
+            let rs = ws_query(taos, sql);
+            assert_eq!(ws_errno(rs), 0);
+
+            ws_stop_query(rs);
+            // stop twice is ok.
+            ws_stop_query(rs);
+
+            let mut block: *const c_void = std::ptr::null();
+            let mut rows = -1;
+            let code = ws_fetch_block(rs, &mut block as *mut *const c_void, &mut rows as _);
+            assert_eq!(code, 0);
+            assert_eq!(rows, 0);
+            assert!(ws_fetch_row(rs).is_null());
+
+            ws_free_result(rs);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn database_name() {
+        for (db, name) in [("db_1", "db_1"), ("`db_1`", "db_1"), ("`DB`", "DB")] {
+            assert_eq!(super::database_name(db).unwrap(), name);
+        }
+        for db in [
+            "",
+            "``",
+            "`db",
+            "db`",
+            "``db``",
+            "`a b`",
+            "a;b",
+            "`db`;drop",
+        ] {
+            assert!(super::database_name(db).is_err(), "{db}");
+        }
+        assert!(super::database_name(&format!("`{}`", "d".repeat(65))).is_err());
+    }
+
+    #[test]
+    fn select_db() {
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null(), "client pointer is not null when success");
+
+            exec_sql(taos, b"drop database if exists ws_select_db_a\0");
+            exec_sql(taos, b"drop database if exists ws_select_db_b\0");
+            exec_sql(taos, b"create database ws_select_db_a\0");
+            exec_sql(taos, b"create database ws_select_db_b\0");
+            exec_sql(
+                taos,
+                b"create table ws_select_db_a.only_in_a(ts timestamp, v int)\0",
+            );
+            exec_sql(
+                taos,
+                b"create table ws_select_db_b.only_in_b(ts timestamp, v int)\0",
+            );
+
+            assert_eq!(ws_select_db(taos, b"ws_select_db_a\0" as *const u8 as _), 0);
+            exec_sql(taos, b"insert into only_in_a values(now, 1)\0");
+
+            assert_eq!(ws_select_db(taos, b"ws_select_db_b\0" as *const u8 as _), 0);
+            exec_sql(taos, b"insert into only_in_b values(now, 1)\0");
+            let rs = ws_query(
+                taos,
+                b"insert into only_in_a values(now, 1)\0" as *const u8 as _,
+            );
+            assert!(
+                ws_errno(rs) != 0,
+                "only_in_a should not be found in ws_select_db_b"
+            );
+            ws_free_result(rs);
+
+            let mut db = [0 as c_char; 64];
+            let mut required = 0;
+            let code = ws_get_current_db(taos, db.as_mut_ptr(), 64, &mut required);
+            assert_eq!(code, 0);
+            assert_eq!(
+                CStr::from_ptr(db.as_ptr()).to_str().unwrap(),
+                "ws_select_db_b"
+            );
+            assert_eq!(required, "ws_select_db_b".len() as i32 + 1);
+
+            exec_sql(taos, b"use ws_select_db_a\0");
+            let code = ws_get_current_db(taos, db.as_mut_ptr(), 64, &mut required);
+            assert_eq!(code, 0);
+            assert_eq!(
+                CStr::from_ptr(db.as_ptr()).to_str().unwrap(),
+                "ws_select_db_a"
+            );
+
+            let code = ws_get_current_db(taos, db.as_mut_ptr(), 4, &mut required);
+            assert!(code != 0);
+            assert_eq!(required, "ws_select_db_a".len() as i32 + 1);
+            assert_eq!(CStr::from_ptr(db.as_ptr()).to_str().unwrap(), "ws_");
+
+            // a NULL buffer asks for the length.
+            required = 0;
+            let code = ws_get_current_db(taos, std::ptr::null_mut(), 0, &mut required);
+            assert!(code != 0);
+            assert_eq!(required, "ws_select_db_a".len() as i32 + 1);
+
+            assert_eq!(
+                ws_select_db(taos, b"`ws_select_db_b`\0" as *const u8 as _),
+                0
+            );
+            exec_sql(taos, b"insert into only_in_b values(now, 2)\0");
+
+            let code = ws_select_db(taos, b"not a valid name\0" as *const u8 as _);
+            assert!(code != 0);
+            dbg!(CStr::from_ptr(ws_errstr(std::ptr::null_mut())));
+            // connection handle is accepted by ws_errno/ws_errstr.
+            assert_eq!(ws_errno(taos), code);
+            assert!(!CStr::from_ptr(ws_errstr(taos)).to_bytes().is_empty());
+            // the last error is cleared on success.
+            assert_eq!(ws_select_db(taos, b"ws_select_db_a\0" as *const u8 as _), 0);
+            assert_eq!(ws_errno(std::ptr::null_mut()), 0);
+
+            exec_sql(taos, b"drop database ws_select_db_a\0");
+            exec_sql(taos, b"drop database ws_select_db_b\0");
+            ws_close(taos);
         }
     }
 
     #[test]
+    #[allow(deprecated)]
     fn ts_to_rfc3339() {
         unsafe {
             let mut ts = [0; 192];
             ws_timestamp_to_rfc3339(ts.as_mut_ptr(), 0, 0, true);
             let s = CStr::from_ptr(ts.as_ptr() as _);
             dbg!(s);
+
+            let expected = timestamp_to_rfc3339(0, 0, true, &tz::WsTimeZone::Local).unwrap();
+            let mut ts = [0xffu8; 64];
+            let n = ws_timestamp_to_rfc3339_with_len(ts.as_mut_ptr(), ts.len(), 0, 0, true);
+            assert_eq!(n as usize, expected.len());
+            assert_eq!(CStr::from_ptr(ts.as_ptr() as _).to_str().unwrap(), expected);
+
+            let required = ws_timestamp_to_rfc3339_with_len(std::ptr::null_mut(), 0, 0, 0, true);
+            assert_eq!(required as usize, expected.len() + 1);
+
+            let mut ts = [0xffu8; 8];
+            let n = ws_timestamp_to_rfc3339_with_len(ts.as_mut_ptr(), ts.len(), 0, 0, true);
+            assert_eq!(n, required);
+            assert_eq!(
+                CStr::from_ptr(ts.as_ptr() as _).to_bytes(),
+                &expected.as_bytes()[..7]
+            );
+
+            assert_eq!(
+                ws_timestamp_to_rfc3339_with_len(ts.as_mut_ptr(), ts.len(), 0, 3, true),
+                -1
+            );
+            assert_eq!(
+                ws_timestamp_to_rfc3339_with_len(std::ptr::null_mut(), 8, 0, 0, true),
+                -1
+            );
+
+            let mut ts = [0xffu8; 64];
+            let tz = c"+08:00".as_ptr() as _;
+            let n = ws_timestamp_to_rfc3339_tz(ts.as_mut_ptr(), ts.len(), 1, 0, tz);
+            let expected = "1970-01-01T08:00:00.001+08:00";
+            assert_eq!(n as usize, expected.len());
+            assert_eq!(CStr::from_ptr(ts.as_ptr() as _).to_str().unwrap(), expected);
+            let expected = timestamp_to_rfc3339(0, 2, false, &tz::WsTimeZone::Local).unwrap();
+            let n = ws_timestamp_to_rfc3339_tz(ts.as_mut_ptr(), ts.len(), 0, 2, std::ptr::null());
+            assert_eq!(n as usize, expected.len());
+            let tz = c"Mars/Olympus_Mons".as_ptr() as _;
+            assert_eq!(
+                ws_timestamp_to_rfc3339_tz(ts.as_mut_ptr(), ts.len(), 0, 0, tz),
+                -1
+            );
+            // reentrant in threads with different time zones.
+            let threads: Vec<_> = [("+01:00", "01"), ("-03:00", "21")]
+                .into_iter()
+                .map(|(tz, hour)| {
+                    std::thread::spawn(move || {
+                        let tz = CString::new(tz).unwrap();
+                        for _ in 0..100 {
+                            let mut ts = [0u8; 64];
+                            ws_timestamp_to_rfc3339_tz(ts.as_mut_ptr(), 64, 0, 0, tz.as_ptr());
+                            let s = CStr::from_ptr(ts.as_ptr() as _).to_str().unwrap();
+                            assert_eq!(&s[11..13], hour);
+                        }
+                    })
+                })
+                .collect();
+            threads.into_iter().for_each(|t| t.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn schemaless_insert_raw() {
+        unsafe {
+            let rs = ws_schemaless_insert_raw(
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                0,
+                std::ptr::null_mut(),
+                2,
+                0,
+                0,
+                0,
+            );
+            assert_ne!(ws_errno(rs), 0);
+            ws_free_result(rs);
+
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+            exec_sql(taos, b"drop database if exists ws_sml_raw\0");
+            exec_sql(taos, b"create database ws_sml_raw\0");
+            exec_sql(taos, b"use ws_sml_raw\0");
+
+            let telnet = "meters.current 1648432611249 10.3 location=California.SanFrancisco\n\
+                          meters.current 1648432611250 12.6 location=California.SanFrancisco\n\
+                          meters.current 1648432611249 10.8 location=California.LosAngeles";
+            let mut total_rows = 0;
+            let rs = ws_schemaless_insert_raw(
+                taos,
+                telnet.as_ptr() as _,
+                telnet.len() as _,
+                &mut total_rows,
+                2,
+                4,
+                0,
+                0x1234,
+            );
+            assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+            assert_eq!(total_rows, 3);
+            assert_eq!(ws_get_req_id(rs), 0x1234);
+            ws_free_result(rs);
+
+            let json = r#"[{"metric": "meters.voltage", "timestamp": 1648432611249,
+                "value": 219, "tags": {"location": "California.LosAngeles"}}]"#;
+            let rs = ws_schemaless_insert_raw(
+                taos,
+                json.as_ptr() as _,
+                json.len() as _,
+                &mut total_rows,
+                3,
+                4,
+                100,
+                0,
+            );
+            assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+            assert_eq!(total_rows, 1);
+            ws_free_result(rs);
+
+            // invalid protocol and precision are rejected locally.
+            for (protocol, precision) in [(4, 0), (2, 7)] {
+                let rs = ws_schemaless_insert_raw(
+                    taos,
+                    json.as_ptr() as _,
+                    json.len() as _,
+                    std::ptr::null_mut(),
+                    protocol,
+                    precision,
+                    0,
+                    0,
+                );
+                assert_ne!(ws_errno(rs), 0);
+                ws_free_result(rs);
+            }
+
+            exec_sql(taos, b"drop database ws_sml_raw\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn set_timezone() {
+        unsafe {
+            let code = ws_set_timezone(std::ptr::null_mut(), c"UTC".as_ptr() as _);
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+            let sql = b"select cast(0 as timestamp)\0" as *const u8 as _;
+            let value = |taos| {
+                let rs = ws_query(taos, sql);
+                assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+                let mut ptr = std::ptr::null();
+                let mut rows = 0;
+                assert_eq!(ws_fetch_block(rs, &mut ptr, &mut rows), 0);
+                let mut buf = [0 as c_char; 64];
+                ws_get_value_as_string(rs, 0, 0, buf.as_mut_ptr(), 64);
+                ws_free_result(rs);
+                CStr::from_ptr(buf.as_ptr()).to_str().unwrap().to_string()
+            };
+            assert_eq!(ws_set_timezone(taos, c"+08:00".as_ptr() as _), 0);
+            assert_eq!(value(taos), "1970-01-01T08:00:00.000+08:00");
+
+            // invalid zone keeps the current one.
+            assert_ne!(ws_set_timezone(taos, c"Mars/Olympus_Mons".as_ptr() as _), 0);
+            assert_eq!(value(taos), "1970-01-01T08:00:00.000+08:00");
+
+            assert_eq!(ws_set_timezone(taos, c"UTC-5".as_ptr() as _), 0);
+            assert_eq!(value(taos), "1969-12-31T19:00:00.000-05:00");
+            assert_eq!(ws_set_timezone(taos, std::ptr::null()), 0);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn field_e_layout() {
+        // Same to TAOS_FIELD_E: name[65], type, precision, scale, then 4-byte aligned bytes.
+        assert_eq!(std::mem::size_of::<WS_FIELD_E>(), 72);
+        assert_eq!(std::mem::offset_of!(WS_FIELD_E, precision), 66);
+        assert_eq!(std::mem::offset_of!(WS_FIELD_E, scale), 67);
+        assert_eq!(std::mem::offset_of!(WS_FIELD_E, bytes), 68);
+    }
+
+    #[test]
+    fn fetch_fields_stable() {
+        unsafe {
+            assert!(ws_fetch_fields(std::ptr::null_mut()).is_null());
+            assert!(ws_fetch_fields_v2(std::ptr::null_mut()).is_null());
+            assert!(ws_fetch_fields_e(std::ptr::null_mut()).is_null());
+
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+            let rs = ws_query(taos, b"select 1 as a, 'abc' as b\0" as *const u8 as _);
+            assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+            let fields = ws_fetch_fields(rs);
+            let fields_v2 = ws_fetch_fields_v2(rs);
+            assert!(!fields.is_null() && !fields_v2.is_null());
+            let mut block = std::ptr::null();
+            let mut rows = 0;
+            for _ in 0..3 {
+                assert_eq!(ws_fetch_fields(rs), fields);
+                assert_eq!(ws_fetch_fields_v2(rs), fields_v2);
+                assert_eq!(ws_fetch_block(rs, &mut block, &mut rows), 0);
+            }
+            let fields = std::slice::from_raw_parts(fields, ws_field_count(rs) as _);
+            assert_eq!(fields[1].name().to_str().unwrap(), "b");
+            let fields_e = ws_fetch_fields_e(rs);
+            assert_eq!(ws_fetch_fields_e(rs), fields_e);
+            let fields_e = std::slice::from_raw_parts(fields_e, fields.len());
+            for (field, field_e) in fields.iter().zip(fields_e) {
+                assert_eq!(field.name(), field_e.name());
+                assert_eq!(field.r#type, field_e.r#type);
+                assert_eq!(field.bytes, field_e.bytes);
+                assert_eq!(field_e.scale, 0);
+            }
+            ws_free_result(rs);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn result_precision() {
+        unsafe {
+            assert_eq!(ws_result_precision(std::ptr::null()), -1);
+            let mut precision = 0;
+            let code = ws_result_precision_checked(std::ptr::null(), &mut precision);
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+
+            let rs = ws_query(std::ptr::null_mut(), b"select 1\0" as *const u8 as _);
+            assert_eq!(ws_result_precision(rs), -1);
+            let code = ws_result_precision_checked(rs, &mut precision);
+            assert_eq!(code, ws_errno(rs));
+            assert_ne!(code, 0);
+            let code = ws_result_precision_checked(rs, std::ptr::null_mut());
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+            ws_free_result(rs);
+        }
+    }
+
+    #[test]
+    fn errstr_dup() {
+        unsafe {
+            let rs = ws_query(std::ptr::null_mut(), b"select 1\0" as *const u8 as _);
+            assert_ne!(ws_errno(rs), 0);
+            let expected = CStr::from_ptr(ws_errstr(rs)).to_owned();
+            assert!(!expected.as_bytes().is_empty());
+            let dup = ws_errstr_dup(rs as _);
+            ws_free_result(rs);
+            assert_eq!(CStr::from_ptr(dup), expected.as_c_str());
+            ws_free_str(dup);
+            ws_free_str(std::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn write_raw_block_invalid() {
+        unsafe {
+            // 28 bytes header only, with its length 28, no rows and no columns.
+            let mut header = [0u8; 28];
+            header[0] = 1;
+            header[4] = 28;
+            let table = b"tb\0" as *const u8 as _;
+            let block = header.as_ptr() as _;
+
+            let code = ws_write_raw_block(std::ptr::null_mut(), 0, std::ptr::null(), 28, table);
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+            let code = ws_write_raw_block(std::ptr::null_mut(), 0, block, 28, std::ptr::null());
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+
+            // declared rows mismatch with the block.
+            let code = ws_write_raw_block(std::ptr::null_mut(), 1, block, 28, table);
+            assert_ne!(code, 0);
+            assert!(CStr::from_ptr(ws_errstr(std::ptr::null_mut()))
+                .to_string_lossy()
+                .contains("rows"));
+
+            // a buffer shorter than its header, or of a length mismatching the header.
+            for len in [-1, 0, 4, 27, 29] {
+                let code = ws_write_raw_block(std::ptr::null_mut(), 0, block, len, table);
+                assert_eq!(code, i32::from(Code::Failed), "{len}");
+            }
+            assert!(CStr::from_ptr(ws_errstr(std::ptr::null_mut()))
+                .to_string_lossy()
+                .contains("mismatches"));
+            header[4] = 27;
+            let code = ws_write_raw_block_with_fields(
+                std::ptr::null_mut(),
+                0,
+                header.as_ptr() as _,
+                28,
+                table,
+                std::ptr::null(),
+                0,
+            );
+            assert_ne!(code, 0);
+        }
+    }
+
+    #[test]
+    fn write_raw_block() {
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+            exec_sql(taos, b"drop database if exists ws_write_raw_block\0");
+            exec_sql(taos, b"create database ws_write_raw_block\0");
+            exec_sql(taos, b"use ws_write_raw_block\0");
+            exec_sql(
+                taos,
+                b"create table src (ts timestamp, v int, s varchar(10))\0",
+            );
+            exec_sql(
+                taos,
+                b"create table dst1 (ts timestamp, v int, s varchar(10))\0",
+            );
+            exec_sql(
+                taos,
+                b"create table dst2 (ts timestamp, s varchar(10), v int)\0",
+            );
+            exec_sql(
+                taos,
+                b"insert into src values(1648432611249, 1, 'a') (1648432611250, null, 'b')\0",
+            );
+
+            let rs = ws_query(taos, b"select * from src\0" as *const u8 as _);
+            assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+            let fields = ws_fetch_fields(rs);
+            let num_fields = ws_field_count(rs);
+            let mut block = std::ptr::null();
+            let mut rows = 0;
+            assert_eq!(ws_fetch_block(rs, &mut block, &mut rows), 0);
+            assert_eq!(rows, 2);
+
+            // the length of a fetched block is in its header.
+            let len = ((block as *const u8).add(4) as *const i32).read_unaligned();
+            let code = ws_write_raw_block(taos, rows, block, len, b"dst1\0" as *const u8 as _);
+            assert_eq!(
+                code,
+                0,
+                "{:?}",
+                CStr::from_ptr(ws_errstr(std::ptr::null_mut()))
+            );
+            // columns are matched by names with fields.
+            let code = ws_write_raw_block_with_fields(
+                taos,
+                rows,
+                block,
+                len,
+                b"dst2\0" as *const u8 as _,
+                fields,
+                num_fields,
+            );
+            assert_eq!(
+                code,
+                0,
+                "{:?}",
+                CStr::from_ptr(ws_errstr(std::ptr::null_mut()))
+            );
+            // wrong row count is rejected before sending.
+            let code = ws_write_raw_block(taos, rows + 1, block, len, b"dst1\0" as *const u8 as _);
+            assert_ne!(code, 0);
+            ws_free_result(rs);
+
+            for sql in [
+                b"select count(*) from dst1\0".as_slice(),
+                b"select count(*) from dst2 where s = 'a' and v = 1\0",
+            ] {
+                let rs = ws_query(taos, sql.as_ptr() as _);
+                assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+                let row = ws_fetch_row(rs);
+                assert!(!row.is_null());
+                let count = *(*row as *const i64);
+                assert!(count > 0);
+                ws_free_result(rs);
+            }
+
+            exec_sql(taos, b"drop database ws_write_raw_block\0");
+            ws_close(taos);
         }
     }
 
@@ -869,8 +4716,25 @@ mod tests {
         unsafe {
             let taos = ws_connect_with_dsn(std::ptr::null());
             assert!(!taos.is_null());
+            ws_close(taos);
+            let taos = ws_connect_with_dsn(b"\0" as *const u8 as _);
+            assert!(!taos.is_null());
+            ws_close(taos);
         }
     }
+
+    #[test]
+    fn default_dsn_from_env() {
+        assert_eq!(default_dsn(|_| None), "ws://localhost:6041");
+        let env = |key: &str| match key {
+            "TDENGINE_CLOUD_DSN" => Some(" ".to_string()),
+            "TAOS_DSN" => Some("ws://taos:6041".to_string()),
+            _ => None,
+        };
+        assert_eq!(default_dsn(env), "ws://taos:6041");
+        let env = |key: &str| Some(format!("wss://{key}"));
+        assert_eq!(default_dsn(env), "wss://TDENGINE_CLOUD_DSN");
+    }
     #[test]
     fn connect() {
         init_env();
@@ -886,6 +4750,19 @@ mod tests {
 
             let version = ws_get_server_info(taos);
             dbg!(CStr::from_ptr(version as _));
+            assert_eq!(version, ws_get_server_info(taos));
+
+            let client_info = CStr::from_ptr(ws_get_client_info()).to_str().unwrap();
+            assert!(client_info.starts_with(env!("CARGO_PKG_VERSION")));
+            assert_eq!(ws_get_client_info(), ws_get_client_info());
+            let n = ws_client_version_number();
+            assert_eq!(
+                format!("{}.{}.{}", n / 1_000_000, n / 1_000 % 1_000, n % 1_000),
+                env!("CARGO_PKG_VERSION")
+            );
+            assert!(CStr::from_ptr(ws_get_server_info(std::ptr::null_mut()))
+                .to_bytes()
+                .is_empty());
 
             let sql = b"select groupid from test.d0 limit 10\0" as *const u8 as _;
             let rs = ws_query(taos, sql);
@@ -0,0 +1,161 @@
+//! Logger which could be enabled and adjusted at runtime from C.
+//!
+//! Logs go to stderr with `RUST_LOG` filters by default, or to rotating files after a log
+//! directory is set by `ws_enable_log_in_dir`.
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use once_cell::sync::OnceCell;
+
+/// File name of current log file, rotated files are suffixed with `.1`, `.2`, etc.
+const LOG_FILE: &str = "taosws.log";
+/// Rotate log file when it's larger than this size.
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+/// Max rotated files to keep.
+const MAX_ROTATED_FILES: usize = 5;
+
+struct RotatingFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(LOG_FILE))?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            file,
+            size,
+        })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        self.dir.join(format!("{LOG_FILE}.{n}"))
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        for n in (1..MAX_ROTATED_FILES).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                std::fs::rename(from, self.rotated(n + 1))?;
+            }
+        }
+        std::fs::rename(self.dir.join(LOG_FILE), self.rotated(1))?;
+        *self = Self::open(&self.dir)?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > MAX_FILE_SIZE {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+struct WsLogger {
+    stderr: env_logger::Logger,
+    file: Mutex<Option<RotatingFile>>,
+}
+
+impl Log for WsLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level() && self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        match file.as_mut() {
+            Some(file) => {
+                let line = format!(
+                    "{} {:<5} {}: {}\n",
+                    chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Nanos, false),
+                    record.level(),
+                    record.target(),
+                    record.args()
+                );
+                let _ = file.write_line(line.as_bytes());
+            }
+            None => self.stderr.log(record),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            let _ = file.file.flush();
+        }
+        self.stderr.flush();
+    }
+}
+
+/// Initialize the logger once, later calls are no-op.
+fn logger() -> &'static WsLogger {
+    static LOGGER: OnceCell<WsLogger> = OnceCell::new();
+    let mut init = false;
+    let logger = LOGGER.get_or_init(|| {
+        init = true;
+        let mut builder = pretty_env_logger::formatted_timed_builder();
+        builder.format_timestamp_nanos();
+        // Level filter is controlled by `log::set_max_level` when RUST_LOG not set.
+        let level = match std::env::var("RUST_LOG") {
+            Ok(s) => {
+                builder.parse_filters(&s);
+                None
+            }
+            Err(_) => {
+                builder.filter_level(LevelFilter::Trace);
+                Some(LevelFilter::Error)
+            }
+        };
+        let stderr = builder.build();
+        log::set_max_level(level.unwrap_or_else(|| stderr.filter()));
+        WsLogger {
+            stderr,
+            file: Mutex::new(None),
+        }
+    });
+    if init && log::set_logger(logger).is_err() {
+        log::warn!("another logger has been set, logs of taosws may be lost");
+    }
+    logger
+}
+
+/// Enable logger, keeps current log target.
+pub(crate) fn enable() {
+    logger();
+}
+
+/// Enable logger to rotating files in `dir`, or back to stderr if `None`.
+pub(crate) fn enable_in_dir(dir: Option<&str>) -> std::io::Result<()> {
+    let logger = logger();
+    let file = dir
+        .map(|dir| RotatingFile::open(Path::new(dir)))
+        .transpose()?;
+    *logger.file.lock().unwrap_or_else(|e| e.into_inner()) = file;
+    Ok(())
+}
+
+/// Set max log level, like `debug`, `info` or `off`. The logger is enabled if not yet.
+pub(crate) fn set_level(level: &str) -> Result<(), log::ParseLevelError> {
+    let level = LevelFilter::from_str(level)?;
+    logger();
+    log::set_max_level(level);
+    Ok(())
+}
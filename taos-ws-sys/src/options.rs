@@ -0,0 +1,172 @@
+//! Process-wide defaults of connections set by `ws_options`, like `taos_options` of native library.
+//!
+//! Options are read when a connection is established, so changes only affect new connections.
+use std::{
+    ffi::{c_void, CStr},
+    os::raw::c_char,
+    sync::RwLock,
+    time::Duration,
+};
+
+use taos_error::Code;
+use taos_ws::TaosBuilder;
+
+use crate::*;
+
+/// Option ids of `ws_options`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum ws_option_t {
+    /// Timeout in milliseconds to establish connections, `0` for no timeout (default).
+    WS_OPTION_CONNECT_TIMEOUT = 0,
+    /// Timeout in milliseconds of `ws_query`, `0` for no timeout (default).
+    WS_OPTION_QUERY_TIMEOUT = 1,
+    /// Websocket compression, `1` or `true` to enable, `0` or `false` to disable (default).
+    WS_OPTION_COMPRESSION = 2,
+    /// Charset of strings, only `UTF-8` is supported.
+    WS_OPTION_CHARSET = 3,
+}
+
+impl TryFrom<i32> for ws_option_t {
+    type Error = WsError;
+
+    fn try_from(option: i32) -> Result<Self, Self::Error> {
+        use ws_option_t::*;
+        Ok(match option {
+            0 => WS_OPTION_CONNECT_TIMEOUT,
+            1 => WS_OPTION_QUERY_TIMEOUT,
+            2 => WS_OPTION_COMPRESSION,
+            3 => WS_OPTION_CHARSET,
+            _ => {
+                return Err(WsError::new(
+                    WS_ERROR_NO::PARAMS_UNKNOWN.as_code(),
+                    &format!("unknown option {option}"),
+                ))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WsOptions {
+    pub(crate) conn_timeout: Option<Duration>,
+    pub(crate) query_timeout: Option<Duration>,
+}
+
+static OPTIONS: RwLock<WsOptions> = RwLock::new(WsOptions {
+    conn_timeout: None,
+    query_timeout: None,
+});
+
+impl WsOptions {
+    /// Snapshot of current options.
+    pub(crate) fn current() -> Self {
+        OPTIONS.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Apply to builder of a new connection, `connTimeout` in dsn takes precedence.
+    pub(crate) fn apply(&self, builder: &mut TaosBuilder) {
+        if let (Some(timeout), None) = (self.conn_timeout, builder.conn_timeout()) {
+            builder.set_conn_timeout(timeout);
+        }
+    }
+
+    fn set(&mut self, option: ws_option_t, value: Option<&str>) -> Result<(), String> {
+        use ws_option_t::*;
+        let millis = |value: Option<&str>| match value {
+            None => Ok(None),
+            Some(v) => v
+                .trim()
+                .parse::<u64>()
+                .map(|ms| Some(Duration::from_millis(ms)).filter(|t| !t.is_zero()))
+                .map_err(|_| format!("invalid timeout in milliseconds: {v}")),
+        };
+        match option {
+            WS_OPTION_CONNECT_TIMEOUT => self.conn_timeout = millis(value)?,
+            WS_OPTION_QUERY_TIMEOUT => self.query_timeout = millis(value)?,
+            WS_OPTION_COMPRESSION => match value.map(str::trim) {
+                None | Some("0" | "false") => (),
+                Some("1" | "true") => return Err("websocket compression is not supported".into()),
+                Some(v) => return Err(format!("invalid compression value: {v}")),
+            },
+            WS_OPTION_CHARSET => match value.map(str::trim) {
+                None => (),
+                Some(v) if v.eq_ignore_ascii_case("utf-8") || v.eq_ignore_ascii_case("utf8") => (),
+                Some(v) => return Err(format!("unsupported charset: {v}")),
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Same to taos_options, set a process-wide default of new connections.
+///
+/// `value` is a NUL-terminated string for all options, see `ws_option_t` for the values, NULL resets
+/// the option to its default. Connections established before are not affected. Returns 0 on
+/// success, `0xE00B` for unknown options, or an error code for invalid values, the reason could
+/// be got by `ws_last_errstr`.
+///
+/// ## Example
+///
+/// ```c
+/// ws_options(WS_OPTION_CONNECT_TIMEOUT, "5000");
+/// WS_TAOS *taos = ws_connect_with_dsn("ws://localhost:6041");
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn ws_options(option: i32, value: *const c_void) -> i32 {
+    ffi_guard(|| {
+        let res = ws_option_t::try_from(option).and_then(|option| {
+            let value = if value.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(value as *const c_char).to_str()?)
+            };
+            let mut options = OPTIONS.write().unwrap_or_else(|e| e.into_inner());
+            let mut new = options.clone();
+            new.set(option, value)
+                .map_err(|err| WsError::new(Code::Failed, &err))?;
+            *options = new;
+            Ok(())
+        });
+        match res {
+            Ok(()) => 0,
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options() {
+        let mut options = WsOptions::default();
+        use ws_option_t::*;
+        options
+            .set(WS_OPTION_CONNECT_TIMEOUT, Some("1500"))
+            .unwrap();
+        assert_eq!(options.conn_timeout, Some(Duration::from_millis(1500)));
+        options.set(WS_OPTION_QUERY_TIMEOUT, Some("0")).unwrap();
+        assert_eq!(options.query_timeout, None);
+        assert!(options.set(WS_OPTION_QUERY_TIMEOUT, Some("1s")).is_err());
+        options.set(WS_OPTION_CONNECT_TIMEOUT, None).unwrap();
+        assert_eq!(options.conn_timeout, None);
+
+        options.set(WS_OPTION_COMPRESSION, Some("false")).unwrap();
+        assert!(options.set(WS_OPTION_COMPRESSION, Some("1")).is_err());
+        options.set(WS_OPTION_CHARSET, Some("UTF-8")).unwrap();
+        assert!(options.set(WS_OPTION_CHARSET, Some("GBK")).is_err());
+
+        unsafe {
+            let code = ws_options(100, c"1".as_ptr() as _);
+            assert_eq!(code, WS_ERROR_NO::PARAMS_UNKNOWN as i32);
+            assert_ne!(ws_options(WS_OPTION_CHARSET as _, c"GBK".as_ptr() as _), 0);
+            assert_eq!(ws_options(WS_OPTION_CHARSET as _, c"utf8".as_ptr() as _), 0);
+        }
+    }
+}
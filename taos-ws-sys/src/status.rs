@@ -0,0 +1,164 @@
+//! Health probe of taosAdapter and the cluster behind it without a connection object.
+use std::{borrow::Cow, ffi::c_char, time::Duration};
+
+use taos_query::{block_in_place_or_global, AsyncFetchable, AsyncQueryable};
+use taos_ws::TaosBuilder;
+use tokio::time::{timeout_at, Instant};
+
+use crate::*;
+
+/// Timeout of `ws_check_server_status` if not positive.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Server status returned by `ws_check_server_status`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum ws_server_status_t {
+    /// The websocket could not be opened, taosAdapter is not reachable.
+    WS_SRV_STATUS_UNAVAILABLE = 0,
+    /// taosAdapter is reachable but the server refused to serve, e.g. it's not ready yet.
+    WS_SRV_STATUS_STARTING = 1,
+    /// The probe query succeeded.
+    WS_SRV_STATUS_READY = 2,
+    /// Connected but the probe query failed or timed out.
+    WS_SRV_STATUS_DEGRADED = 3,
+}
+
+/// Errors of the websocket itself rather than the server.
+fn is_connection_error(err: &Error) -> bool {
+    (0xE000..=0xE0FF).contains(&i32::from(err.errno()))
+}
+
+async fn check(builder: &TaosBuilder, deadline: Instant) -> (ws_server_status_t, String) {
+    use ws_server_status_t::*;
+    let Ok(taos) = builder.build() else {
+        return (WS_SRV_STATUS_UNAVAILABLE, "invalid dsn".to_string());
+    };
+    let version = match timeout_at(deadline, taos.connect()).await {
+        Err(_) => return (WS_SRV_STATUS_UNAVAILABLE, "connect timeout".to_string()),
+        Ok(Err(err)) if is_connection_error(&err) => {
+            return (WS_SRV_STATUS_UNAVAILABLE, err.errstr())
+        }
+        Ok(Err(err)) => return (WS_SRV_STATUS_STARTING, err.errstr()),
+        Ok(Ok(version)) => version.to_string(),
+    };
+    let probe = async {
+        let mut rs = AsyncQueryable::query(&taos, "select server_status()").await?;
+        std::future::poll_fn(|cx| AsyncFetchable::fetch_raw_block(&mut rs, cx))
+            .await
+            .map(|_| ())
+    };
+    match timeout_at(deadline, probe).await {
+        Err(_) => (WS_SRV_STATUS_DEGRADED, "probe query timeout".to_string()),
+        Ok(Err(err)) => (WS_SRV_STATUS_DEGRADED, err.errstr()),
+        Ok(Ok(())) => (WS_SRV_STATUS_READY, version),
+    }
+}
+
+/// Check status of the server at `dsn` by connecting and a probe query, like
+/// taos_check_server_status of native library.
+///
+/// NULL or empty `dsn` is the same as `ws_connect_with_dsn`. It returns in `timeout_seconds`
+/// (5 seconds if not positive), and the probe connection is closed before return. The server
+/// version on ready, or the reason otherwise, is written into `details` of `details_len` bytes
+/// with NUL terminator if `details` is not NULL.
+///
+/// Returns `ws_server_status_t` as int.
+#[no_mangle]
+pub unsafe extern "C" fn ws_check_server_status(
+    dsn: *const c_char,
+    timeout_seconds: i32,
+    details: *mut c_char,
+    details_len: i32,
+) -> i32 {
+    ffi_guard(|| {
+        let timeout = if timeout_seconds > 0 {
+            Duration::from_secs(timeout_seconds as u64)
+        } else {
+            DEFAULT_TIMEOUT
+        };
+        let (status, detail) = match opt_c_str(dsn) {
+            Ok(dsn) => {
+                let dsn = match dsn.filter(|dsn| !dsn.trim().is_empty()) {
+                    Some(dsn) => Cow::Borrowed(dsn),
+                    None => Cow::Owned(default_dsn(|key| std::env::var(key).ok())),
+                };
+                match TaosBuilder::from_dsn(dsn.as_ref()) {
+                    Ok(mut builder) => {
+                        builder.set_conn_timeout(timeout);
+                        block_in_place_or_global(async {
+                            check(&builder, Instant::now() + timeout).await
+                        })
+                    }
+                    Err(err) => (
+                        ws_server_status_t::WS_SRV_STATUS_UNAVAILABLE,
+                        err.to_string(),
+                    ),
+                }
+            }
+            Err(err) => (
+                ws_server_status_t::WS_SRV_STATUS_UNAVAILABLE,
+                err.message.to_string_lossy().into_owned(),
+            ),
+        };
+        log::debug!("server status {status:?}: {detail}");
+        if !details.is_null() && details_len > 0 {
+            write_c_str(details as _, details_len as _, detail.as_bytes());
+        }
+        status as i32
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_server_status_unavailable() {
+        unsafe {
+            let mut details = [0 as c_char; 128];
+            let now = std::time::Instant::now();
+            let status = ws_check_server_status(
+                c"ws://localhost:1".as_ptr() as _,
+                1,
+                details.as_mut_ptr(),
+                details.len() as _,
+            );
+            assert_eq!(status, ws_server_status_t::WS_SRV_STATUS_UNAVAILABLE as i32);
+            assert!(now.elapsed() < Duration::from_secs(2));
+            assert!(!CStr::from_ptr(details.as_ptr()).to_bytes().is_empty());
+
+            let status = ws_check_server_status(
+                c"unknown://localhost".as_ptr() as _,
+                1,
+                std::ptr::null_mut(),
+                0,
+            );
+            assert_eq!(status, ws_server_status_t::WS_SRV_STATUS_UNAVAILABLE as i32);
+        }
+    }
+
+    #[test]
+    fn check_server_status() {
+        unsafe {
+            let mut details = [0 as c_char; 128];
+            let status = ws_check_server_status(
+                c"ws://localhost:6041".as_ptr() as _,
+                3,
+                details.as_mut_ptr(),
+                details.len() as _,
+            );
+            assert_eq!(
+                status,
+                ws_server_status_t::WS_SRV_STATUS_READY as i32,
+                "{:?}",
+                CStr::from_ptr(details.as_ptr())
+            );
+            assert!(CStr::from_ptr(details.as_ptr())
+                .to_str()
+                .unwrap()
+                .starts_with('3'));
+        }
+    }
+}
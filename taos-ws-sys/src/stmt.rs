@@ -2,8 +2,8 @@ use std::ffi::c_void;
 use std::fmt::Debug;
 use std::os::raw::*;
 
-use taos_query::block_in_place_or_global;
-use taos_query::common::Value;
+use taos_query::common::views::TimestampView;
+use taos_query::common::{ColumnView, Precision, Value};
 use taos_query::prelude::Itertools;
 use taos_query::stmt::Bindable;
 use taos_ws::Stmt;
@@ -14,19 +14,458 @@ use crate::*;
 #[allow(non_camel_case_types)]
 pub type WS_STMT = c_void;
 
-unsafe fn stmt_init(taos: *const WS_TAOS) -> WsResult<Stmt> {
-    let client = (taos as *mut Taos)
-        .as_mut()
+/// Statement with placeholders state of the prepared sql.
+pub struct WsStmt {
+    stmt: Stmt,
+    /// The prepared sql, empty before prepare.
+    sql: String,
+    /// Table name is a placeholder, like `insert into ? using ...`.
+    tbname_placeholder: bool,
+    /// Number of placeholders in `tags(...)`.
+    tag_placeholders: usize,
+    /// Table name has been set since prepare.
+    tbname_set: bool,
+    /// Batches added since last execute.
+    batches: usize,
+    /// Affected rows since prepare.
+    affected_rows: usize,
+    /// Affected rows of last execute.
+    affected_rows_once: usize,
+    /// Connection of the statement handle to run query statements, shared with their result
+    /// sets so they outlive the statement and the handle.
+    conn: Arc<Taos>,
+    /// Prepared query statement, which is not sent to server.
+    query: Option<QueryStmt>,
+    /// Precision of the current database, looked up for timestamps bound to query statements.
+    precision: Option<Precision>,
+}
+
+/// Query statement, placeholders are replaced by bound values as sql literals on execute.
+///
+/// The stmt protocol of taosAdapter executes inserts only, so values are rendered on the client
+/// and the sql is sent as a query, parsed by the server on each execute. Values are escaped by
+/// [sql_literal], see it for the supported types.
+#[derive(Default)]
+struct QueryStmt {
+    params: usize,
+    values: Vec<Value>,
+    result: Option<WsResultSet>,
+}
+
+impl Deref for WsStmt {
+    type Target = Stmt;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stmt
+    }
+}
+
+impl DerefMut for WsStmt {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.stmt
+    }
+}
+
+impl WsStmt {
+    fn new(stmt: Stmt, conn: Arc<Taos>) -> Self {
+        Self {
+            stmt,
+            sql: String::new(),
+            tbname_placeholder: false,
+            tag_placeholders: 0,
+            tbname_set: false,
+            batches: 0,
+            affected_rows: 0,
+            affected_rows_once: 0,
+            conn,
+            query: None,
+            precision: None,
+        }
+    }
+
+    fn prepare(&mut self, sql: &str) -> Result<(), Error> {
+        let tokens = sql_tokens(sql);
+        self.query = if is_query(&tokens) {
+            Some(QueryStmt {
+                params: tokens.iter().filter(|t| *t == "?").count(),
+                ..Default::default()
+            })
+        } else {
+            self.stmt.prepare(sql)?;
+            None
+        };
+        self.sql = sql.to_string();
+        self.precision = None;
+        let (tbname_placeholder, tag_placeholders) = parse_placeholders(&tokens);
+        self.tbname_placeholder = tbname_placeholder;
+        self.tag_placeholders = tag_placeholders;
+        self.tbname_set = false;
+        self.batches = 0;
+        self.affected_rows = 0;
+        self.affected_rows_once = 0;
+        Ok(())
+    }
+
+    fn set_tbname(&mut self, name: &str) -> Result<(), Error> {
+        self.stmt.set_tbname(name)?;
+        self.tbname_set = true;
+        Ok(())
+    }
+
+    /// Discard bound and batched data and the table name, keeps the prepared sql.
+    ///
+    /// The server-side statement is prepared again with the same sql, which clears its data.
+    fn reset(&mut self) -> WsResult<()> {
+        if self.sql.is_empty() {
+            return Err(WsError::new(Code::Failed, "reset a statement not prepared"));
+        }
+        match self.query.as_mut() {
+            Some(query) => query.values.clear(),
+            None => Bindable::prepare(&mut self.stmt, &self.sql)
+                .map(|_| ())
+                .map_err(|e| WsError::new(e.errno(), &e.to_string()))?,
+        }
+        self.tbname_set = false;
+        self.batches = 0;
+        Ok(())
+    }
+
+    /// Precision of raw timestamps bound to the statement.
+    ///
+    /// Inserts keep raw timestamps as is on the wire, so it's only looked up from the current
+    /// database for query statements, whose values are rendered into the sql.
+    fn precision(&mut self) -> WsResult<Precision> {
+        if self.query.is_none() {
+            return Ok(Precision::Millisecond);
+        }
+        if let Some(precision) = self.precision {
+            return Ok(precision);
+        }
+        let precision = match self.conn.current_database()? {
+            Some(db) => {
+                let sql = format!(
+                    "select `precision` from information_schema.ins_databases where name = '{}'",
+                    db.replace('\\', "\\\\").replace('\'', "\\'")
+                );
+                let precision: Option<String> = self.conn.query_one(sql)?;
+                match precision {
+                    Some(precision) => precision
+                        .parse()
+                        .map_err(|err| WsError::new(Code::Failed, &format!("{err}")))?,
+                    None => Precision::Millisecond,
+                }
+            }
+            None => Precision::Millisecond,
+        };
+        self.precision = Some(precision);
+        Ok(precision)
+    }
+
+    /// Bind columns, a query statement accepts only one row of values.
+    fn bind(&mut self, views: &[ColumnView]) -> WsResult<()> {
+        let Some(query) = self.query.as_mut() else {
+            return Bindable::bind(&mut self.stmt, views)
+                .map(|_| ())
+                .map_err(|e| WsError::new(e.errno(), &e.to_string()));
+        };
+        if views.iter().any(|view| view.len() != 1) {
+            return Err(WsError::new(
+                Code::Failed,
+                "query statement binds exactly one row",
+            ));
+        }
+        query.values = views
+            .iter()
+            .flat_map(|view| view.iter().map(|v| v.to_value()))
+            .collect();
+        Ok(())
+    }
+
+    fn add_batch(&mut self) -> Result<(), Error> {
+        if self.query.is_none() {
+            self.stmt.add_batch()?;
+        }
+        self.batches += 1;
+        Ok(())
+    }
+
+    /// Execute added batches, no-op if nothing added since last execute.
+    ///
+    /// Query statements always execute, the result is kept for `ws_stmt_use_result`.
+    fn execute(&mut self) -> WsResult<usize> {
+        let rows = if let Some(query) = self.query.as_mut() {
+            let sql = render_query(&self.sql, query.params, &query.values)?;
+            let rs = self.conn.query(sql)?;
+            let mut rs = WsResultSet::new(rs, Arc::new(AtomicBool::new(false)));
+            rs.conn = Some(self.conn.clone());
+            let rows = rs.affected_rows64().max(0) as usize;
+            query.result = Some(rs);
+            rows
+        } else if self.batches == 0 {
+            0
+        } else {
+            self.stmt
+                .execute()
+                .map_err(|e| WsError::new(e.errno(), &e.to_string()))?
+        };
+        self.batches = 0;
+        self.affected_rows += rows;
+        self.affected_rows_once = rows;
+        Ok(rows)
+    }
+
+    /// Check params metadata could be known by server.
+    fn check_params(&self) -> WsResult<()> {
+        if self.query.is_some() {
+            Err(WsError::new(
+                WS_ERROR_NO::PARAMS_UNKNOWN.as_code(),
+                "params of query statements are unknown until bound",
+            ))
+        } else if self.tbname_placeholder && !self.tbname_set {
+            Err(WsError::new(
+                WS_ERROR_NO::PARAMS_UNKNOWN.as_code(),
+                "params are unknown before table name, call ws_stmt_set_tbname first",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check tags could be set to the prepared sql.
+    fn check_tags(&self, tags: usize) -> WsResult<()> {
+        if self.tag_placeholders == 0 {
+            Err(WsError::new(
+                Code::Failed,
+                "set tags on a statement without tag placeholders",
+            ))
+        } else if self.tbname_placeholder && !self.tbname_set {
+            Err(WsError::new(
+                Code::Failed,
+                "set tags before table name, call ws_stmt_set_tbname first",
+            ))
+        } else if tags != self.tag_placeholders {
+            Err(WsError::new(
+                Code::Failed,
+                &format!(
+                    "tags mismatch: {} placeholders in sql, but {tags} tags",
+                    self.tag_placeholders
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Length of the quoted text or comment at the start of `sql`, with the quote if it's quoted.
+///
+/// Quoted text ends at the same quote, `\'` and `''` are escaped quotes in it. Line comments
+/// `--` end at a newline and block comments at `*/`. Unterminated ones run to the end.
+fn skipped_len(sql: &str) -> Option<(usize, Option<char>)> {
+    let mut chars = sql.char_indices().peekable();
+    match chars.next()? {
+        (_, q @ ('\'' | '"' | '`')) => {
+            while let Some((i, c)) = chars.next() {
+                if c == '\\' {
+                    chars.next();
+                } else if c == q {
+                    if chars.next_if(|&(_, c)| c == q).is_none() {
+                        return Some((i + 1, Some(q)));
+                    }
+                }
+            }
+            Some((sql.len(), Some(q)))
+        }
+        _ if sql.starts_with("--") => Some((sql.find('\n').map_or(sql.len(), |i| i + 1), None)),
+        _ if sql.starts_with("/*") => Some((sql.find("*/").map_or(sql.len(), |i| i + 2), None)),
+        _ => None,
+    }
+}
+
+/// Split sql to lowercase words and punctuations, quoted text is a single quote token and
+/// comments are skipped.
+fn sql_tokens(sql: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut rest = sql;
+    while let Some(c) = rest.chars().next() {
+        let len = match skipped_len(rest) {
+            Some((len, quote)) => {
+                tokens.extend(quote.map(String::from));
+                len
+            }
+            None if "?(),".contains(c) => {
+                tokens.push(String::from(c));
+                1
+            }
+            None if c.is_whitespace() => c.len_utf8(),
+            None => {
+                let len = rest
+                    .char_indices()
+                    .skip(1)
+                    .find(|&(i, c)| {
+                        c.is_whitespace() || "?(),".contains(c) || skipped_len(&rest[i..]).is_some()
+                    })
+                    .map_or(rest.len(), |(i, _)| i);
+                tokens.push(rest[..len].to_ascii_lowercase());
+                len
+            }
+        };
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// Whether tokens are of a query, like `select ...`, `(select ...)` or `with ...`.
+fn is_query(tokens: &[String]) -> bool {
+    tokens
+        .iter()
+        .find(|t| *t != "(")
+        .is_some_and(|t| t == "select" || t == "with")
+}
+
+/// Parse placeholders of table name and tags in tokens of an insert sql.
+///
+/// Returns if table name is a placeholder and the number of placeholders in `tags(...)`.
+fn parse_placeholders(tokens: &[String]) -> (bool, usize) {
+    let tbname_placeholder = tokens.windows(2).any(|w| w[0] == "into" && w[1] == "?");
+    let mut tag_placeholders = 0;
+    if let Some(pos) = tokens.iter().position(|t| t == "tags") {
+        let mut depth = 0;
+        for token in &tokens[pos + 1..] {
+            match token.as_str() {
+                "(" => depth += 1,
+                ")" => {
+                    depth -= 1;
+                    if depth <= 0 {
+                        break;
+                    }
+                }
+                "?" if depth > 0 => tag_placeholders += 1,
+                _ => (),
+            }
+        }
+    }
+    (tbname_placeholder, tag_placeholders)
+}
+
+/// Replace placeholders out of quotes and comments in the query sql with values.
+fn render_query(sql: &str, params: usize, values: &[Value]) -> WsResult<String> {
+    if values.len() != params {
+        return Err(WsError::new(
+            Code::Failed,
+            &format!(
+                "params mismatch: {params} placeholders in sql, but {} values bound",
+                values.len()
+            ),
+        ));
+    }
+    let mut values = values.iter();
+    let mut rendered = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(c) = rest.chars().next() {
+        let len = match skipped_len(rest) {
+            Some((len, _)) => len,
+            None if c == '?' => {
+                let value = values.next().expect("values checked with params");
+                rendered.push_str(&sql_literal(value)?);
+                rest = &rest[1..];
+                continue;
+            }
+            None => c.len_utf8(),
+        };
+        rendered.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+    Ok(rendered)
+}
+
+/// Sql literal of a bound value of query statements.
+///
+/// Strings are single quoted with backslash escapes, timestamps are rendered as RFC 3339 in UTC
+/// of their own precision, so they don't depend on the database precision or the time zone.
+fn sql_literal(value: &Value) -> WsResult<String> {
+    use Value::*;
+    fn quote(s: &str) -> String {
+        format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+    }
+    Ok(match value {
+        Null(_) => "NULL".to_string(),
+        Bool(v) => v.to_string(),
+        TinyInt(v) => v.to_string(),
+        SmallInt(v) => v.to_string(),
+        Int(v) => v.to_string(),
+        BigInt(v) => v.to_string(),
+        UTinyInt(v) => v.to_string(),
+        USmallInt(v) => v.to_string(),
+        UInt(v) => v.to_string(),
+        UBigInt(v) => v.to_string(),
+        Float(v) if v.is_finite() => v.to_string(),
+        Double(v) if v.is_finite() => v.to_string(),
+        VarChar(v) | NChar(v) => quote(v),
+        Json(v) => quote(&v.to_string()),
+        Timestamp(ts) => quote(
+            &ts.to_naive_datetime()
+                .format("%Y-%m-%dT%H:%M:%S%.fZ")
+                .to_string(),
+        ),
+        v => {
+            return Err(WsError::new(
+                Code::Failed,
+                &format!("{v:?} could not be bound to a query statement"),
+            ))
+        }
+    })
+}
+
+unsafe fn stmt_init(taos: *const WS_TAOS) -> WsResult<WsStmt> {
+    let client = (taos as *const WsClient)
+        .as_ref()
         .ok_or(WsError::new(Code::Failed, "client pointer it null"))?;
-    Ok(taos_ws::Stmt::init(client)?)
+    Ok(WsStmt::new(
+        taos_ws::Stmt::init(client)?,
+        client.taos.clone(),
+    ))
     // Ok(client.stmt_init()?)
 }
 
 /// Create new stmt object.
 #[no_mangle]
 pub unsafe extern "C" fn ws_stmt_init(taos: *const WS_TAOS) -> *mut WS_STMT {
-    let stmt: WsMaybeError<Stmt> = stmt_init(taos).into();
-    Box::into_raw(Box::new(stmt)) as _
+    ffi_guard(|| {
+        let stmt: WsMaybeError<WsStmt> = stmt_init(taos).into();
+        Box::into_raw(Box::new(stmt)) as _
+    })
+}
+
+/// Get the statement if it's a valid statement handle.
+unsafe fn stmt_ref<'a>(stmt: *mut WS_STMT) -> Option<&'a WsMaybeError<WsStmt>> {
+    if handle_tag(stmt) != Some(HANDLE_MAYBE_ERROR) {
+        return None;
+    }
+    (stmt as *const WsMaybeError<WsStmt>).as_ref()
+}
+
+/// Get the statement for a new operation.
+///
+/// Fails when the statement is NULL or failed to init. Error of previous operation is cleared,
+/// so a failed call won't poison the statement.
+unsafe fn stmt_mut<'a>(stmt: *mut WS_STMT) -> Result<&'a mut WsMaybeError<WsStmt>, c_int> {
+    let Some(stmt) = (stmt as *mut WsMaybeError<WsStmt>).as_mut() else {
+        return Err(null_pointer_error("WS_STMT is null"));
+    };
+    if stmt.safe_deref().is_none() {
+        return Err(stmt.errno().unwrap_or_else(|| Code::Failed.into()));
+    }
+    stmt.clear_error();
+    Ok(stmt)
+}
+
+/// Store the error in the statement and return its code.
+fn stmt_error(stmt: &mut WsMaybeError<WsStmt>, err: impl Into<WsError>) -> c_int {
+    let err = err.into();
+    let errno = err.code.into();
+    stmt.set_error(err);
+    errno
 }
 
 /// Prepare with sql command
@@ -36,52 +475,63 @@ pub unsafe extern "C" fn ws_stmt_prepare(
     sql: *const c_char,
     len: c_ulong,
 ) -> c_int {
-    match (stmt as *mut WsMaybeError<Stmt>).as_mut() {
-        Some(stmt) => {
-            let sql = if len > 0 {
-                std::str::from_utf8_unchecked(std::slice::from_raw_parts(sql as _, len as _))
-            } else {
-                CStr::from_ptr(sql).to_str().expect(
-                    "ws_stmt_prepare with a sql len 0 means the input should always be valid utf-8",
-                )
-            };
-
-            if let Some(no) = stmt.errno() {
-                return no;
-            }
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(errno) => return errno,
+        };
+        if sql.is_null() {
+            let err = WsError::new(WS_ERROR_NO::NULL_POINTER.as_code(), "sql is null");
+            return stmt_error(stmt, err);
+        }
 
-            if let Err(e) = stmt.prepare(sql) {
-                let errno = e.errno();
-                stmt.error = Some(WsError::new(errno, &e.to_string()));
-                errno.into()
-            } else {
-                0
-            }
+        let sql = if len > 0 {
+            std::str::from_utf8(std::slice::from_raw_parts(sql as _, len as _))
+        } else {
+            CStr::from_ptr(sql).to_str()
+        };
+        let sql = match sql {
+            Ok(sql) => sql,
+            Err(e) => return stmt_error(stmt, e),
+        };
+
+        if let Err(e) = stmt.prepare(sql) {
+            stmt_error(stmt, WsError::new(e.errno(), &e.to_string()))
+        } else {
+            0
         }
-        _ => 0,
-    }
+    })
 }
 
-/// Set table name.
+/// Set table name, could be called multiple times to insert into different child tables.
+///
+/// An invalid table name fails with the server error in `ws_stmt_errstr`, the statement is still
+/// usable with another table name.
 #[no_mangle]
 pub unsafe extern "C" fn ws_stmt_set_tbname(stmt: *mut WS_STMT, name: *const c_char) -> c_int {
-    match (stmt as *mut WsMaybeError<Stmt>).as_mut() {
-        Some(stmt) => {
-            let name = CStr::from_ptr(name).to_str().unwrap();
-
-            if let Err(e) = stmt.set_tbname(name) {
-                let errno = e.errno();
-                stmt.error = Some(WsError::new(errno, &e.to_string()));
-                errno.into()
-            } else {
-                0
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(errno) => return errno,
+        };
+        let name = match opt_c_str(name) {
+            Ok(Some(name)) => name,
+            Ok(None) => {
+                let err = WsError::new(WS_ERROR_NO::NULL_POINTER.as_code(), "table name is null");
+                return stmt_error(stmt, err);
             }
+            Err(err) => return stmt_error(stmt, err),
+        };
+
+        if let Err(e) = stmt.set_tbname(name) {
+            stmt_error(stmt, WsError::new(e.errno(), &e.to_string()))
+        } else {
+            0
         }
-        _ => 0,
-    }
+    })
 }
 
-/// Set table name and tags.
+/// Set table name and tags, same to `ws_stmt_set_tbname` then `ws_stmt_set_tags`.
 #[no_mangle]
 pub unsafe extern "C" fn ws_stmt_set_tbname_tags(
     stmt: *mut WS_STMT,
@@ -89,32 +539,54 @@ pub unsafe extern "C" fn ws_stmt_set_tbname_tags(
     bind: *const WS_MULTI_BIND,
     len: u32,
 ) -> c_int {
-    match (stmt as *mut WsMaybeError<Stmt>).as_mut() {
-        Some(stmt) => {
-            let name = CStr::from_ptr(name).to_str().unwrap();
-            let tags = std::slice::from_raw_parts(bind, len as usize)
-                .iter()
-                .map(|bind| bind.to_tag_value())
-                .collect_vec();
+    ffi_guard(|| match ws_stmt_set_tbname(stmt, name) {
+        0 => ws_stmt_set_tags(stmt, bind, len),
+        errno => errno,
+    })
+}
 
-            if let Err(e) = stmt.set_tbname_tags(name, &tags) {
-                let errno = e.errno();
-                stmt.error = Some(WsError::new(errno, &e.to_string()));
-                errno.into()
-            } else {
-                0
-            }
+/// Reset the statement to reuse the prepared sql, clears the error state.
+///
+/// Bound but not executed data and the table name are discarded, call `ws_stmt_set_tbname`
+/// again before binding. Affected rows counted since prepare are kept.
+///
+/// # Safety
+///
+/// `stmt` must be NULL or a live statement.
+#[no_mangle]
+pub unsafe extern "C" fn ws_stmt_reset(stmt: *mut WS_STMT) -> c_int {
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(errno) => return errno,
+        };
+        if let Err(err) = stmt.reset() {
+            stmt_error(stmt, err)
+        } else {
+            0
         }
-        _ => 0,
-    }
+    })
 }
 
-/// Currently only insert sql is supported.
+/// Check if the prepared sql is an insert, or a query for `ws_stmt_use_result`.
 #[no_mangle]
 pub unsafe extern "C" fn ws_stmt_is_insert(stmt: *mut WS_STMT, insert: *mut c_int) -> c_int {
-    let _ = stmt;
-    *insert = 1;
-    0
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(errno) => return errno,
+        };
+        match insert.as_mut() {
+            Some(insert) => {
+                *insert = stmt.query.is_none() as _;
+                0
+            }
+            None => {
+                let err = WsError::new(WS_ERROR_NO::NULL_POINTER.as_code(), "insert is null");
+                stmt_error(stmt, err)
+            }
+        }
+    })
 }
 
 #[derive(Debug)]
@@ -155,52 +627,164 @@ impl TaosMultiBind {
     pub fn first_to_json(&self) -> serde_json::Value {
         self.to_json().as_array().unwrap().first().unwrap().clone()
     }
-    pub fn to_tag_value(&self) -> Value {
-        if !self.is_null.is_null() && unsafe { self.is_null.read() != 0 } {
-            return Value::Null(self.ty());
-        }
-        match Ty::from(self.buffer_type) {
-            Ty::Null => Value::Null(self.ty()),
-            Ty::Bool => unsafe { Value::Bool(*(self.buffer as *const bool)) },
-            Ty::TinyInt => unsafe { Value::TinyInt(*(self.buffer as *const i8)) },
-            Ty::SmallInt => unsafe { Value::SmallInt(*(self.buffer as *const i16)) },
-            Ty::Int => unsafe { Value::Int(*(self.buffer as *const i32)) },
-            Ty::BigInt => unsafe { Value::BigInt(*(self.buffer as *const _)) },
-            Ty::UTinyInt => unsafe { Value::UTinyInt(*(self.buffer as *const _)) },
-            Ty::USmallInt => unsafe { Value::USmallInt(*(self.buffer as *const _)) },
-            Ty::UInt => unsafe { Value::UInt(*(self.buffer as *const _)) },
-            Ty::UBigInt => unsafe { Value::UBigInt(*(self.buffer as *const _)) },
-            Ty::Float => unsafe { Value::Float(*(self.buffer as *const _)) },
-            Ty::Double => unsafe { Value::Double(*(self.buffer as *const _)) },
-            Ty::Timestamp => unsafe {
-                Value::Timestamp(Timestamp::Milliseconds(*(self.buffer as *const _)))
-            },
-            Ty::VarChar => unsafe {
-                assert!(!self.length.is_null());
-                assert!(!self.buffer.is_null());
-                let slice =
-                    std::slice::from_raw_parts(self.buffer as _, self.length.read() as usize);
-                let v = std::str::from_utf8_unchecked(slice);
-                Value::VarChar(v.to_string())
-            },
-            Ty::NChar => unsafe {
-                assert!(!self.length.is_null());
-                assert!(!self.buffer.is_null());
-                let slice =
-                    std::slice::from_raw_parts(self.buffer as _, self.length.read() as usize);
-                let v = std::str::from_utf8_unchecked(slice);
-                Value::NChar(v.to_string())
-            },
-            Ty::Json => unsafe {
-                assert!(!self.length.is_null());
-                assert!(!self.buffer.is_null());
-                let slice =
-                    std::slice::from_raw_parts(self.buffer as _, self.length.read() as usize);
-                // let v = std::str::from_utf8_unchecked(slice);
-                Value::Json(serde_json::from_slice(slice).unwrap())
+    /// Convert the first value to a tag value.
+    pub fn to_tag_value(&self) -> WsResult<Value> {
+        if self.buffer.is_null() || unsafe { self.is_null_at(0) } {
+            return Ok(Value::Null(self.ty()));
+        }
+        macro_rules! _read {
+            ($v:ident, $t:ty) => {
+                unsafe { Value::$v((self.buffer as *const $t).read_unaligned()) }
+            };
+        }
+        let str_at = || std::str::from_utf8(unsafe { self.bytes_at(0) });
+        let value = match Ty::try_from_u8(self.buffer_type as _) {
+            Some(Ty::Null) => Value::Null(self.ty()),
+            Some(Ty::Bool) => unsafe { Value::Bool(*(self.buffer as *const u8) != 0) },
+            Some(Ty::TinyInt) => _read!(TinyInt, i8),
+            Some(Ty::SmallInt) => _read!(SmallInt, i16),
+            Some(Ty::Int) => _read!(Int, i32),
+            Some(Ty::BigInt) => _read!(BigInt, i64),
+            Some(Ty::UTinyInt) => _read!(UTinyInt, u8),
+            Some(Ty::USmallInt) => _read!(USmallInt, u16),
+            Some(Ty::UInt) => _read!(UInt, u32),
+            Some(Ty::UBigInt) => _read!(UBigInt, u64),
+            Some(Ty::Float) => _read!(Float, f32),
+            Some(Ty::Double) => _read!(Double, f64),
+            Some(Ty::Timestamp) => unsafe {
+                Value::Timestamp(Timestamp::Milliseconds(
+                    (self.buffer as *const i64).read_unaligned(),
+                ))
             },
-            _ => todo!(),
+            Some(Ty::VarChar) => Value::VarChar(str_at()?.to_string()),
+            Some(Ty::NChar) => Value::NChar(str_at()?.to_string()),
+            Some(Ty::Json) => {
+                let json = serde_json::from_slice(unsafe { self.bytes_at(0) }).map_err(|e| {
+                    WsError::new(
+                        WS_ERROR_NO::TYPE_MISMATCH.as_code(),
+                        &format!("invalid json tag: {e}"),
+                    )
+                })?;
+                Value::Json(json)
+            }
+            _ => {
+                return Err(WsError::new(
+                    WS_ERROR_NO::TYPE_MISMATCH.as_code(),
+                    &format!("unsupported tag type {}", self.buffer_type),
+                ))
+            }
+        };
+        Ok(value)
+    }
+
+    /// Check if the value at `row` is NULL by the `is_null` indicators.
+    unsafe fn is_null_at(&self, row: usize) -> bool {
+        !self.is_null.is_null() && *self.is_null.add(row) != 0
+    }
+
+    /// Get bytes at `row` of var-type binds with per-row `length`.
+    ///
+    /// If `length` is NULL, the value is NUL terminated or fills the whole `buffer_length`.
+    unsafe fn bytes_at(&self, row: usize) -> &[u8] {
+        let ptr = (self.buffer as *const u8).add(self.buffer_length * row);
+        let len = if self.length.is_null() {
+            let slot = std::slice::from_raw_parts(ptr, self.buffer_length);
+            slot.iter()
+                .position(|b| *b == 0)
+                .unwrap_or(self.buffer_length)
+        } else {
+            (*self.length.add(row)).max(0) as usize
+        };
+        std::slice::from_raw_parts(ptr, len)
+    }
+
+    /// Convert to column view for binary binding.
+    ///
+    /// Timestamps are raw integers in `precision` of the database.
+    pub fn to_column_view(&self, precision: Precision) -> WsResult<ColumnView> {
+        if self.num < 0 {
+            return Err(WsError::new(Code::Failed, "invalid bind num"));
+        }
+        let rows = self.num as usize;
+
+        macro_rules! _primitives {
+            ($t:ty, $from:ident) => {{
+                let values: Vec<Option<$t>> = (0..rows)
+                    .map(|row| unsafe {
+                        if self.buffer.is_null() || self.is_null_at(row) {
+                            None
+                        } else {
+                            Some((self.buffer as *const $t).add(row).read_unaligned())
+                        }
+                    })
+                    .collect();
+                ColumnView::$from(values)
+            }};
+        }
+        macro_rules! _strings {
+            ($from:ident) => {{
+                let values = (0..rows)
+                    .map(|row| unsafe {
+                        if self.buffer.is_null() || self.is_null_at(row) {
+                            Ok(None)
+                        } else {
+                            std::str::from_utf8(self.bytes_at(row)).map(Some)
+                        }
+                    })
+                    .collect::<Result<Vec<Option<&str>>, Utf8Error>>()?;
+                ColumnView::$from::<&str, _, _, _>(values)
+            }};
         }
+
+        let view = match Ty::try_from_u8(self.buffer_type as _) {
+            Some(Ty::Bool) => {
+                let values: Vec<Option<bool>> = (0..rows)
+                    .map(|row| unsafe {
+                        if self.buffer.is_null() || self.is_null_at(row) {
+                            None
+                        } else {
+                            Some(*(self.buffer as *const u8).add(row) != 0)
+                        }
+                    })
+                    .collect();
+                ColumnView::from_bools(values)
+            }
+            Some(Ty::TinyInt) => _primitives!(i8, from_tiny_ints),
+            Some(Ty::SmallInt) => _primitives!(i16, from_small_ints),
+            Some(Ty::Int) => _primitives!(i32, from_ints),
+            Some(Ty::BigInt) => _primitives!(i64, from_big_ints),
+            Some(Ty::UTinyInt) => _primitives!(u8, from_unsigned_tiny_ints),
+            Some(Ty::USmallInt) => _primitives!(u16, from_unsigned_small_ints),
+            Some(Ty::UInt) => _primitives!(u32, from_unsigned_ints),
+            Some(Ty::UBigInt) => _primitives!(u64, from_unsigned_big_ints),
+            Some(Ty::Float) => _primitives!(f32, from_floats),
+            Some(Ty::Double) => _primitives!(f64, from_doubles),
+            Some(Ty::Timestamp) => {
+                let values: Vec<Option<i64>> = (0..rows)
+                    .map(|row| unsafe {
+                        if self.buffer.is_null() || self.is_null_at(row) {
+                            None
+                        } else {
+                            Some((self.buffer as *const i64).add(row).read_unaligned())
+                        }
+                    })
+                    .collect();
+                ColumnView::Timestamp(match precision {
+                    Precision::Millisecond => TimestampView::from_millis(values),
+                    Precision::Microsecond => TimestampView::from_micros(values),
+                    Precision::Nanosecond => TimestampView::from_nanos(values),
+                })
+            }
+            Some(Ty::VarChar) => _strings!(from_varchar),
+            Some(Ty::NChar) => _strings!(from_nchar),
+            _ => {
+                return Err(WsError::new(
+                    WS_ERROR_NO::TYPE_MISMATCH.as_code(),
+                    &format!("unsupported bind type {}", self.buffer_type),
+                ))
+            }
+        };
+        Ok(view)
     }
 
     pub fn to_json(&self) -> serde_json::Value {
@@ -476,115 +1060,1383 @@ impl Drop for TaosMultiBind {
     }
 }
 
+/// Convert tag binds to values, only the first value of each bind is used.
+unsafe fn binds_to_tags(bind: *const WS_MULTI_BIND, len: u32) -> WsResult<Vec<Value>> {
+    if bind.is_null() && len > 0 {
+        return Err(WsError::new(
+            WS_ERROR_NO::NULL_POINTER.as_code(),
+            "tags bind is null",
+        ));
+    }
+    if len == 0 {
+        return Ok(Vec::new());
+    }
+    std::slice::from_raw_parts(bind, len as usize)
+        .iter()
+        .map(|bind| bind.to_tag_value())
+        .try_collect()
+}
+
+/// Set tags in the same memory layout of `TAOS_MULTI_BIND`, `is_null` makes a NULL tag.
+///
+/// Must be called after `ws_stmt_set_tbname` when the table name is a placeholder. Returns an
+/// error without request to server if the sql has no tag placeholders or `len` mismatches.
 #[no_mangle]
 pub unsafe extern "C" fn ws_stmt_set_tags(
     stmt: *mut WS_STMT,
     bind: *const WS_MULTI_BIND,
     len: u32,
 ) -> c_int {
-    match (stmt as *mut WsMaybeError<Stmt>).as_mut() {
-        Some(stmt) => {
-            let columns = std::slice::from_raw_parts(bind, len as usize)
-                .iter()
-                .map(|bind| bind.to_tag_value())
-                .collect_vec();
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(errno) => return errno,
+        };
+        if let Err(err) = stmt.check_tags(len as usize) {
+            return stmt_error(stmt, err);
+        }
+        let tags = match binds_to_tags(bind, len) {
+            Ok(tags) => tags,
+            Err(err) => return stmt_error(stmt, err),
+        };
+
+        if let Err(e) = stmt.set_tags(&tags) {
+            stmt_error(stmt, WsError::new(e.errno(), &e.to_string()))
+        } else {
+            0
+        }
+    })
+}
 
-            if let Err(e) = stmt.set_tags(&columns) {
-                let errno = e.errno();
-                stmt.error = Some(WsError::new(errno, &e.to_string()));
-                errno.into()
-            } else {
-                0
-            }
+/// Set a JSON document as the only tag of supertables with a JSON tag, NULL for NULL tag.
+///
+/// The JSON is validated before sending, call it after each `ws_stmt_set_tbname` to give
+/// child tables different tags.
+///
+/// # Safety
+///
+/// `stmt` must be NULL or a live statement, `json` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ws_stmt_set_tags_json(stmt: *mut WS_STMT, json: *const c_char) -> c_int {
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(errno) => return errno,
+        };
+        if let Err(err) = stmt.check_tags(1) {
+            return stmt_error(stmt, err);
         }
-        _ => 0,
+        let tag = match opt_c_str(json) {
+            Ok(Some(json)) => match serde_json::from_str::<serde_json::Value>(json) {
+                Ok(serde_json::Value::Object(_)) => Value::VarChar(json.to_string()),
+                Ok(_) => {
+                    let err = WsError::new(
+                        WS_ERROR_NO::TYPE_MISMATCH.as_code(),
+                        "json tag should be an object",
+                    );
+                    return stmt_error(stmt, err);
+                }
+                Err(e) => {
+                    let err = WsError::new(
+                        WS_ERROR_NO::TYPE_MISMATCH.as_code(),
+                        &format!("invalid json tag: {e}"),
+                    );
+                    return stmt_error(stmt, err);
+                }
+            },
+            Ok(None) => Value::Null(Ty::Json),
+            Err(err) => return stmt_error(stmt, err),
+        };
+
+        if let Err(e) = stmt.set_tags(&[tag]) {
+            stmt_error(stmt, WsError::new(e.errno(), &e.to_string()))
+        } else {
+            0
+        }
+    })
+}
+
+/// Convert binds to column views, all binds should have the same rows.
+unsafe fn binds_to_views(
+    bind: *const WS_MULTI_BIND,
+    len: u32,
+    precision: Precision,
+) -> WsResult<Vec<ColumnView>> {
+    if bind.is_null() {
+        return Err(WsError::new(
+            WS_ERROR_NO::NULL_POINTER.as_code(),
+            "bind is null",
+        ));
     }
+    let views = std::slice::from_raw_parts(bind, len as usize)
+        .iter()
+        .map(|bind| bind.to_column_view(precision))
+        .collect::<WsResult<Vec<_>>>()?;
+    if let Some(first) = views.first() {
+        if let Some((col, view)) = views.iter().find_position(|v| v.len() != first.len()) {
+            return Err(WsError::new(
+                Code::Failed,
+                &format!(
+                    "bind rows mismatch: column 0 has {} rows, but column {col} has {}",
+                    first.len(),
+                    view.len()
+                ),
+            ));
+        }
+    }
+    Ok(views)
+}
+
+/// Bind columns to the statement as a single binary block message.
+unsafe fn stmt_bind(stmt: *mut WS_STMT, bind: *const WS_MULTI_BIND, len: u32) -> c_int {
+    let stmt = match stmt_mut(stmt) {
+        Ok(stmt) => stmt,
+        Err(errno) => return errno,
+    };
+    let precision = match stmt.precision() {
+        Ok(precision) => precision,
+        Err(err) => return stmt_error(stmt, err),
+    };
+    let views = match binds_to_views(bind, len, precision) {
+        Ok(views) => views,
+        Err(err) => return stmt_error(stmt, err),
+    };
+    if let Err(err) = stmt.bind(&views) {
+        stmt_error(stmt, err)
+    } else {
+        0
+    }
+}
+
+/// Bind columns in the same memory layout of `TAOS_MULTI_BIND`, `num` of each bind is the rows.
+///
+/// Binds are sent as a binary block, `is_null` per row produces NULL values. Returns 0 on success.
+///
+/// # Safety
+///
+/// `stmt` must be NULL or a live statement, `bind` must be NULL or an array of `len` binds,
+/// each valid for its `num` rows.
+#[no_mangle]
+pub unsafe extern "C" fn ws_stmt_bind_param(
+    stmt: *mut WS_STMT,
+    bind: *const WS_MULTI_BIND,
+    len: u32,
+) -> c_int {
+    ffi_guard(|| stmt_bind(stmt, bind, len))
 }
 
+/// Bind multiple rows of columns at once, `num` of each bind is the rows.
+///
+/// Same as [ws_stmt_bind_param], kept for the `taos_stmt_bind_param_batch` counterpart of the
+/// native API. Returns 0 on success.
+///
+/// # Safety
+///
+/// The same to `ws_stmt_bind_param`.
 #[no_mangle]
 pub unsafe extern "C" fn ws_stmt_bind_param_batch(
     stmt: *mut WS_STMT,
     bind: *const WS_MULTI_BIND,
     len: u32,
 ) -> c_int {
-    match (stmt as *mut WsMaybeError<Stmt>).as_mut() {
-        Some(stmt) => {
-            let columns = std::slice::from_raw_parts(bind, len as usize)
-                .iter()
-                .map(|bind| bind.to_json())
-                .collect();
+    ws_stmt_bind_param(stmt, bind, len)
+}
 
-            if let Err(e) = block_in_place_or_global(stmt.stmt_bind(columns)) {
-                let errno = e.errno();
-                stmt.error = Some(WsError::new(errno, &e.to_string()));
-                errno.into()
-            } else {
+/// Add current binds as a batch, returns 0 on success.
+#[no_mangle]
+pub unsafe extern "C" fn ws_stmt_add_batch(stmt: *mut WS_STMT) -> c_int {
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(errno) => return errno,
+        };
+        if let Err(e) = stmt.add_batch() {
+            stmt_error(stmt, WsError::new(e.errno(), &e.to_string()))
+        } else {
+            0
+        }
+    })
+}
+
+/// Execute all added batches, get inserted rows in `affected_row` pointer if not NULL.
+///
+/// Executing with nothing added is a no-op success with 0 rows.
+#[no_mangle]
+pub unsafe extern "C" fn ws_stmt_execute(stmt: *mut WS_STMT, affected_rows: *mut i32) -> c_int {
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(errno) => return errno,
+        };
+        match stmt.execute() {
+            Ok(rows) => {
+                if let Some(affected_rows) = affected_rows.as_mut() {
+                    *affected_rows = rows as _;
+                }
                 0
             }
+            Err(err) => stmt_error(stmt, err),
         }
-        _ => 0,
-    }
+    })
 }
 
+/// Get result set of the last executed query statement, like `select * from t where ts > ?`.
+///
+/// The result set works with all fetch functions and should be freed by `ws_free_result`,
+/// before or after `ws_stmt_close` or `ws_close`, since it shares the connection of the
+/// `WS_TAOS` handle with the statement. Returns NULL with the statement error set if there's no
+/// result, each result could be taken only once.
+///
+/// Values of query statements are rendered into the sql as escaped literals on the client, since
+/// taosAdapter prepares inserts only, so the server parses the sql on each execute.
+///
+/// # Safety
+///
+/// `stmt` must be NULL or a live statement.
 #[no_mangle]
-pub unsafe extern "C" fn ws_stmt_add_batch(stmt: *mut WS_STMT) -> c_int {
-    match (stmt as *mut WsMaybeError<Stmt>).as_mut() {
-        Some(stmt) => {
-            if let Err(e) = stmt.add_batch() {
-                let errno = e.errno();
-                stmt.error = Some(WsError::new(errno, &e.to_string()));
-                errno.into()
-            } else {
-                0
+pub unsafe extern "C" fn ws_stmt_use_result(stmt: *mut WS_STMT) -> *mut WS_RES {
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(_) => return std::ptr::null_mut(),
+        };
+        match stmt.query.as_mut().and_then(|query| query.result.take()) {
+            Some(rs) => {
+                let rs: WsMaybeError<WsResultSet> = WsResult::Ok(rs).into();
+                Box::into_raw(Box::new(rs)) as _
+            }
+            None => {
+                stmt_error(
+                    stmt,
+                    WsError::new(Code::Failed, "no result, execute a query statement first"),
+                );
+                std::ptr::null_mut()
             }
         }
+    })
+}
+
+/// Get inserted rows since prepare of current statement, 0 for NULL.
+#[no_mangle]
+pub unsafe extern "C" fn ws_stmt_affected_rows(stmt: *mut WS_STMT) -> c_int {
+    ffi_guard(|| match (stmt as *const WsMaybeError<WsStmt>).as_ref() {
+        Some(stmt) => stmt
+            .safe_deref()
+            .map_or(0, |stmt| stmt.affected_rows.min(i32::MAX as usize) as _),
         _ => 0,
-    }
+    })
 }
 
-/// Execute the bind batch, get inserted rows in `affected_row` pointer.
+/// Get inserted rows of the last execute, 0 for NULL.
+///
+/// # Safety
+///
+/// `stmt` must be NULL or a live statement.
 #[no_mangle]
-pub unsafe extern "C" fn ws_stmt_execute(stmt: *mut WS_STMT, affected_rows: *mut i32) -> c_int {
-    match (stmt as *mut WsMaybeError<Stmt>).as_mut() {
-        Some(stmt) => match stmt.execute() {
-            Ok(rows) => {
-                *affected_rows = rows as _;
+pub unsafe extern "C" fn ws_stmt_affected_rows_once(stmt: *mut WS_STMT) -> c_int {
+    ffi_guard(|| match (stmt as *const WsMaybeError<WsStmt>).as_ref() {
+        Some(stmt) => stmt.safe_deref().map_or(0, |stmt| {
+            stmt.affected_rows_once.min(i32::MAX as usize) as _
+        }),
+        _ => 0,
+    })
+}
+
+/// Get number of placeholders in the prepared sql from server.
+///
+/// Returns `0xE00B` when the server could not know it yet, like a supertable insert before
+/// `ws_stmt_set_tbname`.
+///
+/// # Safety
+///
+/// `stmt` must be NULL or a live statement, `nums` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn ws_stmt_num_params(stmt: *mut WS_STMT, nums: *mut c_int) -> c_int {
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(errno) => return errno,
+        };
+        let Some(nums) = nums.as_mut() else {
+            let err = WsError::new(WS_ERROR_NO::NULL_POINTER.as_code(), "nums is null");
+            return stmt_error(stmt, err);
+        };
+        if let Some(query) = &stmt.query {
+            *nums = query.params as _;
+            return 0;
+        }
+        if let Err(err) = stmt.check_params() {
+            return stmt_error(stmt, err);
+        }
+        match block_in_place_or_global(stmt.stmt_num_params()) {
+            Ok(num) => {
+                *nums = num as _;
                 0
             }
-            Err(e) => {
-                let errno = e.errno();
-                stmt.error = Some(WsError::new(errno, &e.to_string()));
-                errno.into()
+            Err(e) => stmt_error(stmt, WsError::new(e.errno(), &e.to_string())),
+        }
+    })
+}
+
+/// Get type and bytes of the placeholder at `idx`, type is the same `Ty` code of `WS_FIELD`.
+///
+/// Returns `0xE00B` when the server could not know it yet, same as `ws_stmt_num_params`.
+///
+/// # Safety
+///
+/// `stmt` must be NULL or a live statement, `type` and `bytes` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn ws_stmt_get_param(
+    stmt: *mut WS_STMT,
+    idx: c_int,
+    r#type: *mut c_int,
+    bytes: *mut c_int,
+) -> c_int {
+    ffi_guard(|| {
+        let stmt = match stmt_mut(stmt) {
+            Ok(stmt) => stmt,
+            Err(errno) => return errno,
+        };
+        if r#type.is_null() || bytes.is_null() {
+            let err = WsError::new(WS_ERROR_NO::NULL_POINTER.as_code(), "type or bytes is null");
+            return stmt_error(stmt, err);
+        }
+        if let Err(err) = stmt.check_params() {
+            return stmt_error(stmt, err);
+        }
+        match block_in_place_or_global(stmt.stmt_get_param(idx as _)) {
+            Ok((ty, len)) => {
+                *r#type = ty;
+                *bytes = len;
+                0
             }
-        },
-        _ => 0,
-    }
+            Err(e) => stmt_error(stmt, WsError::new(e.errno(), &e.to_string())),
+        }
+    })
 }
 
-/// Get inserted rows in current statement.
+/// Error code of the last failed operation on the statement, 0 if it succeeded.
+///
+/// For NULL, it returns the last error of current thread like `ws_errno`.
+///
+/// # Safety
+///
+/// `stmt` must be NULL or a live statement.
 #[no_mangle]
-pub unsafe extern "C" fn ws_stmt_affected_rows(stmt: *mut WS_STMT) -> c_int {
-    match (stmt as *mut WsMaybeError<Stmt>).as_mut() {
-        Some(stmt) => stmt.affected_rows() as _,
-        _ => 0,
-    }
+pub unsafe extern "C" fn ws_stmt_errno(stmt: *mut WS_STMT) -> c_int {
+    ffi_guard(|| match stmt_ref(stmt) {
+        Some(stmt) => stmt.errno().unwrap_or(0),
+        None => ws_errno(stmt as _),
+    })
 }
 
-/// Equivalent to ws_errstr
+/// Error string of the last failed operation on the statement, empty if it succeeded.
+///
+/// The string is owned by the statement, valid until the next failed operation or
+/// `ws_stmt_close`. For NULL, it returns the last error of current thread like `ws_errstr`.
 #[no_mangle]
 pub unsafe extern "C" fn ws_stmt_errstr(stmt: *mut WS_STMT) -> *const c_char {
-    ws_errstr(stmt as _)
+    ffi_guard(|| match stmt_ref(stmt) {
+        Some(stmt) => stmt.errstr().unwrap_or(EMPTY.as_ptr()),
+        None => ws_errstr(stmt as _),
+    })
 }
 
-/// Same to taos_stmt_close
+/// Same to taos_stmt_close, no-op for NULL or an already closed statement.
+///
+/// Statement holds its own connection, it's safe to close the statement after `ws_close`.
 #[no_mangle]
 pub unsafe extern "C" fn ws_stmt_close(stmt: *mut WS_STMT) {
-    let _ = Box::from_raw(stmt as *mut WsMaybeError<Stmt>);
+    ffi_guard(|| {
+        if stmt.is_null() {
+            return;
+        }
+        if handle_tag(stmt) != Some(HANDLE_MAYBE_ERROR) {
+            log::warn!("close an invalid or already closed statement {stmt:p}, ignored");
+            return;
+        }
+        clear_handle_tag(stmt);
+        let _ = Box::from_raw(stmt as *mut WsMaybeError<WsStmt>);
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tests::exec_sql;
+
+    #[test]
+    fn bind_to_column_view() {
+        use taos_query::common::BorrowedValue;
+        let binds = [
+            TaosMultiBind::from_primitives(vec![false, true, false], &[1u32, 2, u32::MAX]),
+            TaosMultiBind::from_string_vec(&[Some("涛思"), None, Some("")]),
+        ];
+        let views =
+            unsafe { binds_to_views(binds.as_ptr(), binds.len() as _, Precision::Millisecond) }
+                .unwrap();
+        let uints: Vec<_> = views[0]
+            .iter()
+            .map(|v| match v {
+                BorrowedValue::UInt(v) => Some(v),
+                BorrowedValue::Null(Ty::UInt) => None,
+                v => panic!("unexpected value {v:?}"),
+            })
+            .collect();
+        assert_eq!(uints, [Some(1), None, Some(u32::MAX)]);
+        let strings: Vec<_> = views[1]
+            .iter()
+            .map(|v| (!v.is_null()).then(|| v.to_string().unwrap()))
+            .collect();
+        assert_eq!(
+            strings,
+            [Some("涛思".to_string()), None, Some(String::new())]
+        );
+
+        let short = TaosMultiBind::from_primitives(vec![false], &[1i8]);
+        let binds = [binds[0].clone(), short];
+        let err =
+            unsafe { binds_to_views(binds.as_ptr(), binds.len() as _, Precision::Millisecond) }
+                .unwrap_err();
+        assert!(err.to_string().contains("mismatch"), "{err}");
+        std::mem::forget(binds);
+
+        // raw timestamps are in the database precision.
+        let ts = [1_655_793_421_375_001i64];
+        let binds = [TaosMultiBind::from_raw_timestamps(vec![false], &ts)];
+        let views =
+            unsafe { binds_to_views(binds.as_ptr(), binds.len() as _, Precision::Microsecond) }
+                .unwrap();
+        assert_eq!(
+            views[0].iter().map(|v| v.to_value()).collect_vec(),
+            [Value::Timestamp(Timestamp::Microseconds(ts[0]))]
+        );
+    }
+
+    #[test]
+    fn tag_placeholders() {
+        assert_eq!(
+            parse_placeholders(&sql_tokens("insert into ? values(?, ?)")),
+            (true, 0)
+        );
+        assert_eq!(
+            parse_placeholders(&sql_tokens(
+                "INSERT INTO ? USING st TAGS (?, ?) VALUES (?, ?)"
+            )),
+            (true, 2)
+        );
+        assert_eq!(
+            parse_placeholders(&sql_tokens(
+                "insert into t1 using st tags('?', ?) values(?, '(?)')"
+            )),
+            (false, 1)
+        );
+        assert_eq!(
+            parse_placeholders(&sql_tokens("insert into `?` values(?)")),
+            (false, 0)
+        );
+    }
+
+    #[test]
+    fn query_render() {
+        let sql = "select * from t where ts > ? and tag = ? and v = '?'";
+        let values = [
+            Value::BigInt(1_655_793_421_375),
+            Value::VarChar(r#"a' or "1"="1"#.to_string()),
+        ];
+        let rendered = render_query(sql, 2, &values).unwrap();
+        assert_eq!(
+            rendered,
+            r#"select * from t where ts > 1655793421375 and tag = 'a\' or "1"="1' and v = '?'"#
+        );
+        assert!(render_query(sql, 2, &values[..1]).is_err());
+
+        // the same instant in any precision.
+        let ts = [
+            Timestamp::Milliseconds(1_655_793_421_375),
+            Timestamp::Nanoseconds(1_655_793_421_375_000_001),
+        ];
+        let values = ts.map(Value::Timestamp);
+        assert_eq!(
+            render_query("select ?, ?", 2, &values).unwrap(),
+            "select '2022-06-21T06:37:01.375Z', '2022-06-21T06:37:01.375000001Z'"
+        );
+        let err = render_query("select ?", 1, &[Value::VarBinary(vec![1])]).unwrap_err();
+        assert_eq!(err.code, Code::Failed);
+        assert!(render_query("select ?", 1, &[Value::Double(f64::NAN)]).is_err());
+        assert_eq!(
+            render_query("select ?", 1, &[Value::NChar(r"a\b".to_string())]).unwrap(),
+            r"select 'a\\b'"
+        );
+
+        // escaped quotes and comments keep their `?`.
+        let values = [Value::Int(1)];
+        for sql in [
+            r"select * from t where v = 'a\'?' and 1 = ?",
+            "select * from t where v = 'a''?' and 1 = ?",
+            "select * from t where v = \"\\\"?\" and 1 = ?",
+            "select * -- ?\nfrom t where 1 = ?",
+            "/* ? */ select * from t where 1 = ?",
+        ] {
+            let tokens = sql_tokens(sql);
+            assert_eq!(tokens.iter().filter(|t| *t == "?").count(), 1, "{sql}");
+            assert!(is_query(&tokens), "{sql}");
+            let rendered = render_query(sql, 1, &values).unwrap();
+            assert_eq!(rendered, format!("{}1", &sql[..sql.len() - 1]));
+        }
+        assert_eq!(
+            sql_tokens("select 'a'' b' from t"),
+            ["select", "'", "from", "t"]
+        );
+        assert_eq!(sql_tokens("v--comment\n, w"), ["v", ",", "w"]);
+    }
+
+    #[test]
+    fn query_detected() {
+        for sql in [
+            "select ?",
+            "SELECT ?",
+            "(select ?) union (select ?)",
+            "with t as (select ?) select * from t",
+            "-- comment\nselect ?",
+            "/* comment */ select ?",
+        ] {
+            assert!(is_query(&sql_tokens(sql)), "{sql}");
+        }
+        for sql in [
+            "insert into t values(?)",
+            "/* select */ insert into t values(?)",
+        ] {
+            assert!(!is_query(&sql_tokens(sql)), "{sql}");
+        }
+    }
+
+    #[test]
+    fn bind_to_tag_value() {
+        let binds = [
+            TaosMultiBind::from_primitives(vec![false], &[true]),
+            TaosMultiBind::from_primitives(vec![true], &[0i16]),
+            TaosMultiBind::from_primitives(vec![false], &[u64::MAX]),
+            TaosMultiBind::from_primitives(vec![false], &[0.5f64]),
+            TaosMultiBind::from_binary_vec(&[Some("abc")]),
+            TaosMultiBind::from_string_vec(&[Some("涛思")]),
+        ];
+        let tags = unsafe { binds_to_tags(binds.as_ptr(), binds.len() as _) }.unwrap();
+        assert!(matches!(tags[0], Value::Bool(true)));
+        assert!(matches!(tags[1], Value::Null(Ty::SmallInt)));
+        assert!(matches!(tags[2], Value::UBigInt(u64::MAX)));
+        assert!(matches!(tags[3], Value::Double(v) if v == 0.5));
+        assert!(matches!(&tags[4], Value::VarChar(v) if v == "abc"));
+        assert!(matches!(&tags[5], Value::NChar(v) if v == "涛思"));
+
+        let err = unsafe { binds_to_tags(std::ptr::null(), 1) }.unwrap_err();
+        assert_eq!(err.code, WS_ERROR_NO::NULL_POINTER.as_code());
+    }
+
+    #[test]
+    fn stmt_bind_param() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            exec_sql(taos, b"drop database if exists ws_stmt_bind_param\0");
+            exec_sql(
+                taos,
+                b"create database ws_stmt_bind_param precision 'us' keep 36500\0",
+            );
+            exec_sql(taos, b"create table ws_stmt_bind_param.s1 (ts timestamp, u int unsigned, b binary(20), n nchar(20))\0");
+
+            let stmt = ws_stmt_init(taos);
+            let sql = "insert into ws_stmt_bind_param.s1 values(?, ?, ?, ?)";
+            let code = ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _);
+            assert_eq!(code, 0, "{:?}", CStr::from_ptr(ws_errstr(stmt)));
+
+            const ROWS: usize = 300;
+            let ts: Vec<i64> = (0..ROWS as i64)
+                .map(|i| 1_655_793_421_375_001 + i)
+                .collect();
+            let nulls: Vec<bool> = (0..ROWS).map(|i| i % 10 == 0).collect();
+            let uints: Vec<u32> = (0..ROWS as u32).map(|i| u32::MAX - i).collect();
+            let strings: Vec<Option<String>> = (0..ROWS)
+                .map(|i| (i % 10 != 0).then(|| format!("涛思{i}")))
+                .collect();
+            let params = [
+                TaosMultiBind::from_raw_timestamps(vec![false; ROWS], &ts),
+                TaosMultiBind::from_primitives(nulls, &uints),
+                TaosMultiBind::from_binary_vec(&strings),
+                TaosMultiBind::from_string_vec(&strings),
+            ];
+            let code = ws_stmt_bind_param(stmt, params.as_ptr(), params.len() as _);
+            assert_eq!(code, 0, "{:?}", CStr::from_ptr(ws_errstr(stmt)));
+            assert_eq!(ws_stmt_add_batch(stmt), 0);
+            let mut rows = 0;
+            assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+            assert_eq!(rows, ROWS as i32);
+
+            // mismatched rows is rejected
+            let short = [TaosMultiBind::from_raw_timestamps(vec![false; 2], &ts[..2])];
+            let params = [&params[..1], &short].concat();
+            let code = ws_stmt_bind_param(stmt, params.as_ptr(), params.len() as _);
+            assert_ne!(code, 0);
+            std::mem::forget(params);
+            ws_stmt_close(stmt);
+
+            let rs = ws_query(
+                taos,
+                b"select ts, u, b, n from ws_stmt_bind_param.s1 order by ts\0" as *const u8 as _,
+            );
+            assert_eq!(ws_errno(rs), 0);
+            let mut row = 0;
+            loop {
+                let values = ws_fetch_row(rs);
+                if values.is_null() {
+                    break;
+                }
+                let values = std::slice::from_raw_parts(values, 4);
+                assert_eq!(*(values[0] as *const i64), ts[row]);
+                if row % 10 == 0 {
+                    assert!(values[1].is_null());
+                    assert!(values[2].is_null());
+                    assert!(values[3].is_null());
+                } else {
+                    assert_eq!(*(values[1] as *const u32), uints[row]);
+                    let lengths = std::slice::from_raw_parts(ws_fetch_lengths(rs), 4);
+                    let expect = strings[row].as_ref().unwrap().as_bytes();
+                    for col in 2..4 {
+                        let value = std::slice::from_raw_parts(
+                            values[col] as *const u8,
+                            lengths[col] as usize,
+                        );
+                        assert_eq!(value, expect);
+                    }
+                }
+                row += 1;
+            }
+            assert_eq!(row, ROWS);
+            ws_free_result(rs);
+
+            exec_sql(taos, b"drop database ws_stmt_bind_param\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_bind_batch_rows() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            exec_sql(taos, b"drop database if exists ws_stmt_batch\0");
+            exec_sql(taos, b"create database ws_stmt_batch keep 36500\0");
+            exec_sql(
+                taos,
+                b"create table ws_stmt_batch.s1 (ts timestamp, v int)\0",
+            );
+
+            const ROWS: usize = 1000;
+            let sql = "insert into ws_stmt_batch.s1 values(?, ?)";
+
+            let stmt = ws_stmt_init(taos);
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+            let ts: Vec<i64> = (0..ROWS as i64).map(|i| 1_655_793_421_375 + i).collect();
+            let values: Vec<i32> = (0..ROWS as i32).collect();
+            for row in 0..ROWS {
+                let params = [
+                    TaosMultiBind::from_raw_timestamps(vec![false], &ts[row..row + 1]),
+                    TaosMultiBind::from_primitives(vec![false], &values[row..row + 1]),
+                ];
+                assert_eq!(ws_stmt_bind_param(stmt, params.as_ptr(), 2), 0);
+                assert_eq!(ws_stmt_add_batch(stmt), 0);
+            }
+            let mut rows = 0;
+            assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+            assert_eq!(rows, ROWS as i32);
+
+            let batch_ts: Vec<i64> = ts.iter().map(|ts| ts + ROWS as i64).collect();
+            let params = [
+                TaosMultiBind::from_raw_timestamps(vec![false; ROWS], &batch_ts),
+                TaosMultiBind::from_primitives(vec![false; ROWS], &values),
+            ];
+            assert_eq!(ws_stmt_bind_param_batch(stmt, params.as_ptr(), 2), 0);
+            assert_eq!(ws_stmt_add_batch(stmt), 0);
+            assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+            assert_eq!(rows, ROWS as i32);
+            ws_stmt_close(stmt);
+
+            // rows bound one by one and in a batch are inserted the same.
+            let rs = ws_query(
+                taos,
+                b"select ts, v from ws_stmt_batch.s1 order by ts\0" as *const u8 as _,
+            );
+            assert_eq!(ws_errno(rs), 0);
+            let expected = ts.iter().chain(&batch_ts).zip(values.iter().cycle());
+            let mut row = 0;
+            for (ts, v) in expected {
+                let fetched = ws_fetch_row(rs);
+                assert!(!fetched.is_null(), "row {row} is missing");
+                let fetched = std::slice::from_raw_parts(fetched, 2);
+                assert_eq!(*(fetched[0] as *const i64), *ts);
+                assert_eq!(*(fetched[1] as *const i32), *v);
+                row += 1;
+            }
+            assert!(ws_fetch_row(rs).is_null());
+            assert_eq!(row, ROWS * 2);
+            ws_free_result(rs);
+
+            exec_sql(taos, b"drop database ws_stmt_batch\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_bind_batch_throughput() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            exec_sql(taos, b"drop database if exists ws_stmt_batch_time\0");
+            exec_sql(taos, b"create database ws_stmt_batch_time keep 36500\0");
+            exec_sql(
+                taos,
+                b"create table ws_stmt_batch_time.s1 (ts timestamp, v int)\0",
+            );
+
+            const ROWS: usize = 1000;
+            let sql = "insert into ws_stmt_batch_time.s1 values(?, ?)";
+
+            let stmt = ws_stmt_init(taos);
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+            let ts: Vec<i64> = (0..ROWS as i64).map(|i| 1_655_793_421_375 + i).collect();
+            let values: Vec<i32> = (0..ROWS as i32).collect();
+            let now = std::time::Instant::now();
+            for row in 0..ROWS {
+                let params = [
+                    TaosMultiBind::from_raw_timestamps(vec![false], &ts[row..row + 1]),
+                    TaosMultiBind::from_primitives(vec![false], &values[row..row + 1]),
+                ];
+                assert_eq!(ws_stmt_bind_param(stmt, params.as_ptr(), 2), 0);
+                assert_eq!(ws_stmt_add_batch(stmt), 0);
+            }
+            let mut rows = 0;
+            assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+            let per_row = now.elapsed();
+            assert_eq!(rows, ROWS as i32);
+
+            let ts: Vec<i64> = ts.iter().map(|ts| ts + ROWS as i64).collect();
+            let now = std::time::Instant::now();
+            let params = [
+                TaosMultiBind::from_raw_timestamps(vec![false; ROWS], &ts),
+                TaosMultiBind::from_primitives(vec![false; ROWS], &values),
+            ];
+            assert_eq!(ws_stmt_bind_param_batch(stmt, params.as_ptr(), 2), 0);
+            assert_eq!(ws_stmt_add_batch(stmt), 0);
+            assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+            let batch = now.elapsed();
+            assert_eq!(rows, ROWS as i32);
+            ws_stmt_close(stmt);
+
+            // one bind message against a bind and an add batch round trip per row.
+            log::info!("bind {ROWS} rows per row: {per_row:?}, in batch: {batch:?}");
+            assert!(
+                batch * 10 < per_row,
+                "per row: {per_row:?}, batch: {batch:?}"
+            );
+
+            exec_sql(taos, b"drop database ws_stmt_batch_time\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_switch_tbname() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            exec_sql(taos, b"drop database if exists ws_stmt_switch\0");
+            exec_sql(taos, b"create database ws_stmt_switch keep 36500\0");
+            exec_sql(
+                taos,
+                b"create stable ws_stmt_switch.st (ts timestamp, v int) tags(t int)\0",
+            );
+            exec_sql(
+                taos,
+                b"create table ws_stmt_switch.t1 using ws_stmt_switch.st tags(1)\0",
+            );
+            exec_sql(
+                taos,
+                b"create table ws_stmt_switch.t2 using ws_stmt_switch.st tags(2)\0",
+            );
+
+            let stmt = ws_stmt_init(taos);
+            let sql = "insert into ? values(?, ?)";
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+
+            // invalid table name reports server error but not poisons the statement.
+            let code = ws_stmt_set_tbname(stmt, c"ws_stmt_switch.no_such_table".as_ptr() as _);
+            assert_ne!(code, 0);
+            assert_eq!(ws_errno(stmt), code);
+            assert!(!CStr::from_ptr(ws_stmt_errstr(stmt)).to_bytes().is_empty());
+            let code = ws_stmt_set_tbname(stmt, std::ptr::null());
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+
+            for (i, name) in [b"ws_stmt_switch.t1\0", b"ws_stmt_switch.t2\0"]
+                .iter()
+                .enumerate()
+            {
+                let code = ws_stmt_set_tbname(stmt, name.as_ptr() as _);
+                assert_eq!(code, 0, "{:?}", CStr::from_ptr(ws_stmt_errstr(stmt)));
+                assert_eq!(ws_errno(stmt), 0);
+                let ts = [1_655_793_421_375 + i as i64];
+                let params = [
+                    TaosMultiBind::from_raw_timestamps(vec![false], &ts),
+                    TaosMultiBind::from_primitives(vec![false], &[i as i32]),
+                ];
+                assert_eq!(ws_stmt_bind_param(stmt, params.as_ptr(), 2), 0);
+                assert_eq!(ws_stmt_add_batch(stmt), 0);
+            }
+            let mut rows = 0;
+            assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+            assert_eq!(rows, 2);
+            ws_stmt_close(stmt);
+
+            let rs = ws_query(
+                taos,
+                b"select count(*) from ws_stmt_switch.st group by t\0" as *const u8 as _,
+            );
+            assert_eq!(ws_errno(rs), 0);
+            let mut groups = 0;
+            while !ws_fetch_row(rs).is_null() {
+                groups += 1;
+            }
+            assert_eq!(groups, 2);
+            ws_free_result(rs);
+
+            exec_sql(taos, b"drop database ws_stmt_switch\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_set_tags() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            exec_sql(taos, b"drop database if exists ws_stmt_tags\0");
+            exec_sql(taos, b"create database ws_stmt_tags keep 36500\0");
+            exec_sql(
+                taos,
+                b"create stable ws_stmt_tags.st (ts timestamp, v int) tags(t1 bool, t2 tinyint, \
+                  t3 smallint, t4 int, t5 bigint, t6 tinyint unsigned, t7 smallint unsigned, \
+                  t8 int unsigned, t9 bigint unsigned, t10 float, t11 double, \
+                  t12 binary(10), t13 nchar(10))\0",
+            );
+
+            let stmt = ws_stmt_init(taos);
+            let sql = "insert into ? using ws_stmt_tags.st tags(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) values(?, ?)";
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+
+            let tags = [
+                TaosMultiBind::from_primitives(vec![false], &[true]),
+                TaosMultiBind::from_primitives(vec![false], &[i8::MIN]),
+                TaosMultiBind::from_primitives(vec![true], &[0i16]),
+                TaosMultiBind::from_primitives(vec![false], &[i32::MAX]),
+                TaosMultiBind::from_primitives(vec![false], &[i64::MIN]),
+                TaosMultiBind::from_primitives(vec![false], &[u8::MAX]),
+                TaosMultiBind::from_primitives(vec![false], &[u16::MAX]),
+                TaosMultiBind::from_primitives(vec![false], &[u32::MAX]),
+                TaosMultiBind::from_primitives(vec![false], &[u64::MAX]),
+                TaosMultiBind::from_primitives(vec![false], &[0.5f32]),
+                TaosMultiBind::from_primitives(vec![false], &[0.25f64]),
+                TaosMultiBind::from_binary_vec(&[Some("abc")]),
+                TaosMultiBind::from_string_vec(&[None::<&str>]),
+            ];
+
+            // tags before table name are rejected locally.
+            let code = ws_stmt_set_tags(stmt, tags.as_ptr(), tags.len() as _);
+            assert_ne!(code, 0);
+            let err = CStr::from_ptr(ws_stmt_errstr(stmt)).to_str().unwrap();
+            assert!(err.contains("ws_stmt_set_tbname"), "{err}");
+
+            assert_eq!(
+                ws_stmt_set_tbname(stmt, c"ws_stmt_tags.t1".as_ptr() as _),
+                0
+            );
+            let code = ws_stmt_set_tags(stmt, tags.as_ptr(), 2);
+            assert_ne!(code, 0);
+            let code = ws_stmt_set_tags(stmt, tags.as_ptr(), tags.len() as _);
+            assert_eq!(code, 0, "{:?}", CStr::from_ptr(ws_stmt_errstr(stmt)));
+
+            let params = [
+                TaosMultiBind::from_raw_timestamps(vec![false], &[1_655_793_421_375]),
+                TaosMultiBind::from_primitives(vec![false], &[1]),
+            ];
+            assert_eq!(ws_stmt_bind_param(stmt, params.as_ptr(), 2), 0);
+            assert_eq!(ws_stmt_add_batch(stmt), 0);
+            let mut rows = 0;
+            assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+            assert_eq!(rows, 1);
+            ws_stmt_close(stmt);
+
+            let rs = ws_query(
+                taos,
+                b"select count(*) from ws_stmt_tags.st where t1 = true and t3 is null \
+                  and t9 = 18446744073709551615 and t12 = 'abc' and t13 is null\0"
+                    as *const u8 as _,
+            );
+            assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+            let row = ws_fetch_row(rs);
+            assert!(!row.is_null());
+            assert_eq!(*(*row as *const i64), 1);
+            ws_free_result(rs);
+
+            // no tag placeholders in sql.
+            let stmt = ws_stmt_init(taos);
+            let sql = "insert into ? values(?, ?)";
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+            assert_eq!(
+                ws_stmt_set_tbname(stmt, c"ws_stmt_tags.t1".as_ptr() as _),
+                0
+            );
+            let code = ws_stmt_set_tags(stmt, tags.as_ptr(), tags.len() as _);
+            assert_ne!(code, 0);
+            let err = CStr::from_ptr(ws_stmt_errstr(stmt)).to_str().unwrap();
+            assert!(err.contains("without tag placeholders"), "{err}");
+            ws_stmt_close(stmt);
+
+            exec_sql(taos, b"drop database ws_stmt_tags\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_set_tags_json() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            exec_sql(taos, b"drop database if exists ws_stmt_json\0");
+            exec_sql(taos, b"create database ws_stmt_json keep 36500\0");
+            exec_sql(
+                taos,
+                b"create stable ws_stmt_json.st (ts timestamp, v int) tags(j json)\0",
+            );
+
+            let stmt = ws_stmt_init(taos);
+            let sql = "insert into ? using ws_stmt_json.st tags(?) values(?, ?)";
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+            assert_eq!(
+                ws_stmt_set_tbname(stmt, c"ws_stmt_json.t0".as_ptr() as _),
+                0
+            );
+
+            // typos fail fast, the statement is still usable.
+            let code = ws_stmt_set_tags_json(stmt, c"{\"name\":".as_ptr() as _);
+            assert_eq!(code, WS_ERROR_NO::TYPE_MISMATCH as i32);
+            let err = CStr::from_ptr(ws_stmt_errstr(stmt)).to_str().unwrap();
+            assert!(err.contains("invalid json tag"), "{err}");
+
+            let tables: [(&[u8], &[u8]); 2] = [
+                (b"ws_stmt_json.t1\0", b"{\"name\":\"t1\"}\0"),
+                (b"ws_stmt_json.t2\0", b"{\"name\":\"t2\",\"v\":2}\0"),
+            ];
+            for (i, (name, json)) in tables.iter().enumerate() {
+                assert_eq!(ws_stmt_set_tbname(stmt, name.as_ptr() as _), 0);
+                let code = ws_stmt_set_tags_json(stmt, json.as_ptr() as _);
+                assert_eq!(code, 0, "{:?}", CStr::from_ptr(ws_stmt_errstr(stmt)));
+                let ts = [1_655_793_421_375 + i as i64];
+                let params = [
+                    TaosMultiBind::from_raw_timestamps(vec![false], &ts),
+                    TaosMultiBind::from_primitives(vec![false], &[i as i32]),
+                ];
+                assert_eq!(ws_stmt_bind_param(stmt, params.as_ptr(), 2), 0);
+                assert_eq!(ws_stmt_add_batch(stmt), 0);
+            }
+            let mut rows = 0;
+            assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+            assert_eq!(rows, 2);
+            ws_stmt_close(stmt);
+
+            let rs = ws_query(
+                taos,
+                b"select count(*) from ws_stmt_json.st where j->'name' = 't2'\0" as *const u8 as _,
+            );
+            assert_eq!(ws_errno(rs), 0, "{:?}", CStr::from_ptr(ws_errstr(rs)));
+            let row = ws_fetch_row(rs);
+            assert!(!row.is_null());
+            assert_eq!(*(*row as *const i64), 1);
+            ws_free_result(rs);
+
+            exec_sql(taos, b"drop database ws_stmt_json\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_execute_affected_rows() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            exec_sql(taos, b"drop database if exists ws_stmt_exec\0");
+            exec_sql(taos, b"create database ws_stmt_exec keep 36500\0");
+            exec_sql(
+                taos,
+                b"create stable ws_stmt_exec.st (ts timestamp, v int) tags(t int)\0",
+            );
+            for sql in [
+                b"create table ws_stmt_exec.t1 using ws_stmt_exec.st tags(1)\0",
+                b"create table ws_stmt_exec.t2 using ws_stmt_exec.st tags(2)\0",
+                b"create table ws_stmt_exec.t3 using ws_stmt_exec.st tags(3)\0",
+            ] {
+                exec_sql(taos, sql);
+            }
+
+            let stmt = ws_stmt_init(taos);
+            let sql = "insert into ? values(?, ?)";
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+
+            // nothing bound yet.
+            let mut rows = -1;
+            assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+            assert_eq!(rows, 0);
+            assert_eq!(ws_stmt_affected_rows(stmt), 0);
+
+            let tables = [
+                b"ws_stmt_exec.t1\0",
+                b"ws_stmt_exec.t2\0",
+                b"ws_stmt_exec.t3\0",
+            ];
+            let mut total = 0;
+            for round in 0..2 {
+                // table i gets i + 1 rows in each round.
+                for (i, name) in tables.iter().enumerate() {
+                    assert_eq!(ws_stmt_set_tbname(stmt, name.as_ptr() as _), 0);
+                    let nulls = vec![false; i + 1];
+                    let ts: Vec<i64> = (0..=i as i64)
+                        .map(|n| 1_655_793_421_375 + round * 100 + n)
+                        .collect();
+                    let vs: Vec<i32> = (0..=i as i32).collect();
+                    let params = [
+                        TaosMultiBind::from_raw_timestamps(nulls.clone(), &ts),
+                        TaosMultiBind::from_primitives(nulls, &vs),
+                    ];
+                    assert_eq!(ws_stmt_bind_param_batch(stmt, params.as_ptr(), 2), 0);
+                    assert_eq!(ws_stmt_add_batch(stmt), 0);
+                }
+                let mut rows = 0;
+                assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+                assert_eq!(rows, 6);
+                total += rows;
+                assert_eq!(ws_stmt_affected_rows_once(stmt), 6);
+                assert_eq!(ws_stmt_affected_rows(stmt), total);
+            }
+            assert_eq!(total, 12);
+
+            assert_eq!(ws_stmt_execute(stmt, std::ptr::null_mut()), 0);
+            assert_eq!(ws_stmt_affected_rows_once(stmt), 0);
+            assert_eq!(ws_stmt_affected_rows(stmt), 12);
+
+            // counts restart with a new prepare.
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+            assert_eq!(ws_stmt_affected_rows(stmt), 0);
+            ws_stmt_close(stmt);
+
+            assert_eq!(ws_stmt_affected_rows(std::ptr::null_mut()), 0);
+            assert_eq!(ws_stmt_affected_rows_once(std::ptr::null_mut()), 0);
+            assert_eq!(
+                ws_stmt_add_batch(std::ptr::null_mut()),
+                WS_ERROR_NO::NULL_POINTER as i32
+            );
+
+            exec_sql(taos, b"drop database ws_stmt_exec\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_errno() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            let stmt = ws_stmt_init(taos);
+            assert_eq!(ws_stmt_errno(stmt), 0);
+            assert!(CStr::from_ptr(ws_stmt_errstr(stmt)).to_bytes().is_empty());
+
+            let sql = "insert into ? values(?, ?)";
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+            let code = ws_stmt_set_tags_json(stmt, c"{}".as_ptr() as _);
+            assert_ne!(code, 0);
+            assert_eq!(ws_stmt_errno(stmt), code);
+            let err = CStr::from_ptr(ws_stmt_errstr(stmt)).to_str().unwrap();
+            assert!(err.contains("tag placeholders"), "{err}");
+
+            // errors are per statement, the connection is not affected.
+            assert_eq!(ws_errno(taos), 0);
+
+            // cleared by the next successful call.
+            assert_eq!(ws_stmt_execute(stmt, std::ptr::null_mut()), 0);
+            assert_eq!(ws_stmt_errno(stmt), 0);
+            assert!(CStr::from_ptr(ws_stmt_errstr(stmt)).to_bytes().is_empty());
+
+            let code = ws_stmt_is_insert(stmt, std::ptr::null_mut());
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+            assert_eq!(ws_stmt_errno(stmt), code);
+            ws_stmt_close(stmt);
+
+            let code = ws_stmt_set_tbname(std::ptr::null_mut(), std::ptr::null());
+            assert_eq!(ws_stmt_errno(std::ptr::null_mut()), code);
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_params() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            exec_sql(taos, b"drop database if exists ws_stmt_params\0");
+            exec_sql(taos, b"create database ws_stmt_params keep 36500\0");
+            exec_sql(taos, b"create stable ws_stmt_params.st (ts timestamp, v int, b binary(16)) tags(t int)\0");
+            exec_sql(
+                taos,
+                b"create table ws_stmt_params.t1 using ws_stmt_params.st tags(1)\0",
+            );
+
+            let stmt = ws_stmt_init(taos);
+            let sql = "insert into ? values(?, ?, ?)";
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+
+            let mut nums = -1;
+            let code = ws_stmt_num_params(stmt, &mut nums);
+            assert_eq!(code, WS_ERROR_NO::PARAMS_UNKNOWN as i32);
+            assert_eq!(nums, -1);
+
+            assert_eq!(
+                ws_stmt_set_tbname(stmt, c"ws_stmt_params.t1".as_ptr() as _),
+                0
+            );
+            let code = ws_stmt_num_params(stmt, &mut nums);
+            assert_eq!(code, 0, "{:?}", CStr::from_ptr(ws_stmt_errstr(stmt)));
+            assert_eq!(nums, 3);
+
+            let expected = [(Ty::Timestamp, 8), (Ty::Int, 4), (Ty::VarChar, 16)];
+            for (idx, (ty, len)) in expected.into_iter().enumerate() {
+                let (mut r#type, mut bytes) = (0, 0);
+                let code = ws_stmt_get_param(stmt, idx as _, &mut r#type, &mut bytes);
+                assert_eq!(code, 0, "{:?}", CStr::from_ptr(ws_stmt_errstr(stmt)));
+                assert_eq!(r#type, ty as c_int);
+                assert_eq!(bytes, len);
+            }
+            let code = ws_stmt_get_param(stmt, 0, std::ptr::null_mut(), std::ptr::null_mut());
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+            ws_stmt_close(stmt);
+
+            exec_sql(taos, b"drop database ws_stmt_params\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_use_result() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            exec_sql(taos, b"drop database if exists ws_stmt_select\0");
+            exec_sql(taos, b"create database ws_stmt_select keep 36500\0");
+            exec_sql(
+                taos,
+                b"create stable ws_stmt_select.st (ts timestamp, v int) tags(t binary(8))\0",
+            );
+            exec_sql(
+                taos,
+                b"insert into ws_stmt_select.t1 using ws_stmt_select.st tags('a') \
+                  values(1655793421375, 1)(1655793421376, 2)(1655793421377, 3)\0",
+            );
+
+            let stmt = ws_stmt_init(taos);
+            let sql = "select ts, v from ws_stmt_select.st where ts > ? and t = ?";
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+            let mut insert = 1;
+            assert_eq!(ws_stmt_is_insert(stmt, &mut insert), 0);
+            assert_eq!(insert, 0);
+            let mut nums = 0;
+            assert_eq!(ws_stmt_num_params(stmt, &mut nums), 0);
+            assert_eq!(nums, 2);
+            assert!(ws_stmt_use_result(stmt).is_null());
+
+            for (ts, expected) in [(1_655_793_421_375i64, 2), (1_655_793_421_376, 1)] {
+                let params = [
+                    TaosMultiBind::from_raw_timestamps(vec![false], &[ts]),
+                    TaosMultiBind::from_binary_vec(&[Some("a")]),
+                ];
+                assert_eq!(ws_stmt_bind_param(stmt, params.as_ptr(), 2), 0);
+                assert_eq!(ws_stmt_add_batch(stmt), 0);
+                assert_eq!(ws_stmt_execute(stmt, std::ptr::null_mut()), 0);
+
+                let rs = ws_stmt_use_result(stmt);
+                assert!(!rs.is_null(), "{:?}", CStr::from_ptr(ws_stmt_errstr(stmt)));
+                assert_eq!(ws_errno(rs), 0);
+                assert_eq!(ws_field_count(rs), 2);
+                let mut rows = 0;
+                while !ws_fetch_row(rs).is_null() {
+                    rows += 1;
+                }
+                assert_eq!(rows, expected);
+                ws_free_result(rs);
+            }
+
+            // free the result after the statement is closed.
+            let ts = [0];
+            let params = [
+                TaosMultiBind::from_raw_timestamps(vec![false], &ts),
+                TaosMultiBind::from_binary_vec(&[Some("a")]),
+            ];
+            assert_eq!(ws_stmt_bind_param(stmt, params.as_ptr(), 2), 0);
+            assert_eq!(ws_stmt_execute(stmt, std::ptr::null_mut()), 0);
+            let rs = ws_stmt_use_result(stmt);
+            assert!(!rs.is_null());
+            ws_stmt_close(stmt);
+            let mut rows = 0;
+            while !ws_fetch_row(rs).is_null() {
+                rows += 1;
+            }
+            assert_eq!(rows, 3);
+            ws_free_result(rs);
+
+            exec_sql(taos, b"drop database ws_stmt_select\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_reset() {
+        use crate::*;
+        init_env();
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+
+            exec_sql(taos, b"drop database if exists ws_stmt_reset\0");
+            exec_sql(taos, b"create database ws_stmt_reset keep 36500\0");
+            exec_sql(
+                taos,
+                b"create stable ws_stmt_reset.st (ts timestamp, v int) tags(t int)\0",
+            );
+            exec_sql(
+                taos,
+                b"create table ws_stmt_reset.t1 using ws_stmt_reset.st tags(1)\0",
+            );
+            exec_sql(
+                taos,
+                b"create table ws_stmt_reset.t2 using ws_stmt_reset.st tags(2)\0",
+            );
+
+            let stmt = ws_stmt_init(taos);
+            assert_ne!(ws_stmt_reset(stmt), 0);
+            let sql = "insert into ? values(?, ?)";
+            assert_eq!(ws_stmt_prepare(stmt, sql.as_ptr() as _, sql.len() as _), 0);
+
+            // batch to t1 and a failed bind, then reset.
+            assert_eq!(
+                ws_stmt_set_tbname(stmt, c"ws_stmt_reset.t1".as_ptr() as _),
+                0
+            );
+            let params = [
+                TaosMultiBind::from_raw_timestamps(vec![false], &[1_655_793_421_375]),
+                TaosMultiBind::from_primitives(vec![false], &[1]),
+            ];
+            assert_eq!(ws_stmt_bind_param(stmt, params.as_ptr(), 2), 0);
+            assert_eq!(ws_stmt_add_batch(stmt), 0);
+            assert_ne!(ws_stmt_bind_param(stmt, params.as_ptr(), 1), 0);
+            assert_ne!(ws_stmt_errno(stmt), 0);
+
+            assert_eq!(
+                ws_stmt_reset(stmt),
+                0,
+                "{:?}",
+                CStr::from_ptr(ws_stmt_errstr(stmt))
+            );
+            assert_eq!(ws_stmt_errno(stmt), 0);
+
+            // table name is cleared too.
+            let mut nums = 0;
+            let code = ws_stmt_num_params(stmt, &mut nums);
+            assert_eq!(code, WS_ERROR_NO::PARAMS_UNKNOWN as i32);
+
+            assert_eq!(
+                ws_stmt_set_tbname(stmt, c"ws_stmt_reset.t2".as_ptr() as _),
+                0
+            );
+            assert_eq!(ws_stmt_bind_param(stmt, params.as_ptr(), 2), 0);
+            assert_eq!(ws_stmt_add_batch(stmt), 0);
+            let mut rows = 0;
+            assert_eq!(ws_stmt_execute(stmt, &mut rows), 0);
+            assert_eq!(rows, 1);
+            ws_stmt_close(stmt);
+
+            let rs = ws_query(
+                taos,
+                b"select count(*) from ws_stmt_reset.t1\0" as *const u8 as _,
+            );
+            assert_eq!(ws_errno(rs), 0);
+            let row = ws_fetch_row(rs);
+            assert!(!row.is_null());
+            assert_eq!(*(*row as *const i64), 0);
+            ws_free_result(rs);
+
+            exec_sql(taos, b"drop database ws_stmt_reset\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn stmt_after_close() {
+        use crate::*;
+        init_env();
+        unsafe {
+            ws_stmt_close(std::ptr::null_mut());
+            let sql = "insert into ? values(?, ?)";
+            let code = ws_stmt_prepare(std::ptr::null_mut(), sql.as_ptr() as _, sql.len() as _);
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+            let stmt = ws_stmt_init(taos);
+            assert_eq!(ws_errno(stmt), 0);
+            ws_close(taos);
+
+            let code = ws_stmt_prepare(stmt, std::ptr::null(), 0);
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+            assert_eq!(ws_errno(stmt), code);
+            assert!(!CStr::from_ptr(ws_stmt_errstr(stmt)).to_bytes().is_empty());
+            ws_stmt_close(stmt);
+        }
+    }
 
     #[test]
     fn stmt_common() {
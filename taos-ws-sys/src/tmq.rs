@@ -0,0 +1,1270 @@
+use std::ffi::c_void;
+use std::os::raw::*;
+
+use taos_error::Error as RawError;
+use taos_query::common::RawData;
+use taos_query::tmq::{AsConsumer, IsAsyncData, IsAsyncMeta, IsOffset, MessageSet, Timeout};
+use taos_query::{Address, Dsn, TBuilder};
+use taos_ws::consumer::{Consumer, Data, Meta, Offset};
+use taos_ws::TmqBuilder;
+use tokio::sync::watch;
+
+use crate::*;
+
+/// Opaque TMQ configuration type alias.
+#[allow(non_camel_case_types)]
+pub type ws_tmq_conf_t = c_void;
+
+/// Opaque TMQ consumer type alias.
+#[allow(non_camel_case_types)]
+pub type ws_tmq_t = c_void;
+
+/// Opaque TMQ topic list type alias.
+#[allow(non_camel_case_types)]
+pub type ws_tmq_list_t = c_void;
+
+/// Tag of a boxed TMQ configuration handle (`ws_tmq_conf_t`), see [`WsTmqConf`].
+const HANDLE_TMQ_CONF: u32 = 0x5754_4346;
+/// Tag of a boxed TMQ consumer handle (`ws_tmq_t`), see [`WsTmq`].
+const HANDLE_TMQ: u32 = 0x5754_4d51;
+/// Tag of a boxed TMQ topic list handle (`ws_tmq_list_t`), see [`WsTmqList`].
+const HANDLE_TMQ_LIST: u32 = 0x5754_4c53;
+
+/// Same to `TSDB_CODE_TMQ_INVALID_MSG` of native, the message is not of the consumer.
+const TMQ_INVALID_MSG: Code = Code::new(0x4000);
+
+/// Consumer configuration behind the `ws_tmq_conf_t` pointer.
+#[derive(Debug)]
+#[repr(C)]
+struct WsTmqConf {
+    tag: u32,
+    dsn: Dsn,
+}
+
+/// Result of `ws_tmq_conf_set`, same to `tmq_conf_res_t`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum ws_tmq_conf_res_t {
+    WS_TMQ_CONF_UNKNOWN = -2,
+    WS_TMQ_CONF_INVALID = -1,
+    WS_TMQ_CONF_OK = 0,
+}
+
+impl PanicDefault for ws_tmq_conf_res_t {
+    fn panic_default() -> Self {
+        ws_tmq_conf_res_t::WS_TMQ_CONF_INVALID
+    }
+}
+
+impl WsTmqConf {
+    fn new() -> Self {
+        Self {
+            tag: HANDLE_TMQ_CONF,
+            dsn: Dsn {
+                driver: "tmq".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn address(&mut self) -> &mut Address {
+        if self.dsn.addresses.is_empty() {
+            self.dsn.addresses.push(Address::default());
+        }
+        &mut self.dsn.addresses[0]
+    }
+
+    /// Set a property, the value is validated here instead of at subscribe time.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), ws_tmq_conf_res_t> {
+        use ws_tmq_conf_res_t::*;
+        let valid = match key {
+            "group.id" | "td.connect.ip" | "td.connect.user" => !value.is_empty(),
+            "client.id" | "td.connect.pass" => true,
+            "auto.offset.reset" => matches!(value, "earliest" | "latest" | "none"),
+            "enable.auto.commit" => matches!(value, "true" | "false"),
+            "auto.commit.interval.ms" => value.parse::<u64>().is_ok(),
+            "td.connect.port" => value.parse::<u16>().is_ok_and(|port| port > 0),
+            _ => return Err(WS_TMQ_CONF_UNKNOWN),
+        };
+        if !valid {
+            return Err(WS_TMQ_CONF_INVALID);
+        }
+        let value = value.to_string();
+        match key {
+            "td.connect.ip" => self.address().host = Some(value),
+            "td.connect.port" => self.address().port = value.parse().ok(),
+            "td.connect.user" => self.dsn.username = Some(value),
+            "td.connect.pass" => self.dsn.password = Some(value),
+            _ => {
+                self.dsn.params.insert(key.to_string(), value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Consumer behind the `ws_tmq_t` pointer.
+///
+/// The consumer is shared with calls in flight, so closing it in one thread while another is
+/// blocked in a poll frees only the handle, the poll keeps its own reference until it returns.
+#[repr(C)]
+struct WsTmq {
+    tag: u32,
+    shared: Arc<WsTmqShared>,
+}
+
+struct WsTmqShared {
+    builder: TmqBuilder,
+    /// Commit the last polled message when closing, same to `enable.auto.commit`.
+    auto_commit: bool,
+    /// Set when closing to wake up the blocked polls.
+    closing: watch::Sender<bool>,
+    state: std::sync::Mutex<WsTmqState>,
+}
+
+/// State of current connection, locked only briefly so polls do not block other calls.
+struct WsTmqState {
+    consumer: Arc<Consumer>,
+    /// A subscription has been tried on current connection.
+    subscribed: bool,
+    /// Closed flag of current connection, shared with the polled messages.
+    closed: Arc<AtomicBool>,
+    /// Offset of the last polled message, committed by a commit without message.
+    last: Option<Offset>,
+}
+
+impl WsTmqState {
+    /// Drop the subscription with current connection and connect again, no-op if not subscribed.
+    fn unsubscribe(&mut self, builder: &TmqBuilder) -> WsResult<()> {
+        if self.subscribed {
+            self.consumer = Arc::new(builder.build()?);
+            self.subscribed = false;
+            self.closed.store(true, Ordering::SeqCst);
+            self.closed = Arc::new(AtomicBool::new(false));
+            self.last = None;
+        }
+        Ok(())
+    }
+}
+
+impl WsTmqShared {
+    fn state(&self) -> std::sync::MutexGuard<'_, WsTmqState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Subscribe topics, replaces the previous subscription and unsubscribes for empty topics.
+    ///
+    /// Each subscription after the first one is made on a new connection, so the previous
+    /// subscription, even a failed one, is dropped with the old connection.
+    fn subscribe(&self, topics: Vec<String>) -> WsResult<()> {
+        let mut state = self.state();
+        state.unsubscribe(&self.builder)?;
+        if topics.is_empty() {
+            return Ok(());
+        }
+        let state = &mut *state;
+        let consumer = Arc::get_mut(&mut state.consumer)
+            .ok_or_else(|| WsError::new(Code::Failed, "consumer is in use by a poll or commit"))?;
+        state.subscribed = true;
+        consumer.subscribe(topics)?;
+        Ok(())
+    }
+
+    fn unsubscribe(&self) -> WsResult<()> {
+        self.state().unsubscribe(&self.builder)
+    }
+
+    /// Poll next message, negative timeout waits forever.
+    fn poll(&self, timeout_ms: i64) -> WsResult<Option<WsResultSet>> {
+        let timeout = match u64::try_from(timeout_ms) {
+            Ok(ms) => Timeout::Duration(Duration::from_millis(ms)),
+            Err(_) => Timeout::Never,
+        };
+        let (consumer, closed) = {
+            let state = self.state();
+            (state.consumer.clone(), state.closed.clone())
+        };
+        let mut closing = self.closing.subscribe();
+        let polled = block_in_place_or_global(async move {
+            tokio::select! {
+                polled = <Consumer as taos_query::tmq::AsAsyncConsumer>::recv_timeout(&consumer, timeout) => {
+                    Some(polled)
+                }
+                _ = closing.wait_for(|closing| *closing) => None,
+            }
+        });
+        let Some(polled) = polled else {
+            return Err(WsError::new(
+                WS_ERROR_NO::CONN_CLOSED.as_code(),
+                "consumer closed",
+            ));
+        };
+        let Some((offset, message)) = polled? else {
+            return Ok(None);
+        };
+        let (meta, data) = match message {
+            MessageSet::Meta(meta) => (Some(meta), None),
+            MessageSet::Data(data) => (None, Some(data)),
+            MessageSet::MetaData(meta, data) => (Some(meta), Some(data)),
+        };
+        // not recorded if unsubscribed while polling.
+        let mut state = self.state();
+        if Arc::ptr_eq(&state.closed, &closed) {
+            state.last = Some(offset.clone());
+        }
+        drop(state);
+        let msg = WsTmqMessage::new(offset, meta, data)?;
+        Ok(Some(WsResultSet::with_source(
+            ResultSource::Tmq(Box::new(msg)),
+            0,
+            closed,
+        )))
+    }
+
+    /// Consumer and offset to commit for a polled message, or the last polled one for NULL.
+    unsafe fn commit_offset(
+        &self,
+        msg: *const WS_RES,
+    ) -> WsResult<(Arc<Consumer>, Option<Offset>)> {
+        let state = self.state();
+        let consumer = state.consumer.clone();
+        if msg.is_null() {
+            return Ok((consumer, state.last.clone()));
+        }
+        if handle_tag(msg) != Some(HANDLE_MAYBE_ERROR) {
+            return Err(WsError::new(
+                WS_ERROR_NO::NULL_POINTER.as_code(),
+                "WS_RES is invalid",
+            ));
+        }
+        match (*(msg as *const WsMaybeError<WsResultSet>)).safe_deref() {
+            Some(WsResultSet {
+                rs: ResultSource::Tmq(msg),
+                closed,
+                ..
+            }) if Arc::ptr_eq(closed, &state.closed) => Ok((consumer, Some(msg.offset.clone()))),
+            _ => Err(WsError::new(
+                TMQ_INVALID_MSG,
+                "message is not polled by this consumer",
+            )),
+        }
+    }
+
+    unsafe fn commit_sync(&self, msg: *const WS_RES) -> WsResult<()> {
+        if let (consumer, Some(offset)) = self.commit_offset(msg)? {
+            consumer.commit(offset)?;
+        }
+        Ok(())
+    }
+
+    /// Wake up the blocked polls and commit the last polled message if auto commit enabled,
+    /// the connection is closed when the last reference is dropped.
+    fn close(&self) -> WsResult<()> {
+        self.closing.send_replace(true);
+        let (consumer, last) = {
+            let mut state = self.state();
+            (state.consumer.clone(), state.last.take())
+        };
+        if let Some(offset) = last.filter(|_| self.auto_commit) {
+            consumer.commit(offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl WsTmq {
+    /// The consumer behind a checked `ws_tmq_t` pointer, cloned so the handle is not borrowed
+    /// while the call is in flight.
+    unsafe fn shared(tmq: *const ws_tmq_t) -> Arc<WsTmqShared> {
+        (*(tmq as *const WsTmq)).shared.clone()
+    }
+}
+
+impl Drop for WsTmq {
+    fn drop(&mut self) {
+        self.shared.closing.send_replace(true);
+        self.shared.state().closed.store(true, Ordering::SeqCst);
+    }
+}
+
+/// A polled message behind a `WS_RES` pointer, meta messages have no data blocks.
+pub(crate) struct WsTmqMessage {
+    offset: Offset,
+    meta: Option<Meta>,
+    data: Option<Data>,
+    fields: Vec<Field>,
+    precision: Precision,
+    /// First block fetched when polled, so fields are known before fetching.
+    first: Option<Block>,
+    topic: CString,
+    db: CString,
+}
+
+impl Debug for WsTmqMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WsTmqMessage")
+            .field("topic", &self.offset.topic())
+            .field("vgroup_id", &self.offset.vgroup_id())
+            .field("fields", &self.fields)
+            .finish()
+    }
+}
+
+impl WsTmqMessage {
+    pub(crate) fn req_id(&self) -> u64 {
+        self.offset.req_id()
+    }
+
+    fn new(offset: Offset, meta: Option<Meta>, data: Option<Data>) -> WsResult<Self> {
+        let topic = CString::new(offset.topic()).unwrap_or_default();
+        let db = CString::new(offset.database()).unwrap_or_default();
+        let mut msg = Self {
+            offset,
+            meta,
+            data,
+            topic,
+            db,
+            fields: Vec::new(),
+            precision: Precision::Millisecond,
+            first: None,
+        };
+        msg.first = msg.fetch_raw_block()?;
+        Ok(msg)
+    }
+
+    pub(crate) fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    pub(crate) fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub(crate) fn fetch_raw_block(&mut self) -> Result<Option<Block>, Error> {
+        block_in_place_or_global(self.fetch_raw_block_async())
+    }
+
+    pub(crate) async fn fetch_raw_block_async(&mut self) -> Result<Option<Block>, Error> {
+        if let Some(block) = self.first.take() {
+            return Ok(Some(block));
+        }
+        let Some(data) = self.data.as_ref() else {
+            return Ok(None);
+        };
+        let block = data
+            .fetch_block()
+            .await
+            .map_err(|err| Error::TaosError(RawError::new(err.errno(), err.errstr())))?;
+        if let Some(block) = block.as_ref() {
+            self.fields = block.fields();
+            self.precision = block.precision();
+        }
+        Ok(block)
+    }
+
+    /// Copy of the raw payload of the message, data first if it carries both meta and data.
+    fn raw_data(&self) -> Result<(Vec<u8>, u16), Error> {
+        let raw = block_in_place_or_global(async {
+            match (&self.meta, &self.data) {
+                (_, Some(data)) => <Data as IsAsyncData>::as_raw_data(data).await.map(Some),
+                (Some(meta), None) => <Meta as IsAsyncMeta>::as_raw_meta(meta)
+                    .await
+                    .map(|meta| Some(RawData::from(meta.as_bytes().into_owned()))),
+                (None, None) => Ok(None),
+            }
+        })
+        .map_err(|err| Error::TaosError(RawError::new(err.errno(), err.errstr())))?;
+        let Some(raw) = raw else {
+            return Ok((Vec::new(), 0));
+        };
+        let bytes =
+            unsafe { std::slice::from_raw_parts(raw.raw() as *const u8, raw.raw_len() as _) };
+        Ok((bytes.to_vec(), raw.raw_type()))
+    }
+}
+
+/// Raw payload of a TMQ message, like `tmq_raw_data`, filled by `ws_tmq_get_raw`.
+///
+/// The buffer is owned by the struct, free it with `ws_free_raw`.
+#[repr(C)]
+#[derive(Debug)]
+#[allow(non_camel_case_types)]
+pub struct ws_raw_data {
+    pub raw: *mut c_void,
+    pub raw_len: u32,
+    pub raw_type: u16,
+}
+
+/// Topic list behind the `ws_tmq_list_t` pointer.
+#[derive(Debug)]
+#[repr(C)]
+struct WsTmqList {
+    tag: u32,
+    topics: Vec<String>,
+}
+
+/// Create a new TMQ configuration, free it with `ws_tmq_conf_destroy`.
+#[no_mangle]
+pub extern "C" fn ws_tmq_conf_new() -> *mut ws_tmq_conf_t {
+    ffi_guard(|| Box::into_raw(Box::new(WsTmqConf::new())) as _)
+}
+
+/// Same to tmq_conf_set, set a consumer property.
+///
+/// Supported keys are `group.id`, `client.id`, `auto.offset.reset` (earliest, latest or none),
+/// `enable.auto.commit` (true or false), `auto.commit.interval.ms` and the connection keys
+/// `td.connect.ip`, `td.connect.user`, `td.connect.pass` and `td.connect.port`.
+///
+/// Returns `WS_TMQ_CONF_UNKNOWN` for unknown keys and `WS_TMQ_CONF_INVALID` for invalid values,
+/// the reason could be got by `ws_last_errstr`.
+///
+/// # Safety
+///
+/// `conf` must be NULL or a live configuration, `key` and `value` must be NULL or
+/// NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_conf_set(
+    conf: *mut ws_tmq_conf_t,
+    key: *const c_char,
+    value: *const c_char,
+) -> ws_tmq_conf_res_t {
+    ffi_guard(|| {
+        if handle_tag(conf) != Some(HANDLE_TMQ_CONF) {
+            null_pointer_error("ws_tmq_conf_t is null or invalid");
+            return ws_tmq_conf_res_t::WS_TMQ_CONF_INVALID;
+        }
+        let conf = &mut *(conf as *mut WsTmqConf);
+        let (key, value) = match (opt_c_str(key), opt_c_str(value)) {
+            (Ok(Some(key)), Ok(Some(value))) => (key, value),
+            (Ok(None), _) | (_, Ok(None)) => {
+                null_pointer_error("key or value is null");
+                return ws_tmq_conf_res_t::WS_TMQ_CONF_INVALID;
+            }
+            (Err(err), _) | (_, Err(err)) => {
+                set_c_error(&err);
+                return ws_tmq_conf_res_t::WS_TMQ_CONF_INVALID;
+            }
+        };
+        match conf.set(key, value) {
+            Ok(()) => ws_tmq_conf_res_t::WS_TMQ_CONF_OK,
+            Err(res) => {
+                let message = if res == ws_tmq_conf_res_t::WS_TMQ_CONF_UNKNOWN {
+                    format!("unknown tmq config key {key}")
+                } else {
+                    format!("invalid value {value:?} for tmq config {key}")
+                };
+                set_c_error(&WsError::new(Code::Failed, &message));
+                res
+            }
+        }
+    })
+}
+
+/// Same to tmq_conf_destroy, no-op for NULL or an already destroyed configuration.
+///
+/// # Safety
+///
+/// `conf` must be NULL or a configuration from `ws_tmq_conf_new`, and must not be used after it.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_conf_destroy(conf: *mut ws_tmq_conf_t) {
+    ffi_guard(|| {
+        if conf.is_null() {
+            return;
+        }
+        if handle_tag(conf) != Some(HANDLE_TMQ_CONF) {
+            log::warn!("destroy an invalid or already destroyed tmq config {conf:p}, ignored");
+            return;
+        }
+        clear_handle_tag(conf);
+        let _ = Box::from_raw(conf as *mut WsTmqConf);
+    })
+}
+
+unsafe fn tmq_consumer_new(conf: *mut ws_tmq_conf_t) -> WsResult<WsTmq> {
+    if handle_tag(conf) != Some(HANDLE_TMQ_CONF) {
+        return Err(WsError::new(
+            WS_ERROR_NO::NULL_POINTER.as_code(),
+            "ws_tmq_conf_t is null or invalid",
+        ));
+    }
+    let conf = &*(conf as *const WsTmqConf);
+    let builder = TmqBuilder::new(&conf.dsn)?;
+    let consumer = Arc::new(builder.build()?);
+    let auto_commit = conf
+        .dsn
+        .params
+        .get("enable.auto.commit")
+        .is_none_or(|v| v != "false");
+    Ok(WsTmq {
+        tag: HANDLE_TMQ,
+        shared: Arc::new(WsTmqShared {
+            builder,
+            auto_commit,
+            closing: watch::channel(false).0,
+            state: std::sync::Mutex::new(WsTmqState {
+                consumer,
+                subscribed: false,
+                closed: Arc::new(AtomicBool::new(false)),
+                last: None,
+            }),
+        }),
+    })
+}
+
+/// Same to tmq_consumer_new, create a consumer with the configuration.
+///
+/// The websocket connection to `/rest/tmq` is established here, the subscription is made by
+/// `ws_tmq_subscribe` later. On failure NULL is returned and the reason is written into `errstr`
+/// of `errstr_len` bytes, if it's not NULL.
+///
+/// # Safety
+///
+/// `conf` must be NULL or a live configuration, `errstr` must be NULL or writable for
+/// `errstr_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_consumer_new(
+    conf: *mut ws_tmq_conf_t,
+    errstr: *mut c_char,
+    errstr_len: c_int,
+) -> *mut ws_tmq_t {
+    ffi_guard(|| match tmq_consumer_new(conf) {
+        Ok(tmq) => Box::into_raw(Box::new(tmq)) as _,
+        Err(err) => {
+            set_c_error(&err);
+            if !errstr.is_null() && errstr_len > 0 {
+                write_c_str(errstr as _, errstr_len as _, err.message.to_bytes());
+            }
+            std::ptr::null_mut()
+        }
+    })
+}
+
+/// Same to tmq_consumer_close, close the consumer and free it.
+///
+/// The last polled message is committed before closing when `enable.auto.commit` is not false,
+/// and polls blocked in other threads return NULL with a "consumer closed" error. Returns 0 on
+/// success, `0xE008` for NULL or the commit error code, the consumer is freed anyway. Closing an
+/// invalid or already closed consumer is ignored.
+///
+/// # Safety
+///
+/// `tmq` must be NULL or a consumer from `ws_tmq_consumer_new`, and must not be used after it.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_consumer_close(tmq: *mut ws_tmq_t) -> i32 {
+    ffi_guard(|| {
+        if tmq.is_null() {
+            return null_pointer_error("ws_tmq_t is null");
+        }
+        if handle_tag(tmq) != Some(HANDLE_TMQ) {
+            log::warn!("close an invalid or already closed consumer {tmq:p}, ignored");
+            return 0;
+        }
+        clear_handle_tag(tmq);
+        let tmq = Box::from_raw(tmq as *mut WsTmq);
+        match tmq.shared.close() {
+            Ok(()) => 0,
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
+}
+
+/// Same to tmq_unsubscribe, drop current subscription and keep the consumer to subscribe again.
+///
+/// The subscription is dropped by closing current connection and a new one is made for the
+/// next subscription. Returns 0 on success.
+///
+/// # Safety
+///
+/// `tmq` must be NULL or a live consumer.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_unsubscribe(tmq: *mut ws_tmq_t) -> i32 {
+    ffi_guard(|| {
+        if handle_tag(tmq) != Some(HANDLE_TMQ) {
+            return null_pointer_error("ws_tmq_t is null or invalid");
+        }
+        match WsTmq::shared(tmq).unsubscribe() {
+            Ok(()) => 0,
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
+}
+
+/// Same to tmq_consumer_poll, wait up to `timeout_ms` milliseconds for next message.
+///
+/// Negative timeout waits forever. Returns NULL when no message arrived in time or polling
+/// failed, in which case `ws_last_errno` is non-zero. The message is a result set readable by
+/// `ws_fetch_block`, `ws_fetch_fields` and others, and should be freed by `ws_free_result`.
+///
+/// # Safety
+///
+/// `tmq` must be NULL or a live consumer.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_consumer_poll(tmq: *mut ws_tmq_t, timeout_ms: i64) -> *mut WS_RES {
+    ffi_guard(|| {
+        if handle_tag(tmq) != Some(HANDLE_TMQ) {
+            null_pointer_error("ws_tmq_t is null or invalid");
+            return std::ptr::null_mut();
+        }
+        let tmq = WsTmq::shared(tmq);
+        clear_c_error();
+        match tmq.poll(timeout_ms) {
+            Ok(Some(rs)) => {
+                let rs: WsMaybeError<WsResultSet> = WsResult::Ok(rs).into();
+                Box::into_raw(Box::new(rs)) as _
+            }
+            Ok(None) => std::ptr::null_mut(),
+            Err(err) => {
+                set_c_error(&err);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// The TMQ message behind a `WS_RES`, None for NULL, failed or ordinary query results.
+unsafe fn tmq_message<'a>(rs: *const WS_RES) -> Option<&'a WsTmqMessage> {
+    if handle_tag(rs) != Some(HANDLE_MAYBE_ERROR) {
+        return None;
+    }
+    match (*(rs as *const WsMaybeError<WsResultSet>)).safe_deref() {
+        Some(WsResultSet {
+            rs: ResultSource::Tmq(msg),
+            ..
+        }) => Some(msg),
+        _ => None,
+    }
+}
+
+/// Same to tmq_get_topic_name, NULL if `rs` is not a TMQ message.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, the name is owned by it.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_get_topic_name(rs: *const WS_RES) -> *const c_char {
+    ffi_guard(|| match tmq_message(rs) {
+        Some(msg) => msg.topic.as_ptr(),
+        None => std::ptr::null(),
+    })
+}
+
+/// Same to tmq_get_db_name, NULL if `rs` is not a TMQ message.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, the name is owned by it.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_get_db_name(rs: *const WS_RES) -> *const c_char {
+    ffi_guard(|| match tmq_message(rs) {
+        Some(msg) => msg.db.as_ptr(),
+        None => std::ptr::null(),
+    })
+}
+
+/// Same to tmq_get_vgroup_id, -1 if `rs` is not a TMQ message.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_get_vgroup_id(rs: *const WS_RES) -> i32 {
+    ffi_guard(|| match tmq_message(rs) {
+        Some(msg) => msg.offset.vgroup_id(),
+        None => -1,
+    })
+}
+
+/// Same to tmq_get_raw, copy the untouched raw meta or data payload of a polled message to `raw`.
+///
+/// The copy could be used after the message is freed, and must be freed by `ws_free_raw`.
+/// Returns 0 on success, or `0x4000` if `rs` is not a TMQ message.
+///
+/// # Safety
+///
+/// `rs` must be NULL or a live result set, `raw` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_get_raw(rs: *const WS_RES, raw: *mut ws_raw_data) -> i32 {
+    ffi_guard(|| {
+        if raw.is_null() {
+            return null_pointer_error("ws_raw_data is null");
+        }
+        let Some(msg) = tmq_message(rs) else {
+            set_c_error(&WsError::new(TMQ_INVALID_MSG, "not a TMQ message"));
+            return TMQ_INVALID_MSG.into();
+        };
+        match msg.raw_data() {
+            Ok((bytes, raw_type)) => {
+                let raw_len = bytes.len() as u32;
+                *raw = ws_raw_data {
+                    raw: Box::into_raw(bytes.into_boxed_slice()) as *mut u8 as _,
+                    raw_len,
+                    raw_type,
+                };
+                0
+            }
+            Err(err) => {
+                let err = WsError::from(err);
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
+}
+
+/// Same to tmq_free_raw, free the buffer copied by `ws_tmq_get_raw` and reset `raw`.
+///
+/// # Safety
+///
+/// `raw` must be NULL or filled by `ws_tmq_get_raw`, its buffer must not be used after it.
+#[no_mangle]
+pub unsafe extern "C" fn ws_free_raw(raw: *mut ws_raw_data) {
+    ffi_guard(|| {
+        let Some(raw) = raw.as_mut() else {
+            return;
+        };
+        if !raw.raw.is_null() {
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                raw.raw as *mut u8,
+                raw.raw_len as usize,
+            )));
+        }
+        raw.raw = std::ptr::null_mut();
+        raw.raw_len = 0;
+        raw.raw_type = 0;
+    })
+}
+
+/// Same to tmq_commit_sync, commit offset of the message and wait for the acknowledgement.
+///
+/// A NULL message commits offset of the last polled message. Returns 0 on success, or `0x4000`
+/// if the message was not polled by this consumer.
+///
+/// # Safety
+///
+/// `tmq` must be NULL or a live consumer, `msg` must be NULL or a live message.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_commit_sync(tmq: *mut ws_tmq_t, msg: *const WS_RES) -> i32 {
+    ffi_guard(|| {
+        if handle_tag(tmq) != Some(HANDLE_TMQ) {
+            return null_pointer_error("ws_tmq_t is null or invalid");
+        }
+        match WsTmq::shared(tmq).commit_sync(msg) {
+            Ok(()) => 0,
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
+}
+
+/// Callback of `ws_tmq_commit_async`, `code` is 0 on success.
+#[allow(non_camel_case_types)]
+pub type ws_tmq_commit_cb = unsafe extern "C" fn(tmq: *mut ws_tmq_t, code: i32, param: *mut c_void);
+
+/// Same to tmq_commit_async, commit offset of the message in background.
+///
+/// The callback is invoked with the result code on the runtime thread, or on the calling thread
+/// when the arguments are invalid.
+///
+/// # Safety
+///
+/// `tmq` must be NULL or a live consumer, `msg` must be NULL or a live message. `param` is
+/// passed to the callback as is.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_commit_async(
+    tmq: *mut ws_tmq_t,
+    msg: *const WS_RES,
+    cb: Option<ws_tmq_commit_cb>,
+    param: *mut c_void,
+) {
+    ffi_guard(|| {
+        let callback = |code: i32| {
+            if let Some(cb) = cb {
+                cb(tmq, code, param);
+            }
+        };
+        if handle_tag(tmq) != Some(HANDLE_TMQ) {
+            return callback(null_pointer_error("ws_tmq_t is null or invalid"));
+        }
+        let (consumer, offset) = match WsTmq::shared(tmq).commit_offset(msg) {
+            Ok((consumer, Some(offset))) => (consumer, offset),
+            Ok((_, None)) => return callback(0),
+            Err(err) => {
+                set_c_error(&err);
+                return callback(err.code.into());
+            }
+        };
+        let (tmq, param) = (tmq as usize, param as usize);
+        taos_query::global_tokio_runtime().spawn(async move {
+            let code =
+                match <Consumer as taos_query::tmq::AsAsyncConsumer>::commit(&consumer, offset)
+                    .await
+                {
+                    Ok(()) => 0,
+                    Err(err) => err.errno().into(),
+                };
+            if let Some(cb) = cb {
+                cb(tmq as *mut ws_tmq_t, code, param as *mut c_void);
+            }
+        });
+    })
+}
+
+/// Same to tmq_list_new, create an empty topic list.
+#[no_mangle]
+pub extern "C" fn ws_tmq_list_new() -> *mut ws_tmq_list_t {
+    ffi_guard(|| {
+        Box::into_raw(Box::new(WsTmqList {
+            tag: HANDLE_TMQ_LIST,
+            topics: Vec::new(),
+        })) as _
+    })
+}
+
+/// Same to tmq_list_append, append a topic to the list. Returns 0 on success.
+///
+/// # Safety
+///
+/// `list` must be NULL or a live list, `topic` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_list_append(list: *mut ws_tmq_list_t, topic: *const c_char) -> i32 {
+    ffi_guard(|| {
+        if handle_tag(list) != Some(HANDLE_TMQ_LIST) {
+            return null_pointer_error("ws_tmq_list_t is null or invalid");
+        }
+        let list = &mut *(list as *mut WsTmqList);
+        match opt_c_str(topic) {
+            Ok(Some(topic)) if !topic.is_empty() => {
+                list.topics.push(topic.to_string());
+                0
+            }
+            Ok(_) => null_pointer_error("topic is null or empty"),
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
+}
+
+/// Same to tmq_list_destroy, no-op for NULL or an already destroyed list.
+///
+/// # Safety
+///
+/// `list` must be NULL or a list from `ws_tmq_list_new`, and must not be used after it.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_list_destroy(list: *mut ws_tmq_list_t) {
+    ffi_guard(|| {
+        if list.is_null() {
+            return;
+        }
+        if handle_tag(list) != Some(HANDLE_TMQ_LIST) {
+            log::warn!("destroy an invalid or already destroyed tmq list {list:p}, ignored");
+            return;
+        }
+        clear_handle_tag(list);
+        let _ = Box::from_raw(list as *mut WsTmqList);
+    })
+}
+
+/// Same to tmq_subscribe, subscribe all topics in the list in one request.
+///
+/// A new subscription replaces the previous one, and an empty list unsubscribes. When it fails
+/// with the server error code, the consumer is still usable to subscribe again.
+///
+/// # Safety
+///
+/// `tmq` must be NULL or a live consumer, `list` must be NULL or a live list.
+#[no_mangle]
+pub unsafe extern "C" fn ws_tmq_subscribe(tmq: *mut ws_tmq_t, list: *const ws_tmq_list_t) -> i32 {
+    ffi_guard(|| {
+        if handle_tag(tmq) != Some(HANDLE_TMQ) {
+            return null_pointer_error("ws_tmq_t is null or invalid");
+        }
+        if handle_tag(list) != Some(HANDLE_TMQ_LIST) {
+            return null_pointer_error("ws_tmq_list_t is null or invalid");
+        }
+        let topics = (*(list as *const WsTmqList)).topics.clone();
+        match WsTmq::shared(tmq).subscribe(topics) {
+            Ok(()) => 0,
+            Err(err) => {
+                set_c_error(&err);
+                err.code.into()
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::exec_sql;
+
+    #[test]
+    fn tmq_conf_set() {
+        use ws_tmq_conf_res_t::*;
+        unsafe {
+            let conf = ws_tmq_conf_new();
+            macro_rules! set {
+                ($k:literal, $v:literal) => {
+                    ws_tmq_conf_set(
+                        conf,
+                        concat!($k, "\0").as_ptr() as _,
+                        concat!($v, "\0").as_ptr() as _,
+                    )
+                };
+            }
+            assert_eq!(set!("group.id", "g1"), WS_TMQ_CONF_OK);
+            assert_eq!(set!("client.id", "c1"), WS_TMQ_CONF_OK);
+            assert_eq!(set!("auto.offset.reset", "earliest"), WS_TMQ_CONF_OK);
+            assert_eq!(set!("enable.auto.commit", "false"), WS_TMQ_CONF_OK);
+            assert_eq!(set!("auto.commit.interval.ms", "1000"), WS_TMQ_CONF_OK);
+            assert_eq!(set!("td.connect.ip", "localhost"), WS_TMQ_CONF_OK);
+            assert_eq!(set!("td.connect.port", "6041"), WS_TMQ_CONF_OK);
+            assert_eq!(set!("td.connect.user", "root"), WS_TMQ_CONF_OK);
+            assert_eq!(set!("td.connect.pass", "taosdata"), WS_TMQ_CONF_OK);
+
+            assert_eq!(set!("group.idd", "g1"), WS_TMQ_CONF_UNKNOWN);
+            let err = CStr::from_ptr(ws_last_errstr()).to_str().unwrap();
+            assert!(err.contains("group.idd"), "{err}");
+            assert_eq!(set!("auto.offset.reset", "smallest"), WS_TMQ_CONF_INVALID);
+            assert_eq!(set!("enable.auto.commit", "yes"), WS_TMQ_CONF_INVALID);
+            assert_eq!(set!("td.connect.port", "0"), WS_TMQ_CONF_INVALID);
+            assert_eq!(
+                ws_tmq_conf_set(conf, std::ptr::null(), std::ptr::null()),
+                WS_TMQ_CONF_INVALID
+            );
+
+            let dsn = &(*(conf as *const WsTmqConf)).dsn;
+            assert_eq!(dsn.addresses, [Address::new("localhost", 6041)]);
+            assert_eq!(dsn.username.as_deref(), Some("root"));
+            assert_eq!(dsn.params["auto.offset.reset"], "earliest");
+            TmqBuilder::new(dsn).unwrap();
+
+            ws_tmq_conf_destroy(conf);
+            ws_tmq_conf_destroy(conf);
+            ws_tmq_conf_destroy(std::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn tmq_consumer_new_error() {
+        unsafe {
+            let mut errstr = [0 as c_char; 128];
+            let tmq = ws_tmq_consumer_new(std::ptr::null_mut(), errstr.as_mut_ptr(), 128);
+            assert!(tmq.is_null());
+            let err = CStr::from_ptr(errstr.as_ptr()).to_str().unwrap();
+            assert!(err.contains("ws_tmq_conf_t"), "{err}");
+            assert_eq!(ws_last_errno(), WS_ERROR_NO::NULL_POINTER as i32);
+
+            // group.id is required.
+            let conf = ws_tmq_conf_new();
+            let tmq = ws_tmq_consumer_new(conf, errstr.as_mut_ptr(), 8);
+            assert!(tmq.is_null());
+            assert_eq!(CStr::from_ptr(errstr.as_ptr()).to_bytes().len(), 7);
+            assert!(ws_tmq_consumer_new(conf, std::ptr::null_mut(), 0).is_null());
+            ws_tmq_conf_destroy(conf);
+
+            assert_eq!(
+                ws_tmq_consumer_close(std::ptr::null_mut()),
+                WS_ERROR_NO::NULL_POINTER as i32
+            );
+            assert_eq!(
+                ws_tmq_unsubscribe(std::ptr::null_mut()),
+                WS_ERROR_NO::NULL_POINTER as i32
+            );
+        }
+    }
+
+    #[test]
+    fn tmq_list() {
+        unsafe {
+            let list = ws_tmq_list_new();
+            assert_eq!(ws_tmq_list_append(list, c"topic1".as_ptr() as _), 0);
+            assert_eq!(ws_tmq_list_append(list, c"topic2".as_ptr() as _), 0);
+            let code = ws_tmq_list_append(list, std::ptr::null());
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+            assert_eq!((*(list as *const WsTmqList)).topics, ["topic1", "topic2"]);
+            ws_tmq_list_destroy(list);
+            ws_tmq_list_destroy(list);
+            let code = ws_tmq_list_append(list, c"topic3".as_ptr() as _);
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+        }
+    }
+
+    #[test]
+    fn tmq_subscribe() {
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+            exec_sql(taos, b"drop topic if exists ws_tmq_sub_t1\0");
+            exec_sql(taos, b"drop topic if exists ws_tmq_sub_t2\0");
+            exec_sql(taos, b"drop database if exists ws_tmq_sub\0");
+            exec_sql(taos, b"create database ws_tmq_sub\0");
+            exec_sql(taos, b"create table ws_tmq_sub.t1 (ts timestamp, v int)\0");
+            exec_sql(
+                taos,
+                b"create topic ws_tmq_sub_t1 as select * from ws_tmq_sub.t1\0",
+            );
+            exec_sql(taos, b"create topic ws_tmq_sub_t2 as database ws_tmq_sub\0");
+
+            let conf = ws_tmq_conf_new();
+            ws_tmq_conf_set(conf, c"group.id".as_ptr() as _, c"g1".as_ptr() as _);
+            let mut errstr = [0 as c_char; 256];
+            let tmq = ws_tmq_consumer_new(conf, errstr.as_mut_ptr(), 256);
+            ws_tmq_conf_destroy(conf);
+            assert!(!tmq.is_null(), "{:?}", CStr::from_ptr(errstr.as_ptr()));
+
+            // not exist topic fails, retry with the right one.
+            let list = ws_tmq_list_new();
+            ws_tmq_list_append(list, c"ws_tmq_sub_not_exist".as_ptr() as _);
+            assert_ne!(ws_tmq_subscribe(tmq, list), 0);
+            ws_tmq_list_destroy(list);
+
+            let list = ws_tmq_list_new();
+            ws_tmq_list_append(list, c"ws_tmq_sub_t1".as_ptr() as _);
+            ws_tmq_list_append(list, c"ws_tmq_sub_t2".as_ptr() as _);
+            let code = ws_tmq_subscribe(tmq, list);
+            assert_eq!(code, 0, "{:?}", CStr::from_ptr(ws_last_errstr()));
+            ws_tmq_list_destroy(list);
+
+            // replace the subscription.
+            let list = ws_tmq_list_new();
+            ws_tmq_list_append(list, c"ws_tmq_sub_t2".as_ptr() as _);
+            assert_eq!(ws_tmq_subscribe(tmq, list), 0);
+
+            // subscribe again after unsubscribe.
+            assert_eq!(ws_tmq_unsubscribe(tmq), 0);
+            assert_eq!(ws_tmq_unsubscribe(tmq), 0);
+            assert_eq!(ws_tmq_subscribe(tmq, list), 0);
+            ws_tmq_list_destroy(list);
+
+            // close wakes up a blocked poll.
+            let poll = std::thread::spawn({
+                let tmq = tmq as usize;
+                move || {
+                    let msg = ws_tmq_consumer_poll(tmq as _, -1);
+                    (msg.is_null(), ws_last_errno())
+                }
+            });
+            std::thread::sleep(std::time::Duration::from_millis(500));
+
+            assert_eq!(ws_tmq_consumer_close(tmq), 0);
+            let (is_null, code) = poll.join().unwrap();
+            assert!(is_null);
+            assert_eq!(code, WS_ERROR_NO::CONN_CLOSED as i32);
+
+            exec_sql(taos, b"drop topic ws_tmq_sub_t1\0");
+            exec_sql(taos, b"drop topic ws_tmq_sub_t2\0");
+            exec_sql(taos, b"drop database ws_tmq_sub\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn tmq_commit_invalid() {
+        unsafe extern "C" fn cb(_: *mut ws_tmq_t, code: i32, param: *mut c_void) {
+            *(param as *mut i32) = code;
+        }
+        unsafe {
+            let code = ws_tmq_commit_sync(std::ptr::null_mut(), std::ptr::null());
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+            let mut code = 0;
+            let param = &mut code as *mut i32 as *mut c_void;
+            ws_tmq_commit_async(std::ptr::null_mut(), std::ptr::null(), Some(cb), param);
+            assert_eq!(code, WS_ERROR_NO::NULL_POINTER as i32);
+        }
+    }
+
+    #[test]
+    fn tmq_message_metadata_invalid() {
+        unsafe {
+            assert!(ws_tmq_get_topic_name(std::ptr::null()).is_null());
+            assert!(ws_tmq_get_db_name(std::ptr::null()).is_null());
+            assert_eq!(ws_tmq_get_vgroup_id(std::ptr::null()), -1);
+
+            let mut raw = ws_raw_data {
+                raw: std::ptr::null_mut(),
+                raw_len: 0,
+                raw_type: 0,
+            };
+            assert_eq!(ws_tmq_get_raw(std::ptr::null(), &mut raw), 0x4000);
+            assert!(raw.raw.is_null());
+            ws_free_raw(&mut raw);
+            ws_free_raw(std::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn tmq_commit() {
+        unsafe extern "C" fn cb(_: *mut ws_tmq_t, code: i32, param: *mut c_void) {
+            let sender = &*(param as *const std::sync::mpsc::Sender<i32>);
+            sender.send(code).unwrap();
+        }
+        unsafe fn consumer(topic: &[u8]) -> *mut ws_tmq_t {
+            let conf = ws_tmq_conf_new();
+            let set = |k: &[u8], v: &[u8]| ws_tmq_conf_set(conf, k.as_ptr() as _, v.as_ptr() as _);
+            set(b"group.id\0", b"ws_tmq_commit\0");
+            set(b"auto.offset.reset\0", b"earliest\0");
+            set(b"enable.auto.commit\0", b"false\0");
+            let tmq = ws_tmq_consumer_new(conf, std::ptr::null_mut(), 0);
+            ws_tmq_conf_destroy(conf);
+            assert!(!tmq.is_null());
+            let list = ws_tmq_list_new();
+            ws_tmq_list_append(list, topic.as_ptr() as _);
+            assert_eq!(ws_tmq_subscribe(tmq, list), 0);
+            ws_tmq_list_destroy(list);
+            tmq
+        }
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+            exec_sql(taos, b"drop topic if exists ws_tmq_commit\0");
+            exec_sql(taos, b"drop database if exists ws_tmq_commit\0");
+            exec_sql(taos, b"create database ws_tmq_commit\0");
+            exec_sql(
+                taos,
+                b"create table ws_tmq_commit.t1 (ts timestamp, v int)\0",
+            );
+            exec_sql(
+                taos,
+                b"insert into ws_tmq_commit.t1 values(now, 1)(now+1s, 2)\0",
+            );
+            exec_sql(
+                taos,
+                b"create topic ws_tmq_commit as select * from ws_tmq_commit.t1\0",
+            );
+
+            let tmq = consumer(b"ws_tmq_commit\0");
+            let other = consumer(b"ws_tmq_commit\0");
+            let (sender, receiver) = std::sync::mpsc::channel::<i32>();
+            let mut messages = 0;
+            loop {
+                let msg = ws_tmq_consumer_poll(tmq, 1000);
+                if msg.is_null() {
+                    break;
+                }
+                messages += 1;
+                assert_eq!(ws_tmq_commit_sync(other, msg), 0x4000);
+                let param = &sender as *const _ as *mut c_void;
+                ws_tmq_commit_async(tmq, msg, Some(cb), param);
+                assert_eq!(receiver.recv().unwrap(), 0);
+                assert_eq!(ws_tmq_commit_sync(tmq, msg), 0);
+                ws_free_result(msg);
+            }
+            assert!(messages > 0);
+            assert_eq!(ws_tmq_commit_sync(tmq, std::ptr::null()), 0);
+            ws_tmq_consumer_close(other);
+            ws_tmq_consumer_close(tmq);
+
+            // committed data is not received again by the group.
+            let tmq = consumer(b"ws_tmq_commit\0");
+            let msg = ws_tmq_consumer_poll(tmq, 3000);
+            assert!(msg.is_null());
+            ws_tmq_consumer_close(tmq);
+
+            exec_sql(taos, b"drop topic ws_tmq_commit\0");
+            exec_sql(taos, b"drop database ws_tmq_commit\0");
+            ws_close(taos);
+        }
+    }
+
+    #[test]
+    fn tmq_consumer_poll() {
+        unsafe {
+            let taos = ws_connect_with_dsn(b"ws://localhost:6041\0" as *const u8 as _);
+            assert!(!taos.is_null());
+            exec_sql(taos, b"drop topic if exists ws_tmq_poll\0");
+            exec_sql(taos, b"drop database if exists ws_tmq_poll\0");
+            exec_sql(taos, b"create database ws_tmq_poll\0");
+            exec_sql(taos, b"create table ws_tmq_poll.t1 (ts timestamp, v int)\0");
+            exec_sql(
+                taos,
+                b"insert into ws_tmq_poll.t1 values(now, 1)(now+1s, 2)\0",
+            );
+            exec_sql(
+                taos,
+                b"create topic ws_tmq_poll as select * from ws_tmq_poll.t1\0",
+            );
+
+            let conf = ws_tmq_conf_new();
+            ws_tmq_conf_set(conf, c"group.id".as_ptr() as _, c"g1".as_ptr() as _);
+            ws_tmq_conf_set(
+                conf,
+                c"auto.offset.reset".as_ptr() as _,
+                c"earliest".as_ptr() as _,
+            );
+            let tmq = ws_tmq_consumer_new(conf, std::ptr::null_mut(), 0);
+            ws_tmq_conf_destroy(conf);
+            assert!(!tmq.is_null());
+            let list = ws_tmq_list_new();
+            ws_tmq_list_append(list, c"ws_tmq_poll".as_ptr() as _);
+            assert_eq!(ws_tmq_subscribe(tmq, list), 0);
+            ws_tmq_list_destroy(list);
+
+            let mut values = Vec::new();
+            loop {
+                let msg = ws_tmq_consumer_poll(tmq, 1000);
+                if msg.is_null() {
+                    assert_eq!(ws_last_errno(), 0);
+                    break;
+                }
+                assert_eq!(ws_field_count(msg), 2);
+                let topic = CStr::from_ptr(ws_tmq_get_topic_name(msg));
+                assert_eq!(topic.to_str().unwrap(), "ws_tmq_poll");
+                let db = CStr::from_ptr(ws_tmq_get_db_name(msg));
+                assert_eq!(db.to_str().unwrap(), "ws_tmq_poll");
+                assert!(ws_tmq_get_vgroup_id(msg) >= 0);
+                assert_ne!(ws_get_req_id(msg), 0);
+                let mut raw = ws_raw_data {
+                    raw: std::ptr::null_mut(),
+                    raw_len: 0,
+                    raw_type: 0,
+                };
+                assert_eq!(ws_tmq_get_raw(msg, &mut raw), 0);
+                assert!(!raw.raw.is_null());
+                assert!(raw.raw_len > 0);
+                let mut block = std::ptr::null();
+                let mut rows = 0;
+                loop {
+                    assert_eq!(ws_fetch_block(msg, &mut block, &mut rows), 0);
+                    if rows == 0 {
+                        break;
+                    }
+                    for row in 0..rows {
+                        let mut ty = 0;
+                        let mut len = 0;
+                        let v = ws_get_value_in_block(msg, row, 1, &mut ty, &mut len);
+                        values.push(*(v as *const i32));
+                    }
+                }
+                ws_free_result(msg);
+                // the raw copy outlives the message.
+                let bytes = std::slice::from_raw_parts(raw.raw as *const u8, raw.raw_len as _);
+                assert_eq!(bytes.len(), raw.raw_len as usize);
+                ws_free_raw(&mut raw);
+                assert!(raw.raw.is_null());
+            }
+            assert_eq!(values, [1, 2]);
+
+            let rs = ws_query(taos, b"select 1\0" as *const u8 as _);
+            assert!(ws_tmq_get_topic_name(rs).is_null());
+            assert!(ws_tmq_get_db_name(rs).is_null());
+            assert_eq!(ws_tmq_get_vgroup_id(rs), -1);
+            ws_free_result(rs);
+
+            assert_eq!(ws_tmq_consumer_close(tmq), 0);
+            exec_sql(taos, b"drop topic ws_tmq_poll\0");
+            exec_sql(taos, b"drop database ws_tmq_poll\0");
+            ws_close(taos);
+        }
+    }
+}
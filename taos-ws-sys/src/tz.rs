@@ -0,0 +1,175 @@
+//! Time zones of connections, used to format timestamps of their result sets.
+//!
+//! A time zone is a fixed offset like `+08:00`, or an IANA name like `Asia/Shanghai` of the
+//! database built in by `chrono-tz`, so zones work the same without a system zoneinfo. The
+//! database has daylight saving transitions until 2037.
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use taos_query::common::{Precision, Timestamp};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) enum WsTimeZone {
+    /// Time zone of the process.
+    #[default]
+    Local,
+    Fixed(FixedOffset),
+    Zone(Tz),
+}
+
+impl WsTimeZone {
+    /// Parse a fixed offset (`Z`, `UTC`, `+08:00`, `-0530`, `UTC+8`) or an IANA zone name.
+    ///
+    /// Signs of offsets are in ISO 8601 sense, so `UTC+8` is 8 hours ahead of UTC.
+    pub(crate) fn parse(tz: &str) -> Result<Self, String> {
+        let tz = tz.trim();
+        if tz.is_empty() {
+            return Err("time zone is empty".to_string());
+        }
+        let offset = tz
+            .strip_prefix("UTC")
+            .or_else(|| tz.strip_prefix("GMT"))
+            .unwrap_or(tz);
+        if offset.is_empty() || offset == "Z" {
+            return Ok(Self::Fixed(FixedOffset::east_opt(0).unwrap()));
+        }
+        if offset.starts_with(['+', '-']) {
+            return parse_fixed_offset(offset)
+                .map(Self::Fixed)
+                .ok_or_else(|| format!("invalid time zone offset: {tz}"));
+        }
+        tz.parse()
+            .map(Self::Zone)
+            .map_err(|_| format!("unknown time zone: {tz}"))
+    }
+
+    /// Convert UTC date time to local date time with its offset.
+    pub(crate) fn local_datetime(&self, utc: &NaiveDateTime) -> DateTime<FixedOffset> {
+        let offset = match self {
+            Self::Local => return Local.from_utc_datetime(utc).fixed_offset(),
+            Self::Fixed(offset) => *offset,
+            Self::Zone(zone) => return zone.from_utc_datetime(utc).fixed_offset(),
+        };
+        offset.from_utc_datetime(utc)
+    }
+
+    /// Format timestamp in RFC3339 with its precision.
+    pub(crate) fn to_rfc3339(&self, ts: Timestamp, use_z: bool) -> String {
+        let precision: Precision = ts.precision();
+        self.local_datetime(&ts.to_naive_datetime())
+            .to_rfc3339_opts(precision.to_seconds_format(), use_z)
+    }
+}
+
+/// Parse `+hh[:mm[:ss]]`, `+hhmm` or `-hh`, hours should be less than 24.
+fn parse_fixed_offset(s: &str) -> Option<FixedOffset> {
+    let (sign, s) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let secs = if s.len() == 4 && !s.contains(':') {
+        parse_hms(&format!("{}:{}", &s[..2], &s[2..]))?
+    } else {
+        parse_hms(s)?
+    };
+    if secs >= 24 * 3600 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * secs)
+}
+
+/// Parse `hh[:mm[:ss]]` to seconds.
+fn parse_hms(s: &str) -> Option<i32> {
+    let mut secs = 0;
+    let mut parts = 0;
+    for (i, part) in s.split(':').enumerate() {
+        if part.is_empty() || part.len() > 3 || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let v: i32 = part.parse().ok()?;
+        if i > 0 && v >= 60 {
+            return None;
+        }
+        secs = secs * 60 + v;
+        parts = i + 1;
+    }
+    match parts {
+        1 => Some(secs * 3600),
+        2 => Some(secs * 60),
+        3 => Some(secs),
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for WsTimeZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local => f.write_str("Local"),
+            Self::Fixed(offset) => write!(f, "{offset}"),
+            Self::Zone(zone) => f.write_str(zone.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> NaiveDateTime {
+        DateTime::parse_from_rfc3339(s).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn fixed_offset() {
+        for (tz, secs) in [
+            ("Z", 0),
+            ("UTC", 0),
+            ("+08:00", 8 * 3600),
+            ("-0530", -(5 * 3600 + 30 * 60)),
+            ("UTC+8", 8 * 3600),
+            ("GMT-03:30", -(3 * 3600 + 30 * 60)),
+        ] {
+            let WsTimeZone::Fixed(offset) = WsTimeZone::parse(tz).unwrap() else {
+                panic!("{tz} should be a fixed offset");
+            };
+            assert_eq!(offset.local_minus_utc(), secs, "{tz}");
+        }
+        for tz in [
+            "",
+            "+24:00",
+            "+08:60",
+            "UTC+",
+            "+8:00:00:00",
+            "../etc/passwd",
+        ] {
+            assert!(WsTimeZone::parse(tz).is_err(), "{tz}");
+        }
+        assert!(WsTimeZone::parse("Mars/Olympus_Mons").is_err());
+
+        let tz = WsTimeZone::parse("+08:00").unwrap();
+        let ts = Timestamp::new(0, Precision::Millisecond);
+        assert_eq!(tz.to_rfc3339(ts, false), "1970-01-01T08:00:00.000+08:00");
+    }
+
+    #[test]
+    fn zone_info() {
+        let tz = WsTimeZone::parse("Europe/Berlin").unwrap();
+        assert_eq!(tz.to_string(), "Europe/Berlin");
+        for (at, offset) in [
+            ("1970-01-01T00:00:00Z", 3600),
+            ("2023-07-01T00:00:00Z", 7200),
+            ("2023-12-01T00:00:00Z", 3600),
+            // the database has transitions until 2037, later times keep the last offset.
+            ("2037-07-01T00:00:00Z", 7200),
+        ] {
+            assert_eq!(
+                tz.local_datetime(&utc(at)).offset().local_minus_utc(),
+                offset,
+                "{at}"
+            );
+        }
+        let tz = WsTimeZone::parse("Asia/Shanghai").unwrap();
+        let ts = Timestamp::new(1_690_000_000_000, Precision::Millisecond);
+        assert_eq!(tz.to_rfc3339(ts, false), "2023-07-22T12:26:40.000+08:00");
+    }
+}
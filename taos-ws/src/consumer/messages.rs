@@ -50,6 +50,8 @@ pub struct TmqInit {
     pub group_id: String,
     pub client_id: Option<String>,
     pub offset_reset: Option<String>,
+    pub auto_commit: Option<String>,
+    pub auto_commit_interval_ms: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
@@ -67,8 +69,9 @@ pub enum TmqSend {
         req_id: ReqId,
         #[serde(flatten)]
         conn: WsConnReq,
+        /// Boxed to keep the other messages small.
         #[serde(flatten)]
-        req: TmqInit,
+        req: Box<TmqInit>,
         topics: Vec<String>,
     },
     Poll {
@@ -7,7 +7,7 @@ use itertools::Itertools;
 use dashmap::DashMap as HashMap;
 
 use taos_query::block_in_place_or_global;
-use taos_query::common::{JsonMeta, RawMeta};
+use taos_query::common::{JsonMeta, RawData, RawMeta};
 use taos_query::prelude::{Code, RawError};
 use taos_query::tmq::{
     AsAsyncConsumer, AsConsumer, IsAsyncData, IsAsyncMeta, IsOffset, MessageSet, SyncOnAsync,
@@ -21,18 +21,18 @@ use taos_query::prelude::tokio;
 use tokio::sync::{oneshot, watch};
 
 use tokio::time;
+use tokio_tungstenite::tungstenite::protocol::Message;
 use tokio_tungstenite::tungstenite::Error as WsError;
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 
-use crate::query::asyn::WS_ERROR_NO;
+use crate::query::asyn::{dial_endpoints, DialError, WS_ERROR_NO};
 use crate::query::infra::{ToMessage, WsConnReq};
+use crate::req_id::ReqIds;
 use crate::TaosBuilder;
 use messages::*;
 
 use std::fmt::Debug;
 use std::result::Result as StdResult;
 use std::str::FromStr;
-use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -43,7 +43,7 @@ type WsTmqAgent = Arc<HashMap<ReqId, oneshot::Sender<StdResult<TmqRecvData, RawE
 
 #[derive(Debug, Clone)]
 struct WsTmqSender {
-    req_id: Arc<AtomicU64>,
+    req_id: ReqIds,
     sender: WsSender,
     queries: WsTmqAgent,
     #[allow(dead_code)]
@@ -52,8 +52,7 @@ struct WsTmqSender {
 
 impl WsTmqSender {
     fn req_id(&self) -> ReqId {
-        self.req_id
-            .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+        self.req_id.next()
     }
     async fn send_recv(&self, msg: TmqSend) -> Result<TmqRecvData> {
         self.send_recv_timeout(msg, Duration::MAX).await
@@ -94,7 +93,15 @@ impl TBuilder for TmqBuilder {
     type Error = Error;
 
     fn available_params() -> &'static [&'static str] {
-        &["token", "timeout", "group.id", "client.id"]
+        &[
+            "token",
+            "timeout",
+            "group.id",
+            "client.id",
+            "auto.offset.reset",
+            "enable.auto.commit",
+            "auto.commit.interval.ms",
+        ]
     }
 
     fn from_dsn<D: IntoDsn>(dsn: D) -> StdResult<Self, Self::Error> {
@@ -178,7 +185,8 @@ impl WsMessageBase {
         }
         unreachable!()
     }
-    async fn fetch_raw_meta(&self) -> Result<RawMeta> {
+    /// Raw meta or data of the message, as is from `tmq_get_raw`.
+    async fn fetch_raw_data(&self) -> Result<RawData> {
         let req_id = self.sender.req_id();
         let msg = TmqSend::FetchRaw(MessageArgs {
             req_id,
@@ -187,12 +195,14 @@ impl WsMessageBase {
         let data = self.sender.send_recv(msg).await?;
         if let TmqRecvData::Bytes(bytes) = data {
             let message_type = bytes.as_ref().read_u64().unwrap();
-            debug_assert_eq!(message_type, 3, "should be meta message type");
-            let raw = RawMeta::new(bytes.slice(8..)); // first u64 is message type.
-            return Ok(raw);
+            debug_assert_eq!(message_type, 3, "should be raw message type");
+            return Ok(RawData::new(bytes.slice(8..))); // first u64 is message type.
         }
         unreachable!()
     }
+    async fn fetch_raw_meta(&self) -> Result<RawMeta> {
+        Ok(RawMeta::from(self.fetch_raw_data().await?))
+    }
 }
 
 pub struct Meta(WsMessageBase);
@@ -241,8 +251,8 @@ impl Iterator for Data {
 impl IsAsyncData for Data {
     type Error = Error;
 
-    async fn as_raw_data(&self) -> StdResult<taos_query::common::RawData, Self::Error> {
-        todo!()
+    async fn as_raw_data(&self) -> StdResult<RawData, Self::Error> {
+        self.0.fetch_raw_data().await
     }
 
     async fn fetch_raw_block(&self) -> StdResult<Option<RawBlock>, Self::Error> {
@@ -311,6 +321,7 @@ impl Consumer {
                     if have_message {
                         let dur = elapsed.elapsed();
                         let offset = Offset {
+                            req_id,
                             message_id,
                             database,
                             topic,
@@ -369,7 +380,7 @@ impl AsAsyncConsumer for Consumer {
         let req_id = self.sender.req_id();
         let action = TmqSend::Subscribe {
             req_id,
-            req: self.tmq_conf.clone(),
+            req: Box::new(self.tmq_conf.clone()),
             topics: topics.into_iter().map(Into::into).collect_vec(),
             conn: self.conn.clone(),
         };
@@ -449,6 +460,14 @@ impl TmqBuilder {
             .ok_or_else(|| DsnError::RequireParam("group.id".to_string()))?;
         let client_id = dsn.params.get("client.id").map(ToString::to_string);
         let offset_reset = dsn.params.get("auto.offset.reset").map(ToString::to_string);
+        let auto_commit = dsn
+            .params
+            .get("enable.auto.commit")
+            .map(ToString::to_string);
+        let auto_commit_interval_ms = dsn
+            .params
+            .get("auto.commit.interval.ms")
+            .map(ToString::to_string);
         let timeout = if let Some(timeout) = dsn.get("timeout") {
             Timeout::from_str(&timeout).map_err(RawError::from_any)?
         } else {
@@ -458,6 +477,8 @@ impl TmqBuilder {
             group_id,
             client_id,
             offset_reset,
+            auto_commit,
+            auto_commit_interval_ms,
         };
 
         Ok(Self {
@@ -467,10 +488,15 @@ impl TmqBuilder {
         })
     }
 
+    /// Connect to endpoints of dsn in turn until one is connected.
     async fn build_consumer(&self) -> Result<Consumer> {
-        let url = self.info.to_tmq_url();
+        dial_endpoints(&self.info, |info| self.connect(info)).await
+    }
+
+    async fn connect(&self, info: TaosBuilder) -> Result<Consumer> {
+        let url = info.to_tmq_url();
         // let (ws, _) = futures::executor::block_on(connect_async(url))?;
-        let (ws, _) = connect_async(&url).await?;
+        let (ws, _) = info.connect_ws(url.clone()).await?;
         let (mut sender, mut reader) = ws.split();
 
         let queries = Arc::new(HashMap::<ReqId, tokio::sync::oneshot::Sender<_>>::new());
@@ -681,7 +707,7 @@ impl TmqBuilder {
                                     if let Some((_, sender)) = queries_sender.remove(&k) {
                                         let _ = sender.send(Err(RawError::new(
                                             WS_ERROR_NO::CONN_CLOSED.as_code(),
-                                            format!("WebSocket internal error: {err}")
+                                            crate::query::infra::read_error(&err),
                                         )));
                                     }
                                 }
@@ -698,10 +724,10 @@ impl TmqBuilder {
             log::debug!("Consuming done in {:?}", instant.elapsed());
         });
         let consumer = Consumer {
-            conn: self.info.to_conn_request(),
+            conn: info.to_conn_request(),
             tmq_conf: self.conf.clone(),
             sender: WsTmqSender {
-                req_id: Arc::new(AtomicU64::new(1)),
+                req_id: info.req_ids(),
                 queries,
                 sender: ws,
                 timeout: Timeout::Duration(Duration::MAX),
@@ -729,13 +755,22 @@ impl Drop for Consumer {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct Offset {
+    req_id: ReqId,
     message_id: MessageId,
     database: String,
     topic: String,
     vgroup_id: i32,
 }
 
+impl Offset {
+    /// Request id of the poll which got this message.
+    pub fn req_id(&self) -> ReqId {
+        self.req_id
+    }
+}
+
 impl IsOffset for Offset {
     fn database(&self) -> &str {
         &self.database
@@ -792,6 +827,19 @@ impl Error {
     }
 }
 
+impl DialError for Error {
+    fn errno(&self) -> Code {
+        Error::errno(self)
+    }
+
+    fn unavailable(code: Code, tried: String) -> Self {
+        Error::TaosError(RawError::new(
+            code,
+            format!("Failed to connect to any endpoint, {tried}"),
+        ))
+    }
+}
+
 type Result<T> = std::result::Result<T, Error>;
 
 #[cfg(test)]
@@ -801,6 +849,17 @@ mod tests {
     use super::{TaosBuilder, TmqBuilder};
     use taos_query::prelude::tokio;
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn build_consumer_failover() -> anyhow::Result<()> {
+        let builder = TmqBuilder::new("taos://localhost:1,localhost:2?group.id=10")?;
+        let err = builder.build_consumer().await.err().unwrap().to_string();
+        assert!(
+            err.contains("localhost:1:") && err.contains("localhost:2:"),
+            "{err}"
+        );
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_ws_tmq_meta() -> anyhow::Result<()> {
         use taos_query::prelude::*;
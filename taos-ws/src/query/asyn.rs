@@ -1,4 +1,3 @@
-use derive_more::Deref;
 use futures::stream::SplitStream;
 use futures::{FutureExt, SinkExt, StreamExt};
 // use scc::HashMap;
@@ -18,13 +17,11 @@ use tokio::net::TcpStream;
 use tokio::sync::watch;
 
 use tokio::time;
-use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 use tokio_tungstenite::tungstenite::Error as WsError;
-use tokio_tungstenite::{
-    connect_async_with_config, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream,
-};
+use tokio_tungstenite::{tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream};
 
-use super::{infra::*, TaosBuilder};
+use super::{infra::*, ServerVersion, TaosBuilder};
+use crate::req_id::ReqIds;
 
 use std::fmt::Debug;
 use std::io::Write;
@@ -32,8 +29,8 @@ use std::mem::transmute;
 use std::pin::Pin;
 // use std::io::Write;
 use std::result::Result as StdResult;
-use std::sync::atomic::AtomicU64;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use std::task::Poll;
 use std::time::{Duration, Instant};
 
@@ -55,29 +52,107 @@ type QueryInner = HashMap<ReqId, QueryChannelSender>;
 type QueryAgent = Arc<QueryInner>;
 type QueryResMapper = HashMap<ResId, ReqId>;
 
-#[derive(Debug, Clone, Deref)]
-struct Version(String);
+/// Liveness of a connection, shared by the sender, reader and writer tasks.
+#[derive(Debug, Default)]
+struct ConnState {
+    /// Set when the connection is lost, later requests fail fast then.
+    lost: AtomicBool,
+    /// A keepalive ping is sent and its pong not received yet.
+    pong_pending: AtomicBool,
+}
+
+impl ConnState {
+    fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::SeqCst)
+    }
+
+    fn set_lost(&self) {
+        self.lost.store(true, Ordering::SeqCst);
+    }
+}
 
-// impl Version {
-//     pub fn is_v3(&self) -> bool {
-//         !self.0.starts_with("2")
-//     }
-// }
 #[derive(Debug, Clone)]
 struct WsQuerySender {
-    version: Version,
-    req_id: Arc<AtomicU64>,
+    adapter: AdapterInfo,
+    req_id: ReqIds,
     results: Arc<QueryResMapper>,
     sender: WsSender,
     queries: QueryAgent,
+    state: Arc<ConnState>,
 }
 
 impl WsQuerySender {
+    /// Fetch round trip bounded by `timeout`, the reply arriving after timeout is discarded.
+    async fn fetch_recv(
+        &self,
+        id: ResId,
+        timeout: Option<Duration>,
+        msg: WsSend,
+    ) -> Result<WsRecvData> {
+        let Some(timeout) = timeout else {
+            return self.send_recv(msg).await;
+        };
+        let req_id = msg.req_id();
+        match tokio::time::timeout(timeout, self.send_recv(msg)).await {
+            Ok(res) => res,
+            Err(_) => {
+                self.queries.remove(&req_id);
+                self.results.remove(&id);
+                Err(Error::FetchTimeout(id))
+            }
+        }
+    }
+
+    /// Fetch the next block of result `id`, by `fetch` and `fetch_block` if not completed.
+    async fn fetch_block(&self, id: ResId, timeout: Option<Duration>) -> Result<Fetched> {
+        let args = WsResArgs {
+            req_id: self.req_id(),
+            id,
+        };
+        let fetch_resp = match self.fetch_recv(id, timeout, WsSend::Fetch(args)).await? {
+            WsRecvData::Fetch(fetch) => fetch,
+            data => panic!("unexpected result {data:?}"),
+        };
+        if fetch_resp.completed {
+            return Ok(Fetched::Completed(fetch_resp.timing));
+        }
+
+        let args = WsResArgs {
+            req_id: self.req_id(),
+            id,
+        };
+        let data = self
+            .fetch_recv(id, timeout, WsSend::FetchBlock(args))
+            .await?;
+        Ok(Fetched::Block(Box::new((fetch_resp, data))))
+    }
+
     fn req_id(&self) -> ReqId {
-        self.req_id
-            .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+        self.req_id.next()
     }
     async fn send_recv(&self, msg: WsSend) -> Result<WsRecvData> {
+        let req_id = msg.req_id();
+        let rx = self.send_msg(msg).await?;
+        log::debug!("[req id: {req_id}] message sent, wait for receiving");
+        Self::recv(rx).await
+    }
+
+    /// Reply of a message sent by [WsQuerySender::send_msg].
+    async fn recv(rx: oneshot::Receiver<StdResult<WsRecvData, RawError>>) -> Result<WsRecvData> {
+        match rx.await {
+            Ok(res) => Ok(res?),
+            Err(_) => Err(Error::WsClosed("connection lost before reply".to_string())),
+        }
+    }
+
+    /// Send a message without waiting, the reply is received by the returned channel.
+    async fn send_msg(
+        &self,
+        msg: WsSend,
+    ) -> Result<oneshot::Receiver<StdResult<WsRecvData, RawError>>> {
+        if self.state.is_lost() {
+            return Err(Error::WsClosed("connection lost".to_string()));
+        }
         let send_timeout = Duration::from_millis(1000);
         let req_id = msg.req_id();
         let (tx, rx) = query_channel();
@@ -108,9 +183,7 @@ impl WsQuerySender {
                 self.sender.send_timeout(msg.to_msg(), send_timeout).await?;
             }
         }
-        // handle the error
-        log::debug!("[req id: {req_id}] message sent, wait for receiving");
-        Ok(rx.await.unwrap()?)
+        Ok(rx)
     }
     async fn send_only(&self, msg: WsSend) -> Result<()> {
         let send_timeout = Duration::from_millis(1000);
@@ -119,12 +192,15 @@ impl WsQuerySender {
     }
 }
 
+/// A websocket connection with its spawned reader and writer tasks.
 #[derive(Debug)]
-pub struct WsTaos {
+struct WsConn {
     close_signal: watch::Sender<bool>,
     sender: WsQuerySender,
+    /// Endpoint connected to.
+    addr: String,
 }
-impl Drop for WsTaos {
+impl Drop for WsConn {
     fn drop(&mut self) {
         log::debug!("dropping connection");
         // send close signal to reader/writer spawned tasks.
@@ -132,21 +208,83 @@ impl Drop for WsTaos {
     }
 }
 
+#[derive(Debug)]
+pub struct WsTaos {
+    /// Current connection, replaced when re-dialed.
+    conn: RwLock<WsConn>,
+    /// Capabilities of the adapter first connected, see [WsTaos::adapter_info].
+    adapter: AdapterInfo,
+    /// Parsed server version, see [WsTaos::server_version].
+    server_version: once_cell::sync::OnceCell<ServerVersion>,
+    /// Builder to re-dial lost connections, `None` if reconnect is disabled.
+    reconnect: Option<TaosBuilder>,
+    /// Database switched by `use` statements, restored on reconnect.
+    database: RwLock<Option<String>>,
+    /// Default timeout of `s_query` and `s_exec`.
+    query_timeout: Option<Duration>,
+    /// Default timeout of each fetch round trip of result sets.
+    fetch_timeout: Option<Duration>,
+    /// Blocks fetched ahead by result sets, see [TaosBuilder::set_prefetch].
+    prefetch: usize,
+}
+
+/// Summary of an executed query, see [ResultSet::execution_summary].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecSummary {
+    /// Rows affected by an update query, 0 for queries with rows to fetch.
+    pub affected_rows: i64,
+    /// Number of fields of the result, 0 for update queries.
+    pub fields_count: usize,
+    /// Timestamp precision of the result.
+    pub precision: Precision,
+    /// Whether the query is an update query, see [ResultSet::is_update].
+    pub is_update: bool,
+}
+
+/// Reply of a fetch round trip, parsed into a block by the result set.
+enum Fetched {
+    /// No more blocks, with the server timing of the fetch.
+    Completed(Duration),
+    Block(Box<(WsFetchResp, WsRecvData)>),
+}
+
+/// Background task fetching blocks ahead of the caller, at most the channel capacity of them.
+struct Prefetch {
+    blocks: tokio::sync::mpsc::Receiver<Result<Fetched>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
 pub struct ResultSet {
     sender: WsQuerySender,
     args: WsResArgs,
     fields: Option<Vec<Field>>,
     fields_count: usize,
-    affected_rows: usize,
+    fields_precisions: Vec<u8>,
+    fields_scales: Vec<u8>,
+    is_update: bool,
+    affected_rows: u64,
     precision: Precision,
     summary: (usize, usize),
+    /// Server side timing of the query.
     timing: Duration,
+    /// Server side timing of each fetch, including the last one with no data.
+    fetch_timings: Vec<Duration>,
+    /// Timeout of each fetch round trip, no timeout if `None`.
+    fetch_timeout: Option<Duration>,
+    /// Blocks fetched ahead of the caller, 0 to fetch on demand by `block_future`.
+    prefetch: usize,
+    /// Task fetching ahead, spawned at the first fetch if `prefetch` is not 0.
+    prefetched: Option<Prefetch>,
     block_future: Option<Pin<Box<dyn Future<Output = Result<Option<RawBlock>>> + Send>>>,
     closer: Option<oneshot::Sender<()>>,
+    /// All blocks are fetched, the result is freed by the reader then.
+    completed: bool,
+    /// Stopped by [ResultSet::stop], later fetches end with no data.
+    stopped: AtomicBool,
 }
 
+// SAFETY: `block_future`, the only field not `Sync`, is touched only through `&mut self`.
 unsafe impl Sync for ResultSet {}
-unsafe impl Send for ResultSet {}
 
 impl Debug for ResultSet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -162,19 +300,19 @@ impl Debug for ResultSet {
 
 impl Drop for ResultSet {
     fn drop(&mut self) {
+        if let Some(prefetch) = &self.prefetched {
+            prefetch.task.abort();
+        }
         if let Some((_, req_id)) = self.sender.results.remove(&self.args.id) {
             self.sender.queries.remove(&req_id);
         }
 
         self.closer.take().unwrap().send(()).unwrap();
-        // let _ = self
-        //     .sender
-        //     .blocking_send_only(WsSend::FreeResult(self.args));
-
-        // tokio::spawn(async move { sender.send_only(WsSend::FreeResult(self.args)).await });
-        // block_in_place_or_global(async move {
-        //     let _ = self.sender.send_only(WsSend::FreeResult(self.args)).await;
-        // });
+        // Dropped mid-stream, free the result so the server stops computing.
+        if !self.completed && !*self.stopped.get_mut() {
+            let free = WsSend::FreeResult(self.args).to_msg();
+            let _ = self.sender.sender.try_send(free);
+        }
     }
 }
 
@@ -202,12 +340,33 @@ pub enum Error {
     TaosError(#[from] RawError),
     #[error("{0}")]
     DeError(#[from] DeError),
+    /// Boxed to keep results of [Error] small, the websocket error is the largest one.
     #[error("WebSocket internal error: {0}")]
-    WsError(#[from] WsError),
+    WsError(Box<WsError>),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
     #[error("Websocket has been closed: {0}")]
     WsClosed(String),
+    #[error("Connection timed out to {0}")]
+    ConnTimeout(String),
+    #[error("Fetch timed out of result {0}")]
+    FetchTimeout(ResId),
+    #[error("Connection lost and {retries} reconnect attempts failed: {source}")]
+    ReconnectFailed { retries: u32, source: Box<Error> },
+    /// All endpoints of dsn failed, `code` is of the last error.
+    #[error("Failed to connect to any endpoint, {tried}")]
+    EndpointsUnavailable { code: Code, tried: String },
+    /// The server is older than required by [WsTaos::assert_compatible].
+    #[error("Server version {found} is older than required {required}")]
+    IncompatibleServer { found: String, required: String },
+    /// A statement of [WsTaos::s_exec_many] failed, `index` is counted from 0.
+    #[error("Statement {index} failed: {source}, sql: {sql}")]
+    ExecMany {
+        index: usize,
+        sql: String,
+        code: Code,
+        source: Box<Error>,
+    },
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -221,6 +380,13 @@ pub enum WS_ERROR_NO {
     RECV_MESSAGE_TIMEOUT = 0xE004,
     IO_ERROR = 0xE005,
     UNAUTHORIZED = 0xE006,
+    QUERY_TIMEOUT = 0xE007,
+    NULL_POINTER = 0xE008,
+    NULL_VALUE = 0xE009,
+    TYPE_MISMATCH = 0xE00A,
+    PARAMS_UNKNOWN = 0xE00B,
+    CONN_TIMEOUT = 0xE00C,
+    BUFFER_TOO_SMALL = 0xE00D,
 }
 
 impl WS_ERROR_NO {
@@ -229,6 +395,12 @@ impl WS_ERROR_NO {
     }
 }
 
+impl From<WsError> for Error {
+    fn from(err: WsError) -> Self {
+        Error::WsError(Box::new(err))
+    }
+}
+
 impl Error {
     pub const fn errno(&self) -> Code {
         match self {
@@ -239,9 +411,49 @@ impl Error {
             Error::WsError(_) => Code::new(WS_ERROR_NO::WEBSOCKET_ERROR as _),
             Error::SendTimeoutError(_) => Code::new(WS_ERROR_NO::SEND_MESSAGE_TIMEOUT as _),
             Error::RecvTimeout(_) => Code::new(WS_ERROR_NO::RECV_MESSAGE_TIMEOUT as _),
+            Error::QueryTimeout(_) => Code::new(WS_ERROR_NO::QUERY_TIMEOUT as _),
+            Error::WsClosed(_) => Code::new(WS_ERROR_NO::CONN_CLOSED as _),
+            Error::ConnTimeout(_) => Code::new(WS_ERROR_NO::CONN_TIMEOUT as _),
+            Error::FetchTimeout(_) => Code::new(WS_ERROR_NO::RECV_MESSAGE_TIMEOUT as _),
+            Error::ReconnectFailed { .. } => Code::new(WS_ERROR_NO::CONN_CLOSED as _),
+            Error::EndpointsUnavailable { code, .. } => *code,
+            Error::ExecMany { code, .. } => *code,
             _ => Code::Failed,
         }
     }
+
+    /// Whether the credentials are refused by taosAdapter or the server.
+    pub fn is_auth_failure(&self) -> bool {
+        // TSDB_CODE_RPC_AUTH_FAILURE and TSDB_CODE_MND_AUTH_FAILURE of TDengine 3.x, the
+        // generic codes of 2.x like 0x0003 are used for other errors too.
+        const AUTH_FAILURE: [i32; 2] = [0x0018, 0x0357];
+        match self {
+            Error::WsError(err) => {
+                matches!(err.as_ref(), WsError::Http(resp) if resp.status() == 401)
+            }
+            err => {
+                let code = i32::from(err.errno());
+                code == WS_ERROR_NO::UNAUTHORIZED as i32 || AUTH_FAILURE.contains(&(code & 0xFFFF))
+            }
+        }
+    }
+
+    /// Whether the websocket connection is lost, so the request could be retried on a new one.
+    pub fn is_conn_lost(&self) -> bool {
+        use tokio::sync::mpsc::error::SendTimeoutError;
+        match self {
+            Error::WsClosed(_) | Error::WsError(_) | Error::SendError(_) | Error::IoError(_) => {
+                true
+            }
+            Error::SendTimeoutError(SendTimeoutError::Closed(_)) => true,
+            Error::TaosError(err) => err.code() == WS_ERROR_NO::CONN_CLOSED.as_code(),
+            _ => false,
+        }
+    }
+    /// The message failed to be sent, so the server never received it.
+    fn is_unsent(&self) -> bool {
+        matches!(self, Error::SendError(_) | Error::SendTimeoutError(_))
+    }
     pub fn errstr(&self) -> String {
         match self {
             Error::TaosError(error) => error.message().to_string(),
@@ -259,6 +471,7 @@ async fn read_queries(
     ws2: WsSender,
     is_v3: bool,
     mut close_listener: watch::Receiver<bool>,
+    state: Arc<ConnState>,
 ) {
     'ws: loop {
         tokio::select! {
@@ -270,32 +483,38 @@ async fn read_queries(
                             let v: WsRecv = serde_json::from_str(&text).unwrap();
                             let (req_id, data, ok) = v.ok();
                             match &data {
-                                WsRecvData::Query(_) => {
+                                WsRecvData::Query(resp) => {
                                     if let Some((_, sender)) = queries_sender.remove(&req_id)
                                     {
-                                        sender.send(ok.map(|_| data)).unwrap();
+                                        // The receiver may be dropped by query timeout.
+                                        let _ = sender.send(ok.map(|_| data));
                                     } else {
                                         debug_assert!(!queries_sender.contains_key(&req_id));
                                         log::warn!("req_id {req_id} not detected, message might be lost");
+                                        if ok.is_ok() && resp.id != 0 {
+                                            // Stale reply of a timed out query, free the result on server side.
+                                            let args = WsResArgs { req_id, id: resp.id };
+                                            let _ = ws2.send(WsSend::FreeResult(args).to_msg()).await;
+                                        }
                                     }
                                 }
                                 WsRecvData::Fetch(fetch) => {
                                     let id = fetch.id;
                                     if fetch.completed {
-                                        ws2.send(
+                                        let _ = ws2.send(
                                             WsSend::FreeResult(WsResArgs {
                                                 req_id,
                                                 id,
                                             })
                                             .to_msg(),
                                         )
-                                        .await
-                                        .unwrap();
+                                        .await;
                                     }
                                     // dbg!(&queries_sender);
                                     if let Some((_, sender)) = queries_sender.remove(&req_id)
                                     {
-                                        sender.send(ok.map(|_| data)).unwrap();
+                                        // The receiver is dropped if the result set is stopped mid-fetch.
+                                        let _ = sender.send(ok.map(|_| data));
                                     } else {
                                         log::warn!("req_id {req_id} not detected, message might be lost");
                                     }
@@ -304,15 +523,15 @@ async fn read_queries(
                                     assert!(ok.is_err());
                                     if let Some((_, sender)) = queries_sender.remove(&req_id)
                                     {
-                                        sender.send(ok.map(|_| data)).unwrap();
+                                        let _ = sender.send(ok.map(|_| data));
                                     } else {
                                         log::warn!("req_id {req_id} not detected, message might be lost");
                                     }
                                 }
-                                WsRecvData::WriteMeta => {
+                                WsRecvData::Insert(_) | WsRecvData::WriteMeta => {
                                     if let Some((_, sender)) = queries_sender.remove(&req_id)
                                     {
-                                        sender.send(ok.map(|_| data)).unwrap();
+                                        let _ = sender.send(ok.map(|_| data));
                                     } else {
                                         log::warn!("req_id {req_id} not detected, message might be lost");
                                     }
@@ -320,7 +539,7 @@ async fn read_queries(
                                 WsRecvData::WriteRaw => {
                                     if let Some((_, sender)) = queries_sender.remove(&req_id)
                                     {
-                                        sender.send(ok.map(|_| data)).unwrap();
+                                        let _ = sender.send(ok.map(|_| data));
                                     } else {
                                         log::warn!("req_id {req_id} not detected, message might be lost");
                                     }
@@ -328,7 +547,7 @@ async fn read_queries(
                                 WsRecvData::WriteRawBlock | WsRecvData::WriteRawBlockWithFields => {
                                     if let Some((_, sender)) = queries_sender.remove(&req_id)
                                     {
-                                        sender.send(ok.map(|_| data)).unwrap();
+                                        let _ = sender.send(ok.map(|_| data));
                                     } else {
                                         log::warn!("req_id {req_id} not detected, message might be lost");
                                     }
@@ -356,7 +575,7 @@ async fn read_queries(
                                     // v3
                                     if let Some((_, sender)) = queries_sender.remove(&req_id) {
                                         log::debug!("send data to fetches with id {}", res_id);
-                                        sender.send(Ok(WsRecvData::Block { timing, raw: block[offset..].to_vec() })).unwrap();
+                                        let _ = sender.send(Ok(WsRecvData::Block { timing, raw: block[offset..].to_vec() }));
                                     } else {
                                         log::warn!("req_id {res_id} not detected, message might be lost");
                                     }
@@ -364,7 +583,7 @@ async fn read_queries(
                                     // v2
                                     if let Some((_, sender)) = queries_sender.remove(&req_id) {
                                         log::debug!("send data to fetches with id {}", res_id);
-                                        sender.send(Ok(WsRecvData::BlockV2 { timing, raw: block[offset..].to_vec() })).unwrap();
+                                        let _ = sender.send(Ok(WsRecvData::BlockV2 { timing, raw: block[offset..].to_vec() }));
                                     } else {
                                         log::warn!("req_id {res_id} not detected, message might be lost");
                                     }
@@ -410,8 +629,8 @@ async fn read_queries(
                             ws2.send(Message::Pong(bytes)).await.unwrap();
                         }
                         Message::Pong(_) => {
-                            // do nothing
-                            log::warn!("received (unexpected) pong message, do nothing");
+                            log::trace!("received keepalive pong");
+                            state.pong_pending.store(false, Ordering::SeqCst);
                         }
                         Message::Frame(frame) => {
                             // do nothing
@@ -430,7 +649,7 @@ async fn read_queries(
                         // }).await;
                         for k in keys {
                             if let Some((_, sender)) = queries_sender.remove(&k) {
-                                let _ = sender.send(Err(RawError::new(WS_ERROR_NO::CONN_CLOSED.as_code(), err.to_string())));
+                                let _ = sender.send(Err(RawError::new(WS_ERROR_NO::CONN_CLOSED.as_code(), read_error(&err))));
                             }
                         }
                         break 'ws;
@@ -455,6 +674,7 @@ async fn read_queries(
             }
         }
     }
+    state.set_lost();
     if queries_sender.is_empty() {
         return;
     }
@@ -470,8 +690,99 @@ async fn read_queries(
     //     .await;
     for k in keys {
         if let Some((_, sender)) = queries_sender.remove(&k) {
-            let _ = sender.send(Err(RawError::from_any("websocket connection is closed")));
+            let _ = sender.send(Err(RawError::new(
+                WS_ERROR_NO::CONN_CLOSED.as_code(),
+                "websocket connection is closed",
+            )));
+        }
+    }
+}
+
+/// Database of a `use <db>` statement.
+fn parse_use_database(sql: &str) -> Option<String> {
+    let sql = sql.trim().trim_end_matches(';');
+    let (keyword, db) = sql.split_once(char::is_whitespace)?;
+    let db = db.trim().trim_matches('`');
+    if !keyword.eq_ignore_ascii_case("use") || db.is_empty() || db.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(db.to_string())
+}
+
+/// Inserts, which are pipelined by [WsTaos::s_exec_many].
+fn is_insert(sql: &str) -> bool {
+    sql.trim_start()
+        .split(char::is_whitespace)
+        .next()
+        .is_some_and(|keyword| keyword.eq_ignore_ascii_case("insert"))
+}
+
+/// Statements safe to execute twice, which are sent again after reconnecting even if the
+/// connection was lost after sending.
+fn is_idempotent(sql: &str) -> bool {
+    let keyword = sql
+        .trim_start()
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .next()
+        .unwrap_or_default();
+    ["select", "show", "describe", "desc", "use"]
+        .iter()
+        .any(|k| keyword.eq_ignore_ascii_case(k))
+}
+
+/// Error of a connection to an endpoint, to fail over to the next one by [dial_endpoints].
+pub(crate) trait DialError: std::fmt::Display {
+    fn errno(&self) -> Code;
+    /// All endpoints failed, `code` is of the last error.
+    fn unavailable(code: Code, tried: String) -> Self;
+}
+
+impl DialError for Error {
+    fn errno(&self) -> Code {
+        Error::errno(self)
+    }
+
+    fn unavailable(code: Code, tried: String) -> Self {
+        Error::EndpointsUnavailable { code, tried }
+    }
+}
+
+/// Connect by `dial` to endpoints in turn from `info.addr` until one is connected, for query,
+/// stmt and tmq connections alike.
+pub(crate) async fn dial_endpoints<T, E, F, Fut>(info: &TaosBuilder, mut dial: F) -> StdResult<T, E>
+where
+    E: DialError,
+    F: FnMut(TaosBuilder) -> Fut,
+    Fut: Future<Output = StdResult<T, E>>,
+{
+    let endpoints = info.endpoints();
+    if endpoints.len() == 1 {
+        return dial(info.clone()).await;
+    }
+    let mut tried = Vec::with_capacity(endpoints.len());
+    let mut code = Code::Failed;
+    for addr in endpoints {
+        let mut info = info.clone();
+        info.addr = addr.clone();
+        match dial(info).await {
+            Ok(conn) => return Ok(conn),
+            Err(err) => {
+                log::warn!("failed to connect to {addr}: {err}");
+                code = err.errno();
+                tried.push(format!("{addr}: {err}"));
+            }
+        }
+    }
+    Err(E::unavailable(code, tried.join("; ")))
+}
+
+/// Tick of keepalive pings, never if disabled.
+async fn keepalive_tick(keepalive: &mut Option<time::Interval>) {
+    match keepalive {
+        Some(interval) => {
+            interval.tick().await;
         }
+        None => std::future::pending().await,
     }
 }
 
@@ -488,19 +799,59 @@ impl WsTaos {
         Self::from_wsinfo(&info).await
     }
     pub(crate) async fn from_wsinfo(info: &TaosBuilder) -> Result<Self> {
-        let mut config = WebSocketConfig::default();
-        config.max_frame_size = Some(1024 * 1024 * 16);
+        let conn = Self::dial_any(&info.balanced(), info.req_ids()).await?;
+        Ok(Self {
+            adapter: conn.sender.adapter.clone(),
+            server_version: Default::default(),
+            conn: RwLock::new(conn),
+            reconnect: info.reconnect.then(|| info.clone()),
+            database: RwLock::new(info.database.clone()),
+            query_timeout: info.query_timeout,
+            fetch_timeout: info.fetch_timeout,
+            prefetch: info.prefetch,
+        })
+    }
 
-        let (ws, _) = connect_async_with_config(info.to_query_url(), Some(config))
-            .await
-            .map_err(|err| {
-                let err_string = err.to_string();
-                if err_string.contains("401 Unauthorized") {
-                    Error::Unauthorized(info.to_query_url())
-                } else {
-                    err.into()
-                }
-            })?;
+    /// Dial with credentials from the provider if any, which is asked once more on
+    /// authentication failure since the credentials may expire just then.
+    async fn dial_any(info: &TaosBuilder, req_ids: ReqIds) -> Result<WsConn> {
+        match Self::dial_endpoints(&info.with_provided_auth(), req_ids.clone()).await {
+            Err(err) if info.has_auth_provider() && err.is_auth_failure() => {
+                log::warn!("authentication failed, retry with new credentials: {err}");
+                Self::dial_endpoints(&info.with_provided_auth(), req_ids).await
+            }
+            res => res,
+        }
+    }
+
+    /// Dial endpoints in turn from `info.addr` until one is connected.
+    async fn dial_endpoints(info: &TaosBuilder, req_ids: ReqIds) -> Result<WsConn> {
+        dial_endpoints(info, |info| {
+            let req_ids = req_ids.clone();
+            async move { Self::dial(&info, req_ids).await }
+        })
+        .await
+    }
+
+    /// Establish a connection and spawn its tasks, request ids are taken from `req_ids`.
+    async fn dial(info: &TaosBuilder, req_ids: ReqIds) -> Result<WsConn> {
+        // Both websocket handshake and login are bounded by the connection timeout.
+        let deadline = info.conn_timeout.map(|t| tokio::time::Instant::now() + t);
+        let connect = info.connect_ws(info.to_query_url());
+        let connected = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, connect)
+                .await
+                .map_err(|_| Error::ConnTimeout(info.to_query_url()))?,
+            None => connect.await,
+        };
+        let (ws, _) = connected.map_err(|err| {
+            let err_string = err.to_string();
+            if err_string.contains("401 Unauthorized") {
+                Error::Unauthorized(info.to_query_url())
+            } else {
+                err.into()
+            }
+        })?;
         let req_id = 0;
         let (mut sender, mut reader) = ws.split();
 
@@ -508,31 +859,25 @@ impl WsTaos {
         sender.send(version.to_msg()).await?;
 
         let duration = Duration::from_secs(2);
-        let version = match tokio::time::timeout(duration, reader.next()).await {
-            Ok(Some(Ok(message))) => match message {
-                Message::Text(text) => {
-                    let v: WsRecv = serde_json::from_str(&text).unwrap();
-                    let (_, data, ok) = v.ok();
-                    match data {
-                        WsRecvData::Version { version } => {
-                            ok?;
-                            version
-                        }
-                        _ => "2.x".to_string(),
-                    }
-                }
-                _ => "2.x".to_string(),
-            },
-            _ => "2.x".to_string(),
+        let adapter = match tokio::time::timeout(duration, reader.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => AdapterInfo::negotiate(Some(&text)),
+            _ => AdapterInfo::negotiate(None),
         };
-        let is_v3 = !version.starts_with("2");
+        log::debug!("negotiated with taosAdapter: {adapter:?}");
+        let is_v3 = adapter.block_v3;
 
         let login = WsSend::Conn {
             req_id,
             req: info.to_conn_request(),
         };
         sender.send(login.to_msg()).await?;
-        if let Some(Ok(message)) = reader.next().await {
+        let logged = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, reader.next())
+                .await
+                .map_err(|_| Error::ConnTimeout(info.to_query_url()))?,
+            None => reader.next().await,
+        };
+        if let Some(Ok(message)) = logged {
             match message {
                 Message::Text(text) => {
                     let v: WsRecv = serde_json::from_str(&text).unwrap();
@@ -561,6 +906,13 @@ impl WsTaos {
         let (tx, mut rx) = watch::channel(false);
         let close_listener = rx.clone();
 
+        let state = Arc::new(ConnState::default());
+        let writer_state = state.clone();
+        let reader_state = state.clone();
+        let mut keepalive = info
+            .keepalive
+            .map(|period| time::interval_at(time::Instant::now() + period, period));
+
         tokio::spawn(async move {
             let mut interval = time::interval(Duration::from_millis(10));
 
@@ -570,6 +922,24 @@ impl WsTaos {
                         //
                         // println!("10ms passed");
                     }
+                    _ = keepalive_tick(&mut keepalive) => {
+                        // No pong since the last ping, the connection is considered dead.
+                        if writer_state.pong_pending.swap(true, Ordering::SeqCst)
+                            || sender.send(Message::Ping(Vec::new())).await.is_err()
+                        {
+                            log::error!("websocket connection lost, no pong for keepalive ping");
+                            writer_state.set_lost();
+                            let mut keys = Vec::new();
+                            queries3.iter().for_each(|r| keys.push(*r.key()));
+                            for k in keys {
+                                if let Some((_, sender)) = queries3.remove(&k) {
+                                    let _ = sender.send(Err(RawError::new(WS_ERROR_NO::CONN_CLOSED.as_code(), "connection lost")));
+                                }
+                            }
+                            let _ = sender.close().await;
+                            break 'ws;
+                        }
+                    }
                     Some(msg) = msg_recv.recv() => {
                         // dbg!(&msg);
                         if let Err(err) = sender.send(msg).await {
@@ -597,24 +967,170 @@ impl WsTaos {
         });
 
         tokio::spawn(async move {
-            read_queries(reader, queries2, fetches_sender, ws2, is_v3, close_listener).await
+            read_queries(
+                reader,
+                queries2,
+                fetches_sender,
+                ws2,
+                is_v3,
+                close_listener,
+                reader_state,
+            )
+            .await
         });
         let ws_cloned = ws.clone();
 
-        Ok(Self {
+        Ok(WsConn {
+            addr: info.addr.clone(),
             close_signal: tx,
             sender: WsQuerySender {
-                version: Version(version),
-                req_id: Default::default(),
+                adapter,
+                req_id: req_ids,
                 sender: ws_cloned,
                 queries: queries2_cloned,
                 results,
+                state,
             },
         })
     }
 
+    fn sender(&self) -> WsQuerySender {
+        self.conn
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .sender
+            .clone()
+    }
+
+    /// Replace the connection of `lost` by a new one, unless another task has done it.
+    ///
+    /// The endpoint of the lost connection is tried first, then others of dsn in turn.
+    async fn redial(&self, info: &TaosBuilder, lost: &WsQuerySender) -> Result<WsQuerySender> {
+        let (current, addr) = {
+            let conn = self.conn.read().unwrap_or_else(|e| e.into_inner());
+            (conn.sender.clone(), conn.addr.clone())
+        };
+        if !Arc::ptr_eq(&current.state, &lost.state) && !current.state.is_lost() {
+            return Ok(current);
+        }
+        // the new connection logs in to the database in use, before any request is sent on it.
+        let mut info = info.clone();
+        info.addr = addr;
+        info.database = self
+            .database
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let conn = Self::dial_any(&info, lost.req_id.clone()).await?;
+        let mut guard = self.conn.write().unwrap_or_else(|e| e.into_inner());
+        if Arc::ptr_eq(&guard.sender.state, &current.state) {
+            log::info!("websocket reconnected to {}", conn.addr);
+            *guard = conn;
+        }
+        Ok(guard.sender.clone())
+    }
+
+    /// Send query `sql`, and if the connection is lost with reconnect enabled, re-dial and retry
+    /// with exponential backoff, all by `deadline` if any. Returns the reply with the sender it's
+    /// received from.
+    ///
+    /// A statement may have been executed when the connection is lost after sending it, so it's
+    /// sent again only if it's idempotent like queries, or if it was not sent at all.
+    async fn send_query(
+        &self,
+        sql: &str,
+        req_id: ReqId,
+        deadline: Option<time::Instant>,
+    ) -> Result<(WsQuerySender, WsRecvData)> {
+        let res = self.send_query_retry(sql, req_id, deadline).await;
+        if let (Ok(_), Some(db)) = (&res, parse_use_database(sql)) {
+            *self.database.write().unwrap_or_else(|e| e.into_inner()) = Some(db);
+        }
+        res
+    }
+
+    async fn send_query_retry(
+        &self,
+        sql: &str,
+        req_id: ReqId,
+        deadline: Option<time::Instant>,
+    ) -> Result<(WsQuerySender, WsRecvData)> {
+        let idempotent = is_idempotent(sql);
+        let retryable = |err: &Error, unsent: bool| {
+            err.is_conn_lost() && (idempotent || unsent || err.is_unsent())
+        };
+        let mut sender = self.sender();
+        let unsent = sender.state.is_lost();
+        let (info, cause) = match (
+            &self.reconnect,
+            Self::send_query_once(&sender, sql, req_id, deadline).await,
+        ) {
+            (Some(info), Err(err)) if retryable(&err, unsent) => (info, err),
+            (_, res) => return res.map(|data| (sender, data)),
+        };
+        log::warn!("[req id: {req_id}] connection lost: {cause}, reconnecting");
+        for retry in 0..info.max_retries {
+            let redial = async {
+                time::sleep(
+                    info.retry_backoff
+                        .saturating_mul(2u32.saturating_pow(retry)),
+                )
+                .await;
+                self.redial(info, &sender).await
+            };
+            let redialed = match deadline {
+                Some(deadline) => time::timeout_at(deadline, redial)
+                    .await
+                    .map_err(|_| Error::QueryTimeout(sql.to_string()))?,
+                None => redial.await,
+            };
+            match redialed {
+                Ok(new) => sender = new,
+                Err(err) => {
+                    log::warn!("reconnect attempt {} failed: {err}", retry + 1);
+                    continue;
+                }
+            }
+            let unsent = sender.state.is_lost();
+            match Self::send_query_once(&sender, sql, req_id, deadline).await {
+                Err(err) if retryable(&err, unsent) => {
+                    log::warn!("[req id: {req_id}] retry {} failed: {err}", retry + 1);
+                }
+                res => return res.map(|data| (sender, data)),
+            }
+        }
+        Err(Error::ReconnectFailed {
+            retries: info.max_retries,
+            source: Box::new(cause),
+        })
+    }
+
+    /// Round trip of query `sql` on `sender` by `deadline`, the reply arriving after it is
+    /// discarded.
+    async fn send_query_once(
+        sender: &WsQuerySender,
+        sql: &str,
+        req_id: ReqId,
+        deadline: Option<time::Instant>,
+    ) -> Result<WsRecvData> {
+        let query = WsSend::Query {
+            req_id,
+            sql: sql.to_string(),
+        };
+        let Some(deadline) = deadline else {
+            return sender.send_recv(query).await;
+        };
+        match time::timeout_at(deadline, sender.send_recv(query)).await {
+            Ok(res) => res,
+            Err(_) => {
+                sender.queries.remove(&req_id);
+                Err(Error::QueryTimeout(sql.to_string()))
+            }
+        }
+    }
+
     pub async fn write_meta(&self, raw: RawMeta) -> Result<()> {
-        let req_id = self.sender.req_id();
+        let req_id = self.sender().req_id();
         let message_id = req_id;
         let raw_meta_message = 3; // magic number from taosAdapter.
 
@@ -627,46 +1143,39 @@ impl WsTaos {
 
         log::debug!("write meta with req_id: {req_id}, raw data length: {len}",);
 
-        match self.sender.send_recv(WsSend::Binary(meta)).await? {
+        match self.sender().send_recv(WsSend::Binary(meta)).await? {
             WsRecvData::WriteMeta => Ok(()),
             WsRecvData::WriteRaw => Ok(()),
             _ => unreachable!(),
         }
     }
     async fn s_write_raw_block(&self, raw: &RawBlock) -> Result<()> {
-        let req_id = self.sender.req_id();
-        let message_id = req_id;
         // if self.version().starts_with('2') {
         //     panic!("TDengine v2.x does not support to write_raw_block");
         // }
-        if self.version().starts_with("3.0.1.") {
-            let raw_block_message = 4; // action number from `taosAdapter/controller/rest/const.go:L56`.
-
-            let mut meta = Vec::new();
-            meta.write_u64_le(req_id)?;
-            meta.write_u64_le(message_id)?;
-            meta.write_u64_le(raw_block_message as u64)?;
-            meta.write_u32_le(raw.nrows() as u32)?;
-            meta.write_inlined_str::<2>(raw.table_name().unwrap())?;
-            meta.write_all(raw.as_raw_bytes())?;
-
-            let len = meta.len();
-            log::debug!("write block with req_id: {req_id}, raw data len: {len}",);
-
-            match self.sender.send_recv(WsSend::Binary(meta)).await? {
-                WsRecvData::WriteRawBlock | WsRecvData::WriteRawBlockWithFields => Ok(()),
-                _ => Err(RawError::from_string("write raw block error"))?,
-            }
-        } else {
-            let raw_block_message = 5; // action number from `taosAdapter/controller/rest/const.go:L56`.
-
-            let mut meta = Vec::new();
-            meta.write_u64_le(req_id)?;
-            meta.write_u64_le(message_id)?;
-            meta.write_u64_le(raw_block_message as u64)?;
-            meta.write_u32_le(raw.nrows() as u32)?;
-            meta.write_inlined_str::<2>(raw.table_name().unwrap())?;
-            meta.write_all(raw.as_raw_bytes())?;
+        let with_fields = self.sender().adapter.raw_block_with_fields;
+        self.send_raw_block(raw, with_fields).await
+    }
+
+    /// Write a raw block without its fields, the columns are written by position.
+    pub async fn s_write_raw_block_without_fields(&self, raw: &RawBlock) -> Result<()> {
+        self.send_raw_block(raw, false).await
+    }
+
+    async fn send_raw_block(&self, raw: &RawBlock, with_fields: bool) -> Result<()> {
+        let req_id = self.sender().req_id();
+        let message_id = req_id;
+        // action number from `taosAdapter/controller/rest/const.go:L56`.
+        let raw_block_message = if with_fields { 5 } else { 4 };
+
+        let mut meta = Vec::new();
+        meta.write_u64_le(req_id)?;
+        meta.write_u64_le(message_id)?;
+        meta.write_u64_le(raw_block_message as u64)?;
+        meta.write_u32_le(raw.nrows() as u32)?;
+        meta.write_inlined_str::<2>(raw.table_name().unwrap())?;
+        meta.write_all(raw.as_raw_bytes())?;
+        if with_fields {
             let fields = raw
                 .fields()
                 .into_iter()
@@ -676,24 +1185,36 @@ impl WsTaos {
             let fields =
                 unsafe { std::slice::from_raw_parts(fields.as_ptr() as _, fields.len() * 72) };
             meta.write_all(fields)?;
-            let len = meta.len();
-            log::debug!("write block with req_id: {req_id}, raw data len: {len}",);
+        }
+        let len = meta.len();
+        log::debug!("write block with req_id: {req_id}, raw data len: {len}",);
 
-            match self.sender.send_recv(WsSend::Binary(meta)).await? {
-                WsRecvData::WriteRawBlock | WsRecvData::WriteRawBlockWithFields => Ok(()),
-                _ => Err(RawError::from_string("write raw block error"))?,
-            }
+        match self.sender().send_recv(WsSend::Binary(meta)).await? {
+            WsRecvData::WriteRawBlock | WsRecvData::WriteRawBlockWithFields => Ok(()),
+            _ => Err(RawError::from_string("write raw block error"))?,
         }
     }
 
+    /// Query with the default timeout of `queryTimeout` in dsn if set.
     pub async fn s_query(&self, sql: &str) -> Result<ResultSet> {
-        let req_id = self.sender.req_id();
-        let action = WsSend::Query {
-            req_id,
-            sql: sql.to_string(),
-        };
+        match self.query_timeout {
+            Some(timeout) => self.s_query_timeout(sql, timeout).await,
+            None => self.s_query_with_req_id(sql, self.sender().req_id()).await,
+        }
+    }
 
-        let req = self.sender.send_recv(action).await?;
+    /// Query with a caller-supplied request id, which is sent to taosAdapter for tracing.
+    pub async fn s_query_with_req_id(&self, sql: &str, req_id: ReqId) -> Result<ResultSet> {
+        self.query_by(sql, req_id, None).await
+    }
+
+    async fn query_by(
+        &self,
+        sql: &str,
+        req_id: ReqId,
+        deadline: Option<time::Instant>,
+    ) -> Result<ResultSet> {
+        let (sender, req) = self.send_query(sql, req_id, deadline).await?;
 
         let resp = match req {
             WsRecvData::Query(resp) => resp,
@@ -722,6 +1243,9 @@ impl WsTaos {
             Ok(ResultSet {
                 fields: Some(fields),
                 fields_count: resp.fields_count,
+                fields_precisions: resp.fields_precisions.unwrap_or_default(),
+                fields_scales: resp.fields_scales.unwrap_or_default(),
+                is_update: resp.is_update,
                 precision: resp.precision,
                 affected_rows: resp.affected_rows,
                 args: WsResArgs {
@@ -729,10 +1253,16 @@ impl WsTaos {
                     id: resp.id,
                 },
                 summary: (0, 0),
-                sender: self.sender.clone(),
+                sender: sender.clone(),
                 timing: resp.timing,
+                fetch_timings: Vec::new(),
+                fetch_timeout: self.fetch_timeout,
+                prefetch: self.prefetch,
+                prefetched: None,
                 block_future: None,
                 closer: Some(closer),
+                completed: false,
+                stopped: AtomicBool::new(false),
             })
         } else {
             Ok(ResultSet {
@@ -743,66 +1273,310 @@ impl WsTaos {
                 },
                 fields: None,
                 fields_count: 0,
+                fields_precisions: Vec::new(),
+                fields_scales: Vec::new(),
+                is_update: true,
                 precision: resp.precision,
                 summary: (0, 0),
-                sender: self.sender.clone(),
+                sender: sender.clone(),
                 timing: resp.timing,
+                fetch_timings: Vec::new(),
+                fetch_timeout: self.fetch_timeout,
+                prefetch: self.prefetch,
+                prefetched: None,
                 block_future: None,
                 closer: Some(closer),
+                completed: true,
+                stopped: AtomicBool::new(false),
             })
         }
     }
 
+    /// Query with a deadline on the round trip, the reply arriving after timeout is discarded.
+    pub async fn s_query_timeout(&self, sql: &str, timeout: Duration) -> Result<ResultSet> {
+        let deadline = time::Instant::now() + timeout;
+        self.query_by(sql, self.sender().req_id(), Some(deadline))
+            .await
+    }
+
+    /// Execute with the default timeout of `queryTimeout` in dsn if set.
     pub async fn s_exec(&self, sql: &str) -> Result<usize> {
-        let req_id = self.sender.req_id();
-        let action = WsSend::Query {
+        match self.query_timeout {
+            Some(timeout) => self.s_exec_timeout(sql, timeout).await,
+            None => self.exec_by(sql, self.sender().req_id(), None).await,
+        }
+    }
+
+    /// Execute with a deadline on the round trip, the reply arriving after timeout is discarded.
+    pub async fn s_exec_timeout(&self, sql: &str, timeout: Duration) -> Result<usize> {
+        let deadline = time::Instant::now() + timeout;
+        self.exec_by(sql, self.sender().req_id(), Some(deadline))
+            .await
+    }
+
+    async fn exec_by(
+        &self,
+        sql: &str,
+        req_id: ReqId,
+        deadline: Option<time::Instant>,
+    ) -> Result<usize> {
+        match self.send_query(sql, req_id, deadline).await?.1 {
+            WsRecvData::Query(query) => Ok(query.affected_rows as usize),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Execute statements in order, returns the sum of affected rows.
+    ///
+    /// Consecutive inserts are pipelined: all of them are sent before waiting for any reply, so
+    /// they cost about one round trip, and the adapter may execute them in any order. Any other
+    /// statement, like `use` or `create table`, is a barrier which is sent after all earlier
+    /// replies and waited for before later statements. Execution stops at the first failure,
+    /// which is returned as [Error::ExecMany], inserts pipelined along with it are still
+    /// executed. See [WsTaos::s_exec_many_results] to continue on errors.
+    pub async fn s_exec_many<T: AsRef<str>>(
+        &self,
+        sqls: impl IntoIterator<Item = T>,
+    ) -> Result<usize> {
+        let mut rows = 0;
+        for (index, (sql, res)) in self
+            .exec_pipelined(sqls, true)
+            .await
+            .into_iter()
+            .enumerate()
+        {
+            match res {
+                Ok(affected) => rows += affected,
+                Err(err) => {
+                    return Err(Error::ExecMany {
+                        index,
+                        sql,
+                        code: err.errno(),
+                        source: Box::new(err),
+                    })
+                }
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Execute statements like [WsTaos::s_exec_many] but continue on errors, returns affected
+    /// rows or the error of each statement in order.
+    pub async fn s_exec_many_results<T: AsRef<str>>(
+        &self,
+        sqls: impl IntoIterator<Item = T>,
+    ) -> Vec<Result<usize>> {
+        let (_, results): (Vec<_>, _) = self.exec_pipelined(sqls, false).await.into_iter().unzip();
+        results
+    }
+
+    /// Statements with their results, runs of inserts are pipelined and the others executed
+    /// alone. With `stop_on_error`, nothing is sent after a failed run.
+    async fn exec_pipelined<T: AsRef<str>>(
+        &self,
+        sqls: impl IntoIterator<Item = T>,
+        stop_on_error: bool,
+    ) -> Vec<(String, Result<usize>)> {
+        let mut sqls = sqls
+            .into_iter()
+            .map(|sql| sql.as_ref().to_string())
+            .peekable();
+        let mut results = Vec::new();
+        while let Some(sql) = sqls.next() {
+            let done = results.len();
+            let mut run = vec![sql];
+            if is_insert(&run[0]) {
+                run.extend(std::iter::from_fn(|| sqls.next_if(|sql| is_insert(sql))));
+            }
+            if run.len() == 1 {
+                let res = self.s_exec(&run[0]).await;
+                results.extend(run.into_iter().zip([res]));
+            } else {
+                results.extend(self.exec_run(run).await);
+            }
+            if stop_on_error && results[done..].iter().any(|(_, res)| res.is_err()) {
+                break;
+            }
+        }
+        results
+    }
+
+    /// Send `sqls` before waiting for any reply on the current connection. They are not retried
+    /// on reconnect since some may have been executed.
+    async fn exec_run(&self, sqls: Vec<String>) -> Vec<(String, Result<usize>)> {
+        let sender = self.sender();
+        let mut pending = Vec::with_capacity(sqls.len());
+        for sql in sqls {
+            let req_id = sender.req_id();
+            let query = WsSend::Query {
+                req_id,
+                sql: sql.clone(),
+            };
+            let rx = sender.send_msg(query).await;
+            pending.push((sql, req_id, rx));
+        }
+
+        let mut results = Vec::with_capacity(pending.len());
+        for (sql, req_id, rx) in pending {
+            let reply = match (rx, self.query_timeout) {
+                (Err(err), _) => Err(err),
+                (Ok(rx), None) => WsQuerySender::recv(rx).await,
+                (Ok(rx), Some(timeout)) => {
+                    match time::timeout(timeout, WsQuerySender::recv(rx)).await {
+                        Ok(reply) => reply,
+                        Err(_) => {
+                            sender.queries.remove(&req_id);
+                            Err(Error::QueryTimeout(sql.clone()))
+                        }
+                    }
+                }
+            };
+            let res = reply.map(|data| match data {
+                WsRecvData::Query(query) => query.affected_rows as usize,
+                _ => unreachable!(),
+            });
+            results.push((sql, res));
+        }
+        results
+    }
+
+    /// Schemaless insert, see [Taos::schemaless_insert](super::Taos::schemaless_insert).
+    pub async fn s_schemaless_insert(
+        &self,
+        data: &str,
+        protocol: u8,
+        precision: &str,
+        ttl: i32,
+        req_id: ReqId,
+    ) -> Result<WsSchemalessResp> {
+        let req_id = if req_id == 0 {
+            self.sender().req_id()
+        } else {
+            req_id
+        };
+        let action = WsSend::Insert {
             req_id,
-            sql: sql.to_string(),
+            protocol,
+            precision: precision.to_string(),
+            data: data.to_string(),
+            ttl,
         };
-        match self.sender.send_recv(action).await? {
-            WsRecvData::Query(query) => Ok(query.affected_rows),
+        match self.sender().send_recv(action).await? {
+            WsRecvData::Insert(resp) => Ok(resp),
             _ => unreachable!(),
         }
     }
 
     pub fn version(&self) -> &str {
-        &self.sender.version.0
+        &self.adapter.version
     }
-}
 
-impl ResultSet {
-    async fn fetch(&mut self) -> Result<Option<RawBlock>> {
-        let args = WsResArgs {
-            req_id: self.sender.req_id(),
-            id: self.args.id,
-        };
-        let fetch = WsSend::Fetch(args);
-        let fetch = self.sender.send_recv(fetch).await?;
+    /// Capabilities negotiated with the adapter of the current connection.
+    pub fn adapter_info(&self) -> AdapterInfo {
+        self.sender().adapter
+    }
 
-        let fetch_resp = match fetch {
-            WsRecvData::Fetch(fetch) => fetch,
-            data => panic!("unexpected result {data:?}"),
+    /// Switch to database `db`, which is recorded to log in to on reconnection.
+    pub async fn use_database(&self, db: &str) -> Result<()> {
+        self.s_exec(&format!("use {db}")).await?;
+        Ok(())
+    }
+
+    /// Database switched by [WsTaos::use_database] or `use` statements, otherwise of the dsn.
+    ///
+    /// It's the database recorded to log in to on reconnection, not queried from the server
+    /// like [Taos::current_database](crate::Taos::current_database).
+    pub fn recorded_database(&self) -> Option<String> {
+        self.database
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Parsed server version, from the handshake or `select server_version()` if taosAdapter
+    /// does not report it. Cached after the first call.
+    pub async fn server_version(&self) -> Result<&ServerVersion> {
+        if let Some(version) = self.server_version.get() {
+            return Ok(version);
+        }
+        let version = match self.version().parse() {
+            Ok(version) => version,
+            Err(_) => {
+                let version: Option<String> = self.query_one("select server_version()").await?;
+                version
+                    .unwrap_or_default()
+                    .parse()
+                    .map_err(|err: String| RawError::from_string(err))?
+            }
         };
+        Ok(self.server_version.get_or_init(|| version))
+    }
 
-        if fetch_resp.completed {
-            self.timing = fetch_resp.timing;
-            return Ok(None);
+    /// Check the server is `min` or newer, like `3.0.2`.
+    pub async fn assert_compatible(&self, min: &str) -> Result<()> {
+        let required: ServerVersion = min
+            .parse()
+            .map_err(|err: String| RawError::from_string(err))?;
+        let version = self.server_version().await?;
+        if version.at_least(&required) {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleServer {
+                found: version.to_string(),
+                required: required.to_string(),
+            })
         }
+    }
+}
 
-        let args = WsResArgs {
-            req_id: self.sender.req_id(),
-            id: self.args.id,
-        };
+impl ResultSet {
+    /// Set timeout of each fetch round trip of this result set, zero means no timeout.
+    pub fn set_fetch_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.fetch_timeout = Some(timeout).filter(|t| !t.is_zero());
+        self
+    }
+
+    /// Set blocks fetched ahead of the caller like [TaosBuilder::set_prefetch], it takes effect
+    /// only before the first fetch.
+    pub fn set_prefetch(&mut self, blocks: usize) -> &mut Self {
+        self.prefetch = blocks;
+        self
+    }
+
+    async fn fetch(&mut self) -> Result<Option<RawBlock>> {
+        let fetched = self
+            .sender
+            .fetch_block(self.args.id, self.fetch_timeout)
+            .await?;
+        Ok(self.on_fetched(fetched))
+    }
 
-        let fetch_block = WsSend::FetchBlock(args);
+    /// Block of a fetched reply, or `None` if the result is completed.
+    fn on_fetched(&mut self, fetched: Fetched) -> Option<RawBlock> {
+        let (fetch_resp, data) = match fetched {
+            Fetched::Completed(timing) => {
+                self.fetch_timings.push(timing);
+                self.completed = true;
+                log::debug!(
+                    "[req id: {}] result {} completed, server timing {:?} (query {:?}, fetches {:?})",
+                    self.args.req_id,
+                    self.args.id,
+                    self.timing(),
+                    self.timing,
+                    self.fetch_timings
+                );
+                return None;
+            }
+            Fetched::Block(block) => *block,
+        };
 
-        match self.sender.send_recv(fetch_block).await? {
+        match data {
             WsRecvData::Block { timing, raw } => {
                 let mut raw = RawBlock::parse_from_raw_block(raw, self.precision);
 
                 raw.with_field_names(self.fields.as_ref().unwrap().iter().map(Field::name));
-                self.timing = timing + fetch_resp.timing;
-                Ok(Some(raw))
+                self.fetch_timings.push(timing + fetch_resp.timing);
+                Some(raw)
             }
             WsRecvData::BlockV2 { timing, raw } => {
                 let mut raw = RawBlock::parse_from_raw_block_v2(
@@ -814,22 +1588,124 @@ impl ResultSet {
                 );
 
                 raw.with_field_names(self.fields.as_ref().unwrap().iter().map(Field::name));
-                self.timing = timing + fetch_resp.timing;
-                Ok(Some(raw))
+                self.fetch_timings.push(timing + fetch_resp.timing);
+                Some(raw)
             }
             _ => unreachable!(),
         }
     }
+
+    /// Next block from the prefetch task, which is spawned at the first call.
+    fn poll_prefetched(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<Option<RawBlock>>> {
+        if self.prefetched.is_none() {
+            let (tx, blocks) = tokio::sync::mpsc::channel(self.prefetch);
+            let (sender, id, timeout) = (self.sender.clone(), self.args.id, self.fetch_timeout);
+            let task = tokio::spawn(async move {
+                // reserve before fetching, so blocks not received are bounded by the capacity.
+                while let Ok(permit) = tx.reserve().await {
+                    let fetched = sender.fetch_block(id, timeout).await;
+                    let done = !matches!(fetched, Ok(Fetched::Block(..)));
+                    permit.send(fetched);
+                    if done {
+                        break;
+                    }
+                }
+            });
+            self.prefetched = Some(Prefetch { blocks, task });
+        }
+        let prefetch = self.prefetched.as_mut().unwrap();
+        match prefetch.blocks.poll_recv(cx) {
+            Poll::Ready(Some(fetched)) => Poll::Ready(fetched.map(|f| self.on_fetched(f))),
+            // the task ends after sending the completion or an error.
+            Poll::Ready(None) => {
+                self.completed = true;
+                Poll::Ready(Ok(None))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Request id of the query which produced this result set.
+    pub fn req_id(&self) -> ReqId {
+        self.args.req_id
+    }
+
+    /// Precision of each field like decimal columns, empty if the server does not send it.
+    pub fn fields_precisions(&self) -> &[u8] {
+        &self.fields_precisions
+    }
+
+    /// Scale of each field like decimal columns, empty if the server does not send it.
+    pub fn fields_scales(&self) -> &[u8] {
+        &self.fields_scales
+    }
+
+    /// The query is an update query (insert, create, etc.) which has no rows to fetch.
+    pub fn is_update(&self) -> bool {
+        self.is_update || self.fields_count == 0
+    }
+
+    /// Rows affected by an update query, saturated to `i32::MAX`, see
+    /// [ResultSet::affected_rows64].
+    pub fn affected_rows(&self) -> i32 {
+        self.affected_rows.min(i32::MAX as u64) as i32
+    }
+
+    /// Affected rows without truncating to i32, see [AsyncFetchable::affected_rows].
+    pub fn affected_rows64(&self) -> i64 {
+        self.affected_rows.min(i64::MAX as u64) as i64
+    }
+
+    /// Summary of the execution, available without fetching any block.
+    pub fn execution_summary(&self) -> ExecSummary {
+        ExecSummary {
+            affected_rows: self.affected_rows64(),
+            fields_count: self.fields_count,
+            precision: self.precision,
+            is_update: self.is_update(),
+        }
+    }
+
+    /// Server side timing of the latest round trip, the query itself or the latest fetch.
     pub fn take_timing(&self) -> Duration {
-        self.timing
+        self.fetch_timings.last().copied().unwrap_or(self.timing)
+    }
+
+    /// Total server side timing of the query and all fetches so far, excluding the network.
+    pub fn timing(&self) -> Duration {
+        self.timing + self.fetch_timings.iter().sum::<Duration>()
+    }
+
+    /// Server side timing of each fetch so far, see [ResultSet::timing].
+    pub fn fetch_timings(&self) -> &[Duration] {
+        &self.fetch_timings
     }
 
+    /// Stop the query, the result is freed so the server stops computing.
+    ///
+    /// Later fetches end with no data, and a reply of the fetch in flight is discarded.
     pub async fn stop(&self) {
+        if self.stopped.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if let Some(prefetch) = &self.prefetched {
+            prefetch.task.abort();
+        }
         if let Some((_, req_id)) = self.sender.results.remove(&self.args.id) {
             self.sender.queries.remove(&req_id);
         }
 
-        let _ = self.sender.send_only(WsSend::FreeResult(self.args)).await;
+        if !self.completed {
+            let _ = self.sender.send_only(WsSend::FreeResult(self.args)).await;
+        }
+    }
+
+    /// Same to [ResultSet::stop] for sync code.
+    pub fn blocking_stop(&self) {
+        block_in_place_or_global(self.stop())
     }
 }
 
@@ -837,7 +1713,11 @@ impl AsyncFetchable for ResultSet {
     type Error = Error;
 
     fn affected_rows(&self) -> i32 {
-        self.affected_rows as i32
+        ResultSet::affected_rows(self)
+    }
+
+    fn affected_rows64(&self) -> i64 {
+        ResultSet::affected_rows64(self)
     }
 
     fn precision(&self) -> taos_query::common::Precision {
@@ -861,6 +1741,13 @@ impl AsyncFetchable for ResultSet {
         self: &mut Self,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<StdResult<Option<RawBlock>, Self::Error>> {
+        if self.completed || *self.stopped.get_mut() {
+            self.block_future = None;
+            return Poll::Ready(Ok(None));
+        }
+        if self.prefetch > 0 {
+            return self.poll_prefetched(cx);
+        }
         if let Some(mut f) = self.block_future.take() {
             // let mut f = self.block_future.take().unwrap();
             let res = f.poll_unpin(cx);
@@ -892,7 +1779,11 @@ impl taos_query::Fetchable for ResultSet {
     type Error = Error;
 
     fn affected_rows(&self) -> i32 {
-        self.affected_rows as i32
+        ResultSet::affected_rows(self)
+    }
+
+    fn affected_rows64(&self) -> i64 {
+        ResultSet::affected_rows64(self)
     }
 
     fn precision(&self) -> taos_query::common::Precision {
@@ -914,7 +1805,9 @@ impl taos_query::Fetchable for ResultSet {
     }
 
     fn fetch_raw_block(&mut self) -> StdResult<Option<RawBlock>, Self::Error> {
-        block_in_place_or_global(self.fetch())
+        block_in_place_or_global(std::future::poll_fn(|cx| {
+            AsyncFetchable::fetch_raw_block(self, cx)
+        }))
     }
 }
 
@@ -930,6 +1823,20 @@ impl AsyncQueryable for WsTaos {
     ) -> StdResult<Self::AsyncResultSet, Self::Error> {
         self.s_query(sql.as_ref()).await
     }
+
+    async fn exec<T: AsRef<str> + Send + Sync>(&self, sql: T) -> StdResult<usize, Self::Error> {
+        self.s_exec(sql.as_ref()).await
+    }
+
+    async fn exec_many<T, I>(&self, input: I) -> StdResult<usize, Self::Error>
+    where
+        T: AsRef<str> + Send + Sync,
+        I::IntoIter: Send,
+        I: IntoIterator<Item = T> + Send,
+    {
+        self.s_exec_many(input).await
+    }
+
     async fn write_raw_meta(&self, raw: RawMeta) -> StdResult<(), Self::Error> {
         self.write_meta(raw).await
     }
@@ -1010,6 +1917,1032 @@ async fn test_client_cloud() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// A fake taosAdapter accepting websocket logins.
+#[cfg(test)]
+struct MockAdapter {
+    addr: std::net::SocketAddr,
+    /// Connections not closed by a close frame yet.
+    open: Arc<std::sync::atomic::AtomicUsize>,
+    /// Result ids freed by `free_result`.
+    freed: Arc<std::sync::Mutex<Vec<u64>>>,
+    /// Fetch requests received.
+    fetches: Arc<std::sync::atomic::AtomicUsize>,
+    /// Notified of [MockAdapter::fetches] when a fetch request is received.
+    fetched: watch::Receiver<usize>,
+    /// Sql of `insert <n>`, `fail` and `<sql> lost` queries received, with queries in flight by
+    /// then.
+    queries: Arc<std::sync::Mutex<Vec<(String, usize)>>>,
+    /// Databases of `conn` requests received, in order of connections.
+    conns: Arc<std::sync::Mutex<Vec<Option<String>>>>,
+}
+
+/// Start a [MockAdapter].
+///
+/// `insert <n>` queries are replied with `n` affected rows, larger `n` earlier, so concurrent
+/// replies arrive out of order. `select slow` returns a result set whose fetch is replied only
+/// after the result is freed, like a stopped long aggregation. `select empty` returns a result
+/// set with no rows, timing 1000ns for the query and 500ns for the fetch. `select blocks` returns
+/// [MOCK_BLOCKS] blocks of one tinyint row valued the block index, each fetch replied after
+/// [MOCK_FETCH_LATENCY]. `fail` is replied with error code 0x2600. `<sql> lost` closes the
+/// connection without reply at the first time, later it's replied like others.
+#[cfg(test)]
+const MOCK_BLOCKS: i8 = 4;
+#[cfg(test)]
+const MOCK_FETCH_LATENCY: Duration = Duration::from_millis(40);
+
+#[cfg(test)]
+async fn mock_adapter() -> MockAdapter {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let open = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let freed = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let counter = open.clone();
+    let freed_ids = freed.clone();
+    let fetches = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let fetch_counter = fetches.clone();
+    let (fetch_notify, fetched) = watch::channel(0);
+    let fetch_notify = Arc::new(fetch_notify);
+    let queries = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let received = queries.clone();
+    let in_flight = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let lost = Arc::new(std::sync::Mutex::new(std::collections::HashSet::new()));
+    let conns = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let logins = conns.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let counter = counter.clone();
+            let freed_ids = freed_ids.clone();
+            let fetch_counter = fetch_counter.clone();
+            let fetch_notify = fetch_notify.clone();
+            let received = received.clone();
+            let in_flight = in_flight.clone();
+            let lost = lost.clone();
+            let logins = logins.clone();
+            tokio::spawn(async move {
+                let Ok(ws) = tokio_tungstenite::accept_async(stream).await else {
+                    return;
+                };
+                counter.fetch_add(1, Ordering::SeqCst);
+                let (mut sink, mut stream) = ws.split();
+                let mut parked_fetch = None;
+                let mut empty_results = Vec::new();
+                // result id to blocks fetched.
+                let mut block_results = std::collections::HashMap::new();
+                let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Message>();
+                tokio::spawn(async move {
+                    while let Some(reply) = rx.recv().await {
+                        if sink.send(reply).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+                while let Some(Ok(message)) = stream.next().await {
+                    let text = match message {
+                        Message::Text(text) => text,
+                        Message::Close(_) => {
+                            counter.fetch_sub(1, Ordering::SeqCst);
+                            break;
+                        }
+                        _ => continue,
+                    };
+                    let v: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    let req_id = v["args"]["req_id"].as_u64().unwrap_or_default();
+                    let id = v["args"]["id"].as_u64().unwrap_or_default();
+                    if v["action"] == "fetch" {
+                        let n = fetch_counter.fetch_add(1, Ordering::SeqCst);
+                        fetch_notify.send_replace(n + 1);
+                    }
+                    if v["action"] == "conn" {
+                        let db = v["args"]["db"].as_str().filter(|db| !db.is_empty());
+                        logins.lock().unwrap().push(db.map(ToString::to_string));
+                    }
+                    match v["action"].as_str() {
+                        Some("version") => {
+                            let _ = tx.send(Message::Text(
+                                r#"{"code":0,"message":"","action":"version","version":"3.0.0.0"}"#
+                                    .to_string(),
+                            ));
+                        }
+                        Some("query") if v["args"]["sql"] == "select slow" => {
+                            let _ = tx.send(Message::Text(
+                                serde_json::json!({
+                                    "code": 0, "message": "", "action": "query",
+                                    "req_id": req_id, "id": req_id, "fields_count": 1,
+                                    "fields_names": ["v"], "fields_types": [5],
+                                    "fields_lengths": [8],
+                                })
+                                .to_string(),
+                            ));
+                        }
+                        Some("query") if v["args"]["sql"] == "select empty" => {
+                            let _ = tx.send(Message::Text(
+                                serde_json::json!({
+                                    "code": 0, "message": "", "action": "query",
+                                    "req_id": req_id, "id": req_id, "fields_count": 1,
+                                    "fields_names": ["v"], "fields_types": [5],
+                                    "fields_lengths": [8], "timing": 1000,
+                                })
+                                .to_string(),
+                            ));
+                            empty_results.push(serde_json::json!(req_id));
+                        }
+                        Some("query") if v["args"]["sql"] == "select blocks" => {
+                            let _ = tx.send(Message::Text(
+                                serde_json::json!({
+                                    "code": 0, "message": "", "action": "query",
+                                    "req_id": req_id, "id": req_id, "fields_count": 1,
+                                    "fields_names": ["v"], "fields_types": [2],
+                                    "fields_lengths": [1],
+                                })
+                                .to_string(),
+                            ));
+                            block_results.insert(req_id, 0i8);
+                        }
+                        Some("fetch") if block_results.contains_key(&id) => {
+                            let n = block_results.get_mut(&id).unwrap();
+                            let completed = *n == MOCK_BLOCKS;
+                            *n += !completed as i8;
+                            let tx = tx.clone();
+                            tokio::spawn(async move {
+                                tokio::time::sleep(MOCK_FETCH_LATENCY).await;
+                                let _ = tx.send(Message::Text(
+                                    serde_json::json!({
+                                        "code": 0, "message": "", "action": "fetch",
+                                        "req_id": req_id, "id": id, "completed": completed,
+                                        "lengths": [1], "rows": 1,
+                                    })
+                                    .to_string(),
+                                ));
+                            });
+                        }
+                        Some("fetch_block") => {
+                            let n = block_results[&id] - 1;
+                            // timing, result id, then a v3 block of one tinyint row: header of
+                            // version, length, rows, cols, flag and group id, the column schema,
+                            // the column length, an empty nulls bitmap and the value.
+                            let mut bytes = vec![0; 8];
+                            bytes.extend(id.to_le_bytes());
+                            for v in [1u32, 39, 1, 1, u32::MAX] {
+                                bytes.extend(v.to_le_bytes());
+                            }
+                            bytes.extend(0u64.to_le_bytes());
+                            bytes.push(taos_query::common::Ty::TinyInt as u8);
+                            bytes.extend(1u32.to_le_bytes());
+                            bytes.extend(1u32.to_le_bytes());
+                            bytes.push(0);
+                            bytes.push(n as u8);
+                            let _ = tx.send(Message::Binary(bytes));
+                        }
+                        Some("fetch") if empty_results.contains(&v["args"]["id"]) => {
+                            let _ = tx.send(Message::Text(
+                                serde_json::json!({
+                                    "code": 0, "message": "", "action": "fetch",
+                                    "req_id": req_id, "id": v["args"]["id"], "completed": true,
+                                    "timing": 500,
+                                })
+                                .to_string(),
+                            ));
+                        }
+                        Some("fetch") => parked_fetch = Some((req_id, v["args"]["id"].clone())),
+                        Some("free_result") => {
+                            freed_ids
+                                .lock()
+                                .unwrap()
+                                .push(v["args"]["id"].as_u64().unwrap_or_default());
+                            // the late reply of the fetch in flight.
+                            if let Some((req_id, id)) = parked_fetch.take() {
+                                let _ = tx.send(Message::Text(
+                                    serde_json::json!({
+                                        "code": 0, "message": "", "action": "fetch",
+                                        "req_id": req_id, "id": id, "completed": false,
+                                        "lengths": [8], "rows": 1,
+                                    })
+                                    .to_string(),
+                                ));
+                            }
+                        }
+                        Some("query")
+                            if v["args"]["sql"].as_str().is_some_and(|sql| {
+                                sql.ends_with(" lost")
+                                    && lost.lock().unwrap().insert(sql.to_string())
+                            }) =>
+                        {
+                            let sql = v["args"]["sql"].as_str().unwrap_or_default();
+                            received.lock().unwrap().push((sql.to_string(), 0));
+                            // the connection is lost after receiving the query.
+                            break;
+                        }
+                        Some("query") if v["args"]["sql"] == "fail" => {
+                            let n = in_flight.load(Ordering::SeqCst);
+                            received.lock().unwrap().push(("fail".to_string(), n));
+                            let _ = tx.send(Message::Text(
+                                serde_json::json!({
+                                    "code": 0x2600, "message": "syntax error", "action": "query",
+                                    "req_id": req_id,
+                                })
+                                .to_string(),
+                            ));
+                        }
+                        Some("query") => {
+                            let sql = v["args"]["sql"].as_str().unwrap_or_default();
+                            let rows: u64 = sql
+                                .strip_prefix("insert ")
+                                .and_then(|n| n.parse().ok())
+                                .unwrap_or_default();
+                            let n = in_flight.fetch_add(1, Ordering::SeqCst);
+                            received.lock().unwrap().push((sql.to_string(), n));
+                            let tx = tx.clone();
+                            let in_flight = in_flight.clone();
+                            tokio::spawn(async move {
+                                let delay = 50u64.saturating_sub(rows % 50);
+                                tokio::time::sleep(Duration::from_millis(delay)).await;
+                                in_flight.fetch_sub(1, Ordering::SeqCst);
+                                let _ = tx.send(Message::Text(
+                                    serde_json::json!({
+                                        "code": 0, "message": "", "action": "query",
+                                        "req_id": req_id, "id": req_id,
+                                        "is_update": true, "affected_rows": rows,
+                                    })
+                                    .to_string(),
+                                ));
+                            });
+                        }
+                        _ => {
+                            let _ = tx.send(Message::Text(
+                                r#"{"code":0,"message":"","action":"conn","req_id":0}"#.to_string(),
+                            ));
+                        }
+                    }
+                }
+            });
+        }
+    });
+    MockAdapter {
+        addr,
+        open,
+        freed,
+        fetches,
+        fetched,
+        queries,
+        conns,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_stop_result_set() -> anyhow::Result<()> {
+    async fn fetch(
+        rs: &mut ResultSet,
+    ) -> StdResult<Result<Option<RawBlock>>, time::error::Elapsed> {
+        let f = std::future::poll_fn(|cx| AsyncFetchable::fetch_raw_block(rs, cx));
+        tokio::time::timeout(Duration::from_millis(100), f).await
+    }
+    let mock = mock_adapter().await;
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock.addr)).await?;
+
+    let mut rs = taos.s_query("select slow").await?;
+    fetch(&mut rs).await.unwrap_err();
+    rs.stop().await;
+    assert!(fetch(&mut rs).await?.unwrap().is_none());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(*mock.freed.lock().unwrap(), [rs.args.id]);
+    // the late fetch reply is discarded, the connection is still usable.
+    assert_eq!(taos.s_exec("insert 3").await?, 3);
+
+    // dropping mid-stream stops the query too.
+    let mut rs = taos.s_query("select slow").await?;
+    let id = rs.args.id;
+    fetch(&mut rs).await.unwrap_err();
+    drop(rs);
+    assert_eq!(taos.s_exec("insert 4").await?, 4);
+    assert_eq!(mock.freed.lock().unwrap().last(), Some(&id));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_result_set_timing() -> anyhow::Result<()> {
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock_adapter().await.addr)).await?;
+    let mut rs = taos.s_query("select empty").await?;
+    assert_eq!(rs.timing(), Duration::from_nanos(1000));
+    assert!(rs.fetch_timings().is_empty());
+
+    let block = std::future::poll_fn(|cx| AsyncFetchable::fetch_raw_block(&mut rs, cx)).await?;
+    assert!(block.is_none());
+    assert_eq!(rs.fetch_timings(), [Duration::from_nanos(500)]);
+    assert_eq!(rs.timing(), Duration::from_nanos(1500));
+    assert_eq!(rs.take_timing(), Duration::from_nanos(500));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_adapter_info() -> anyhow::Result<()> {
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock_adapter().await.addr)).await?;
+    let info = taos.adapter_info();
+    assert_eq!(info.version, "3.0.0.0");
+    assert!(info.version_action && info.block_v3 && info.raw_block_with_fields);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_server_version() -> anyhow::Result<()> {
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock_adapter().await.addr)).await?;
+    let version = taos.server_version().await?;
+    assert_eq!(
+        (version.major, version.minor, version.raw()),
+        (3, 0, "3.0.0.0")
+    );
+    assert!(std::ptr::eq(version, taos.server_version().await?));
+
+    taos.assert_compatible("3.0").await?;
+    let err = taos.assert_compatible("3.1.0").await.unwrap_err();
+    assert!(matches!(err, Error::IncompatibleServer { .. }));
+    assert_eq!(
+        err.to_string(),
+        "Server version 3.0.0.0 is older than required 3.1.0"
+    );
+    taos.assert_compatible("latest").await.unwrap_err();
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_prefetch_elapsed() -> anyhow::Result<()> {
+    use futures::TryStreamExt;
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock_adapter().await.addr)).await?;
+
+    // blocks processed as slow as fetched, sequential fetching takes about twice of prefetching.
+    let mut elapsed = Vec::new();
+    for prefetch in [0, 1] {
+        let mut rs = taos.s_query("select blocks").await?;
+        rs.set_prefetch(prefetch);
+        let started = Instant::now();
+        let mut blocks = rs.blocks();
+        let mut n = 0;
+        while blocks.try_next().await?.is_some() {
+            n += 1;
+            tokio::time::sleep(MOCK_FETCH_LATENCY).await;
+        }
+        assert_eq!(n, MOCK_BLOCKS);
+        elapsed.push(started.elapsed());
+    }
+    dbg!(&elapsed);
+    assert!(elapsed[1] * 4 < elapsed[0] * 3, "{elapsed:?}");
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_prefetch() -> anyhow::Result<()> {
+    use futures::TryStreamExt;
+    let mock = mock_adapter().await;
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock.addr)).await?;
+
+    let mut fetched = mock.fetched.clone();
+    let blocks: Vec<_> = (0..MOCK_BLOCKS)
+        .map(|n| vec![vec![taos_query::common::Value::TinyInt(n)]])
+        .collect();
+    // the last fetch tells the result is completed.
+    let fetches = MOCK_BLOCKS as usize + 1;
+    for prefetch in [0, 1] {
+        let mut rs = taos.s_query("select blocks").await?;
+        rs.set_prefetch(prefetch);
+        let before = mock.fetches.load(Ordering::SeqCst);
+        let mut values = Vec::new();
+        let mut stream = rs.blocks();
+        while let Some(block) = stream.try_next().await? {
+            values.push(block.to_values());
+            // blocks are fetched ahead while the caller holds the current one.
+            let expected = (values.len() + prefetch).min(fetches);
+            time::timeout(
+                Duration::from_secs(5),
+                fetched.wait_for(|n| *n >= before + expected),
+            )
+            .await??;
+            assert_eq!(mock.fetches.load(Ordering::SeqCst) - before, expected);
+        }
+        assert_eq!(values, blocks);
+        assert_eq!(mock.fetches.load(Ordering::SeqCst) - before, fetches);
+    }
+
+    // at most one block is fetched ahead while the caller is busy.
+    let mut rs = taos.s_query("select blocks").await?;
+    let before = mock.fetches.load(Ordering::SeqCst);
+    let mut stream = rs.blocks();
+    assert!(stream.try_next().await?.is_some());
+    time::timeout(
+        Duration::from_secs(5),
+        fetched.wait_for(|n| *n >= before + 2),
+    )
+    .await??;
+    let more = time::timeout(
+        MOCK_FETCH_LATENCY * 4,
+        fetched.wait_for(|n| *n > before + 2),
+    )
+    .await;
+    assert!(more.is_err(), "fetched more than one block ahead");
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_sync_rows() -> anyhow::Result<()> {
+    use taos_query::common::Value;
+    use taos_query::Fetchable;
+    let mock = mock_adapter().await;
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock.addr)).await?;
+    let mut rs = taos.s_query("select blocks").await?;
+    rs.set_prefetch(0);
+    let before = mock.fetches.load(Ordering::SeqCst);
+    let mut rows = Fetchable::rows(&mut rs);
+    let row = rows.next().unwrap()?;
+    // blocks are fetched only when rows of the last one run out.
+    assert_eq!(mock.fetches.load(Ordering::SeqCst) - before, 1);
+    assert_eq!(row.get::<i64>(0)?, Some(0));
+    assert_eq!(row.get_by_name::<i8>("v")?, Some(0));
+    let err = row.get::<&str>(0).unwrap_err();
+    assert!(err.to_string().contains("column `v` (index 0)"), "{err}");
+    row.get::<i64>(1).unwrap_err();
+    row.get_by_name::<i64>("ts").unwrap_err();
+    let pairs = row.map(|(name, v)| (name, v.to_value())).collect_vec();
+    assert_eq!(pairs, [("v", Value::TinyInt(0))]);
+
+    let mut rest = Vec::new();
+    for row in rows {
+        rest.push(row?.get::<i64>(0)?);
+    }
+    assert_eq!(rest, [Some(1), Some(2), Some(3)]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_block_stream() -> anyhow::Result<()> {
+    use futures::TryStreamExt;
+    use taos_query::common::Value;
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock_adapter().await.addr)).await?;
+    let value = |block: RawBlock| block.to_values()[0][0].clone();
+    for prefetch in [0, 1] {
+        let mut rs = taos.s_query("select blocks").await?;
+        rs.set_prefetch(prefetch);
+        let mut blocks = rs.blocks();
+        assert_eq!(blocks.try_next().await?.map(value), Some(Value::TinyInt(0)));
+        // a stream dropped while fetching, the next one resumes with the block in flight.
+        let next = tokio::time::timeout(Duration::from_millis(5), blocks.try_next()).await;
+        assert!(next.is_err());
+        drop(blocks);
+        let rest: Vec<_> = rs.blocks().map_ok(value).try_collect().await?;
+        assert_eq!(
+            rest,
+            (1..MOCK_BLOCKS).map(Value::TinyInt).collect_vec(),
+            "prefetch: {prefetch}"
+        );
+        // completed streams stay terminated.
+        assert!(rs.blocks().try_next().await?.is_none());
+
+        // dropping mid-stream with the result set doesn't poison the connection.
+        let mut rs = taos.s_query("select blocks").await?;
+        rs.set_prefetch(prefetch);
+        let mut blocks = rs.blocks();
+        blocks.try_next().await?;
+        let _ = tokio::time::timeout(Duration::from_millis(5), blocks.try_next()).await;
+        drop(blocks);
+        drop(rs);
+        assert_eq!(taos.s_exec("insert 3").await?, 3);
+        let mut rs = taos.s_query("select blocks").await?;
+        assert_eq!(
+            rs.blocks().try_collect::<Vec<_>>().await?.len(),
+            MOCK_BLOCKS as usize
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_concurrent_queries() -> anyhow::Result<()> {
+    let addr = mock_adapter().await.addr;
+    let taos = Arc::new(WsTaos::from_dsn(format!("ws://{addr}")).await?);
+    let started = Instant::now();
+    let tasks = (0..64u64).map(|n| {
+        let taos = taos.clone();
+        tokio::spawn(async move { (n, taos.s_exec(&format!("insert {n}")).await) })
+    });
+    for task in futures::future::join_all(tasks).await {
+        let (n, rows) = task?;
+        assert_eq!(rows? as u64, n);
+    }
+    // replies of 50ms at most, not serialized.
+    assert!(started.elapsed() < Duration::from_secs(1));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_exec_many() -> anyhow::Result<()> {
+    let mock = mock_adapter().await;
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock.addr)).await?;
+    // inserts are sent before any reply, earlier ones are replied later.
+    let sqls: Vec<_> = (0..20).map(|n| format!("insert {n}")).collect();
+    assert_eq!(AsyncQueryable::exec_many(&taos, &sqls).await?, 190);
+    let received = std::mem::take(&mut *mock.queries.lock().unwrap());
+    assert_eq!(
+        received,
+        sqls.into_iter()
+            .enumerate()
+            .map(|(n, sql)| (sql, n))
+            .collect::<Vec<_>>()
+    );
+
+    // other statements wait for earlier replies and are waited for by later ones.
+    let sqls = [
+        "insert 1",
+        "insert 2",
+        "create table t",
+        "insert 3",
+        "insert 4",
+    ];
+    assert_eq!(taos.s_exec_many(sqls).await?, 10);
+    let received = std::mem::take(&mut *mock.queries.lock().unwrap());
+    assert_eq!(
+        received,
+        sqls.into_iter()
+            .zip([0, 1, 0, 0, 1])
+            .map(|(sql, n)| (sql.to_string(), n))
+            .collect::<Vec<_>>()
+    );
+
+    let err = taos
+        .s_exec_many(["insert 1", "fail", "insert 2"])
+        .await
+        .unwrap_err();
+    match &err {
+        Error::ExecMany {
+            index, sql, code, ..
+        } => {
+            assert_eq!((*index, sql.as_str()), (1, "fail"));
+            assert_eq!(*code, Code::new(0x2600));
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+    assert_eq!(err.errno(), Code::new(0x2600));
+    // statements after the failure are not executed.
+    let received = std::mem::take(&mut *mock.queries.lock().unwrap());
+    assert_eq!(
+        received,
+        [("insert 1".to_string(), 0), ("fail".to_string(), 0)]
+    );
+
+    let results = taos
+        .s_exec_many_results(["insert 30", "fail", "insert 2"])
+        .await;
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().ok(), Some(&30));
+    assert_eq!(results[1].as_ref().unwrap_err().errno(), Code::new(0x2600));
+    assert_eq!(results[2].as_ref().ok(), Some(&2));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_retry_idempotent() -> anyhow::Result<()> {
+    let mock = mock_adapter().await;
+    let dsn = format!("ws://{}?reconnect=true&retryBackoffMs=10", mock.addr);
+    let taos = WsTaos::from_dsn(dsn).await?;
+    let received = || std::mem::take(&mut *mock.queries.lock().unwrap());
+
+    // the insert may have been executed before the connection is lost, it's not sent again.
+    let err = taos.s_exec("insert 2 lost").await.unwrap_err();
+    assert!(err.is_conn_lost(), "{err}");
+    assert_eq!(received(), [("insert 2 lost".to_string(), 0)]);
+
+    // queries are sent again on a new connection, which is used later by the insert too.
+    assert_eq!(taos.s_exec("select 1 lost").await?, 0);
+    assert_eq!(received(), vec![("select 1 lost".to_string(), 0); 2]);
+    assert_eq!(taos.s_exec("insert 2").await?, 2);
+    assert_eq!(received(), [("insert 2".to_string(), 0)]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_req_id_provider() -> anyhow::Result<()> {
+    let addr = mock_adapter().await.addr;
+    let ids = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let generated = ids.clone();
+    let mut builder = TaosBuilder::from_dsn(format!("ws://{addr}"))?;
+    builder.set_req_id_provider(move || {
+        let mut ids = generated.lock().unwrap();
+        let id = 0x1234_0000 + ids.len() as u64;
+        ids.push(id);
+        id
+    });
+    let taos = WsTaos::from_wsinfo(&builder).await?;
+    assert_eq!(taos.s_exec_many(["insert 1", "insert 2"]).await?, 3);
+    assert_eq!(*ids.lock().unwrap(), [0x1234_0000, 0x1234_0001]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_affected_rows64() -> anyhow::Result<()> {
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock_adapter().await.addr)).await?;
+    let rows = 5_000_000_000u64;
+    let sql = format!("insert {rows}");
+    assert_eq!(AsyncQueryable::exec(&taos, &sql).await? as u64, rows);
+    assert_eq!(taos.s_exec_many([&sql, &sql]).await? as u64, rows * 2);
+
+    let rs = taos.s_query(&sql).await?;
+    assert_eq!(rs.affected_rows64(), rows as i64);
+    assert_eq!(AsyncFetchable::affected_rows64(&rs), rows as i64);
+    assert_eq!(taos_query::Fetchable::affected_rows64(&rs), rows as i64);
+    assert_eq!(rs.execution_summary().affected_rows, rows as i64);
+    // the 32-bit one saturates instead of wrapping around.
+    assert_eq!(rs.affected_rows(), i32::MAX);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_recorded_database() -> anyhow::Result<()> {
+    let addr = mock_adapter().await.addr;
+    let taos = WsTaos::from_dsn(format!("ws://{addr}/db0")).await?;
+    assert_eq!(taos.recorded_database().as_deref(), Some("db0"));
+    taos.use_database("db1").await?;
+    assert_eq!(taos.recorded_database().as_deref(), Some("db1"));
+    taos.s_exec("use `db2`").await?;
+    assert_eq!(taos.recorded_database().as_deref(), Some("db2"));
+    taos.s_exec_many(["insert 1", "use db3"]).await?;
+    assert_eq!(taos.recorded_database().as_deref(), Some("db3"));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_reconnect_database() -> anyhow::Result<()> {
+    let mock = mock_adapter().await;
+    let dsn = format!("ws://{}/db0?reconnect=true&retryBackoffMs=10", mock.addr);
+    let taos = WsTaos::from_dsn(dsn).await?;
+    taos.use_database("db1").await?;
+
+    // the recorded database is sent in the login of the new connection.
+    let lost = taos.sender();
+    let _ = taos.conn.read().unwrap().close_signal.send(true);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(lost.state.is_lost());
+    assert_eq!(taos.s_exec("insert 1").await?, 1);
+    assert!(!Arc::ptr_eq(&taos.sender().state, &lost.state));
+    assert_eq!(
+        *mock.conns.lock().unwrap(),
+        [Some("db0".to_string()), Some("db1".to_string())]
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_execution_summary() -> anyhow::Result<()> {
+    let mock = mock_adapter().await;
+    let taos = WsTaos::from_dsn(format!("ws://{}", mock.addr)).await?;
+    assert_eq!(AsyncQueryable::exec(&taos, "insert 5").await?, 5);
+
+    let rs = taos.s_query("insert 7").await?;
+    assert_eq!(rs.affected_rows(), 7);
+    assert_eq!(
+        rs.execution_summary(),
+        ExecSummary {
+            affected_rows: 7,
+            fields_count: 0,
+            precision: Precision::Millisecond,
+            is_update: true,
+        }
+    );
+
+    let rs = taos.s_query("create table t(ts timestamp, v int)").await?;
+    assert_eq!(rs.affected_rows64(), 0);
+    assert!(rs.execution_summary().is_update);
+
+    let rs = taos.s_query("select slow").await?;
+    let summary = rs.execution_summary();
+    assert_eq!((summary.affected_rows, summary.fields_count), (0, 1));
+    assert!(!summary.is_update);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_sync_threads() -> anyhow::Result<()> {
+    use taos_query::{Fetchable, Queryable, TBuilder};
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<super::Taos>();
+    assert_send_sync::<WsTaos>();
+
+    let addr = mock_adapter().await.addr;
+    let taos = Arc::new(TaosBuilder::from_dsn(format!("ws://{addr}"))?.build()?);
+    // sync calls from plain threads, outside of the runtime.
+    let threads: Vec<_> = (0..16usize)
+        .map(|t| {
+            let taos = taos.clone();
+            std::thread::spawn(move || -> anyhow::Result<()> {
+                for i in 0..16 {
+                    let n = t * 16 + i;
+                    if i % 2 == 0 {
+                        assert_eq!(Queryable::exec(&*taos, format!("insert {n}"))?, n);
+                    } else {
+                        let rs = Queryable::query(&*taos, format!("insert {n}"))?;
+                        assert_eq!(Fetchable::affected_rows(&rs) as usize, n);
+                    }
+                }
+                Ok(())
+            })
+        })
+        .collect();
+    tokio::task::spawn_blocking(move || {
+        for thread in threads {
+            thread.join().unwrap()?;
+        }
+        anyhow::Ok(())
+    })
+    .await??;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_concurrent_select() -> anyhow::Result<()> {
+    use futures::TryStreamExt;
+    use taos_query::AsyncQueryable;
+    let taos = Arc::new(WsTaos::from_dsn("ws://localhost:6041").await?);
+    let tasks = (0..48i64).map(|n| {
+        let taos = taos.clone();
+        tokio::spawn(async move {
+            let rows: Vec<(i64, String)> = taos
+                .query(format!("select {n}, '{n}' union all select {n}, 'x{n}'"))
+                .await?
+                .deserialize()
+                .try_collect()
+                .await?;
+            anyhow::Ok((n, rows))
+        })
+    });
+    for task in futures::future::join_all(tasks).await {
+        let (n, rows) = task??;
+        assert_eq!(rows.len(), 2);
+        assert!(rows
+            .iter()
+            .all(|(v, s)| *v == n && s.ends_with(&n.to_string())));
+    }
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_exec_summary_live() -> anyhow::Result<()> {
+    let taos = WsTaos::from_dsn("ws://localhost:6041").await?;
+    AsyncQueryable::exec(&taos, "drop database if exists ws_exec_summary").await?;
+    let rs = taos
+        .s_query("create database ws_exec_summary precision 'us'")
+        .await?;
+    assert!(rs.is_update());
+    assert_eq!(rs.affected_rows(), 0);
+    AsyncQueryable::exec(
+        &taos,
+        "create table ws_exec_summary.tb(ts timestamp, v int)",
+    )
+    .await?;
+
+    let rows = AsyncQueryable::exec(
+        &taos,
+        "insert into ws_exec_summary.tb values(now, 1)(now + 1s, 2)",
+    )
+    .await?;
+    assert_eq!(rows, 2);
+    let summary = taos
+        .s_query("insert into ws_exec_summary.tb values(now + 2s, 3)")
+        .await?
+        .execution_summary();
+    assert_eq!((summary.affected_rows, summary.is_update), (1, true));
+
+    let summary = taos
+        .s_query("select * from ws_exec_summary.tb")
+        .await?
+        .execution_summary();
+    assert_eq!(summary.fields_count, 2);
+    assert_eq!(summary.precision, Precision::Microsecond);
+    assert!(!summary.is_update);
+
+    AsyncQueryable::exec(&taos, "drop database ws_exec_summary").await?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_result_set_timing_live() -> anyhow::Result<()> {
+    use futures::TryStreamExt;
+    let taos = WsTaos::from_dsn("ws://localhost:6041").await?;
+    let mut rs = taos
+        .s_query("select * from information_schema.ins_columns")
+        .await?;
+    let blocks: Vec<_> = rs.blocks().try_collect().await?;
+    // one fetch per block and the last one with no data.
+    assert_eq!(rs.fetch_timings().len(), blocks.len() + 1);
+    assert!(rs.timing() >= rs.fetch_timings().iter().sum::<Duration>());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_drop_closes_connection() -> anyhow::Result<()> {
+    let MockAdapter { addr, open, .. } = mock_adapter().await;
+    let builder = TaosBuilder::from_dsn(format!("ws://{addr}"))?;
+    let metrics = tokio::runtime::Handle::current().metrics();
+
+    // warm up, so tasks of the mock adapter and runtime internals are counted in the baseline.
+    drop(WsTaos::from_wsinfo(&builder).await?);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let baseline = metrics.num_alive_tasks();
+
+    for _ in 0..20 {
+        let taos = WsTaos::from_wsinfo(&builder).await?;
+        let stmt = crate::Stmt::from_wsinfo(&builder).await?;
+        assert!(open.load(Ordering::SeqCst) >= 2);
+        drop(stmt);
+        drop(taos);
+    }
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while (open.load(Ordering::SeqCst) > 0 || metrics.num_alive_tasks() > baseline)
+        && std::time::Instant::now() < deadline
+    {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(
+        open.load(Ordering::SeqCst),
+        0,
+        "connections not closed by close frame"
+    );
+    assert!(
+        metrics.num_alive_tasks() <= baseline,
+        "tasks leaked: {} > {baseline}",
+        metrics.num_alive_tasks()
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_auth_provider() -> anyhow::Result<()> {
+    use std::sync::atomic::AtomicUsize;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // an adapter refusing all tokens.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 401 Unauthorized\r\ncontent-length: 0\r\n\r\n")
+                .await;
+        }
+    });
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut builder = TaosBuilder::from_dsn(format!("ws://{addr}?token=expired"))?;
+    let counter = calls.clone();
+    builder.set_auth_provider(move || {
+        let n = counter.fetch_add(1, Ordering::SeqCst);
+        crate::WsAuth::Token(format!("token{n}"))
+    });
+    let err = WsTaos::from_wsinfo(&builder).await.unwrap_err();
+    assert!(err.is_auth_failure(), "{err}");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // without provider, the failure is surfaced at once.
+    builder.auth_provider = None;
+    let err = WsTaos::from_wsinfo(&builder).await.unwrap_err();
+    assert!(err.is_auth_failure(), "{err}");
+
+    let err = |code| Error::TaosError(RawError::new(code, "refused"));
+    assert!(err(0x0357).is_auth_failure());
+    assert!(err(0x0018).is_auth_failure());
+    assert!(!err(0x0003).is_auth_failure());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_failover() -> anyhow::Result<()> {
+    let err = WsTaos::from_dsn("ws://localhost:1,localhost:2")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::EndpointsUnavailable { .. }));
+    let message = err.to_string();
+    assert!(message.contains("localhost:1:") && message.contains("localhost:2:"));
+
+    let client = WsTaos::from_dsn("ws://localhost:1,localhost:6041").await?;
+    assert_eq!(client.conn.read().unwrap().addr, "localhost:6041");
+    assert_eq!(client.exec("select 1").await?, 0);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_stmt_failover() -> anyhow::Result<()> {
+    let builder = TaosBuilder::from_dsn("ws://localhost:1,localhost:2")?;
+    let err = crate::stmt::Stmt::from_wsinfo(&builder).await.unwrap_err();
+    assert!(matches!(err, Error::EndpointsUnavailable { .. }), "{err}");
+
+    let mock = mock_adapter().await;
+    let builder = TaosBuilder::from_dsn(format!("ws://localhost:1,{}", mock.addr))?;
+    crate::stmt::Stmt::from_wsinfo(&builder).await?;
+    Ok(())
+}
+
+#[test]
+fn use_database_statement() {
+    assert_eq!(parse_use_database("use db1"), Some("db1".to_string()));
+    assert_eq!(parse_use_database(" USE `db1`;"), Some("db1".to_string()));
+    assert_eq!(parse_use_database("use"), None);
+    assert_eq!(parse_use_database("used db1"), None);
+    assert_eq!(parse_use_database("select 1"), None);
+}
+
+#[test]
+fn idempotent_statement() {
+    assert!(is_idempotent(" SELECT * from t"));
+    assert!(is_idempotent("show databases"));
+    assert!(is_idempotent("select(1)"));
+    assert!(is_idempotent("use db1"));
+    assert!(!is_idempotent("insert into t values(now, 1)"));
+    assert!(!is_idempotent("create table t(ts timestamp, v int)"));
+    assert!(!is_idempotent("selective"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_reconnect() -> anyhow::Result<()> {
+    use futures::TryStreamExt;
+    let client = WsTaos::from_dsn("ws://localhost:6041?reconnect=true&retryBackoffMs=10").await?;
+    // close the connection as if taosAdapter is restarted.
+    let lost = client.sender();
+    let _ = client.conn.read().unwrap().close_signal.send(true);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(lost.state.is_lost());
+
+    assert_eq!(client.exec("select 1").await?, 0);
+    assert!(!Arc::ptr_eq(&client.sender().state, &lost.state));
+
+    // database of `use` is restored.
+    client
+        .exec("create database if not exists ws_reconnect")
+        .await?;
+    client.exec("use ws_reconnect").await?;
+    let _ = client.conn.read().unwrap().close_signal.send(true);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut rs = client.query("select database()").await?;
+    let db: Vec<(String,)> = rs.deserialize().try_collect().await?;
+    assert_eq!(db, [("ws_reconnect".to_string(),)]);
+    client.exec("drop database ws_reconnect").await?;
+
+    let client = WsTaos::from_dsn("ws://localhost:6041").await?;
+    let _ = client.conn.read().unwrap().close_signal.send(true);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let err = client.exec("select 1").await.unwrap_err();
+    assert!(err.is_conn_lost());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_use_database() -> anyhow::Result<()> {
+    use futures::TryStreamExt;
+    let client = WsTaos::from_dsn("ws://localhost:6041?reconnect=true&retryBackoffMs=10").await?;
+    for db in ["ws_use_database_a", "ws_use_database_b"] {
+        client.exec(format!("drop database if exists {db}")).await?;
+        client.exec(format!("create database {db}")).await?;
+        client
+            .exec(format!("create table {db}.tb(ts timestamp, v int)"))
+            .await?;
+    }
+    client
+        .exec("insert into ws_use_database_b.tb values(now, 2)")
+        .await?;
+    assert_eq!(client.recorded_database(), None);
+    client.use_database("ws_use_database_a").await?;
+    client.use_database("ws_use_database_b").await?;
+    assert_eq!(
+        client.recorded_database().as_deref(),
+        Some("ws_use_database_b")
+    );
+    client.use_database("not a valid name").await.unwrap_err();
+    assert_eq!(
+        client.recorded_database().as_deref(),
+        Some("ws_use_database_b")
+    );
+
+    // unqualified tables still resolve in the selected database after reconnection.
+    let lost = client.sender();
+    let _ = client.conn.read().unwrap().close_signal.send(true);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(lost.state.is_lost());
+    let mut rs = client.query("select v from tb").await?;
+    let v: Vec<(i32,)> = rs.deserialize::<(i32,)>().try_collect().await?;
+    assert_eq!(v, [(2,)]);
+    assert!(!Arc::ptr_eq(&client.sender().state, &lost.state));
+
+    client.exec("drop database ws_use_database_a").await?;
+    client.exec("drop database ws_use_database_b").await?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ws_max_message_size() -> anyhow::Result<()> {
+    use futures::TryStreamExt;
+    let client = WsTaos::from_dsn("ws://localhost:6041?maxMessageSize=1k").await?;
+    let sql = format!("select '{}'", "x".repeat(2048));
+    let mut rs = client.query(&sql).await?;
+    let err = rs.blocks().try_next().await.unwrap_err();
+    assert!(err.to_string().contains("maxMessageSize"), "{err}");
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn ws_show_databases() -> anyhow::Result<()> {
     std::env::set_var("RUST_LOG", "debug");
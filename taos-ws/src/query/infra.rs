@@ -14,7 +14,9 @@ pub type ResId = u64;
 #[serde_as]
 #[derive(Debug, Serialize, Default, Clone)]
 pub struct WsConnReq {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) password: Option<String>,
     #[serde_as(as = "NoneAsEmptyString")]
     pub(crate) db: Option<String>,
@@ -60,6 +62,14 @@ pub enum WsSend {
     FetchBlock(WsResArgs),
     Binary(Vec<u8>),
     FreeResult(WsResArgs),
+    /// Schemaless insert, `precision` is empty for not configured.
+    Insert {
+        req_id: ReqId,
+        protocol: u8,
+        precision: String,
+        data: String,
+        ttl: i32,
+    },
 }
 
 impl WsSend {
@@ -70,15 +80,13 @@ impl WsSend {
             WsSend::Fetch(args) => args.req_id,
             WsSend::FetchBlock(args) => args.req_id,
             WsSend::FreeResult(args) => args.req_id,
+            WsSend::Insert { req_id, .. } => *req_id,
             WsSend::Binary(bytes) => unsafe { *(bytes.as_ptr() as *const u64) as _ },
             _ => unreachable!(),
         }
     }
 }
 
-unsafe impl Send for WsSend {}
-unsafe impl Sync for WsSend {}
-
 #[test]
 fn test_serde_send() {
     let s = WsSend::Conn {
@@ -110,16 +118,29 @@ pub struct WsFetchArgs {
 pub struct WsQueryResp {
     pub id: ResId,
     pub is_update: bool,
-    pub affected_rows: usize,
+    pub affected_rows: u64,
     pub fields_count: usize,
     pub fields_names: Option<Vec<String>>,
     pub fields_types: Option<Vec<Ty>>,
     pub fields_lengths: Option<Vec<u32>>,
+    pub fields_precisions: Option<Vec<u8>>,
+    pub fields_scales: Option<Vec<u8>>,
     pub precision: Precision,
     #[serde_as(as = "serde_with::DurationNanoSeconds")]
     pub timing: Duration,
 }
 
+/// Response of a schemaless insert, rows are 0 if the server doesn't report them.
+#[serde_as]
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct WsSchemalessResp {
+    pub affected_rows: usize,
+    pub total_rows: usize,
+    #[serde_as(as = "serde_with::DurationNanoSeconds")]
+    pub timing: Duration,
+}
+
 #[serde_as]
 #[derive(Debug, Default, Deserialize, Clone)]
 #[serde(default)]
@@ -157,6 +178,7 @@ pub enum WsRecvData {
         timing: Duration,
         raw: Vec<u8>,
     },
+    Insert(WsSchemalessResp),
     WriteMeta,
     WriteRaw,
     WriteRawBlock,
@@ -189,6 +211,91 @@ impl WsRecv {
     }
 }
 
+/// Protocol capabilities of the connected taosAdapter, negotiated by the `version` action before
+/// login.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdapterInfo {
+    /// Server version reported, `2.x` if the adapter predates the `version` action.
+    pub version: String,
+    /// The adapter answers the `version` action.
+    pub version_action: bool,
+    /// Binary blocks of fetch are in v3 layout with timing, or in v2 layout otherwise.
+    pub block_v3: bool,
+    /// `write_raw_block_with_fields` is supported, missing in 3.0.1.x only, so it's assumed for
+    /// adapters predating the `version` action too.
+    pub raw_block_with_fields: bool,
+}
+
+impl AdapterInfo {
+    /// Capabilities from the reply to the `version` action, `None` if no reply in time.
+    ///
+    /// Adapters predating the action reply nothing or an error like `unknown action`, the
+    /// legacy formats are used with them.
+    pub(crate) fn negotiate(reply: Option<&str>) -> Self {
+        let version = reply.and_then(|text| match serde_json::from_str::<WsRecv>(text) {
+            Ok(WsRecv {
+                code: 0,
+                data: WsRecvData::Version { version },
+                ..
+            }) => Some(version),
+            _ => {
+                log::warn!("version action is not supported by taosAdapter: {text}");
+                None
+            }
+        });
+        match version {
+            Some(version) => {
+                let block_v3 = !version.starts_with('2');
+                AdapterInfo {
+                    raw_block_with_fields: !version.starts_with("3.0.1."),
+                    version,
+                    version_action: true,
+                    block_v3,
+                }
+            }
+            None => AdapterInfo {
+                version: "2.x".to_string(),
+                version_action: false,
+                block_v3: false,
+                raw_block_with_fields: true,
+            },
+        }
+    }
+}
+
+#[test]
+fn test_adapter_info() {
+    let info = AdapterInfo::negotiate(Some(
+        r#"{"code":0,"message":"","action":"version","version":"3.0.4.1"}"#,
+    ));
+    assert_eq!(
+        info,
+        AdapterInfo {
+            version: "3.0.4.1".to_string(),
+            version_action: true,
+            block_v3: true,
+            raw_block_with_fields: true,
+        }
+    );
+    let info = AdapterInfo::negotiate(Some(
+        r#"{"code":0,"message":"","action":"version","version":"3.0.1.8"}"#,
+    ));
+    assert!(info.block_v3 && !info.raw_block_with_fields);
+
+    let legacy = AdapterInfo::negotiate(None);
+    assert_eq!(legacy.version, "2.x");
+    assert!(!legacy.version_action && !legacy.block_v3);
+    // only 3.0.1.x lacks write_raw_block_with_fields, unknown versions are sent it as always.
+    assert!(legacy.raw_block_with_fields);
+    for reply in [
+        r#"{"code":65535,"message":"unknown action","action":"version"}"#,
+        r#"{"code":65535,"message":"unknown action","action":""}"#,
+        "not json",
+    ] {
+        assert_eq!(AdapterInfo::negotiate(Some(reply)), legacy);
+    }
+}
+
 #[test]
 fn test_serde_recv_data() {
     let json = r#"{
@@ -201,6 +308,40 @@ fn test_serde_recv_data() {
     dbg!(d);
 }
 
+#[test]
+fn test_serde_insert() {
+    let s = WsSend::Insert {
+        req_id: 1,
+        protocol: 2,
+        precision: "ms".to_string(),
+        data: "meters.current 1648432611249 10.3 location=California".to_string(),
+        ttl: 0,
+    };
+    let v = serde_json::to_value(&s).unwrap();
+    assert_eq!(v["action"], "insert");
+    assert_eq!(v["args"]["protocol"], 2);
+    assert_eq!(v["args"]["precision"], "ms");
+
+    let json = r#"{
+        "code": 0,
+        "message": "",
+        "action": "insert",
+        "req_id": 1,
+        "timing": 100,
+        "affected_rows": 2,
+        "total_rows": 3
+    }"#;
+    let d: WsRecv = serde_json::from_str(json).unwrap();
+    match d.data {
+        WsRecvData::Insert(resp) => {
+            assert_eq!(resp.affected_rows, 2);
+            assert_eq!(resp.total_rows, 3);
+            assert_eq!(resp.timing, Duration::from_nanos(100));
+        }
+        _ => panic!("not an insert response"),
+    }
+}
+
 pub(crate) trait ToMessage: Serialize {
     // #[cfg(feature = "async")]
     fn to_msg(&self) -> tokio_tungstenite::tungstenite::Message {
@@ -210,6 +351,19 @@ pub(crate) trait ToMessage: Serialize {
 
 impl ToMessage for WsSend {}
 
+/// Describe error `err` reading websocket, telling which option to raise if the message exceeds
+/// the client limit.
+pub(crate) fn read_error(err: &tokio_tungstenite::tungstenite::Error) -> String {
+    use tokio_tungstenite::tungstenite::{error::CapacityError, Error};
+    match err {
+        Error::Capacity(CapacityError::MessageTooLong { size, max_size }) => format!(
+            "websocket message of {size} bytes exceeds the client limit of {max_size} bytes, \
+             raise maxMessageSize in dsn"
+        ),
+        err => format!("WebSocket internal error: {err}"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -221,4 +375,288 @@ mod tests {
     fn dsn_error() {
         TaosBuilder::from_dsn("").unwrap_err();
     }
+
+    #[test]
+    fn path_prefix() {
+        let builder = TaosBuilder::from_dsn("ws://gateway.example.com:443/tdengine/db1").unwrap();
+        assert_eq!(
+            builder.to_query_url(),
+            "ws://gateway.example.com:443/tdengine/rest/ws"
+        );
+        assert_eq!(builder.database.as_deref(), Some("db1"));
+
+        let builder =
+            TaosBuilder::from_dsn("wss://gateway.example.com/db1?path=/tdengine/&token=abc")
+                .unwrap();
+        assert_eq!(
+            builder.to_stmt_url(),
+            "wss://gateway.example.com:443/tdengine/rest/stmt?token=abc"
+        );
+        assert_eq!(
+            builder.to_tmq_url(),
+            "wss://gateway.example.com:443/tdengine/rest/tmq?token=abc"
+        );
+        assert_eq!(builder.database.as_deref(), Some("db1"));
+
+        let builder = TaosBuilder::from_dsn("ws://gateway.example.com/a/b/").unwrap();
+        assert!(builder.to_query_url().ends_with(".com/a/b/rest/ws"));
+        assert_eq!(builder.database, None);
+
+        let builder = TaosBuilder::from_dsn("ws://localhost:6041/db1").unwrap();
+        assert_eq!(builder.to_query_url(), "ws://localhost:6041/rest/ws");
+        assert_eq!(builder.database.as_deref(), Some("db1"));
+    }
+
+    #[test]
+    fn cloud_dsn() {
+        let env = |key: &str| (key == "TDENGINE_CLOUD_TOKEN").then(|| "abc".to_string());
+        let builder = TaosBuilder::from_dsn_with_env("wss://gw.cloud.example.com", env).unwrap();
+        assert_eq!(
+            builder.to_query_url(),
+            "wss://gw.cloud.example.com:443/rest/ws?token=abc"
+        );
+        assert_eq!(
+            builder.to_tmq_url(),
+            "wss://gw.cloud.example.com:443/rest/tmq?token=abc"
+        );
+        let builder =
+            TaosBuilder::from_dsn_with_env("wss://gw.cloud.example.com?token=xyz", env).unwrap();
+        assert!(builder.to_stmt_url().ends_with(":443/rest/stmt?token=xyz"));
+
+        // credentials or plain ws never take the token from environment.
+        let builder =
+            TaosBuilder::from_dsn_with_env("wss://u:p@gw.cloud.example.com:6041", env).unwrap();
+        assert_eq!(
+            builder.to_query_url(),
+            "wss://gw.cloud.example.com:6041/rest/ws"
+        );
+        let builder = TaosBuilder::from_dsn_with_env("ws://localhost", env).unwrap();
+        assert_eq!(builder.to_query_url(), "ws://localhost:6041/rest/ws");
+
+        let err =
+            TaosBuilder::from_dsn_with_env("wss://gw.cloud.example.com?token=", env).unwrap_err();
+        assert!(err.to_string().contains("TDENGINE_CLOUD_TOKEN"), "{err}");
+    }
+
+    #[test]
+    fn conn_request() {
+        let builder = TaosBuilder::from_dsn("ws://localhost:6041/db1?token=abc").unwrap();
+        assert!(builder.to_query_url().ends_with("/rest/ws?token=abc"));
+        let req = builder.to_conn_request();
+        assert_eq!(req.user, None);
+        assert_eq!(req.password, None);
+        assert_eq!(req.db.as_deref(), Some("db1"));
+        assert_eq!(serde_json::to_string(&req).unwrap(), r#"{"db":"db1"}"#);
+
+        let builder = TaosBuilder::from_dsn("ws://u1:p1@localhost:6041").unwrap();
+        assert!(builder.to_query_url().ends_with("/rest/ws"));
+        let req = builder.to_conn_request();
+        assert_eq!(req.user.as_deref(), Some("u1"));
+        assert_eq!(req.password.as_deref(), Some("p1"));
+        assert_eq!(
+            serde_json::to_string(&req).unwrap(),
+            r#"{"user":"u1","password":"p1","db":""}"#
+        );
+    }
+
+    #[test]
+    fn conn_timeout_param() {
+        use std::time::Duration;
+        let timeout = |dsn: &str| TaosBuilder::from_dsn(dsn).unwrap().conn_timeout;
+        assert_eq!(timeout("ws://localhost:6041"), None);
+        assert_eq!(
+            timeout("ws://localhost:6041?connTimeout=5"),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            timeout("ws://localhost:6041?connTimeout=5s"),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            timeout("ws://localhost:6041?connTimeout=500ms"),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(timeout("ws://localhost:6041?connTimeout=0"), None);
+        TaosBuilder::from_dsn("ws://localhost:6041?connTimeout=abc").unwrap_err();
+    }
+
+    #[test]
+    fn query_timeout_param() {
+        use std::time::Duration;
+        let builder = TaosBuilder::from_dsn("ws://localhost:6041?queryTimeout=1m").unwrap();
+        assert_eq!(builder.query_timeout(), Some(Duration::from_secs(60)));
+        assert_eq!(builder.conn_timeout(), None);
+        let builder = TaosBuilder::from_dsn("ws://localhost:6041?queryTimeout=0").unwrap();
+        assert_eq!(builder.query_timeout(), None);
+        TaosBuilder::from_dsn("ws://localhost:6041?queryTimeout=-1").unwrap_err();
+    }
+
+    #[test]
+    fn fetch_timeout_param() {
+        use std::time::Duration;
+        let timeout = |dsn: &str| TaosBuilder::from_dsn(dsn).unwrap().fetch_timeout();
+        assert_eq!(
+            timeout("ws://localhost:6041"),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            timeout("ws://localhost:6041?fetchTimeout=10s"),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(timeout("ws://localhost:6041?fetchTimeout=0"), None);
+    }
+
+    #[test]
+    fn prefetch_param() {
+        let prefetch = |dsn: &str| TaosBuilder::from_dsn(dsn).map(|b| b.prefetch());
+        assert_eq!(prefetch("ws://localhost:6041").unwrap(), 1);
+        assert_eq!(prefetch("ws://localhost:6041?prefetch=4").unwrap(), 4);
+        assert_eq!(prefetch("ws://localhost:6041?prefetch=0").unwrap(), 0);
+        prefetch("ws://localhost:6041?prefetch=-1").unwrap_err();
+    }
+
+    #[test]
+    fn keepalive_param() {
+        use std::time::Duration;
+        let keepalive = |dsn: &str| TaosBuilder::from_dsn(dsn).unwrap().keepalive();
+        assert_eq!(
+            keepalive("ws://localhost:6041"),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            keepalive("ws://localhost:6041?keepaliveInterval=5"),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(keepalive("ws://localhost:6041?keepaliveInterval=0"), None);
+    }
+
+    #[test]
+    fn endpoints() {
+        let builder = TaosBuilder::from_dsn("ws://node1:6041,node2:6041/db").unwrap();
+        assert_eq!(builder.endpoints(), ["node1:6041", "node2:6041"]);
+        assert_eq!(builder.balanced().endpoints(), builder.endpoints());
+
+        let mut builder = builder;
+        builder.addr = "node2:6041".to_string();
+        assert_eq!(builder.endpoints(), ["node2:6041", "node1:6041"]);
+
+        let builder =
+            TaosBuilder::from_dsn("ws://node1:6041,node2:6041,node3:6041?loadBalance=true")
+                .unwrap();
+        let endpoints = builder.balanced().endpoints();
+        assert_eq!(endpoints.len(), 3);
+        assert!(builder
+            .endpoints()
+            .iter()
+            .all(|addr| endpoints.contains(addr)));
+    }
+
+    #[test]
+    fn ssl_ca_param() {
+        let builder =
+            TaosBuilder::from_dsn("wss://localhost:6041?sslCa=/etc/ca1.pem,/etc/ca2.pem").unwrap();
+        assert_eq!(builder.ssl_ca().len(), 2);
+        assert_eq!(builder.ssl_ca()[1], std::path::Path::new("/etc/ca2.pem"));
+        assert!(TaosBuilder::from_dsn("wss://localhost:6041")
+            .unwrap()
+            .ssl_ca()
+            .is_empty());
+    }
+
+    #[test]
+    fn ssl_verify_params() {
+        let builder = TaosBuilder::from_dsn("wss://localhost:6041").unwrap();
+        assert!(builder.ssl_verify() && builder.ssl_verify_hostname());
+        let builder =
+            TaosBuilder::from_dsn("wss://localhost:6041?sslVerifyHostname=false").unwrap();
+        assert!(builder.ssl_verify() && !builder.ssl_verify_hostname());
+        let builder = TaosBuilder::from_dsn("wss://localhost:6041?sslVerify=false").unwrap();
+        assert!(!builder.ssl_verify());
+        TaosBuilder::from_dsn("wss://localhost:6041?sslVerify=no").unwrap_err();
+    }
+
+    #[test]
+    fn proxy_param() {
+        let builder = TaosBuilder::from_dsn("wss://cloud.example.com?proxy=proxy:3128").unwrap();
+        assert_eq!(format!("{}", builder.proxy.unwrap()), "proxy:3128");
+        let builder =
+            TaosBuilder::from_dsn("wss://cloud.example.com/db?proxy=http://u:p@proxy:3128")
+                .unwrap();
+        assert_eq!(
+            format!("{:?}", builder.proxy.unwrap()),
+            "Proxy { addr: \"proxy:3128\", user: Some(\"u\") }"
+        );
+        TaosBuilder::from_dsn("wss://cloud.example.com?proxy=socks5://proxy:1080").unwrap_err();
+    }
+
+    #[test]
+    fn reconnect_params() {
+        use std::time::Duration;
+        let builder = TaosBuilder::from_dsn("ws://localhost:6041").unwrap();
+        assert!(!builder.reconnect());
+        assert_eq!(builder.max_retries(), 3);
+        assert_eq!(builder.retry_backoff(), Duration::from_millis(200));
+        let builder = TaosBuilder::from_dsn(
+            "ws://localhost:6041?reconnect=true&maxRetries=5&retryBackoffMs=50",
+        )
+        .unwrap();
+        assert!(builder.reconnect());
+        assert_eq!(builder.max_retries(), 5);
+        assert_eq!(builder.retry_backoff(), Duration::from_millis(50));
+        TaosBuilder::from_dsn("ws://localhost:6041?reconnect=yes").unwrap_err();
+        TaosBuilder::from_dsn("ws://localhost:6041?maxRetries=-1").unwrap_err();
+    }
+
+    #[test]
+    fn max_message_size_param() {
+        let size = |dsn: &str| TaosBuilder::from_dsn(dsn).map(|b| b.max_message_size());
+        assert_eq!(size("ws://localhost:6041").unwrap(), Some(256 << 20));
+        assert_eq!(
+            size("ws://localhost:6041?maxMessageSize=1048576").unwrap(),
+            Some(1 << 20)
+        );
+        assert_eq!(
+            size("ws://localhost:6041?maxMessageSize=64k").unwrap(),
+            Some(64 << 10)
+        );
+        assert_eq!(
+            size("ws://localhost:6041?maxMessageSize=1GB").unwrap(),
+            Some(1 << 30)
+        );
+        assert_eq!(size("ws://localhost:6041?maxMessageSize=0").unwrap(), None);
+        size("ws://localhost:6041?maxMessageSize=10t").unwrap_err();
+        size("ws://localhost:6041?maxMessageSize=-1").unwrap_err();
+
+        use tokio_tungstenite::tungstenite::{error::CapacityError, Error as WsError};
+        let err = WsError::Capacity(CapacityError::MessageTooLong {
+            size: 2048,
+            max_size: 1024,
+        });
+        assert!(super::read_error(&err).contains("raise maxMessageSize"));
+    }
+
+    #[test]
+    fn conn_timeout_non_routable() {
+        use std::time::{Duration, Instant};
+        use taos_query::Queryable;
+        let taos = TaosBuilder::from_dsn("ws://10.255.255.1:6041?connTimeout=1s")
+            .unwrap()
+            .build()
+            .unwrap();
+        let now = Instant::now();
+        taos.exec("select 1").unwrap_err();
+        assert!(now.elapsed() < Duration::from_secs(3));
+    }
+
+    /// Connect with a token of TDengine Cloud in `TDENGINE_CLOUD_DSN`, skipped if not set.
+    #[test]
+    fn token_auth() -> anyhow::Result<()> {
+        use taos_query::Queryable;
+        let Ok(dsn) = std::env::var("TDENGINE_CLOUD_DSN") else {
+            return Ok(());
+        };
+        let taos = TaosBuilder::from_dsn(dsn)?.build()?;
+        let _: Option<String> = taos.query_one("select server_version()")?;
+        Ok(())
+    }
 }
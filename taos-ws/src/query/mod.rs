@@ -1,41 +1,164 @@
+use std::{sync::RwLock, time::Duration};
+
 use once_cell::sync::OnceCell;
 use taos_query::{block_in_place_or_global, common::RawMeta, AsyncQueryable};
 
 pub mod asyn;
 pub(crate) mod infra;
+mod version;
 // pub mod sync;
 
 pub use asyn::Error;
+pub use asyn::ExecSummary;
 pub use asyn::ResultSet;
 pub(crate) use asyn::WsTaos;
+pub use infra::AdapterInfo;
 pub(crate) use infra::WsConnReq;
+pub use infra::WsSchemalessResp;
+pub use version::ServerVersion;
 
 use crate::TaosBuilder;
 
 #[derive(Debug)]
 pub struct Taos {
-    pub(crate) dsn: TaosBuilder,
+    /// Builder of new connections, the database is updated by `select_db`.
+    pub(crate) dsn: RwLock<TaosBuilder>,
     pub(crate) async_client: OnceCell<WsTaos>,
 }
 
 impl Taos {
+    /// Server version reported on connecting.
+    ///
+    /// Panics if the connection can't be established, see [Taos::connect] for the error.
     pub fn version(&self) -> &str {
-        block_in_place_or_global(self.client()).version()
+        block_in_place_or_global(self.client())
+            .expect("failed to connect to taosAdapter")
+            .version()
+    }
+
+    /// Capabilities negotiated with taosAdapter, for debugging.
+    ///
+    /// Panics if the connection can't be established, see [Taos::connect] for the error.
+    pub fn adapter_info(&self) -> AdapterInfo {
+        block_in_place_or_global(self.client())
+            .expect("failed to connect to taosAdapter")
+            .adapter_info()
+    }
+
+    /// Parsed server version, cached after the first call.
+    pub fn server_version(&self) -> Result<&ServerVersion, Error> {
+        block_in_place_or_global(async { self.client().await?.server_version().await })
+    }
+
+    /// Check the server is `min` or newer, like `3.0.2`, the error tells both versions.
+    pub fn assert_compatible(&self, min: &str) -> Result<(), Error> {
+        block_in_place_or_global(async { self.client().await?.assert_compatible(min).await })
     }
 
-    async fn client(&self) -> &WsTaos {
+    /// Establish the connection if not yet, returns the server version.
+    pub async fn connect(&self) -> Result<&str, Error> {
+        Ok(self.client().await?.version())
+    }
+
+    /// Switch to database `db`, same to [Taos::use_database].
+    pub fn select_db(&self, db: &str) -> Result<(), Error> {
+        self.use_database(db)
+    }
+
+    /// Switch to database `db`.
+    ///
+    /// The database is recorded and will be used when a new connection is established, both on
+    /// reconnection and by [Taos::builder].
+    pub fn use_database(&self, db: &str) -> Result<(), Error> {
+        block_in_place_or_global(self.use_database_async(db))
+    }
+
+    /// Async version of [Taos::use_database].
+    pub async fn use_database_async(&self, db: &str) -> Result<(), Error> {
+        self.client().await?.use_database(db).await?;
+        self.dsn.write().unwrap_or_else(|e| e.into_inner()).database = Some(db.to_string());
+        Ok(())
+    }
+
+    /// Builder of the connection, with the database switched by `select_db`.
+    pub fn builder(&self) -> TaosBuilder {
+        self.dsn.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Current database of the connection, queried from server so it reflects `use` statements.
+    pub fn current_database(&self) -> Result<Option<String>, Error> {
+        taos_query::Queryable::query_one(self, "select database()")
+    }
+
+    /// Query with a caller-supplied request id instead of the generated one.
+    pub fn query_with_req_id(&self, sql: &str, req_id: u64) -> Result<ResultSet, Error> {
+        block_in_place_or_global(async {
+            self.client().await?.s_query_with_req_id(sql, req_id).await
+        })
+    }
+
+    /// Query with a deadline on the round trip.
+    pub fn query_timeout(&self, sql: &str, timeout: Duration) -> Result<ResultSet, Error> {
+        block_in_place_or_global(async { self.client().await?.s_query_timeout(sql, timeout).await })
+    }
+
+    /// Execute with a deadline on the round trip, returns affected rows.
+    pub fn exec_timeout(&self, sql: &str, timeout: Duration) -> Result<usize, Error> {
+        block_in_place_or_global(async { self.client().await?.s_exec_timeout(sql, timeout).await })
+    }
+
+    /// Schemaless insert of `data` in `protocol` (1 line, 2 OpenTSDB telnet, 3 OpenTSDB json)
+    /// with a caller-supplied request id, 0 for a generated one.
+    ///
+    /// `precision` is one of `h`, `m`, `s`, `ms`, `u`, `ns`, or empty for not configured.
+    pub fn schemaless_insert(
+        &self,
+        data: &str,
+        protocol: u8,
+        precision: &str,
+        ttl: i32,
+        req_id: u64,
+    ) -> Result<WsSchemalessResp, Error> {
+        block_in_place_or_global(async {
+            self.client()
+                .await?
+                .s_schemaless_insert(data, protocol, precision, ttl, req_id)
+                .await
+        })
+    }
+
+    /// Write a raw block without its fields, the columns are written to the table by position.
+    pub fn write_raw_block_without_fields(
+        &self,
+        block: &taos_query::RawBlock,
+    ) -> Result<(), Error> {
+        block_in_place_or_global(async {
+            self.client()
+                .await?
+                .s_write_raw_block_without_fields(block)
+                .await
+        })
+    }
+
+    /// Execute statements pipelined like [WsTaos::s_exec_many], returns affected rows or the
+    /// error of each statement in order, statements after a failure are still executed.
+    pub fn exec_many_results<T: AsRef<str>>(
+        &self,
+        sqls: impl IntoIterator<Item = T>,
+    ) -> Result<Vec<Result<usize, Error>>, Error> {
+        block_in_place_or_global(async { Ok(self.client().await?.s_exec_many_results(sqls).await) })
+    }
+
+    /// The connection, established on first use.
+    async fn client(&self) -> Result<&WsTaos, Error> {
         if let Some(ws) = self.async_client.get() {
-            ws
-        } else {
-            let async_client = WsTaos::from_wsinfo(&self.dsn).await.unwrap();
-            self.async_client.get_or_init(|| async_client)
+            return Ok(ws);
         }
+        let async_client = WsTaos::from_wsinfo(&self.builder()).await?;
+        Ok(self.async_client.get_or_init(|| async_client))
     }
 }
 
-unsafe impl Send for Taos {}
-unsafe impl Sync for Taos {}
-
 #[async_trait::async_trait]
 impl taos_query::AsyncQueryable for Taos {
     type Error = asyn::Error;
@@ -46,39 +169,28 @@ impl taos_query::AsyncQueryable for Taos {
         &self,
         sql: T,
     ) -> Result<Self::AsyncResultSet, Self::Error> {
-        if let Some(ws) = self.async_client.get() {
-            ws.s_query(sql.as_ref()).await
-        } else {
-            let async_client = WsTaos::from_wsinfo(&self.dsn).await?;
-            self.async_client
-                .get_or_init(|| async_client)
-                .s_query(sql.as_ref())
-                .await
-        }
+        self.client().await?.s_query(sql.as_ref()).await
+    }
+
+    async fn exec<T: AsRef<str> + Send + Sync>(&self, sql: T) -> Result<usize, Self::Error> {
+        self.client().await?.s_exec(sql.as_ref()).await
+    }
+
+    async fn exec_many<T, I>(&self, input: I) -> Result<usize, Self::Error>
+    where
+        T: AsRef<str> + Send + Sync,
+        I::IntoIter: Send,
+        I: IntoIterator<Item = T> + Send,
+    {
+        self.client().await?.s_exec_many(input).await
     }
 
     async fn write_raw_meta(&self, raw: RawMeta) -> Result<(), Self::Error> {
-        if let Some(ws) = self.async_client.get() {
-            ws.write_meta(raw).await
-        } else {
-            let async_client = WsTaos::from_wsinfo(&self.dsn).await?;
-            self.async_client
-                .get_or_init(|| async_client)
-                .write_meta(raw)
-                .await
-        }
+        self.client().await?.write_meta(raw).await
     }
 
     async fn write_raw_block(&self, block: &taos_query::RawBlock) -> Result<(), Self::Error> {
-        if let Some(ws) = self.async_client.get() {
-            ws.write_raw_block(block).await
-        } else {
-            let async_client = WsTaos::from_wsinfo(&self.dsn).await?;
-            self.async_client
-                .get_or_init(|| async_client)
-                .write_raw_block(block)
-                .await
-        }
+        self.client().await?.write_raw_block(block).await
     }
 }
 
@@ -91,6 +203,23 @@ impl taos_query::Queryable for Taos {
         let sql = sql.as_ref();
         block_in_place_or_global(<Self as AsyncQueryable>::query(self, sql))
     }
+
+    fn exec<T: AsRef<str>>(&self, sql: T) -> Result<usize, Self::Error> {
+        let sql = sql.as_ref();
+        block_in_place_or_global(<Self as AsyncQueryable>::exec(self, sql))
+    }
+
+    fn exec_many<T: AsRef<str>, I: IntoIterator<Item = T>>(
+        &self,
+        input: I,
+    ) -> Result<usize, Self::Error> {
+        let sqls: Vec<_> = input
+            .into_iter()
+            .map(|sql| sql.as_ref().to_string())
+            .collect();
+        block_in_place_or_global(<Self as AsyncQueryable>::exec_many(self, sqls))
+    }
+
     fn write_raw_meta(&self, meta: RawMeta) -> Result<(), Self::Error> {
         block_in_place_or_global(<Self as AsyncQueryable>::write_raw_meta(self, meta))
     }
@@ -108,6 +237,91 @@ mod tests {
 
     use crate::TaosBuilder;
 
+    #[test]
+    fn ping() -> anyhow::Result<()> {
+        let mut builder = TaosBuilder::from_dsn("taosws://localhost:1/")?;
+        builder.set_conn_timeout(std::time::Duration::from_secs(1));
+        let mut taos = builder.build()?;
+        assert!(builder.ping(&mut taos).is_err());
+
+        let builder = TaosBuilder::from_dsn("taosws://localhost:6041/")?;
+        let mut taos = builder.build()?;
+        builder.ping(&mut taos)?;
+        // reuses the connection established by the first ping.
+        let version = taos.version().to_string();
+        builder.ping(&mut taos)?;
+        assert_eq!(taos.version(), version);
+        Ok(())
+    }
+
+    #[test]
+    fn server_version_unreachable() -> anyhow::Result<()> {
+        let mut builder = TaosBuilder::from_dsn("taosws://localhost:1/")?;
+        builder.set_conn_timeout(std::time::Duration::from_secs(1));
+        let taos = builder.build()?;
+        // connection errors are returned instead of panicking.
+        taos.server_version().unwrap_err();
+        taos.assert_compatible("3.0").unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn query_timeout() -> anyhow::Result<()> {
+        use std::time::Duration;
+        use taos_query::Queryable;
+        let taos = TaosBuilder::from_dsn("taosws://localhost:6041/")?.build()?;
+        let err = taos
+            .exec_timeout("select 1", Duration::from_nanos(1))
+            .unwrap_err();
+        assert_eq!(
+            err.errno(),
+            super::asyn::WS_ERROR_NO::QUERY_TIMEOUT.as_code()
+        );
+        // late reply of the timed out query is not delivered to the next one.
+        let v: Option<i64> = taos.query_one("select 2")?;
+        assert_eq!(v, Some(2));
+
+        let taos = TaosBuilder::from_dsn("taosws://localhost:6041/?queryTimeout=1ns")?.build()?;
+        let err = taos.exec("select 1").unwrap_err();
+        assert_eq!(
+            err.errno(),
+            super::asyn::WS_ERROR_NO::QUERY_TIMEOUT.as_code()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keepalive() -> anyhow::Result<()> {
+        use taos_query::Queryable;
+        let taos =
+            TaosBuilder::from_dsn("taosws://localhost:6041/?keepaliveInterval=1s")?.build()?;
+        let _: Option<i64> = taos.query_one("select 1")?;
+        // idle for a few keepalive rounds, pongs keep the connection alive.
+        std::thread::sleep(std::time::Duration::from_secs(3));
+        let v: Option<i64> = taos.query_one("select 2")?;
+        assert_eq!(v, Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_timeout() -> anyhow::Result<()> {
+        use std::time::Duration;
+        use taos_query::{Fetchable, Queryable};
+        let taos = TaosBuilder::from_dsn("taosws://localhost:6041/")?.build()?;
+        let mut rs = taos.query("show databases")?;
+        rs.set_fetch_timeout(Duration::from_nanos(1));
+        let err = rs.fetch_raw_block().unwrap_err();
+        assert_eq!(
+            err.errno(),
+            super::asyn::WS_ERROR_NO::RECV_MESSAGE_TIMEOUT.as_code()
+        );
+
+        let taos = TaosBuilder::from_dsn("taosws://localhost:6041/?fetchTimeout=1ns")?.build()?;
+        let mut rs = taos.query("show databases")?;
+        assert!(rs.fetch_raw_block().is_err());
+        Ok(())
+    }
+
     #[test]
     fn ws_sync_json() -> anyhow::Result<()> {
         std::env::set_var("RUST_LOG", "debug");
@@ -314,6 +528,106 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn ws_sync_all_types() -> anyhow::Result<()> {
+        use taos_query::common::{Timestamp, Ty, Value};
+        use taos_query::{Fetchable, Queryable, TBuilder};
+        let client = TaosBuilder::from_dsn("ws://localhost:6041/")?.build()?;
+        client.exec("drop database if exists ws_sync_all_types")?;
+        client.exec("create database ws_sync_all_types keep 36500")?;
+        client.exec(
+            "create table ws_sync_all_types.tb(ts timestamp, cb bool,\
+                c8i tinyint, c16i smallint, c32i int, c64i bigint,\
+                c8u tinyint unsigned, c16u smallint unsigned, c32u int unsigned, c64u bigint unsigned,\
+                cf float, cd double, cbin binary(16), cn nchar(16))",
+        )?;
+        client.exec(
+            "insert into ws_sync_all_types.tb values\
+                (1000, true, -1, -2, -3, -4, 1, 2, 3, 4, 1.5, 2.5, 'abc', '涛思数据')\
+                (2000, null, null, null, null, null, null, null, null, null, null, null, null, null)",
+        )?;
+
+        let rows = client
+            .query("select * from ws_sync_all_types.tb")?
+            .to_rows_vec()?;
+        assert_eq!(
+            rows,
+            [
+                vec![
+                    Value::Timestamp(Timestamp::Milliseconds(1000)),
+                    Value::Bool(true),
+                    Value::TinyInt(-1),
+                    Value::SmallInt(-2),
+                    Value::Int(-3),
+                    Value::BigInt(-4),
+                    Value::UTinyInt(1),
+                    Value::USmallInt(2),
+                    Value::UInt(3),
+                    Value::UBigInt(4),
+                    Value::Float(1.5),
+                    Value::Double(2.5),
+                    Value::VarChar("abc".to_string()),
+                    Value::NChar("涛思数据".to_string()),
+                ],
+                vec![
+                    Value::Timestamp(Timestamp::Milliseconds(2000)),
+                    Value::Null(Ty::Bool),
+                    Value::Null(Ty::TinyInt),
+                    Value::Null(Ty::SmallInt),
+                    Value::Null(Ty::Int),
+                    Value::Null(Ty::BigInt),
+                    Value::Null(Ty::UTinyInt),
+                    Value::Null(Ty::USmallInt),
+                    Value::Null(Ty::UInt),
+                    Value::Null(Ty::UBigInt),
+                    Value::Null(Ty::Float),
+                    Value::Null(Ty::Double),
+                    Value::Null(Ty::VarChar),
+                    Value::Null(Ty::NChar),
+                ],
+            ]
+        );
+
+        client.exec("drop database ws_sync_all_types")?;
+        Ok(())
+    }
+
+    #[test]
+    fn ws_sync_row_get() -> anyhow::Result<()> {
+        use taos_query::{Fetchable, Queryable, TBuilder};
+        let client = TaosBuilder::from_dsn("ws://localhost:6041/")?.build()?;
+        client.exec("drop database if exists ws_sync_row_get")?;
+        client.exec("create database ws_sync_row_get keep 36500")?;
+        client.exec(
+            "create table ws_sync_row_get.tb(ts timestamp, c32i int, c64i bigint,\
+                cbin binary(16), cn nchar(16))",
+        )?;
+        client.exec(
+            "insert into ws_sync_row_get.tb values\
+                (1000, -3, -4, 'abc', '涛思数据')\
+                (2000, null, null, null, null)",
+        )?;
+
+        // rows without a schema type, NULLs are `None`.
+        let mut rs = client.query("select * from ws_sync_row_get.tb")?;
+        let mut rows = rs.rows();
+        let row = rows.next().unwrap()?;
+        assert_eq!(row.get::<i64>(2)?, Some(-4));
+        assert_eq!(row.get_by_name::<&str>("cbin")?, Some("abc"));
+        assert_eq!(
+            row.get_by_name::<String>("cn")?.as_deref(),
+            Some("涛思数据")
+        );
+        let err = row.get_by_name::<i32>("cbin").unwrap_err();
+        assert!(err.to_string().contains("column `cbin`"), "{err}");
+        let row = rows.next().unwrap()?;
+        assert_eq!(row.get_by_name::<i32>("c32i")?, None);
+        assert_eq!(row.get_by_name::<&str>("cbin")?, None);
+
+        client.exec("drop database ws_sync_row_get")?;
+        Ok(())
+    }
+
     #[test]
     fn ws_show_databases() -> anyhow::Result<()> {
         use taos_query::{Fetchable, Queryable, TBuilder};
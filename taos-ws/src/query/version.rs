@@ -0,0 +1,99 @@
+//! Parsed server versions, to check server capabilities before using them.
+use std::fmt;
+use std::str::FromStr;
+
+/// Version of TDengine server like `3.0.1.5`, components missing are 0.
+///
+/// Components are compared numerically, suffixes like `-beta` are kept in [ServerVersion::raw]
+/// only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// The fourth component of TDengine versions.
+    pub build: u32,
+    raw: String,
+}
+
+impl ServerVersion {
+    /// The version string as reported by the server.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// Whether this version is the same to or newer than `min`.
+    pub fn at_least(&self, min: &ServerVersion) -> bool {
+        self.components() >= min.components()
+    }
+
+    fn components(&self) -> (u32, u32, u32, u32) {
+        (self.major, self.minor, self.patch, self.build)
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl FromStr for ServerVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw = s.trim();
+        let numbers = raw
+            .split(|c: char| !c.is_ascii_digit() && c != '.')
+            .next()
+            .unwrap_or_default();
+        let mut components = [0u32; 4];
+        let mut count = 0;
+        for part in numbers.split('.').take(4) {
+            components[count] = part
+                .parse()
+                .map_err(|_| format!("invalid server version {raw:?}"))?;
+            count += 1;
+        }
+        if count < 2 {
+            return Err(format!("invalid server version {raw:?}"));
+        }
+        let [major, minor, patch, build] = components;
+        Ok(ServerVersion {
+            major,
+            minor,
+            patch,
+            build,
+            raw: raw.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let v: ServerVersion = "3.0.1.5".parse().unwrap();
+        assert_eq!((v.major, v.minor, v.patch, v.build), (3, 0, 1, 5));
+        assert_eq!(v.raw(), "3.0.1.5");
+
+        let v: ServerVersion = "3.2-beta".parse().unwrap();
+        assert_eq!((v.major, v.minor, v.patch, v.build), (3, 2, 0, 0));
+        assert_eq!(v.to_string(), "3.2-beta");
+
+        "2.x".parse::<ServerVersion>().unwrap_err();
+        "3".parse::<ServerVersion>().unwrap_err();
+        "".parse::<ServerVersion>().unwrap_err();
+    }
+
+    #[test]
+    fn at_least() {
+        let v: ServerVersion = "3.0.1.5".parse().unwrap();
+        assert!(v.at_least(&"3.0".parse().unwrap()));
+        assert!(v.at_least(&"3.0.1.5".parse().unwrap()));
+        assert!(!v.at_least(&"3.0.1.6".parse().unwrap()));
+        assert!(!v.at_least(&"3.1".parse().unwrap()));
+    }
+}
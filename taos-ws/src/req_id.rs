@@ -0,0 +1,159 @@
+//! Request ids of websocket messages, replies are matched to requests by them and taosAdapter
+//! logs them, so ids should be unique in a process to attribute logs to connections.
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+
+/// Generator of request ids of connections, set by
+/// [TaosBuilder::set_req_id_provider](crate::TaosBuilder::set_req_id_provider).
+///
+/// Ids must be unique among in-flight requests of a connection, closures returning `u64` are
+/// providers too.
+pub trait ReqIdProvider: Send + Sync {
+    fn next_req_id(&self) -> u64;
+}
+
+impl<F> ReqIdProvider for F
+where
+    F: Fn() -> u64 + Send + Sync,
+{
+    fn next_req_id(&self) -> u64 {
+        self()
+    }
+}
+
+/// Milliseconds of 2020-01-01T00:00:00Z, timestamps of [SnowflakeReqId] start from it.
+const EPOCH_MS: u64 = 1_577_836_800_000;
+const NODE_BITS: u32 = 10;
+const SEQUENCE_BITS: u32 = 12;
+
+/// The default [ReqIdProvider], ids of 41 bits milliseconds, 10 bits node and 12 bits sequence.
+///
+/// The node is random unless set by [SnowflakeReqId::with_node], each connection has its own
+/// generator of a random node by default. More than 4096 ids in a millisecond borrow the
+/// timestamp of the next one, so ids of a generator never repeat and always increase.
+#[derive(Debug)]
+pub struct SnowflakeReqId {
+    node: u64,
+    /// Milliseconds and sequence of the last id.
+    last: AtomicU64,
+}
+
+impl Default for SnowflakeReqId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SnowflakeReqId {
+    /// A generator of a random node.
+    pub fn new() -> Self {
+        use std::hash::{BuildHasher, Hasher};
+        let random = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        Self::with_node(random as u16)
+    }
+
+    /// A generator of `node`, only the lower 10 bits are used, e.g. to embed a node identifier
+    /// for log correlation.
+    pub fn with_node(node: u16) -> Self {
+        Self {
+            node: node as u64 & ((1 << NODE_BITS) - 1),
+            last: AtomicU64::new(0),
+        }
+    }
+
+    /// The node embedded in ids.
+    pub fn node(&self) -> u16 {
+        self.node as u16
+    }
+
+    /// Generate an id.
+    pub fn generate(&self) -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64)
+            .saturating_sub(EPOCH_MS);
+        let floor = now << SEQUENCE_BITS;
+        let last = self
+            .last
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+                Some(floor.max(last + 1))
+            })
+            .unwrap_or_default();
+        let current = floor.max(last + 1);
+        let millis = current >> SEQUENCE_BITS;
+        let sequence = current & ((1 << SEQUENCE_BITS) - 1);
+        // the top bit is kept 0 so ids are positive as i64 too.
+        ((millis << (NODE_BITS + SEQUENCE_BITS)) | (self.node << SEQUENCE_BITS) | sequence)
+            & (i64::MAX as u64)
+    }
+}
+
+impl ReqIdProvider for SnowflakeReqId {
+    fn next_req_id(&self) -> u64 {
+        self.generate()
+    }
+}
+
+static GLOBAL: Lazy<SnowflakeReqId> = Lazy::new(SnowflakeReqId::new);
+
+/// Generate an id from a process-wide [SnowflakeReqId], for an explicit request id like of
+/// [Taos::query_with_req_id](crate::Taos::query_with_req_id).
+pub fn generate_req_id() -> u64 {
+    GLOBAL.generate()
+}
+
+/// Provider of a connection, shared by its reconnections.
+#[derive(Clone)]
+pub(crate) struct ReqIds(Arc<dyn ReqIdProvider>);
+
+impl Debug for ReqIds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ReqIds")
+    }
+}
+
+impl Default for ReqIds {
+    fn default() -> Self {
+        Self(Arc::new(SnowflakeReqId::new()))
+    }
+}
+
+impl ReqIds {
+    pub(crate) fn new(provider: impl ReqIdProvider + 'static) -> Self {
+        Self(Arc::new(provider))
+    }
+
+    pub(crate) fn next(&self) -> u64 {
+        self.0.next_req_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snowflake() {
+        let ids = SnowflakeReqId::with_node(0x7ff);
+        assert_eq!(ids.node(), 0x3ff);
+        let generated: Vec<u64> = (0..10000).map(|_| ids.generate()).collect();
+        // more than a millisecond of sequences, still increasing.
+        assert!(generated.windows(2).all(|w| w[0] < w[1]));
+        assert!(generated
+            .iter()
+            .all(|id| (id >> SEQUENCE_BITS) & 0x3ff == 0x3ff && *id < i64::MAX as u64));
+
+        let other = SnowflakeReqId::with_node(1);
+        assert_ne!(other.generate(), ids.generate());
+        assert_ne!(generate_req_id(), generate_req_id());
+
+        let provider = ReqIds::new(|| 42);
+        assert_eq!(provider.next(), 42);
+    }
+}
@@ -111,6 +111,12 @@ pub enum StmtSend {
     },
     AddBatch(StmtArgs),
     Exec(StmtArgs),
+    NumParams(StmtArgs),
+    GetParam {
+        #[serde(flatten)]
+        args: StmtArgs,
+        index: i64,
+    },
 }
 
 impl ToMessage for StmtSend {}
@@ -150,6 +156,20 @@ pub enum StmtRecvData {
         #[serde(default)]
         affected: usize,
     },
+    NumParams {
+        #[serde(default)]
+        stmt_id: StmtId,
+        #[serde(default)]
+        num_params: usize,
+    },
+    GetParam {
+        #[serde(default)]
+        stmt_id: StmtId,
+        #[serde(default)]
+        data_type: i32,
+        #[serde(default)]
+        length: i32,
+    },
 }
 
 #[serde_as]
@@ -163,11 +183,19 @@ pub struct StmtRecv {
     pub data: StmtRecvData,
 }
 
+/// Data in stmt responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StmtData {
+    Affected(usize),
+    NumParams(usize),
+    Param { ty: i32, bytes: i32 },
+}
+
 #[derive(Debug)]
 pub enum StmtOk {
     Conn(Result<(), Error>),
     Init(ReqId, Result<StmtId, Error>),
-    Stmt(StmtId, Result<Option<usize>, Error>),
+    Stmt(StmtId, Result<Option<StmtData>, Error>),
 }
 
 impl StmtRecv {
@@ -208,7 +236,31 @@ impl StmtRecv {
             }),
             StmtRecvData::Exec { stmt_id, affected } => StmtOk::Stmt(stmt_id, {
                 if self.code == 0 {
-                    Ok(Some(affected))
+                    Ok(Some(StmtData::Affected(affected)))
+                } else {
+                    _e!()
+                }
+            }),
+            StmtRecvData::NumParams {
+                stmt_id,
+                num_params,
+            } => StmtOk::Stmt(stmt_id, {
+                if self.code == 0 {
+                    Ok(Some(StmtData::NumParams(num_params)))
+                } else {
+                    _e!()
+                }
+            }),
+            StmtRecvData::GetParam {
+                stmt_id,
+                data_type,
+                length,
+            } => StmtOk::Stmt(stmt_id, {
+                if self.code == 0 {
+                    Ok(Some(StmtData::Param {
+                        ty: data_type,
+                        bytes: length,
+                    }))
                 } else {
                     _e!()
                 }
@@ -225,4 +277,30 @@ mod tests {
     fn stmt() -> anyhow::Result<()> {
         Ok(())
     }
+
+    #[test]
+    fn get_param() -> anyhow::Result<()> {
+        use super::*;
+        let recv: StmtRecv = serde_json::from_str(
+            r#"{"code":0,"message":"","action":"get_param","req_id":2,"stmt_id":1,"index":1,"data_type":4,"length":4}"#,
+        )?;
+        match recv.ok() {
+            StmtOk::Stmt(1, res) => {
+                assert_eq!(res?, Some(StmtData::Param { ty: 4, bytes: 4 }))
+            }
+            ok => panic!("unexpected {ok:?}"),
+        }
+        let send = StmtSend::GetParam {
+            args: StmtArgs {
+                req_id: 2,
+                stmt_id: 1,
+            },
+            index: 1,
+        };
+        assert_eq!(
+            serde_json::to_value(&send)?,
+            serde_json::json!({"action":"get_param","args":{"req_id":2,"stmt_id":1,"index":1}})
+        );
+        Ok(())
+    }
 }
@@ -12,23 +12,23 @@ use taos_query::{block_in_place_or_global, IntoDsn, RawBlock};
 use taos_query::prelude::tokio;
 use tokio::sync::{oneshot, watch};
 
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use tokio_tungstenite::tungstenite::protocol::Message;
 
-use crate::query::asyn::Error;
+use crate::query::asyn::{dial_endpoints, Error, WS_ERROR_NO};
 use crate::query::infra::ToMessage;
+use crate::req_id::ReqIds;
 use crate::{Taos, TaosBuilder};
 use messages::*;
 
 use std::fmt::Debug;
 use std::result::Result as StdResult;
 
-use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::time::Duration;
 
 mod messages;
 
-type StmtResult = StdResult<Option<usize>, RawError>;
+type StmtResult = StdResult<Option<StmtData>, RawError>;
 type StmtSender = std::sync::mpsc::SyncSender<StmtResult>;
 type StmtReceiver = std::sync::mpsc::Receiver<StmtResult>;
 
@@ -67,7 +67,7 @@ impl Bindable<super::Taos> for Stmt {
     type Error = Error;
 
     fn init(taos: &super::Taos) -> StdResult<Self, Self::Error> {
-        let mut dsn = taos.dsn.clone();
+        let mut dsn = taos.builder();
         let database: Option<String> =
             <Taos as taos_query::Queryable>::query_one(taos, "select database()")?;
         dsn.database = database;
@@ -129,7 +129,7 @@ impl Bindable<super::Taos> for Stmt {
 }
 
 pub struct Stmt {
-    req_id: Arc<AtomicU64>,
+    req_id: ReqIds,
     timeout: Duration,
     ws: WsSender,
     close_signal: watch::Sender<bool>,
@@ -226,8 +226,28 @@ impl Drop for Stmt {
 }
 
 impl Stmt {
+    /// Connect with credentials from the provider if any, asked once more on authentication
+    /// failure like the query connection. Endpoints of dsn are tried in turn as well.
     pub(crate) async fn from_wsinfo(info: &TaosBuilder) -> Result<Self> {
-        let (ws, _) = connect_async(info.to_stmt_url()).await?;
+        let connect = |info: TaosBuilder| async move { Self::connect(&info).await };
+        match dial_endpoints(&info.with_provided_auth(), connect).await {
+            Err(err) if info.has_auth_provider() && err.is_auth_failure() => {
+                log::warn!("authentication failed, retry with new credentials: {err}");
+                dial_endpoints(&info.with_provided_auth(), connect).await
+            }
+            res => res,
+        }
+    }
+
+    async fn connect(info: &TaosBuilder) -> Result<Self> {
+        let deadline = info.conn_timeout.map(|t| tokio::time::Instant::now() + t);
+        let connect = info.connect_ws(info.to_stmt_url());
+        let (ws, _) = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, connect)
+                .await
+                .map_err(|_| Error::ConnTimeout(info.to_stmt_url()))??,
+            None => connect.await?,
+        };
         let req_id = 0;
         let (mut sender, mut reader) = ws.split();
 
@@ -236,7 +256,13 @@ impl Stmt {
             req: info.to_conn_request(),
         };
         sender.send(login.to_msg()).await?;
-        if let Some(Ok(message)) = reader.next().await {
+        let logged = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, reader.next())
+                .await
+                .map_err(|_| Error::ConnTimeout(info.to_stmt_url()))?,
+            None => reader.next().await,
+        };
+        if let Some(Ok(message)) = logged {
             match message {
                 Message::Text(text) => {
                     let v: StmtRecv = serde_json::from_str(&text).unwrap();
@@ -266,9 +292,14 @@ impl Stmt {
             loop {
                 tokio::select! {
                     Some(msg) = msg_recv.recv() => {
-                        sender.send(msg).await.unwrap();
+                        if let Err(err) = sender.send(msg).await {
+                            log::error!("send websocket message packet error: {err}");
+                            break;
+                        }
                     }
                     _ = rx.changed() => {
+                        // Close frame lets taosAdapter release the session at once.
+                        let _ = sender.close().await;
                         log::debug!("close sender task");
                         break;
                     }
@@ -333,7 +364,7 @@ impl Stmt {
                                 }
                             },
                             Err(err) => {
-                                log::error!("receiving cause error: {err:?}");
+                                log::error!("receiving cause error: {}", crate::query::infra::read_error(&err));
                                 break;
                             }
                         }
@@ -344,10 +375,22 @@ impl Stmt {
                     }
                 }
             }
+            // Requests in flight would never be replied.
+            let closed = || RawError::new(WS_ERROR_NO::CONN_CLOSED.as_code(), "connection closed");
+            let keys = queries_sender.iter().map(|r| *r.key()).collect_vec();
+            for k in keys {
+                if let Some((_, sender)) = queries_sender.remove(&k) {
+                    let _ = sender.send(Err(closed()));
+                }
+            }
+            for r in fetches_sender.iter() {
+                let _ = r.value().try_send(Err(closed()));
+            }
+            fetches_sender.clear();
         });
 
         Ok(Self {
-            req_id: Arc::new(AtomicU64::new(req_id + 1)),
+            req_id: info.req_ids(),
             queries,
             timeout: Duration::from_secs(5),
             fetches,
@@ -370,8 +413,7 @@ impl Stmt {
     }
 
     fn req_id(&self) -> u64 {
-        self.req_id
-            .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+        self.req_id.next()
     }
 
     pub async fn stmt_init(&mut self) -> Result<&mut Self> {
@@ -505,7 +547,11 @@ impl Stmt {
             tags: tags,
         };
         self.ws.send_timeout(message.to_msg(), self.timeout).await?;
-        let _ = self.receiver.as_ref().unwrap().recv_timeout(self.timeout)?;
+        let _ = self
+            .receiver
+            .as_ref()
+            .unwrap()
+            .recv_timeout(self.timeout)??;
         Ok(())
     }
 
@@ -513,7 +559,7 @@ impl Stmt {
         log::debug!("exec");
         let message = StmtSend::Exec(self.args.unwrap());
         self.ws.send_timeout(message.to_msg(), self.timeout).await?;
-        if let Some(affected) = self
+        if let Some(StmtData::Affected(affected)) = self
             .receiver
             .as_ref()
             .unwrap()
@@ -525,6 +571,43 @@ impl Stmt {
             panic!("")
         }
     }
+
+    /// Number of placeholders in the prepared sql, reported by server.
+    pub async fn stmt_num_params(&mut self) -> Result<usize> {
+        let message = StmtSend::NumParams(self.args.unwrap());
+        self.ws.send_timeout(message.to_msg(), self.timeout).await?;
+        match self
+            .receiver
+            .as_ref()
+            .unwrap()
+            .recv_timeout(self.timeout)??
+        {
+            Some(StmtData::NumParams(num)) => Ok(num),
+            data => Err(
+                RawError::from_string(format!("unexpected num_params response: {data:?}")).into(),
+            ),
+        }
+    }
+
+    /// Type and bytes of the placeholder at `index`, reported by server.
+    pub async fn stmt_get_param(&mut self, index: i64) -> Result<(i32, i32)> {
+        let message = StmtSend::GetParam {
+            args: self.args.unwrap(),
+            index,
+        };
+        self.ws.send_timeout(message.to_msg(), self.timeout).await?;
+        match self
+            .receiver
+            .as_ref()
+            .unwrap()
+            .recv_timeout(self.timeout)??
+        {
+            Some(StmtData::Param { ty, bytes }) => Ok((ty, bytes)),
+            data => Err(
+                RawError::from_string(format!("unexpected get_param response: {data:?}")).into(),
+            ),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,541 @@
+//! Websocket connecting with TLS options of dsn for `wss`: `sslCa`, `sslVerify`,
+//! `sslVerifyHostname` and `tlsServerName`.
+//!
+//! With feature `rustls`, rustls is used for `wss` even if `native-tls` is enabled too, and it
+//! trusts the system trust store besides `sslCa`. If the system store has no certificate, like
+//! in musl or scratch containers, the Mozilla roots bundled by `webpki-roots` are trusted instead.
+use taos_query::prelude::tokio;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::error::UrlError;
+use tokio_tungstenite::tungstenite::handshake::client::Response;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::{proxy, TaosBuilder};
+
+pub(crate) type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+impl TaosBuilder {
+    /// Whether TLS options differ from the defaults of the connector.
+    #[cfg(not(feature = "rustls"))]
+    fn has_tls_options(&self) -> bool {
+        self.scheme == "wss"
+            && (!self.ssl_ca.is_empty()
+                || !self.ssl_verify
+                || !self.ssl_verify_hostname
+                || self.tls_server_name.is_some())
+    }
+
+    /// Websocket configuration with `maxMessageSize` of dsn.
+    fn ws_config(&self) -> WebSocketConfig {
+        WebSocketConfig {
+            max_message_size: self.max_message_size,
+            max_frame_size: self.max_message_size,
+            ..Default::default()
+        }
+    }
+
+    /// Open websocket `url`, through the proxy of dsn or environment if any. CA certificates of
+    /// `sslCa` are loaded here for `wss`.
+    pub(crate) async fn connect_ws(&self, url: String) -> Result<(WsStream, Response), WsError> {
+        let config = Some(self.ws_config());
+        let secure = self.scheme == "wss";
+        let request = url.into_client_request()?;
+        let host = request
+            .uri()
+            .host()
+            .ok_or(WsError::Url(UrlError::NoHostName))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = request
+            .uri()
+            .port_u16()
+            .unwrap_or(if secure { 443 } else { 80 });
+        let proxy = match &self.proxy {
+            Some(proxy) => Some(proxy.clone()),
+            None => {
+                proxy::from_env(|name| std::env::var(name).ok(), secure, &host).map_err(|err| {
+                    WsError::Io(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("invalid proxy in environment: {err}"),
+                    ))
+                })?
+            }
+        };
+        if let Some(proxy) = &proxy {
+            log::debug!("connect to {host}:{port} through proxy {proxy}");
+        }
+
+        #[cfg(any(feature = "native-tls", feature = "rustls"))]
+        {
+            let connector = self.tls_connector(proxy.is_some()).map_err(WsError::Io)?;
+            if proxy.is_none() && self.tls_server_name.is_none() {
+                return tokio_tungstenite::connect_async_tls_with_config(
+                    request, config, connector,
+                )
+                .await;
+            }
+            let tcp = match &proxy {
+                Some(proxy) => proxy.connect(&host, port).await?,
+                None => TcpStream::connect((host.as_str(), port)).await?,
+            };
+            let stream = match connector {
+                Some(connector) => {
+                    let name = self.tls_server_name.as_deref().unwrap_or(&host);
+                    tls_handshake(connector, name, tcp).await?
+                }
+                None => MaybeTlsStream::Plain(tcp),
+            };
+            tokio_tungstenite::client_async_with_config(request, stream, config).await
+        }
+        #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+        {
+            if self.has_tls_options() {
+                return Err(WsError::Io(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "TLS options require feature native-tls or rustls",
+                )));
+            }
+            match proxy {
+                None => tokio_tungstenite::connect_async_with_config(request, config).await,
+                Some(proxy) if !secure => {
+                    let tcp = proxy.connect(&host, port).await?;
+                    tokio_tungstenite::client_async_with_config(
+                        request,
+                        MaybeTlsStream::Plain(tcp),
+                        config,
+                    )
+                    .await
+                }
+                Some(_) => Err(WsError::Io(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "wss through proxy requires feature native-tls or rustls",
+                ))),
+            }
+        }
+    }
+
+    /// Connector of `wss`, `None` for the default one of tungstenite unless `always` is true.
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    fn tls_connector(&self, always: bool) -> std::io::Result<Option<tokio_tungstenite::Connector>> {
+        if self.scheme != "wss" {
+            return Ok(None);
+        }
+        #[cfg(not(feature = "rustls"))]
+        if !always && !self.has_tls_options() {
+            return Ok(None);
+        }
+        // rustls is always set up instead of the default of tungstenite.
+        #[cfg(feature = "rustls")]
+        let _ = always;
+        if !self.ssl_verify {
+            log::warn!(
+                "TLS certificate verification is disabled for {}, any certificate is accepted, never use sslVerify=false in production",
+                self.addr
+            );
+        } else if !self.ssl_verify_hostname {
+            log::warn!(
+                "TLS hostname verification is disabled for {}, never use sslVerifyHostname=false in production",
+                self.addr
+            );
+        }
+        let mut certs = Vec::new();
+        for path in &self.ssl_ca {
+            let pem = std::fs::read_to_string(path).map_err(|err| {
+                ca_error(
+                    path,
+                    err.kind(),
+                    format!("failed to read CA certificate: {err}"),
+                )
+            })?;
+            let ders = pem_certificates(&pem)
+                .map_err(|err| ca_error(path, std::io::ErrorKind::InvalidData, err))?;
+            certs.extend(ders.into_iter().map(|der| (path, der)));
+        }
+
+        // native-tls adds the certificates to the system trust store.
+        #[cfg(not(feature = "rustls"))]
+        {
+            let mut builder = native_tls_crate::TlsConnector::builder();
+            for (path, der) in certs {
+                let cert = native_tls_crate::Certificate::from_der(&der).map_err(|err| {
+                    ca_error(path, std::io::ErrorKind::InvalidData, err.to_string())
+                })?;
+                builder.add_root_certificate(cert);
+            }
+            builder
+                .danger_accept_invalid_certs(!self.ssl_verify)
+                .danger_accept_invalid_hostnames(!self.ssl_verify_hostname);
+            let connector = builder.build().map_err(std::io::Error::other)?;
+            Ok(Some(tokio_tungstenite::Connector::NativeTls(connector)))
+        }
+        #[cfg(feature = "rustls")]
+        {
+            let mut anchors = rustls_verifier::system_certificates();
+            let (mut roots, bundled) = rustls_verifier::root_store(&anchors);
+            for (path, der) in certs {
+                roots
+                    .add(&rustls_crate::Certificate(der.clone()))
+                    .map_err(|err| {
+                        ca_error(path, std::io::ErrorKind::InvalidData, format!("{err:?}"))
+                    })?;
+                anchors.push(der);
+            }
+            let builder = rustls_crate::ClientConfig::builder().with_safe_defaults();
+            let config = if !self.ssl_verify {
+                builder
+                    .with_custom_certificate_verifier(std::sync::Arc::new(
+                        rustls_verifier::AcceptAny,
+                    ))
+                    .with_no_client_auth()
+            } else if !self.ssl_verify_hostname {
+                builder
+                    .with_custom_certificate_verifier(std::sync::Arc::new(
+                        rustls_verifier::IgnoreHostname { anchors, bundled },
+                    ))
+                    .with_no_client_auth()
+            } else {
+                builder.with_root_certificates(roots).with_no_client_auth()
+            };
+            Ok(Some(tokio_tungstenite::Connector::Rustls(
+                std::sync::Arc::new(config),
+            )))
+        }
+    }
+}
+
+/// Valid DNS name for SNI, `None` with a warning otherwise.
+pub(crate) fn server_name(name: String) -> Option<String> {
+    let name = name.trim();
+    let valid = name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        });
+    if !valid {
+        log::warn!("invalid TLS server name {name:?}, the dialed host is used");
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// TLS handshake on `tcp` with SNI and verification of `name`.
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+async fn tls_handshake(
+    connector: tokio_tungstenite::Connector,
+    name: &str,
+    tcp: TcpStream,
+) -> std::io::Result<MaybeTlsStream<TcpStream>> {
+    log::debug!("TLS handshake as {name}");
+    let stream = match connector {
+        #[cfg(feature = "rustls")]
+        tokio_tungstenite::Connector::Rustls(config) => {
+            let name = rustls_crate::ServerName::try_from(name)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+            let tls = tokio_rustls::TlsConnector::from(config)
+                .connect(name, tcp)
+                .await?;
+            MaybeTlsStream::Rustls(tls)
+        }
+        #[cfg(not(feature = "rustls"))]
+        tokio_tungstenite::Connector::NativeTls(connector) => {
+            let tls = tokio_native_tls::TlsConnector::from(connector)
+                .connect(name, tcp)
+                .await
+                .map_err(std::io::Error::other)?;
+            MaybeTlsStream::NativeTls(tls)
+        }
+        _ => MaybeTlsStream::Plain(tcp),
+    };
+    Ok(stream)
+}
+
+#[cfg(feature = "rustls")]
+mod rustls_verifier {
+    use std::time::SystemTime;
+
+    use rustls_crate::client::{ServerCertVerified, ServerCertVerifier};
+    use rustls_crate::{Certificate, Error, OwnedTrustAnchor, RootCertStore, ServerName};
+
+    /// DER of the system trust store by `rustls-native-certs`, empty if it fails to load.
+    ///
+    /// `SSL_CERT_FILE` takes precedence over the platform store, e.g. the CA bundle of Linux.
+    pub(super) fn system_certificates() -> Vec<Vec<u8>> {
+        match rustls_native_certs::load_native_certs() {
+            Ok(certs) => certs.into_iter().map(|cert| cert.0).collect(),
+            Err(err) => {
+                log::warn!("failed to load system CA certificates: {err}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Roots of DER `system` certificates, or the bundled ones of `webpki-roots` if none of them
+    /// is parsable, with whether they are the bundled ones.
+    pub(super) fn root_store(system: &[Vec<u8>]) -> (RootCertStore, bool) {
+        let mut roots = RootCertStore::empty();
+        let (added, ignored) = roots.add_parsable_certificates(system);
+        log::trace!("{added} system CA certificates loaded, {ignored} ignored");
+        if added > 0 {
+            return (roots, false);
+        }
+        log::debug!("no system CA certificates, the bundled roots of webpki-roots are trusted");
+        roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+        (roots, true)
+    }
+
+    /// Accept any certificate, for `sslVerify=false`.
+    pub(super) struct AcceptAny;
+
+    impl ServerCertVerifier for AcceptAny {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &Certificate,
+            _intermediates: &[Certificate],
+            _server_name: &ServerName,
+            _scts: &mut dyn Iterator<Item = &[u8]>,
+            _ocsp_response: &[u8],
+            _now: SystemTime,
+        ) -> Result<ServerCertVerified, Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+    }
+
+    /// Signature algorithms of certificates, same to rustls.
+    static SIGNATURE_ALGORITHMS: &[&webpki::SignatureAlgorithm] = &[
+        &webpki::ECDSA_P256_SHA256,
+        &webpki::ECDSA_P256_SHA384,
+        &webpki::ECDSA_P384_SHA256,
+        &webpki::ECDSA_P384_SHA384,
+        &webpki::ED25519,
+        &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+        &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+        &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+        &webpki::RSA_PKCS1_2048_8192_SHA256,
+        &webpki::RSA_PKCS1_2048_8192_SHA384,
+        &webpki::RSA_PKCS1_2048_8192_SHA512,
+        &webpki::RSA_PKCS1_3072_8192_SHA384,
+    ];
+
+    /// Verify the certificate chain to the trusted certificates of DER `anchors`, and the bundled
+    /// roots if `bundled`, but not the name, for `sslVerifyHostname=false`. Unlike the verifier of
+    /// rustls it accepts IP addresses as the server name too, which are not checked either.
+    pub(super) struct IgnoreHostname {
+        pub(super) anchors: Vec<Vec<u8>>,
+        pub(super) bundled: bool,
+    }
+
+    impl ServerCertVerifier for IgnoreHostname {
+        fn verify_server_cert(
+            &self,
+            end_entity: &Certificate,
+            intermediates: &[Certificate],
+            _server_name: &ServerName,
+            _scts: &mut dyn Iterator<Item = &[u8]>,
+            _ocsp_response: &[u8],
+            now: SystemTime,
+        ) -> Result<ServerCertVerified, Error> {
+            let invalid = |err: webpki::Error| {
+                Error::InvalidCertificateData(format!("invalid peer certificate: {err}"))
+            };
+            let bundled = match self.bundled {
+                true => webpki_roots::TLS_SERVER_ROOTS.0,
+                false => &[],
+            };
+            let anchors: Vec<_> = self
+                .anchors
+                .iter()
+                .filter_map(|der| webpki::TrustAnchor::try_from_cert_der(der).ok())
+                .chain(bundled.iter().map(|ta| webpki::TrustAnchor {
+                    subject: ta.subject,
+                    spki: ta.spki,
+                    name_constraints: ta.name_constraints,
+                }))
+                .collect();
+            let chain: Vec<&[u8]> = intermediates.iter().map(|cert| cert.0.as_slice()).collect();
+            let now = webpki::Time::try_from(now).map_err(|_| Error::FailedToGetCurrentTime)?;
+            webpki::EndEntityCert::try_from(end_entity.0.as_slice())
+                .map_err(invalid)?
+                .verify_is_valid_tls_server_cert(
+                    SIGNATURE_ALGORITHMS,
+                    &webpki::TlsServerTrustAnchors(&anchors),
+                    &chain,
+                    now,
+                )
+                .map_err(invalid)?;
+            Ok(ServerCertVerified::assertion())
+        }
+    }
+}
+
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+fn ca_error(path: &std::path::Path, kind: std::io::ErrorKind, reason: String) -> std::io::Error {
+    std::io::Error::new(kind, format!("{}: {reason}", path.display()))
+}
+
+/// DER of each certificate in PEM `pem`.
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+fn pem_certificates(pem: &str) -> Result<Vec<Vec<u8>>, String> {
+    let certs = rustls_pemfile::certs(&mut pem.as_bytes())
+        .map_err(|err| format!("invalid PEM certificate: {err}"))?;
+    if certs.is_empty() {
+        return Err("no PEM certificate found".to_string());
+    }
+    Ok(certs)
+}
+
+#[cfg(all(test, any(feature = "native-tls", feature = "rustls")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pem() {
+        let pem = "-----BEGIN CERTIFICATE-----\nAAEC\nAw==\n-----END CERTIFICATE-----\n\
+            -----BEGIN CERTIFICATE-----\nBAU=\n-----END CERTIFICATE-----\n";
+        assert_eq!(
+            pem_certificates(pem).unwrap(),
+            [vec![0u8, 1, 2, 3], vec![4, 5]]
+        );
+        pem_certificates("").unwrap_err();
+        pem_certificates("-----BEGIN CERTIFICATE-----\nAAEC").unwrap_err();
+        pem_certificates("-----BEGIN CERTIFICATE-----\n!!\n-----END CERTIFICATE-----").unwrap_err();
+    }
+
+    #[test]
+    fn ssl_verify_connector() {
+        let connector = |dsn: &str| {
+            TaosBuilder::from_dsn(dsn)
+                .unwrap()
+                .tls_connector(false)
+                .unwrap()
+        };
+        // rustls is always set up for wss, so it's used even if native-tls is enabled.
+        assert_eq!(
+            connector("wss://localhost:6041").is_some(),
+            cfg!(feature = "rustls")
+        );
+        assert!(connector("ws://localhost:6041?sslVerify=false").is_none());
+        assert!(connector("wss://localhost:6041?sslVerify=false").is_some());
+        assert!(connector("wss://localhost:6041?sslVerifyHostname=false").is_some());
+    }
+
+    #[test]
+    fn tls_server_name() {
+        let name = |dsn: &str| {
+            TaosBuilder::from_dsn(dsn)
+                .unwrap()
+                .tls_server_name()
+                .map(ToString::to_string)
+        };
+        assert_eq!(
+            name("wss://10.0.0.1:6041?tlsServerName=adapter.internal.example.com").as_deref(),
+            Some("adapter.internal.example.com")
+        );
+        assert_eq!(name("wss://10.0.0.1:6041?tlsServerName="), None);
+        assert_eq!(name("wss://10.0.0.1:6041?tlsServerName=bad_name!"), None);
+        assert!(
+            TaosBuilder::from_dsn("wss://10.0.0.1:6041?tlsServerName=a.example.com")
+                .unwrap()
+                .tls_connector(false)
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    /// Dial a websocket server of a certificate for `adapter.example.com` by IP.
+    #[cfg(feature = "rustls")]
+    #[tokio::test]
+    async fn ssl_verify_hostname_ip() {
+        use rustls_crate::{Certificate, PrivateKey, ServerConfig};
+
+        let mut params = rcgen::CertificateParams::new(vec![]);
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = rcgen::Certificate::from_params(params).unwrap();
+        let leaf =
+            rcgen::generate_simple_self_signed(vec!["adapter.example.com".to_string()]).unwrap();
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(leaf.serialize_der_with_signer(&ca).unwrap())],
+                PrivateKey(leaf.serialize_private_key_der()),
+            )
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(std::sync::Arc::new(config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(tls) = acceptor.accept(tcp).await {
+                        let _ws = tokio_tungstenite::accept_async(tls).await;
+                    }
+                });
+            }
+        });
+        let ca_path = std::env::temp_dir().join(format!("taos-ws-ca-{}.pem", std::process::id()));
+        std::fs::write(&ca_path, ca.serialize_pem().unwrap()).unwrap();
+
+        let connect = |params: String| async move {
+            let builder =
+                TaosBuilder::from_dsn(format!("wss://127.0.0.1:{port}?{params}")).unwrap();
+            builder.connect_ws(builder.to_query_url()).await.map(|_| ())
+        };
+        let ca = ca_path.display();
+        connect(format!("sslCa={ca}&sslVerifyHostname=false"))
+            .await
+            .unwrap();
+        // the name is checked by default, and the chain is checked without the name.
+        connect(format!("sslCa={ca}")).await.unwrap_err();
+        connect("sslVerifyHostname=false".to_string())
+            .await
+            .unwrap_err();
+        std::fs::remove_file(ca_path).unwrap();
+    }
+
+    #[cfg(feature = "rustls")]
+    #[test]
+    fn bundled_roots() {
+        use rustls_verifier::root_store;
+
+        let bundled = webpki_roots::TLS_SERVER_ROOTS.0.len();
+        let (roots, is_bundled) = root_store(&[]);
+        assert!(is_bundled);
+        assert_eq!(roots.len(), bundled);
+        let (roots, is_bundled) = root_store(&[vec![0, 1, 2]]);
+        assert!(is_bundled);
+        assert_eq!(roots.len(), bundled);
+
+        // any certificate of the system store replaces the bundled roots.
+        let ca = rcgen::generate_simple_self_signed(vec!["ca.example.com".to_string()]).unwrap();
+        let (roots, is_bundled) = root_store(&[ca.serialize_der().unwrap()]);
+        assert!(!is_bundled);
+        assert_eq!(roots.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn ssl_ca_missing() {
+        let builder =
+            TaosBuilder::from_dsn("wss://localhost:6041?sslCa=/nonexistent/ca.pem").unwrap();
+        let err = builder
+            .connect_ws(builder.to_query_url())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/ca.pem"), "{err}");
+    }
+}
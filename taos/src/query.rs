@@ -109,6 +109,15 @@ impl AsyncFetchable for ResultSet {
         }
     }
 
+    fn affected_rows64(&self) -> i64 {
+        match &self.0 {
+            ResultSetInner::Native(rs) => {
+                <taos_sys::ResultSet as AsyncFetchable>::affected_rows64(rs)
+            }
+            ResultSetInner::Ws(rs) => <taos_ws::ResultSet as AsyncFetchable>::affected_rows64(rs),
+        }
+    }
+
     fn precision(&self) -> Precision {
         match &self.0 {
             ResultSetInner::Native(rs) => <taos_sys::ResultSet as AsyncFetchable>::precision(rs),
@@ -168,6 +177,15 @@ impl taos_query::Fetchable for ResultSet {
         }
     }
 
+    fn affected_rows64(&self) -> i64 {
+        match &self.0 {
+            ResultSetInner::Native(rs) => {
+                <taos_sys::ResultSet as AsyncFetchable>::affected_rows64(rs)
+            }
+            ResultSetInner::Ws(rs) => <taos_ws::ResultSet as AsyncFetchable>::affected_rows64(rs),
+        }
+    }
+
     fn precision(&self) -> Precision {
         match &self.0 {
             ResultSetInner::Native(rs) => <taos_sys::ResultSet as AsyncFetchable>::precision(rs),